@@ -0,0 +1,238 @@
+use crate::analyzer::SemanticAnalyzer;
+use crate::ast::{ExpressionKind, StatementKind};
+use crate::stack_vm::Xorshift64;
+use std::fmt::Write;
+use std::fs;
+
+/// Generates a self-contained C99 translation unit for a dice expression.
+///
+/// `dice_roll` rolls with the stack VM's [`Xorshift64`], so a state seeded
+/// like `run --seed` gives the same rolls. The `main` it comes with prints
+/// them as the stack VM does and can be left out with `-DDICE_NO_MAIN`.
+pub struct CGenerator {
+    count: u32,
+    faces: u32,
+}
+
+impl CGenerator {
+    pub fn new(expression: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut analyzer = SemanticAnalyzer::new(expression)?;
+        let ast = analyzer.analyze()?;
+
+        let Some(stmt) = ast.statement else {
+            return Err("Invalid expression".into());
+        };
+        let StatementKind::Expression { expr } = stmt.kind;
+        let ExpressionKind::Dice { count, faces } = expr.kind;
+        Ok(Self { count, faces })
+    }
+
+    /// The translation unit
+    pub fn generate_c(&self) -> Result<String, std::fmt::Error> {
+        let (count, faces) = (self.count, self.faces);
+        let mut c = String::new();
+        writeln!(c, "/* {count}d{faces}, generated by dice-rust */")?;
+        writeln!(c, "#include <stddef.h>")?;
+        writeln!(c, "#include <stdint.h>")?;
+        writeln!(c)?;
+        writeln!(c, "#define DICE_COUNT UINT32_C({count})")?;
+        writeln!(c, "#define DICE_FACES UINT32_C({faces})")?;
+        writeln!(c)?;
+        writeln!(
+            c,
+            "/* xorshift64 (13, 7, 17). A zero state, which it would never leave, is"
+        )?;
+        writeln!(c, "   replaced by a fixed one. */")?;
+        writeln!(c, "static uint64_t dice_next(uint64_t *rng_state)")?;
+        writeln!(c, "{{")?;
+        writeln!(
+            c,
+            "    uint64_t x = *rng_state ? *rng_state : UINT64_C({:#018X});",
+            Xorshift64::ZERO_SEED_STATE
+        )?;
+        writeln!(c, "    x ^= x << 13;")?;
+        writeln!(c, "    x ^= x >> 7;")?;
+        writeln!(c, "    x ^= x << 17;")?;
+        writeln!(c, "    *rng_state = x;")?;
+        writeln!(c, "    return x;")?;
+        writeln!(c, "}}")?;
+        writeln!(c)?;
+        writeln!(
+            c,
+            "/* Roll {count}d{faces}, storing the first cap rolls in out_rolls, and return"
+        )?;
+        writeln!(c, "   their total. */")?;
+        writeln!(
+            c,
+            "uint32_t dice_roll(uint64_t *rng_state, int32_t *out_rolls, size_t cap)"
+        )?;
+        writeln!(c, "{{")?;
+        writeln!(c, "    uint32_t total = 0;")?;
+        writeln!(c, "    for (uint32_t i = 0; i < DICE_COUNT; i++) {{")?;
+        writeln!(
+            c,
+            "        uint32_t roll = (uint32_t)(dice_next(rng_state) % DICE_FACES) + 1;"
+        )?;
+        writeln!(c, "        if (i < cap) {{")?;
+        writeln!(c, "            out_rolls[i] = (int32_t)roll;")?;
+        writeln!(c, "        }}")?;
+        writeln!(c, "        total += roll;")?;
+        writeln!(c, "    }}")?;
+        writeln!(c, "    return total;")?;
+        writeln!(c, "}}")?;
+        writeln!(c)?;
+        writeln!(c, "#ifndef DICE_NO_MAIN")?;
+        writeln!(c, "#include <inttypes.h>")?;
+        writeln!(c, "#include <stdio.h>")?;
+        writeln!(c, "#include <stdlib.h>")?;
+        writeln!(c, "#include <time.h>")?;
+        writeln!(c)?;
+        writeln!(c, "/* Usage: program [seed] */")?;
+        writeln!(c, "int main(int argc, char **argv)")?;
+        writeln!(c, "{{")?;
+        writeln!(
+            c,
+            "    uint64_t rng_state = argc > 1 ? strtoull(argv[1], NULL, 10) : (uint64_t)time(NULL);"
+        )?;
+        writeln!(
+            c,
+            "    int32_t *rolls = (int32_t *)malloc(sizeof *rolls * DICE_COUNT);"
+        )?;
+        writeln!(c, "    if (rolls == NULL) {{")?;
+        writeln!(c, "        fputs(\"out of memory\\n\", stderr);")?;
+        writeln!(c, "        return 1;")?;
+        writeln!(c, "    }}")?;
+        if count == 1 {
+            writeln!(c, "    dice_roll(&rng_state, rolls, DICE_COUNT);")?;
+        } else {
+            writeln!(
+                c,
+                "    uint32_t total = dice_roll(&rng_state, rolls, DICE_COUNT);"
+            )?;
+        }
+        writeln!(c, "    for (uint32_t i = 0; i < DICE_COUNT; i++) {{")?;
+        writeln!(
+            c,
+            "        printf(\"%\" PRIu32 \"\\n\", (uint32_t)rolls[i]);"
+        )?;
+        writeln!(c, "    }}")?;
+        if count > 1 {
+            writeln!(
+                c,
+                "    fprintf(stderr, \"Total: %\" PRIu32 \"\\n\", total);"
+            )?;
+        }
+        writeln!(c, "    free(rolls);")?;
+        writeln!(c, "    return 0;")?;
+        writeln!(c, "}}")?;
+        writeln!(c, "#endif")?;
+        Ok(c)
+    }
+}
+
+/// Generate a C source file for a dice expression
+pub fn generate_c_source(expression: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let c = CGenerator::new(expression)?.generate_c()?;
+    let filename = format!("{output}.c");
+    fs::write(&filename, c)?;
+
+    println!("Generated: {filename}");
+    println!("Compile with: cc -std=c99 -o {output} {filename}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack_vm::StackVm;
+    use std::process::Command;
+
+    #[test]
+    fn test_generated_c_matches_snapshots() {
+        let cases = [
+            ("3d6", include_str!("../tests/golden/c/DiceRoll_3d6.c")),
+            ("1D20", include_str!("../tests/golden/c/DiceRoll_1d20.c")),
+        ];
+        for (expression, snapshot) in cases {
+            let c = CGenerator::new(expression).unwrap().generate_c().unwrap();
+            assert_eq!(c, snapshot, "{expression}");
+        }
+        assert!(CGenerator::new("3d0").is_err());
+    }
+
+    #[test]
+    fn test_xorshift_sequence() {
+        let mut rng = Xorshift64::new(1);
+        assert_eq!(rng.next_u64(), 1082269761);
+        assert_eq!(rng.next_u64(), 1152992998833853505);
+
+        // Zero seeds the same as the replacement state
+        let mut zero = Xorshift64::new(0);
+        let mut replacement = Xorshift64::new(Xorshift64::ZERO_SEED_STATE);
+        assert_eq!(zero.next_u64(), replacement.next_u64());
+        assert!((0..1000).all(|_| (1..=6).contains(&zero.roll(6))));
+    }
+
+    /// Compiles the generated C when a C compiler is available and checks
+    /// that it prints what the stack VM prints for the same seed
+    #[test]
+    fn test_compiled_c_matches_stack_vm() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            eprintln!("cc not found, skipping");
+            return;
+        }
+        let directory = std::env::temp_dir().join(format!("dice-c-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        for (expression, seed) in [
+            ("3d6", 42),
+            ("1d20", 7),
+            ("500d1000", 0),
+            ("2d4294967295", 9),
+        ] {
+            let source = directory.join("dice.c");
+            let program = directory.join("dice");
+            let c = CGenerator::new(expression).unwrap().generate_c().unwrap();
+            std::fs::write(&source, c).unwrap();
+            let compiled = Command::new("cc")
+                .args(["-std=c99", "-Wall", "-Wextra", "-pedantic", "-Werror", "-o"])
+                .arg(&program)
+                .arg(&source)
+                .output()
+                .unwrap();
+            assert!(
+                compiled.status.success(),
+                "{}",
+                String::from_utf8_lossy(&compiled.stderr)
+            );
+            let run = Command::new(&program)
+                .arg(seed.to_string())
+                .output()
+                .unwrap();
+            assert!(run.status.success());
+
+            let mut vm = StackVm::new();
+            vm.set_seed(seed);
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            vm.execute_with_output(expression, &mut out, &mut err)
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(run.stdout).unwrap(),
+                String::from_utf8(out).unwrap(),
+                "{expression}"
+            );
+            assert_eq!(run.stderr, err, "{expression}");
+        }
+
+        // Engines embed dice_roll without the main
+        let object = Command::new("cc")
+            .args(["-std=c99", "-Wall", "-Werror", "-DDICE_NO_MAIN", "-c", "-o"])
+            .arg(directory.join("dice.o"))
+            .arg(directory.join("dice.c"))
+            .output()
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(object.status.success());
+    }
+}
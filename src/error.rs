@@ -1,4 +1,5 @@
 use std::fmt;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -139,11 +140,76 @@ pub enum RuntimeError {
     #[error("Invalid opcode: {0}")]
     InvalidOpcode(u8),
     #[error(
-        "Unknown constant pool tag: {tag} at index {index}. Valid tags: 1=Utf8, 3=Integer, 4=Float, 5=Long, 6=Double, 7=Class, 8=String, 9=Fieldref, 10=Methodref, 11=InterfaceMethodref, 12=NameAndType, 15=MethodHandle, 16=MethodType, 18=InvokeDynamic"
+        "Unknown constant pool tag: {tag} at index {index}. Valid tags: 1=Utf8, 3=Integer, 4=Float, 5=Long, 6=Double, 7=Class, 8=String, 9=Fieldref, 10=Methodref, 11=InterfaceMethodref, 12=NameAndType, 15=MethodHandle, 16=MethodType, 17=Dynamic, 18=InvokeDynamic, 19=Module, 20=Package"
     )]
     UnknownConstantPoolTag { tag: u8, index: u16 },
+    #[error("Invalid class file: {0}")]
+    InvalidClassFile(String),
+    #[error("java.lang.VerifyError: {message} in {method} at offset {offset}")]
+    VerifyError {
+        /// Method as `Class.name(descriptor)`
+        method: String,
+        /// Byte offset of the offending instruction
+        offset: usize,
+        message: String,
+    },
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+    #[error("java.lang.NoClassDefFoundError: {0}")]
+    ClassNotFound(String),
+    #[error("Invalid constant pool reference: {0}")]
+    InvalidConstantPoolReference(u16),
+    #[error("Unsupported bootstrap method: {0}")]
+    UnsupportedBootstrapMethod(String),
+    #[error("java.lang.NullPointerException")]
+    NullPointer,
+    #[error(
+        "java.lang.ArrayIndexOutOfBoundsException: Index {index} out of bounds for length {length}"
+    )]
+    ArrayIndexOutOfBounds { index: i32, length: usize },
+    #[error("java.lang.NegativeArraySizeException: {0}")]
+    NegativeArraySize(i32),
+    #[error("java.lang.OutOfMemoryError: Java heap space")]
+    OutOfMemory,
+    #[error("java.lang.NoSuchMethodError: {0}")]
+    NoSuchMethod(String),
+    #[error("java.lang.NoSuchFieldError: {0}")]
+    NoSuchField(String),
+    #[error("Exception object {0} is being thrown")]
+    Exception(usize),
+    #[error("Exception in thread \"main\" {0}")]
+    UncaughtException(String),
     #[error("Call stack overflow")]
     CallStackOverflow,
     #[error("Call stack underflow")]
     CallStackUnderflow,
+    #[error("Instruction limit of {0} exceeded")]
+    InstructionLimitExceeded(u64),
+    #[error("Call depth limit of {0} exceeded")]
+    CallDepthLimitExceeded(usize),
+    #[error("Heap limit of {0} objects exceeded")]
+    HeapLimitExceeded(usize),
+    #[error("String limit of {0} bytes exceeded")]
+    StringLimitExceeded(usize),
+    #[error("Array limit of {0} bytes exceeded")]
+    ArrayLimitExceeded(usize),
+    #[error("Execution timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+impl RuntimeError {
+    /// Attribute a `VerifyError` raised while decoding a method's code to
+    /// that method; other errors are returned unchanged
+    pub fn in_method(self, method: &str) -> Self {
+        match self {
+            Self::VerifyError {
+                offset, message, ..
+            } => Self::VerifyError {
+                method: method.to_string(),
+                offset,
+                message,
+            },
+            error => error,
+        }
+    }
 }
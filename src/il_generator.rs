@@ -0,0 +1,468 @@
+use crate::analyzer::SemanticAnalyzer;
+use crate::ast::{ExpressionKind, StatementKind};
+use crate::stack_vm::{Compiler, Instruction};
+use std::fmt::Write;
+use std::fs;
+
+const CONSOLE: &str = "[mscorlib]System.Console";
+const TEXT_WRITER: &str = "[mscorlib]System.IO.TextWriter";
+const RANDOM: &str = "[mscorlib]System.Random";
+
+/// Lowers stack VM bytecode into ILAsm source for a .NET executable.
+///
+/// The VM's values are unsigned, so they are kept in `uint32` locals and
+/// divided, compared and printed as unsigned. `CallRandom` becomes
+/// `System.Random.Next(1, faces + 1)`, so expressions with `faces` of
+/// `int.MaxValue` or more are rejected, as are counts beyond an `int32`.
+pub struct IlGenerator {
+    class_name: String,
+}
+
+impl IlGenerator {
+    /// `class_name` may have a namespace, written `dice/Roll` as for the
+    /// JVM backends
+    pub fn new(class_name: String) -> Self {
+        Self { class_name }
+    }
+
+    /// Generate ILAsm source from Dice expression
+    pub fn generate_dice_il(&self, expression: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let ast = SemanticAnalyzer::new(expression)?.analyze()?;
+        if let Some(stmt) = ast.statement {
+            let StatementKind::Expression { expr } = stmt.kind;
+            let ExpressionKind::Dice { count, faces } = expr.kind;
+            if i32::try_from(count).is_err() {
+                return Err(format!("dice count {count} does not fit in an int32").into());
+            }
+            // Random.Next(1, faces + 1) needs faces + 1 to be an int32 too
+            if faces > i32::MAX as u32 - 1 {
+                return Err(format!(
+                    "number of faces {faces} is too large for System.Random.Next, the limit is {}",
+                    i32::MAX - 1
+                )
+                .into());
+            }
+        }
+        let bytecode = Compiler::compile(expression)?;
+        self.generate_il(&bytecode)
+    }
+
+    /// Generate ILAsm source with a `Main` entry point running `bytecode`
+    pub(crate) fn generate_il(
+        &self,
+        bytecode: &[Instruction],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let depths = stack_depths(bytecode)?;
+        let max_stack = bytecode
+            .iter()
+            .zip(&depths)
+            .filter_map(|(instruction, depth)| Some(depth.as_ref()? + stack_effect(instruction)?.2))
+            .max()
+            .unwrap_or(0);
+        let uses_random = bytecode
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::CallRandom));
+        let uses_scratch = uses_random
+            || bytecode
+                .iter()
+                .any(|instruction| matches!(instruction, Instruction::CallWriteLineErr));
+
+        let mut targets = vec![false; bytecode.len() + 1];
+        for (index, instruction) in bytecode.iter().enumerate() {
+            if let Some(target) = branch_target(index, instruction, bytecode.len())? {
+                targets[target] = true;
+            }
+        }
+
+        let class_name = self.class_name.replace('/', ".");
+        let assembly_name = class_name.rsplit('.').next().unwrap_or_default();
+        let mut il = String::new();
+        writeln!(il, ".assembly extern mscorlib {{}}")?;
+        writeln!(il, ".assembly {assembly_name} {{}}")?;
+        writeln!(il)?;
+        writeln!(
+            il,
+            ".class public abstract auto ansi sealed beforefieldinit {class_name}"
+        )?;
+        writeln!(il, "    extends [mscorlib]System.Object")?;
+        writeln!(il, "{{")?;
+        writeln!(
+            il,
+            "    .method public hidebysig static void Main() cil managed"
+        )?;
+        writeln!(il, "    {{")?;
+        writeln!(il, "        .entrypoint")?;
+        writeln!(il, "        .maxstack {max_stack}")?;
+        let mut locals = vec!["uint32 V_0", "uint32 V_1", "uint32 V_2"];
+        if uses_scratch {
+            locals.push("uint32 scratch");
+        }
+        if uses_random {
+            locals.push("class [mscorlib]System.Random random");
+        }
+        writeln!(il, "        .locals init ({})", locals.join(", "))?;
+        if uses_random {
+            writeln!(il, "        newobj instance void {RANDOM}::.ctor()")?;
+            writeln!(il, "        stloc.s random")?;
+        }
+
+        for (index, instruction) in bytecode.iter().enumerate() {
+            if targets[index] {
+                writeln!(il, "    {}:", label(index))?;
+            }
+            // Unreachable code has no stack depth to return with
+            let depth = depths[index].unwrap_or(0);
+            for line in lower(index, instruction, depth, bytecode.len())? {
+                writeln!(il, "        {line}")?;
+            }
+        }
+        // Running off the end of the bytecode ends the program
+        if let Some(depth) = depths[bytecode.len()] {
+            if targets[bytecode.len()] {
+                writeln!(il, "    {}:", label(bytecode.len()))?;
+            }
+            for line in lower(bytecode.len(), &Instruction::Ret, depth, bytecode.len())? {
+                writeln!(il, "        {line}")?;
+            }
+        }
+        writeln!(il, "    }}")?;
+        writeln!(il, "}}")?;
+        Ok(il)
+    }
+}
+
+/// CIL for one instruction, entered with `depth` values on the stack
+fn lower(
+    index: usize,
+    instruction: &Instruction,
+    depth: usize,
+    length: usize,
+) -> Result<Vec<String>, String> {
+    let lines = match instruction {
+        Instruction::LdcI4(value) => vec![load_constant(*value as i32)],
+        Instruction::Stloc0 => vec!["stloc.0".to_string()],
+        Instruction::Stloc1 => vec!["stloc.1".to_string()],
+        Instruction::Stloc2 => vec!["stloc.2".to_string()],
+        Instruction::Ldloc0 => vec!["ldloc.0".to_string()],
+        Instruction::Ldloc1 => vec!["ldloc.1".to_string()],
+        Instruction::Ldloc2 => vec!["ldloc.2".to_string()],
+        Instruction::Pop => vec!["pop".to_string()],
+        Instruction::Dup => vec!["dup".to_string()],
+        Instruction::Add => vec!["add".to_string()],
+        Instruction::Sub => vec!["sub".to_string()],
+        Instruction::Mul => vec!["mul".to_string()],
+        Instruction::Div => vec!["div.un".to_string()],
+        Instruction::Rem => vec!["rem.un".to_string()],
+        Instruction::Ceq => vec!["ceq".to_string()],
+        Instruction::Cgt => vec!["cgt.un".to_string()],
+        Instruction::Clt => vec!["clt.un".to_string()],
+        Instruction::Br(_) | Instruction::Brtrue(_) | Instruction::Brfalse(_) => {
+            let opcode = match instruction {
+                Instruction::Br(_) => "br",
+                Instruction::Brtrue(_) => "brtrue",
+                _ => "brfalse",
+            };
+            let target = branch_target(index, instruction, length)?.unwrap_or(length);
+            vec![format!("{opcode} {}", label(target))]
+        }
+        Instruction::Call(_) => {
+            return Err(format!(
+                "instruction {index}: {instruction:?} has no .NET equivalent"
+            ));
+        }
+        Instruction::Ret => {
+            // A void method returns with an empty stack
+            let mut lines = vec!["pop".to_string(); depth];
+            lines.push("ret".to_string());
+            lines
+        }
+        Instruction::CallWriteLine => {
+            vec![format!("call void {CONSOLE}::WriteLine(uint32)")]
+        }
+        Instruction::CallWrite => vec![format!("call void {CONSOLE}::Write(uint32)")],
+        Instruction::CallWriteStr(s) => vec![
+            format!("ldstr {}", string_literal(s)),
+            format!("call void {CONSOLE}::Write(string)"),
+        ],
+        Instruction::CallWriteLineErr => vec![
+            "stloc.s scratch".to_string(),
+            format!("call class {TEXT_WRITER} {CONSOLE}::get_Error()"),
+            "ldloc.s scratch".to_string(),
+            format!("callvirt instance void {TEXT_WRITER}::WriteLine(uint32)"),
+        ],
+        Instruction::CallWriteStrErr(s) => vec![
+            format!("call class {TEXT_WRITER} {CONSOLE}::get_Error()"),
+            format!("ldstr {}", string_literal(s)),
+            format!("callvirt instance void {TEXT_WRITER}::Write(string)"),
+        ],
+        Instruction::CallRandom => vec![
+            "stloc.s scratch".to_string(),
+            "ldloc.s random".to_string(),
+            "ldc.i4.1".to_string(),
+            "ldloc.s scratch".to_string(),
+            "ldc.i4.1".to_string(),
+            "add".to_string(),
+            format!("callvirt instance int32 {RANDOM}::Next(int32, int32)"),
+        ],
+    };
+    Ok(lines)
+}
+
+/// Values an instruction pops and pushes, and how far its CIL grows the
+/// stack above the depth it starts from. `None` for `Call`.
+fn stack_effect(instruction: &Instruction) -> Option<(usize, usize, usize)> {
+    Some(match instruction {
+        Instruction::LdcI4(_) | Instruction::Ldloc0 | Instruction::Ldloc1 | Instruction::Ldloc2 => {
+            (0, 1, 1)
+        }
+        Instruction::Stloc0
+        | Instruction::Stloc1
+        | Instruction::Stloc2
+        | Instruction::Pop
+        | Instruction::Brtrue(_)
+        | Instruction::Brfalse(_)
+        | Instruction::CallWriteLine
+        | Instruction::CallWrite => (1, 0, 0),
+        Instruction::Dup => (1, 2, 1),
+        Instruction::Add
+        | Instruction::Sub
+        | Instruction::Mul
+        | Instruction::Div
+        | Instruction::Rem
+        | Instruction::Ceq
+        | Instruction::Cgt
+        | Instruction::Clt => (2, 1, 0),
+        Instruction::Br(_) | Instruction::Ret => (0, 0, 0),
+        Instruction::CallWriteStr(_) => (0, 0, 1),
+        Instruction::CallWriteLineErr => (1, 0, 1),
+        Instruction::CallWriteStrErr(_) => (0, 0, 2),
+        Instruction::CallRandom => (1, 1, 3),
+        Instruction::Call(_) => return None,
+    })
+}
+
+/// Stack depth on entry to each instruction and at the end of the
+/// bytecode, `None` where execution never gets to
+fn stack_depths(bytecode: &[Instruction]) -> Result<Vec<Option<usize>>, String> {
+    let mut depths = vec![None; bytecode.len() + 1];
+    let mut pending = vec![(0, 0usize)];
+    while let Some((index, depth)) = pending.pop() {
+        match depths[index] {
+            Some(known) if known == depth => continue,
+            Some(known) => {
+                return Err(format!(
+                    "instruction {index}: stack depth is {known} on one path and {depth} on another"
+                ));
+            }
+            None => depths[index] = Some(depth),
+        }
+        let Some(instruction) = bytecode.get(index) else {
+            continue;
+        };
+        let (pops, pushes, _) = stack_effect(instruction).ok_or_else(|| {
+            format!("instruction {index}: {instruction:?} has no .NET equivalent")
+        })?;
+        let depth = depth
+            .checked_sub(pops)
+            .ok_or_else(|| format!("instruction {index}: stack underflow"))?
+            + pushes;
+        if let Some(target) = branch_target(index, instruction, bytecode.len())? {
+            pending.push((target, depth));
+        }
+        if !matches!(instruction, Instruction::Br(_) | Instruction::Ret) {
+            pending.push((index + 1, depth));
+        }
+    }
+    Ok(depths)
+}
+
+/// Index a branch jumps to. Jumps past the end end the program, as they do
+/// in the stack VM.
+fn branch_target(
+    index: usize,
+    instruction: &Instruction,
+    length: usize,
+) -> Result<Option<usize>, String> {
+    let (Instruction::Br(offset) | Instruction::Brtrue(offset) | Instruction::Brfalse(offset)) =
+        instruction
+    else {
+        return Ok(None);
+    };
+    let target = index
+        .checked_add_signed(*offset)
+        .ok_or_else(|| format!("instruction {index}: branch before the start of the program"))?;
+    Ok(Some(target.min(length)))
+}
+
+fn label(index: usize) -> String {
+    format!("L_{index:04}")
+}
+
+/// The shortest `ldc.i4` form for a constant
+fn load_constant(value: i32) -> String {
+    match value {
+        -1 => "ldc.i4.m1".to_string(),
+        0..=8 => format!("ldc.i4.{value}"),
+        _ if i8::try_from(value).is_ok() => format!("ldc.i4.s {value}"),
+        _ => format!("ldc.i4 {value}"),
+    }
+}
+
+/// A quoted ILAsm string
+fn string_literal(value: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            _ => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Generate an ILAsm source file for a dice expression
+pub fn generate_il_source(
+    expression: &str,
+    class_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let il = IlGenerator::new(class_name.to_string()).generate_dice_il(expression)?;
+    let filename = format!("{class_name}.il");
+    fs::write(&filename, il)?;
+
+    println!("Generated: {filename}");
+    println!("Assemble with: ilasm {filename}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_il_matches_golden_files() {
+        let cases = [
+            (
+                "1d6",
+                "DiceRoll",
+                include_str!("../tests/golden/il/DiceRoll_1d6.il"),
+            ),
+            (
+                "3d6",
+                "DiceRoll",
+                include_str!("../tests/golden/il/DiceRoll_3d6.il"),
+            ),
+            (
+                "2D1000",
+                "Games/Dice/Roll",
+                include_str!("../tests/golden/il/Roll_2d1000.il"),
+            ),
+        ];
+        for (expression, class_name, golden) in cases {
+            let il = IlGenerator::new(class_name.to_string())
+                .generate_dice_il(expression)
+                .unwrap();
+            assert_eq!(il, golden, "{expression}");
+        }
+
+        let generator = IlGenerator::new("DiceRoll".to_string());
+        assert!(generator.generate_dice_il("2d2147483646").is_ok());
+        let errors = [
+            (
+                "2d2147483647",
+                "number of faces 2147483647 is too large for System.Random.Next, the limit is 2147483646",
+            ),
+            (
+                "1d4294967295",
+                "number of faces 4294967295 is too large for System.Random.Next, the limit is 2147483646",
+            ),
+            (
+                "3000000000d6",
+                "dice count 3000000000 does not fit in an int32",
+            ),
+        ];
+        for (expression, message) in errors {
+            let error = generator.generate_dice_il(expression).unwrap_err();
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_every_instruction_is_lowered() {
+        use Instruction::*;
+        let bytecode = vec![
+            LdcI4(7),
+            LdcI4(100),
+            LdcI4(70000),
+            LdcI4(u32::MAX),
+            Pop,
+            Stloc0,
+            Stloc1,
+            Stloc2,
+            Ldloc0,
+            Ldloc1,
+            Ldloc2,
+            Dup,
+            Add,
+            Sub,
+            Mul,
+            Ldloc1,
+            Div,
+            Ldloc2,
+            Rem,
+            Ldloc0,
+            Ceq,
+            Brtrue(10),
+            Ldloc0,
+            Ldloc1,
+            Cgt,
+            Brfalse(2),
+            Br(5),
+            Ldloc0,
+            Ldloc1,
+            Clt,
+            Pop,
+            Ldloc1,
+            CallRandom,
+            Dup,
+            CallWriteLine,
+            CallWrite,
+            CallWriteStr("say \"hi\"\n".to_string()),
+            CallWriteStrErr("Total: ".to_string()),
+            Ldloc2,
+            CallWriteLineErr,
+            Ldloc0,
+            Ret,
+        ];
+        let il = IlGenerator::new("Instructions".to_string())
+            .generate_il(&bytecode)
+            .unwrap();
+        assert_eq!(il, include_str!("../tests/golden/il/Instructions.il"));
+
+        let generator = IlGenerator::new("Invalid".to_string());
+        let errors = [
+            (
+                vec![Call("Helper".to_string())],
+                "instruction 0: Call(\"Helper\") has no .NET equivalent",
+            ),
+            (vec![Pop], "instruction 0: stack underflow"),
+            (
+                vec![Br(-1)],
+                "instruction 0: branch before the start of the program",
+            ),
+            (
+                vec![LdcI4(1), Brtrue(2), LdcI4(2), Ret],
+                "instruction 3: stack depth is 1 on one path and 0 on another",
+            ),
+        ];
+        for (bytecode, message) in errors {
+            let error = generator.generate_il(&bytecode).unwrap_err();
+            assert_eq!(error.to_string(), message);
+        }
+    }
+}
@@ -1,23 +1,177 @@
-use super::jvm_types::{ConstantPool, ConstantPoolEntry, JvmInstruction};
+use super::jvm_types::{ConstantPool, JvmInstruction, decode_modified_utf8};
 use crate::error::RuntimeError;
 use std::collections::HashMap;
 use std::io::{Cursor, Read};
 
-#[derive(Debug, Clone)]
+/// A raw attribute whose contents are not interpreted by the parser
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeInfo {
+    pub name_index: u16,
+    pub info: Vec<u8>,
+}
+
+/// One row of a `Code` attribute's exception table (byte offsets)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExceptionTableEntry {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: u16,
+}
+
+/// Decoded `Code` attribute of a method
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeAttribute {
+    pub name_index: u16,
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub bytecode: Vec<JvmInstruction>,
+    pub exception_table: Vec<ExceptionTableEntry>,
+    pub attributes: Vec<AttributeInfo>,
+}
+
+/// One entry of the class-level `BootstrapMethods` attribute
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootstrapMethod {
+    /// Index of the `CONSTANT_MethodHandle` for the bootstrap method
+    pub bootstrap_method_ref: u16,
+    /// Constant pool indices of the static arguments
+    pub bootstrap_arguments: Vec<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInfo {
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub name: String,
+    pub descriptor: String,
+    pub attributes: Vec<AttributeInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodInfo {
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
     pub name: String,
     pub descriptor: String,
-    pub bytecode: Vec<JvmInstruction>,
-    pub max_locals: usize,
-    pub max_stack: usize,
+    /// `None` for abstract and native methods
+    pub code: Option<CodeAttribute>,
+    /// Method attributes other than `Code`
+    pub attributes: Vec<AttributeInfo>,
+}
+
+impl CodeAttribute {
+    /// Decode the `LineNumberTable` attributes into `(start_pc, line_number)`
+    /// pairs sorted by byte offset
+    pub fn line_number_table(
+        &self,
+        constant_pool: &ConstantPool,
+    ) -> Result<Vec<(u16, u16)>, RuntimeError> {
+        let mut line_numbers = Vec::new();
+        for attribute in &self.attributes {
+            if constant_pool.get_utf8(attribute.name_index) != Some("LineNumberTable") {
+                continue;
+            }
+            let mut cursor = Cursor::new(attribute.info.as_slice());
+            let count = read_u16(&mut cursor)?;
+            for _ in 0..count {
+                line_numbers.push((read_u16(&mut cursor)?, read_u16(&mut cursor)?));
+            }
+        }
+        line_numbers.sort_unstable();
+        Ok(line_numbers)
+    }
 }
 
+impl MethodInfo {
+    pub fn bytecode(&self) -> &[JvmInstruction] {
+        self.code.as_ref().map_or(&[], |code| &code.bytecode)
+    }
+
+    pub fn max_locals(&self) -> usize {
+        self.code
+            .as_ref()
+            .map_or(0, |code| code.max_locals as usize)
+    }
+
+    pub fn max_stack(&self) -> usize {
+        self.code.as_ref().map_or(0, |code| code.max_stack as usize)
+    }
+}
+
+/// In-memory model of a complete class file
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClassFile {
+    pub minor_version: u16,
+    pub major_version: u16,
     pub constant_pool: ConstantPool,
-    pub main_method_bytecode: Vec<JvmInstruction>,
-    pub max_locals: usize,
-    pub max_stack: usize,
-    pub methods: HashMap<String, MethodInfo>, // method_name -> MethodInfo
+    pub access_flags: u16,
+    pub this_class: u16,
+    pub super_class: u16,
+    pub interfaces: Vec<u16>,
+    pub fields: Vec<FieldInfo>,
+    pub methods: Vec<MethodInfo>,
+    pub attributes: Vec<AttributeInfo>,
+}
+
+impl ClassFile {
+    /// Binary name of this class, e.g. `DiceRoll` or `com/example/Main`
+    pub fn class_name(&self) -> Option<&str> {
+        self.constant_pool.get_class_name(self.this_class)
+    }
+
+    pub fn find_method(&self, name: &str, descriptor: &str) -> Option<&MethodInfo> {
+        self.methods
+            .iter()
+            .find(|method| method.name == name && method.descriptor == descriptor)
+    }
+
+    /// Entry point of the class. Kotlin's `main()V` is preferred over Java's
+    /// `main([Ljava/lang/String;)V` when both are present.
+    pub fn main_method(&self) -> Option<&MethodInfo> {
+        self.find_method("main", "()V")
+            .or_else(|| self.find_method("main", "([Ljava/lang/String;)V"))
+            .filter(|method| method.code.is_some())
+    }
+
+    /// Source file name recorded in the `SourceFile` attribute
+    pub fn source_file(&self) -> Option<&str> {
+        let attribute = self.attributes.iter().find(|attribute| {
+            self.constant_pool.get_utf8(attribute.name_index) == Some("SourceFile")
+        })?;
+        let [high, low] = attribute.info[..] else {
+            return None;
+        };
+        self.constant_pool.get_utf8(u16::from_be_bytes([high, low]))
+    }
+
+    /// Decode the `BootstrapMethods` attribute referenced by `invokedynamic`.
+    /// Classes without the attribute have no bootstrap methods.
+    pub fn bootstrap_methods(&self) -> Result<Vec<BootstrapMethod>, RuntimeError> {
+        let Some(attribute) = self.attributes.iter().find(|attribute| {
+            self.constant_pool.get_utf8(attribute.name_index) == Some("BootstrapMethods")
+        }) else {
+            return Ok(Vec::new());
+        };
+
+        let mut cursor = Cursor::new(attribute.info.as_slice());
+        let count = read_u16(&mut cursor)?;
+        let mut bootstrap_methods = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let bootstrap_method_ref = read_u16(&mut cursor)?;
+            let argument_count = read_u16(&mut cursor)?;
+            let bootstrap_arguments = (0..argument_count)
+                .map(|_| read_u16(&mut cursor))
+                .collect::<Result<_, _>>()?;
+            bootstrap_methods.push(BootstrapMethod {
+                bootstrap_method_ref,
+                bootstrap_arguments,
+            });
+        }
+        Ok(bootstrap_methods)
+    }
 }
 
 pub struct ClassFileParser;
@@ -29,11 +183,13 @@ impl ClassFileParser {
         // Parse class file header
         let magic = read_u32(&mut cursor)?;
         if magic != 0xCAFEBABE {
-            return Err(RuntimeError::InvalidStackState); // Invalid class file
+            return Err(RuntimeError::InvalidClassFile(format!(
+                "bad magic number 0x{magic:08X}"
+            )));
         }
 
-        let _minor_version = read_u16(&mut cursor)?;
-        let _major_version = read_u16(&mut cursor)?;
+        let minor_version = read_u16(&mut cursor)?;
+        let major_version = read_u16(&mut cursor)?;
 
         // Parse constant pool
         let constant_pool_count = read_u16(&mut cursor)?;
@@ -43,597 +199,647 @@ impl ClassFileParser {
         let mut i = 1;
         while i < constant_pool_count {
             let tag = read_u8(&mut cursor)?;
-            match tag {
+            let result = match tag {
                 1 => {
                     // CONSTANT_Utf8
                     let length = read_u16(&mut cursor)?;
-                    let mut bytes = vec![0u8; length as usize];
-                    cursor
-                        .read_exact(&mut bytes)
-                        .map_err(|_| RuntimeError::InvalidStackState)?;
-
-                    // JVM Utf8 format can contain null bytes and modified UTF-8
-                    // For now, replace invalid UTF-8 with replacement characters
-                    let utf8_string = String::from_utf8_lossy(&bytes).into_owned();
-                    constant_pool
-                        .add_utf8(utf8_string)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
-                }
-                3 => {
-                    // CONSTANT_Integer
-                    let value = read_i32(&mut cursor)?;
-                    constant_pool
-                        .add_integer(value)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
-                }
-                4 => {
-                    // CONSTANT_Float
-                    let value = read_f32(&mut cursor)?;
-                    constant_pool
-                        .add_float(value)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
+                    let bytes = read_bytes(&mut cursor, length as usize)?;
+                    let utf8_string =
+                        decode_modified_utf8(&bytes).map_err(RuntimeError::InvalidClassFile)?;
+                    constant_pool.add_utf8(utf8_string)
                 }
+                3 => constant_pool.add_integer(read_i32(&mut cursor)?), // CONSTANT_Integer
+                4 => constant_pool.add_float(read_f32(&mut cursor)?),   // CONSTANT_Float
                 5 => {
                     // CONSTANT_Long
-                    let value = read_i64(&mut cursor)?;
-                    constant_pool
-                        .add_long(value)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
                     // Placeholder is automatically added by add_long method
                     i += 1; // Skip the next index for 8-byte constant
+                    constant_pool.add_long(read_i64(&mut cursor)?)
                 }
                 6 => {
                     // CONSTANT_Double
-                    let value = read_f64(&mut cursor)?;
-                    constant_pool
-                        .add_double(value)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
                     // Placeholder is automatically added by add_double method
                     i += 1; // Skip the next index for 8-byte constant
+                    constant_pool.add_double(read_f64(&mut cursor)?)
                 }
-                7 => {
-                    // CONSTANT_Class
-                    let name_index = read_u16(&mut cursor)?;
-                    constant_pool
-                        .add_class(name_index)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
-                }
-                8 => {
-                    // CONSTANT_String
-                    let string_index = read_u16(&mut cursor)?;
-                    constant_pool
-                        .add_string(string_index)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
-                }
+                7 => constant_pool.add_class(read_u16(&mut cursor)?), // CONSTANT_Class
+                8 => constant_pool.add_string(read_u16(&mut cursor)?), // CONSTANT_String
                 9 => {
                     // CONSTANT_Fieldref
                     let class_index = read_u16(&mut cursor)?;
                     let name_and_type_index = read_u16(&mut cursor)?;
-                    constant_pool
-                        .add_fieldref(class_index, name_and_type_index)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
+                    constant_pool.add_fieldref(class_index, name_and_type_index)
                 }
                 10 => {
                     // CONSTANT_Methodref
                     let class_index = read_u16(&mut cursor)?;
                     let name_and_type_index = read_u16(&mut cursor)?;
-                    constant_pool
-                        .add_methodref(class_index, name_and_type_index)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
-                }
-                12 => {
-                    // CONSTANT_NameAndType
-                    let name_index = read_u16(&mut cursor)?;
-                    let descriptor_index = read_u16(&mut cursor)?;
-                    constant_pool
-                        .add_name_and_type(name_index, descriptor_index)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
+                    constant_pool.add_methodref(class_index, name_and_type_index)
                 }
                 11 => {
                     // CONSTANT_InterfaceMethodref
                     let class_index = read_u16(&mut cursor)?;
                     let name_and_type_index = read_u16(&mut cursor)?;
-                    constant_pool
-                        .add_methodref(class_index, name_and_type_index)
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
+                    constant_pool.add_interface_methodref(class_index, name_and_type_index)
+                }
+                12 => {
+                    // CONSTANT_NameAndType
+                    let name_index = read_u16(&mut cursor)?;
+                    let descriptor_index = read_u16(&mut cursor)?;
+                    constant_pool.add_name_and_type(name_index, descriptor_index)
                 }
                 15 => {
                     // CONSTANT_MethodHandle
-                    let _reference_kind = read_u8(&mut cursor)?;
-                    let _reference_index = read_u16(&mut cursor)?;
-                    // For now, treat as placeholder
-                    constant_pool
-                        .add_placeholder()
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
-                }
-                16 => {
-                    // CONSTANT_MethodType
-                    let _descriptor_index = read_u16(&mut cursor)?;
-                    // For now, treat as placeholder
-                    constant_pool
-                        .add_placeholder()
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
+                    let reference_kind = read_u8(&mut cursor)?;
+                    let reference_index = read_u16(&mut cursor)?;
+                    constant_pool.add_method_handle(reference_kind, reference_index)
+                }
+                16 => constant_pool.add_method_type(read_u16(&mut cursor)?), // CONSTANT_MethodType
+                17 => {
+                    // CONSTANT_Dynamic
+                    let bootstrap_method_attr_index = read_u16(&mut cursor)?;
+                    let name_and_type_index = read_u16(&mut cursor)?;
+                    constant_pool.add_dynamic(bootstrap_method_attr_index, name_and_type_index)
                 }
                 18 => {
                     // CONSTANT_InvokeDynamic
-                    let _bootstrap_method_attr_index = read_u16(&mut cursor)?;
-                    let _name_and_type_index = read_u16(&mut cursor)?;
-                    // For now, treat as placeholder
+                    let bootstrap_method_attr_index = read_u16(&mut cursor)?;
+                    let name_and_type_index = read_u16(&mut cursor)?;
                     constant_pool
-                        .add_placeholder()
-                        .map_err(|_e| RuntimeError::InvalidStackState)?;
+                        .add_invoke_dynamic(bootstrap_method_attr_index, name_and_type_index)
                 }
+                19 => constant_pool.add_module(read_u16(&mut cursor)?), // CONSTANT_Module
+                20 => constant_pool.add_package(read_u16(&mut cursor)?), // CONSTANT_Package
                 _ => {
                     // Unknown constant pool tag
                     return Err(RuntimeError::UnknownConstantPoolTag { tag, index: i });
                 }
-            }
+            };
+            result.map_err(RuntimeError::InvalidClassFile)?;
             i += 1;
         }
 
-        // Skip access flags, this_class, super_class
-        let _access_flags = read_u16(&mut cursor)?;
-        let _this_class = read_u16(&mut cursor)?;
-        let _super_class = read_u16(&mut cursor)?;
+        let access_flags = read_u16(&mut cursor)?;
+        let this_class = read_u16(&mut cursor)?;
+        let super_class = read_u16(&mut cursor)?;
 
-        // Skip interfaces
         let interfaces_count = read_u16(&mut cursor)?;
+        let mut interfaces = Vec::with_capacity(interfaces_count as usize);
         for _ in 0..interfaces_count {
-            let _interface = read_u16(&mut cursor)?;
+            interfaces.push(read_u16(&mut cursor)?);
         }
 
-        // Skip fields
         let fields_count = read_u16(&mut cursor)?;
+        let mut fields = Vec::with_capacity(fields_count as usize);
         for _ in 0..fields_count {
-            let _access_flags = read_u16(&mut cursor)?;
-            let _name_index = read_u16(&mut cursor)?;
-            let _descriptor_index = read_u16(&mut cursor)?;
-            let attributes_count = read_u16(&mut cursor)?;
-            for _ in 0..attributes_count {
-                let _attribute_name_index = read_u16(&mut cursor)?;
-                let attribute_length = read_u32(&mut cursor)?;
-                // Skip attribute data
-                for _ in 0..attribute_length {
-                    read_u8(&mut cursor)?;
-                }
-            }
+            let access_flags = read_u16(&mut cursor)?;
+            let name_index = read_u16(&mut cursor)?;
+            let descriptor_index = read_u16(&mut cursor)?;
+            let attributes = read_attributes(&mut cursor)?;
+            fields.push(FieldInfo {
+                access_flags,
+                name_index,
+                descriptor_index,
+                name: get_utf8_from_pool(&constant_pool, name_index),
+                descriptor: get_utf8_from_pool(&constant_pool, descriptor_index),
+                attributes,
+            });
         }
 
-        // Parse methods to find all methods including main
         let methods_count = read_u16(&mut cursor)?;
-        let mut main_method_bytecode = Vec::new();
-        let mut max_locals = 0;
-        let mut max_stack = 0;
-        let mut methods = HashMap::new();
-
+        let mut methods = Vec::with_capacity(methods_count as usize);
         for _ in 0..methods_count {
-            let _access_flags = read_u16(&mut cursor)?;
+            let access_flags = read_u16(&mut cursor)?;
             let name_index = read_u16(&mut cursor)?;
             let descriptor_index = read_u16(&mut cursor)?;
             let attributes_count = read_u16(&mut cursor)?;
+            let name = get_utf8_from_pool(&constant_pool, name_index);
+            let descriptor = get_utf8_from_pool(&constant_pool, descriptor_index);
 
-            // Get method name and descriptor
-            let method_name = get_utf8_from_pool(&constant_pool, name_index);
-            let method_descriptor = get_utf8_from_pool(&constant_pool, descriptor_index);
-
-            // Check if this is the main method
-            let (is_main_method, is_preferred) =
-                check_is_main_method(&constant_pool, name_index, descriptor_index);
-
-            let mut method_bytecode = Vec::new();
-            let mut method_max_locals = 0;
-            let mut method_max_stack = 0;
-
+            let mut code = None;
+            let mut attributes = Vec::new();
             for _ in 0..attributes_count {
                 let attribute_name_index = read_u16(&mut cursor)?;
                 let attribute_length = read_u32(&mut cursor)?;
 
                 if check_is_code_attribute(&constant_pool, attribute_name_index) {
-                    method_max_stack = read_u16(&mut cursor)? as usize;
-                    method_max_locals = read_u16(&mut cursor)? as usize;
-                    let code_length = read_u32(&mut cursor)?;
-
-                    // Parse bytecode
-                    let mut bytecode = vec![0u8; code_length as usize];
-                    cursor
-                        .read_exact(&mut bytecode)
-                        .map_err(|_| RuntimeError::InvalidStackState)?;
-                    method_bytecode = parse_bytecode(&bytecode)?;
-
-                    // Skip exception table
-                    let exception_table_length = read_u16(&mut cursor)?;
-                    for _ in 0..exception_table_length {
-                        let _start_pc = read_u16(&mut cursor)?;
-                        let _end_pc = read_u16(&mut cursor)?;
-                        let _handler_pc = read_u16(&mut cursor)?;
-                        let _catch_type = read_u16(&mut cursor)?;
-                    }
-
-                    // Skip code attributes
-                    let code_attributes_count = read_u16(&mut cursor)?;
-                    for _ in 0..code_attributes_count {
-                        let _code_attribute_name_index = read_u16(&mut cursor)?;
-                        let code_attribute_length = read_u32(&mut cursor)?;
-                        for _ in 0..code_attribute_length {
-                            read_u8(&mut cursor)?;
-                        }
-                    }
+                    let method = format!(
+                        "{}.{name}{descriptor}",
+                        constant_pool.get_class_name(this_class).unwrap_or_default()
+                    );
+                    code = Some(
+                        read_code_attribute(&mut cursor, attribute_name_index)
+                            .map_err(|error| error.in_method(&method))?,
+                    );
                 } else {
-                    // Skip other attributes
-                    for _ in 0..attribute_length {
-                        read_u8(&mut cursor)?;
-                    }
+                    attributes.push(AttributeInfo {
+                        name_index: attribute_name_index,
+                        info: read_bytes(&mut cursor, attribute_length as usize)?,
+                    });
                 }
             }
 
-            // Store method information
-            if !method_bytecode.is_empty() {
-                let method_info = MethodInfo {
-                    name: method_name.clone(),
-                    descriptor: method_descriptor.clone(),
-                    bytecode: method_bytecode.clone(),
-                    max_locals: method_max_locals,
-                    max_stack: method_max_stack,
-                };
-                methods.insert(method_name.clone(), method_info);
-
-                // Set as main method if applicable
-                if is_main_method && (main_method_bytecode.is_empty() || is_preferred) {
-                    main_method_bytecode = method_bytecode;
-                    max_locals = method_max_locals;
-                    max_stack = method_max_stack;
-                }
-            }
+            methods.push(MethodInfo {
+                access_flags,
+                name_index,
+                descriptor_index,
+                name,
+                descriptor,
+                code,
+                attributes,
+            });
         }
 
+        let attributes = read_attributes(&mut cursor)?;
+
         Ok(ClassFile {
+            minor_version,
+            major_version,
             constant_pool,
-            main_method_bytecode,
-            max_locals,
-            max_stack,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            fields,
             methods,
+            attributes,
         })
     }
 }
 
-fn parse_bytecode(bytecode: &[u8]) -> Result<Vec<JvmInstruction>, RuntimeError> {
+fn read_code_attribute(
+    cursor: &mut Cursor<&[u8]>,
+    name_index: u16,
+) -> Result<CodeAttribute, RuntimeError> {
+    let max_stack = read_u16(cursor)?;
+    let max_locals = read_u16(cursor)?;
+    let code_length = read_u32(cursor)?;
+    let code = read_bytes(cursor, code_length as usize)?;
+    let bytecode = parse_bytecode(&code)?;
+
+    let exception_table_length = read_u16(cursor)?;
+    let mut exception_table = Vec::with_capacity(exception_table_length as usize);
+    for _ in 0..exception_table_length {
+        exception_table.push(ExceptionTableEntry {
+            start_pc: read_u16(cursor)?,
+            end_pc: read_u16(cursor)?,
+            handler_pc: read_u16(cursor)?,
+            catch_type: read_u16(cursor)?,
+        });
+    }
+
+    let attributes = read_attributes(cursor)?;
+
+    Ok(CodeAttribute {
+        name_index,
+        max_stack,
+        max_locals,
+        bytecode,
+        exception_table,
+        attributes,
+    })
+}
+
+fn read_attributes(cursor: &mut Cursor<&[u8]>) -> Result<Vec<AttributeInfo>, RuntimeError> {
+    let attributes_count = read_u16(cursor)?;
+    let mut attributes = Vec::with_capacity(attributes_count as usize);
+    for _ in 0..attributes_count {
+        let name_index = read_u16(cursor)?;
+        let length = read_u32(cursor)?;
+        attributes.push(AttributeInfo {
+            name_index,
+            info: read_bytes(cursor, length as usize)?,
+        });
+    }
+    Ok(attributes)
+}
+
+/// Decode the raw contents of a `Code` attribute into instructions.
+///
+/// Branch offsets are resolved to the index of the target instruction, so the
+/// result can be executed directly; `assemble_bytecode` performs the inverse.
+/// A branch that does not land on an instruction fails with a `VerifyError`
+/// whose method is filled in by the caller.
+pub fn parse_bytecode(bytecode: &[u8]) -> Result<Vec<JvmInstruction>, RuntimeError> {
     let mut instructions = Vec::new();
+    let mut offsets = Vec::new();
     let mut i = 0;
 
     while i < bytecode.len() {
+        let start = i;
         let opcode = bytecode[i];
         i += 1;
 
-        match opcode {
-            0x00 => {
-                // nop - do nothing
-                instructions.push(JvmInstruction::Nop);
-            }
-            0x02 => instructions.push(JvmInstruction::IconstM1),
-            0x03 => instructions.push(JvmInstruction::Iconst0),
-            0x04 => instructions.push(JvmInstruction::Iconst1),
-            0x05 => instructions.push(JvmInstruction::Iconst2),
-            0x06 => instructions.push(JvmInstruction::Iconst3),
-            0x07 => instructions.push(JvmInstruction::Iconst4),
-            0x08 => instructions.push(JvmInstruction::Iconst5),
-            0x10 => {
-                // bipush
-                if i >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let value = bytecode[i] as i8;
-                instructions.push(JvmInstruction::Bipush(value));
-                i += 1;
-            }
-            0x11 => {
-                // sipush
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let value = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Sipush(value as i16));
-                i += 2;
-            }
-            0x12 => {
-                // ldc
-                if i >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = bytecode[i] as u16;
-                instructions.push(JvmInstruction::Ldc(index));
-                i += 1;
-            }
-            0x14 => {
-                // ldc2_w
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Ldc2W(index));
-                i += 2;
-            }
-            0x15 => {
-                // iload
-                if i >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = bytecode[i];
-                instructions.push(JvmInstruction::Iload(index));
-                i += 1;
-            }
-            0x1A => instructions.push(JvmInstruction::Iload0),
-            0x1B => instructions.push(JvmInstruction::Iload1),
-            0x1C => instructions.push(JvmInstruction::Iload2),
-            0x1D => instructions.push(JvmInstruction::Iload3),
-
-            // Reference type local variable operations
-            0x19 => {
-                // aload
-                if i >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = bytecode[i];
-                instructions.push(JvmInstruction::Aload(index));
-                i += 1;
-            }
-            0x2A => instructions.push(JvmInstruction::Aload0),
-            0x2B => instructions.push(JvmInstruction::Aload1),
-            0x2C => instructions.push(JvmInstruction::Aload2),
-            0x2D => instructions.push(JvmInstruction::Aload3),
-            0x3A => {
-                // astore
-                if i >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = bytecode[i];
-                instructions.push(JvmInstruction::Astore(index));
-                i += 1;
-            }
-            0x4B => instructions.push(JvmInstruction::Astore0),
-            0x4C => instructions.push(JvmInstruction::Astore1),
-            0x4D => instructions.push(JvmInstruction::Astore2),
-            0x4E => instructions.push(JvmInstruction::Astore3),
-
-            0x36 => {
-                // istore
-                if i >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = bytecode[i];
-                instructions.push(JvmInstruction::Istore(index));
-                i += 1;
-            }
-            0x3B => instructions.push(JvmInstruction::Istore0),
-            0x3C => instructions.push(JvmInstruction::Istore1),
-            0x3D => instructions.push(JvmInstruction::Istore2),
-            0x3E => instructions.push(JvmInstruction::Istore3),
-            0x57 => instructions.push(JvmInstruction::Pop),
-            0x59 => instructions.push(JvmInstruction::Dup),
-            0x5F => instructions.push(JvmInstruction::Swap),
-            0x60 => instructions.push(JvmInstruction::Iadd),
-            0x64 => instructions.push(JvmInstruction::Isub),
-            0x68 => instructions.push(JvmInstruction::Imul),
-            0x6C => instructions.push(JvmInstruction::Idiv),
-            0x70 => instructions.push(JvmInstruction::Irem),
-            0x63 => instructions.push(JvmInstruction::Dadd),
-            0x67 => instructions.push(JvmInstruction::Dsub),
-            0x6B => instructions.push(JvmInstruction::Dmul),
-            0x6F => instructions.push(JvmInstruction::Ddiv),
-            0x87 => instructions.push(JvmInstruction::I2d),
-            0x8E => instructions.push(JvmInstruction::D2i),
-
-            // Local variable operations - long
-            0x1E => instructions.push(JvmInstruction::Lload0),
-            0x1F => instructions.push(JvmInstruction::Lload1),
-            0x20 => instructions.push(JvmInstruction::Lload2),
-            0x21 => instructions.push(JvmInstruction::Lload3),
-
-            // Local variable operations - double
-            0x18 => {
-                // dload
-                if i >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = bytecode[i];
-                instructions.push(JvmInstruction::Dload(index));
-                i += 1;
-            }
-            0x26 => instructions.push(JvmInstruction::Dload0),
-            0x27 => instructions.push(JvmInstruction::Dload1),
-            0x28 => instructions.push(JvmInstruction::Dload2),
-            0x29 => instructions.push(JvmInstruction::Dload3),
-            0x39 => {
-                // dstore
-                if i >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = bytecode[i];
-                instructions.push(JvmInstruction::Dstore(index));
-                i += 1;
-            }
-            0x47 => instructions.push(JvmInstruction::Dstore0),
-            0x48 => instructions.push(JvmInstruction::Dstore1),
-            0x49 => instructions.push(JvmInstruction::Dstore2),
-            0x4A => instructions.push(JvmInstruction::Dstore3),
-
-            0x3F => instructions.push(JvmInstruction::Lstore0),
-            0x40 => instructions.push(JvmInstruction::Lstore1),
-            0x41 => instructions.push(JvmInstruction::Lstore2),
-            0x42 => instructions.push(JvmInstruction::Lstore3),
-
-            0xA7 => {
-                // goto
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let offset = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Goto(offset));
-                i += 2;
-            }
-            0x99 => {
-                // ifeq
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let offset = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Ifeq(offset));
-                i += 2;
-            }
-            0x9A => {
-                // ifne
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let offset = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Ifne(offset));
-                i += 2;
-            }
-            0x9B => {
-                // iflt
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let offset = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Iflt(offset));
-                i += 2;
-            }
-            0x9C => {
-                // ifge
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
+        let instruction = match opcode {
+            0x00 => JvmInstruction::Nop,
+            0x01 => JvmInstruction::AconstNull,
+            0x02 => JvmInstruction::IconstM1,
+            0x03 => JvmInstruction::Iconst0,
+            0x04 => JvmInstruction::Iconst1,
+            0x05 => JvmInstruction::Iconst2,
+            0x06 => JvmInstruction::Iconst3,
+            0x07 => JvmInstruction::Iconst4,
+            0x08 => JvmInstruction::Iconst5,
+            0x09 => JvmInstruction::Lconst0,
+            0x0A => JvmInstruction::Lconst1,
+            0x0B => JvmInstruction::Fconst0,
+            0x0C => JvmInstruction::Fconst1,
+            0x0D => JvmInstruction::Fconst2,
+            0x0E => JvmInstruction::Dconst0,
+            0x0F => JvmInstruction::Dconst1,
+            0x10 => JvmInstruction::Bipush(read_operand_u8(bytecode, &mut i)? as i8),
+            0x11 => JvmInstruction::Sipush(read_operand_u16(bytecode, &mut i)? as i16),
+            0x12 => JvmInstruction::Ldc(read_operand_u8(bytecode, &mut i)? as u16),
+            0x13 => JvmInstruction::LdcW(read_operand_u16(bytecode, &mut i)?),
+            0x14 => JvmInstruction::Ldc2W(read_operand_u16(bytecode, &mut i)?),
+
+            // Local variable loads
+            0x15 => JvmInstruction::Iload(read_operand_u8(bytecode, &mut i)?.into()),
+            0x16 => JvmInstruction::Lload(read_operand_u8(bytecode, &mut i)?.into()),
+            0x17 => JvmInstruction::Fload(read_operand_u8(bytecode, &mut i)?.into()),
+            0x18 => JvmInstruction::Dload(read_operand_u8(bytecode, &mut i)?.into()),
+            0x19 => JvmInstruction::Aload(read_operand_u8(bytecode, &mut i)?.into()),
+            0x1A => JvmInstruction::Iload0,
+            0x1B => JvmInstruction::Iload1,
+            0x1C => JvmInstruction::Iload2,
+            0x1D => JvmInstruction::Iload3,
+            0x1E => JvmInstruction::Lload0,
+            0x1F => JvmInstruction::Lload1,
+            0x20 => JvmInstruction::Lload2,
+            0x21 => JvmInstruction::Lload3,
+            0x22 => JvmInstruction::Fload0,
+            0x23 => JvmInstruction::Fload1,
+            0x24 => JvmInstruction::Fload2,
+            0x25 => JvmInstruction::Fload3,
+            0x26 => JvmInstruction::Dload0,
+            0x27 => JvmInstruction::Dload1,
+            0x28 => JvmInstruction::Dload2,
+            0x29 => JvmInstruction::Dload3,
+            0x2A => JvmInstruction::Aload0,
+            0x2B => JvmInstruction::Aload1,
+            0x2C => JvmInstruction::Aload2,
+            0x2D => JvmInstruction::Aload3,
+
+            // Array loads
+            0x2E => JvmInstruction::Iaload,
+            0x2F => JvmInstruction::Laload,
+            0x30 => JvmInstruction::Faload,
+            0x31 => JvmInstruction::Daload,
+            0x32 => JvmInstruction::Aaload,
+            0x33 => JvmInstruction::Baload,
+            0x34 => JvmInstruction::Caload,
+            0x35 => JvmInstruction::Saload,
+
+            // Local variable stores
+            0x36 => JvmInstruction::Istore(read_operand_u8(bytecode, &mut i)?.into()),
+            0x37 => JvmInstruction::Lstore(read_operand_u8(bytecode, &mut i)?.into()),
+            0x38 => JvmInstruction::Fstore(read_operand_u8(bytecode, &mut i)?.into()),
+            0x39 => JvmInstruction::Dstore(read_operand_u8(bytecode, &mut i)?.into()),
+            0x3A => JvmInstruction::Astore(read_operand_u8(bytecode, &mut i)?.into()),
+            0x3B => JvmInstruction::Istore0,
+            0x3C => JvmInstruction::Istore1,
+            0x3D => JvmInstruction::Istore2,
+            0x3E => JvmInstruction::Istore3,
+            0x3F => JvmInstruction::Lstore0,
+            0x40 => JvmInstruction::Lstore1,
+            0x41 => JvmInstruction::Lstore2,
+            0x42 => JvmInstruction::Lstore3,
+            0x43 => JvmInstruction::Fstore0,
+            0x44 => JvmInstruction::Fstore1,
+            0x45 => JvmInstruction::Fstore2,
+            0x46 => JvmInstruction::Fstore3,
+            0x47 => JvmInstruction::Dstore0,
+            0x48 => JvmInstruction::Dstore1,
+            0x49 => JvmInstruction::Dstore2,
+            0x4A => JvmInstruction::Dstore3,
+            0x4B => JvmInstruction::Astore0,
+            0x4C => JvmInstruction::Astore1,
+            0x4D => JvmInstruction::Astore2,
+            0x4E => JvmInstruction::Astore3,
+
+            // Array stores
+            0x4F => JvmInstruction::Iastore,
+            0x50 => JvmInstruction::Lastore,
+            0x51 => JvmInstruction::Fastore,
+            0x52 => JvmInstruction::Dastore,
+            0x53 => JvmInstruction::Aastore,
+            0x54 => JvmInstruction::Bastore,
+            0x55 => JvmInstruction::Castore,
+            0x56 => JvmInstruction::Sastore,
+
+            // Stack operations
+            0x57 => JvmInstruction::Pop,
+            0x58 => JvmInstruction::Pop2,
+            0x59 => JvmInstruction::Dup,
+            0x5A => JvmInstruction::DupX1,
+            0x5B => JvmInstruction::DupX2,
+            0x5C => JvmInstruction::Dup2,
+            0x5D => JvmInstruction::Dup2X1,
+            0x5E => JvmInstruction::Dup2X2,
+            0x5F => JvmInstruction::Swap,
+
+            // Arithmetic
+            0x60 => JvmInstruction::Iadd,
+            0x61 => JvmInstruction::Ladd,
+            0x62 => JvmInstruction::Fadd,
+            0x63 => JvmInstruction::Dadd,
+            0x64 => JvmInstruction::Isub,
+            0x65 => JvmInstruction::Lsub,
+            0x66 => JvmInstruction::Fsub,
+            0x67 => JvmInstruction::Dsub,
+            0x68 => JvmInstruction::Imul,
+            0x69 => JvmInstruction::Lmul,
+            0x6A => JvmInstruction::Fmul,
+            0x6B => JvmInstruction::Dmul,
+            0x6C => JvmInstruction::Idiv,
+            0x6D => JvmInstruction::Ldiv,
+            0x6E => JvmInstruction::Fdiv,
+            0x6F => JvmInstruction::Ddiv,
+            0x70 => JvmInstruction::Irem,
+            0x71 => JvmInstruction::Lrem,
+            0x72 => JvmInstruction::Frem,
+            0x73 => JvmInstruction::Drem,
+            0x74 => JvmInstruction::Ineg,
+            0x75 => JvmInstruction::Lneg,
+            0x76 => JvmInstruction::Fneg,
+            0x77 => JvmInstruction::Dneg,
+
+            // Shifts and bitwise operations
+            0x78 => JvmInstruction::Ishl,
+            0x79 => JvmInstruction::Lshl,
+            0x7A => JvmInstruction::Ishr,
+            0x7B => JvmInstruction::Lshr,
+            0x7C => JvmInstruction::Iushr,
+            0x7D => JvmInstruction::Lushr,
+            0x7E => JvmInstruction::Iand,
+            0x7F => JvmInstruction::Land,
+            0x80 => JvmInstruction::Ior,
+            0x81 => JvmInstruction::Lor,
+            0x82 => JvmInstruction::Ixor,
+            0x83 => JvmInstruction::Lxor,
+            0x84 => JvmInstruction::Iinc(
+                read_operand_u8(bytecode, &mut i)?.into(),
+                (read_operand_u8(bytecode, &mut i)? as i8).into(),
+            ),
+
+            // Conversions
+            0x85 => JvmInstruction::I2l,
+            0x86 => JvmInstruction::I2f,
+            0x87 => JvmInstruction::I2d,
+            0x88 => JvmInstruction::L2i,
+            0x89 => JvmInstruction::L2f,
+            0x8A => JvmInstruction::L2d,
+            0x8B => JvmInstruction::F2i,
+            0x8C => JvmInstruction::F2l,
+            0x8D => JvmInstruction::F2d,
+            0x8E => JvmInstruction::D2i,
+            0x8F => JvmInstruction::D2l,
+            0x90 => JvmInstruction::D2f,
+            0x91 => JvmInstruction::I2b,
+            0x92 => JvmInstruction::I2c,
+            0x93 => JvmInstruction::I2s,
+
+            // Comparisons
+            0x94 => JvmInstruction::Lcmp,
+            0x95 => JvmInstruction::Fcmpl,
+            0x96 => JvmInstruction::Fcmpg,
+            0x97 => JvmInstruction::Dcmpl,
+            0x98 => JvmInstruction::Dcmpg,
+
+            // Control flow (raw relative offsets, resolved below)
+            0x99 => JvmInstruction::Ifeq(read_operand_u16(bytecode, &mut i)?),
+            0x9A => JvmInstruction::Ifne(read_operand_u16(bytecode, &mut i)?),
+            0x9B => JvmInstruction::Iflt(read_operand_u16(bytecode, &mut i)?),
+            0x9C => JvmInstruction::Ifge(read_operand_u16(bytecode, &mut i)?),
+            0x9D => JvmInstruction::Ifgt(read_operand_u16(bytecode, &mut i)?),
+            0x9E => JvmInstruction::Ifle(read_operand_u16(bytecode, &mut i)?),
+            0x9F => JvmInstruction::IfIcmpeq(read_operand_u16(bytecode, &mut i)?),
+            0xA0 => JvmInstruction::IfIcmpne(read_operand_u16(bytecode, &mut i)?),
+            0xA1 => JvmInstruction::IfIcmplt(read_operand_u16(bytecode, &mut i)?),
+            0xA2 => JvmInstruction::IfIcmpge(read_operand_u16(bytecode, &mut i)?),
+            0xA3 => JvmInstruction::IfIcmpgt(read_operand_u16(bytecode, &mut i)?),
+            0xA4 => JvmInstruction::IfIcmple(read_operand_u16(bytecode, &mut i)?),
+            0xA5 => JvmInstruction::IfAcmpeq(read_operand_u16(bytecode, &mut i)?),
+            0xA6 => JvmInstruction::IfAcmpne(read_operand_u16(bytecode, &mut i)?),
+            0xA7 => JvmInstruction::Goto(read_operand_u16(bytecode, &mut i)?),
+            0xAA => {
+                skip_switch_padding(bytecode, &mut i)?;
+                let default = read_switch_offset(bytecode, &mut i, start)?;
+                let low = read_operand_i32(bytecode, &mut i)?;
+                let high = read_operand_i32(bytecode, &mut i)?;
+                if high < low {
+                    return Err(RuntimeError::InvalidClassFile(format!(
+                        "tableswitch at offset {start} has low {low} above high {high}"
+                    )));
+                }
+                let targets = (low..=high)
+                    .map(|_| read_switch_offset(bytecode, &mut i, start))
+                    .collect::<Result<_, _>>()?;
+                JvmInstruction::Tableswitch {
+                    default,
+                    low,
+                    targets,
                 }
-                let offset = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Ifge(offset));
-                i += 2;
             }
-            0x9D => {
-                // ifgt
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let offset = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Ifgt(offset));
-                i += 2;
-            }
-            0x9E => {
-                // ifle
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let offset = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Ifle(offset));
-                i += 2;
+            0xAB => {
+                skip_switch_padding(bytecode, &mut i)?;
+                let default = read_switch_offset(bytecode, &mut i, start)?;
+                let count = read_operand_i32(bytecode, &mut i)?;
+                let count = usize::try_from(count).map_err(|_| {
+                    RuntimeError::InvalidClassFile(format!(
+                        "lookupswitch at offset {start} has {count} pairs"
+                    ))
+                })?;
+                let mut pairs = Vec::new();
+                for _ in 0..count {
+                    let key = read_operand_i32(bytecode, &mut i)?;
+                    pairs.push((key, read_switch_offset(bytecode, &mut i, start)?));
+                }
+                if !pairs.is_sorted_by_key(|(key, _)| *key) {
+                    return Err(RuntimeError::InvalidClassFile(format!(
+                        "lookupswitch at offset {start} has unsorted keys"
+                    )));
+                }
+                JvmInstruction::Lookupswitch { default, pairs }
             }
-            0xB1 => instructions.push(JvmInstruction::Return),
-            0xAC => instructions.push(JvmInstruction::Ireturn),
-            0xB2 => {
-                // getstatic
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Getstatic(index));
-                i += 2;
-            }
-            0xB6 => {
-                // invokevirtual
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Invokevirtual(index));
-                i += 2;
+
+            // Returns
+            0xAC => JvmInstruction::Ireturn,
+            0xAD => JvmInstruction::Lreturn,
+            0xAE => JvmInstruction::Freturn,
+            0xAF => JvmInstruction::Dreturn,
+            0xB0 => JvmInstruction::Areturn,
+            0xB1 => JvmInstruction::Return,
+
+            // Fields, invocation and objects
+            0xB2 => JvmInstruction::Getstatic(read_operand_u16(bytecode, &mut i)?),
+            0xB3 => JvmInstruction::Putstatic(read_operand_u16(bytecode, &mut i)?),
+            0xB4 => JvmInstruction::Getfield(read_operand_u16(bytecode, &mut i)?),
+            0xB5 => JvmInstruction::Putfield(read_operand_u16(bytecode, &mut i)?),
+            0xB6 => JvmInstruction::Invokevirtual(read_operand_u16(bytecode, &mut i)?),
+            0xB7 => JvmInstruction::Invokespecial(read_operand_u16(bytecode, &mut i)?),
+            0xB8 => JvmInstruction::Invokestatic(read_operand_u16(bytecode, &mut i)?),
+            0xBA => {
+                // invokedynamic: index followed by two reserved zero bytes
+                let index = read_operand_u16(bytecode, &mut i)?;
+                read_operand_u16(bytecode, &mut i)?;
+                JvmInstruction::Invokedynamic(index)
             }
-            0xB8 => {
-                // invokestatic
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Invokestatic(index));
-                i += 2;
+            0xB9 => {
+                // invokeinterface: index, argument slot count and a zero byte
+                let index = read_operand_u16(bytecode, &mut i)?;
+                let count = read_operand_u8(bytecode, &mut i)?;
+                read_operand_u8(bytecode, &mut i)?;
+                JvmInstruction::Invokeinterface(index, count)
             }
-            0x0B => instructions.push(JvmInstruction::Lconst0), // Pushes the long constant 0L onto the stack
-            0x0E => instructions.push(JvmInstruction::Dconst0),
-            0x0F => instructions.push(JvmInstruction::Dconst1),
-            0xB7 => {
-                // invokespecial
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
+            0xBB => JvmInstruction::New(read_operand_u16(bytecode, &mut i)?),
+            0xBC => JvmInstruction::Newarray(read_operand_u8(bytecode, &mut i)?),
+            0xBD => JvmInstruction::Anewarray(read_operand_u16(bytecode, &mut i)?),
+            0xBE => JvmInstruction::Arraylength,
+            0xBF => JvmInstruction::Athrow,
+            0xC0 => JvmInstruction::Checkcast(read_operand_u16(bytecode, &mut i)?),
+            0xC1 => JvmInstruction::Instanceof(read_operand_u16(bytecode, &mut i)?),
+            0xC6 => JvmInstruction::Ifnull(read_operand_u16(bytecode, &mut i)?),
+            0xC7 => JvmInstruction::Ifnonnull(read_operand_u16(bytecode, &mut i)?),
+            0xC4 => {
+                // `wide` gives the following load, store or iinc a two-byte
+                // local index, and iinc a two-byte constant
+                let opcode = read_operand_u8(bytecode, &mut i)?;
+                let index = read_operand_u16(bytecode, &mut i)?;
+                match opcode {
+                    0x15 => JvmInstruction::Iload(index),
+                    0x16 => JvmInstruction::Lload(index),
+                    0x17 => JvmInstruction::Fload(index),
+                    0x18 => JvmInstruction::Dload(index),
+                    0x19 => JvmInstruction::Aload(index),
+                    0x36 => JvmInstruction::Istore(index),
+                    0x37 => JvmInstruction::Lstore(index),
+                    0x38 => JvmInstruction::Fstore(index),
+                    0x39 => JvmInstruction::Dstore(index),
+                    0x3A => JvmInstruction::Astore(index),
+                    0x84 => JvmInstruction::Iinc(index, read_operand_u16(bytecode, &mut i)? as i16),
+                    _ => return Err(RuntimeError::InvalidOpcode(opcode)),
                 }
-                let index = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::Invokespecial(index));
-                i += 2;
-            }
-            0xBB => {
-                // new
-                if i + 1 >= bytecode.len() {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                let index = ((bytecode[i] as u16) << 8) | (bytecode[i + 1] as u16);
-                instructions.push(JvmInstruction::New(index));
-                i += 2;
-            }
-            _ => {
-                // Unknown opcode, skip for now
-                eprintln!(
-                    "Warning: Unknown opcode 0x{:02X} at position {}",
-                    opcode,
-                    i - 1
-                );
             }
-        }
+            0xC5 => JvmInstruction::Multianewarray(
+                read_operand_u16(bytecode, &mut i)?,
+                read_operand_u8(bytecode, &mut i)?,
+            ),
+
+            _ => return Err(RuntimeError::InvalidOpcode(opcode)),
+        };
+
+        offsets.push(start);
+        instructions.push(instruction);
     }
 
+    resolve_branch_targets(&mut instructions, &offsets)?;
     Ok(instructions)
 }
 
-fn check_is_main_method(
-    constant_pool: &ConstantPool,
-    name_index: u16,
-    descriptor_index: u16,
-) -> (bool, bool) {
-    let entries = constant_pool.entries();
-
-    // Check method name
-    if let Some(ConstantPoolEntry::Utf8(name)) = entries.get((name_index - 1) as usize) {
-        if name != "main" {
-            return (false, false);
+/// Replace the relative byte offsets of branch instructions with the index of
+/// the instruction they jump to.
+///
+/// Offsets are added modulo 2^16: a method's code is shorter than 64 KiB, so
+/// this is exact for the 16-bit offsets of ordinary branches and for switch
+/// offsets, which `read_switch_offset` has already checked to be in range.
+fn resolve_branch_targets(
+    instructions: &mut [JvmInstruction],
+    offsets: &[usize],
+) -> Result<(), RuntimeError> {
+    let index_by_offset: HashMap<usize, usize> = offsets
+        .iter()
+        .enumerate()
+        .map(|(index, &offset)| (offset, index))
+        .collect();
+
+    for (instruction, &offset) in instructions.iter_mut().zip(offsets) {
+        for target in instruction.branch_targets_mut() {
+            let destination = (offset as u16).wrapping_add(*target) as usize;
+            let index =
+                index_by_offset
+                    .get(&destination)
+                    .ok_or_else(|| RuntimeError::VerifyError {
+                        method: String::new(),
+                        offset,
+                        message: format!(
+                            "branch target {destination} is not an instruction boundary"
+                        ),
+                    })?;
+            *target = *index as u16;
         }
-    } else {
-        return (false, false);
     }
 
-    // Check method descriptor
-    if let Some(ConstantPoolEntry::Utf8(descriptor)) = entries.get((descriptor_index - 1) as usize)
-    {
-        match descriptor.as_str() {
-            "()V" => (true, true),                     // Kotlin main - preferred
-            "([Ljava/lang/String;)V" => (true, false), // Java main - fallback
-            _ => (false, false),
-        }
-    } else {
-        (false, false)
+    Ok(())
+}
+
+/// Skip the zero padding that aligns switch operands to a multiple of four
+/// bytes from the start of the method
+fn skip_switch_padding(bytecode: &[u8], i: &mut usize) -> Result<(), RuntimeError> {
+    while !i.is_multiple_of(4) {
+        read_operand_u8(bytecode, i)?;
     }
+    Ok(())
 }
 
-fn check_is_code_attribute(constant_pool: &ConstantPool, attribute_name_index: u16) -> bool {
-    let entries = constant_pool.entries();
-
-    if let Some(ConstantPoolEntry::Utf8(attr_name)) =
-        entries.get((attribute_name_index - 1) as usize)
-    {
-        attr_name == "Code"
-    } else {
-        false
+/// Read a 32-bit switch offset, keeping it in the 16-bit form of other
+/// branch offsets once it is known to stay within the method
+fn read_switch_offset(bytecode: &[u8], i: &mut usize, start: usize) -> Result<u16, RuntimeError> {
+    let offset = read_operand_i32(bytecode, i)?;
+    let destination = start as i64 + offset as i64;
+    if !(0..bytecode.len() as i64).contains(&destination) {
+        return Err(RuntimeError::VerifyError {
+            method: String::new(),
+            offset: start,
+            message: format!("switch target {destination} is outside the method"),
+        });
     }
+    Ok(offset as u16)
+}
+
+fn read_operand_i32(bytecode: &[u8], i: &mut usize) -> Result<i32, RuntimeError> {
+    let mut bytes = [0; 4];
+    for byte in &mut bytes {
+        *byte = read_operand_u8(bytecode, i)?;
+    }
+    Ok(i32::from_be_bytes(bytes))
+}
+
+fn read_operand_u8(bytecode: &[u8], i: &mut usize) -> Result<u8, RuntimeError> {
+    let value = *bytecode
+        .get(*i)
+        .ok_or_else(|| RuntimeError::InvalidClassFile("truncated instruction".to_string()))?;
+    *i += 1;
+    Ok(value)
+}
+
+fn read_operand_u16(bytecode: &[u8], i: &mut usize) -> Result<u16, RuntimeError> {
+    let high = read_operand_u8(bytecode, i)?;
+    let low = read_operand_u8(bytecode, i)?;
+    Ok(u16::from_be_bytes([high, low]))
+}
+
+fn check_is_code_attribute(constant_pool: &ConstantPool, attribute_name_index: u16) -> bool {
+    constant_pool.get_utf8(attribute_name_index) == Some("Code")
+}
+
+fn unexpected_end_of_file() -> RuntimeError {
+    RuntimeError::InvalidClassFile("unexpected end of file".to_string())
+}
+
+fn read_bytes(cursor: &mut Cursor<&[u8]>, length: usize) -> Result<Vec<u8>, RuntimeError> {
+    let mut buf = vec![0u8; length];
+    cursor
+        .read_exact(&mut buf)
+        .map_err(|_| unexpected_end_of_file())?;
+    Ok(buf)
 }
 
 fn read_u8(cursor: &mut Cursor<&[u8]>) -> Result<u8, RuntimeError> {
     let mut buf = [0u8; 1];
     cursor
         .read_exact(&mut buf)
-        .map_err(|_| RuntimeError::InvalidStackState)?;
+        .map_err(|_| unexpected_end_of_file())?;
     Ok(buf[0])
 }
 
@@ -641,7 +847,7 @@ fn read_u16(cursor: &mut Cursor<&[u8]>) -> Result<u16, RuntimeError> {
     let mut buf = [0u8; 2];
     cursor
         .read_exact(&mut buf)
-        .map_err(|_| RuntimeError::InvalidStackState)?;
+        .map_err(|_| unexpected_end_of_file())?;
     Ok(u16::from_be_bytes(buf))
 }
 
@@ -649,7 +855,7 @@ fn read_u32(cursor: &mut Cursor<&[u8]>) -> Result<u32, RuntimeError> {
     let mut buf = [0u8; 4];
     cursor
         .read_exact(&mut buf)
-        .map_err(|_| RuntimeError::InvalidStackState)?;
+        .map_err(|_| unexpected_end_of_file())?;
     Ok(u32::from_be_bytes(buf))
 }
 
@@ -657,7 +863,7 @@ fn read_i32(cursor: &mut Cursor<&[u8]>) -> Result<i32, RuntimeError> {
     let mut buf = [0u8; 4];
     cursor
         .read_exact(&mut buf)
-        .map_err(|_| RuntimeError::InvalidStackState)?;
+        .map_err(|_| unexpected_end_of_file())?;
     Ok(i32::from_be_bytes(buf))
 }
 
@@ -665,7 +871,7 @@ fn read_f32(cursor: &mut Cursor<&[u8]>) -> Result<f32, RuntimeError> {
     let mut buf = [0u8; 4];
     cursor
         .read_exact(&mut buf)
-        .map_err(|_| RuntimeError::InvalidStackState)?;
+        .map_err(|_| unexpected_end_of_file())?;
     Ok(f32::from_be_bytes(buf))
 }
 
@@ -673,7 +879,7 @@ fn read_i64(cursor: &mut Cursor<&[u8]>) -> Result<i64, RuntimeError> {
     let mut buf = [0u8; 8];
     cursor
         .read_exact(&mut buf)
-        .map_err(|_| RuntimeError::InvalidStackState)?;
+        .map_err(|_| unexpected_end_of_file())?;
     Ok(i64::from_be_bytes(buf))
 }
 
@@ -681,23 +887,13 @@ fn read_f64(cursor: &mut Cursor<&[u8]>) -> Result<f64, RuntimeError> {
     let mut buf = [0u8; 8];
     cursor
         .read_exact(&mut buf)
-        .map_err(|_| RuntimeError::InvalidStackState)?;
+        .map_err(|_| unexpected_end_of_file())?;
     Ok(f64::from_be_bytes(buf))
 }
 
 fn get_utf8_from_pool(constant_pool: &ConstantPool, index: u16) -> String {
-    if index == 0 {
-        return String::new();
-    }
-
-    let entries = constant_pool.entries();
-    let actual_index = (index - 1) as usize;
-
-    if actual_index < entries.len() {
-        if let ConstantPoolEntry::Utf8(s) = &entries[actual_index] {
-            return s.clone();
-        }
-    }
-
-    String::new()
+    constant_pool
+        .get_utf8(index)
+        .map(str::to_string)
+        .unwrap_or_default()
 }
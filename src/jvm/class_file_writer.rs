@@ -0,0 +1,1040 @@
+use super::class_file_parser::{AttributeInfo, ClassFile, CodeAttribute, FieldInfo, MethodInfo};
+use super::jvm_types::{ConstantPool, ConstantPoolEntry, JvmInstruction, encode_modified_utf8};
+
+/// Serializes a parsed or constructed `ClassFile` back into class file bytes
+pub struct ClassFileWriter;
+
+impl ClassFileWriter {
+    pub fn write(class_file: &ClassFile) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+
+        // Magic number and version
+        bytes.extend_from_slice(&0xCAFEBABEu32.to_be_bytes());
+        bytes.extend_from_slice(&class_file.minor_version.to_be_bytes());
+        bytes.extend_from_slice(&class_file.major_version.to_be_bytes());
+
+        write_constant_pool(&mut bytes, &class_file.constant_pool)?;
+
+        bytes.extend_from_slice(&class_file.access_flags.to_be_bytes());
+        bytes.extend_from_slice(&class_file.this_class.to_be_bytes());
+        bytes.extend_from_slice(&class_file.super_class.to_be_bytes());
+
+        write_count(&mut bytes, class_file.interfaces.len(), "interfaces")?;
+        for interface in &class_file.interfaces {
+            bytes.extend_from_slice(&interface.to_be_bytes());
+        }
+
+        write_count(&mut bytes, class_file.fields.len(), "fields")?;
+        for field in &class_file.fields {
+            write_field(&mut bytes, field)?;
+        }
+
+        write_count(&mut bytes, class_file.methods.len(), "methods")?;
+        for method in &class_file.methods {
+            write_method(&mut bytes, method)?;
+        }
+
+        write_attributes(&mut bytes, &class_file.attributes)?;
+
+        Ok(bytes)
+    }
+}
+
+fn write_count(bytes: &mut Vec<u8>, count: usize, what: &str) -> Result<(), String> {
+    let count = u16::try_from(count).map_err(|_| format!("Too many {what}: {count}"))?;
+    bytes.extend_from_slice(&count.to_be_bytes());
+    Ok(())
+}
+
+/// Write constant pool count and entries in binary format
+fn write_constant_pool(bytes: &mut Vec<u8>, constant_pool: &ConstantPool) -> Result<(), String> {
+    // Placeholders occupy the second slot of 8-byte constants, so the
+    // number of entries is already the number of slots
+    write_count(
+        bytes,
+        constant_pool.entries().len() + 1,
+        "constant pool entries",
+    )?;
+
+    for entry in constant_pool.entries() {
+        match entry {
+            ConstantPoolEntry::Utf8(s) => {
+                bytes.push(1); // CONSTANT_Utf8
+                let encoded = encode_modified_utf8(s);
+                let length = u16::try_from(encoded.len())
+                    .map_err(|_| format!("Utf8 constant too long: {} bytes", encoded.len()))?;
+                bytes.extend_from_slice(&length.to_be_bytes());
+                bytes.extend_from_slice(&encoded);
+            }
+            ConstantPoolEntry::Integer(i) => {
+                bytes.push(3); // CONSTANT_Integer
+                bytes.extend_from_slice(&i.to_be_bytes());
+            }
+            ConstantPoolEntry::Float(f) => {
+                bytes.push(4); // CONSTANT_Float
+                bytes.extend_from_slice(&f.to_be_bytes());
+            }
+            ConstantPoolEntry::Long(l) => {
+                bytes.push(5); // CONSTANT_Long
+                bytes.extend_from_slice(&l.to_be_bytes());
+            }
+            ConstantPoolEntry::Double(d) => {
+                bytes.push(6); // CONSTANT_Double
+                bytes.extend_from_slice(&d.to_be_bytes());
+            }
+            ConstantPoolEntry::Class(name_index) => {
+                bytes.push(7); // CONSTANT_Class
+                bytes.extend_from_slice(&name_index.to_be_bytes());
+            }
+            ConstantPoolEntry::String(utf8_index) => {
+                bytes.push(8); // CONSTANT_String
+                bytes.extend_from_slice(&utf8_index.to_be_bytes());
+            }
+            ConstantPoolEntry::Fieldref(class_index, name_and_type_index) => {
+                bytes.push(9); // CONSTANT_Fieldref
+                bytes.extend_from_slice(&class_index.to_be_bytes());
+                bytes.extend_from_slice(&name_and_type_index.to_be_bytes());
+            }
+            ConstantPoolEntry::Methodref(class_index, name_and_type_index) => {
+                bytes.push(10); // CONSTANT_Methodref
+                bytes.extend_from_slice(&class_index.to_be_bytes());
+                bytes.extend_from_slice(&name_and_type_index.to_be_bytes());
+            }
+            ConstantPoolEntry::InterfaceMethodref(class_index, name_and_type_index) => {
+                bytes.push(11); // CONSTANT_InterfaceMethodref
+                bytes.extend_from_slice(&class_index.to_be_bytes());
+                bytes.extend_from_slice(&name_and_type_index.to_be_bytes());
+            }
+            ConstantPoolEntry::NameAndType(name_index, descriptor_index) => {
+                bytes.push(12); // CONSTANT_NameAndType
+                bytes.extend_from_slice(&name_index.to_be_bytes());
+                bytes.extend_from_slice(&descriptor_index.to_be_bytes());
+            }
+            ConstantPoolEntry::MethodHandle(reference_kind, reference_index) => {
+                bytes.push(15); // CONSTANT_MethodHandle
+                bytes.push(*reference_kind);
+                bytes.extend_from_slice(&reference_index.to_be_bytes());
+            }
+            ConstantPoolEntry::MethodType(descriptor_index) => {
+                bytes.push(16); // CONSTANT_MethodType
+                bytes.extend_from_slice(&descriptor_index.to_be_bytes());
+            }
+            ConstantPoolEntry::Dynamic(bootstrap_method_attr_index, name_and_type_index) => {
+                bytes.push(17); // CONSTANT_Dynamic
+                bytes.extend_from_slice(&bootstrap_method_attr_index.to_be_bytes());
+                bytes.extend_from_slice(&name_and_type_index.to_be_bytes());
+            }
+            ConstantPoolEntry::InvokeDynamic(bootstrap_method_attr_index, name_and_type_index) => {
+                bytes.push(18); // CONSTANT_InvokeDynamic
+                bytes.extend_from_slice(&bootstrap_method_attr_index.to_be_bytes());
+                bytes.extend_from_slice(&name_and_type_index.to_be_bytes());
+            }
+            ConstantPoolEntry::Module(name_index) => {
+                bytes.push(19); // CONSTANT_Module
+                bytes.extend_from_slice(&name_index.to_be_bytes());
+            }
+            ConstantPoolEntry::Package(name_index) => {
+                bytes.push(20); // CONSTANT_Package
+                bytes.extend_from_slice(&name_index.to_be_bytes());
+            }
+            ConstantPoolEntry::Placeholder => {
+                // Second slot of a Long/Double constant, not present in the file
+                continue;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_field(bytes: &mut Vec<u8>, field: &FieldInfo) -> Result<(), String> {
+    bytes.extend_from_slice(&field.access_flags.to_be_bytes());
+    bytes.extend_from_slice(&field.name_index.to_be_bytes());
+    bytes.extend_from_slice(&field.descriptor_index.to_be_bytes());
+    write_attributes(bytes, &field.attributes)
+}
+
+fn write_method(bytes: &mut Vec<u8>, method: &MethodInfo) -> Result<(), String> {
+    bytes.extend_from_slice(&method.access_flags.to_be_bytes());
+    bytes.extend_from_slice(&method.name_index.to_be_bytes());
+    bytes.extend_from_slice(&method.descriptor_index.to_be_bytes());
+
+    let code_count = usize::from(method.code.is_some());
+    write_count(
+        bytes,
+        method.attributes.len() + code_count,
+        "method attributes",
+    )?;
+
+    // The Code attribute is always written first, as javac and kotlinc do
+    if let Some(code) = &method.code {
+        write_code_attribute(bytes, code)
+            .map_err(|e| format!("Method {}{}: {e}", method.name, method.descriptor))?;
+    }
+    for attribute in &method.attributes {
+        write_attribute(bytes, attribute)?;
+    }
+
+    Ok(())
+}
+
+fn write_code_attribute(bytes: &mut Vec<u8>, code: &CodeAttribute) -> Result<(), String> {
+    let code_bytes = assemble_bytecode(&code.bytecode)?;
+    check_code_length(code_bytes.len())?;
+
+    let mut info = Vec::new();
+    info.extend_from_slice(&code.max_stack.to_be_bytes());
+    info.extend_from_slice(&code.max_locals.to_be_bytes());
+    info.extend_from_slice(&(code_bytes.len() as u32).to_be_bytes());
+    info.extend_from_slice(&code_bytes);
+
+    write_count(&mut info, code.exception_table.len(), "exception handlers")?;
+    for entry in &code.exception_table {
+        info.extend_from_slice(&entry.start_pc.to_be_bytes());
+        info.extend_from_slice(&entry.end_pc.to_be_bytes());
+        info.extend_from_slice(&entry.handler_pc.to_be_bytes());
+        info.extend_from_slice(&entry.catch_type.to_be_bytes());
+    }
+
+    write_attributes(&mut info, &code.attributes)?;
+
+    write_attribute(
+        bytes,
+        &AttributeInfo {
+            name_index: code.name_index,
+            info,
+        },
+    )
+}
+
+fn write_attributes(bytes: &mut Vec<u8>, attributes: &[AttributeInfo]) -> Result<(), String> {
+    write_count(bytes, attributes.len(), "attributes")?;
+    for attribute in attributes {
+        write_attribute(bytes, attribute)?;
+    }
+    Ok(())
+}
+
+fn write_attribute(bytes: &mut Vec<u8>, attribute: &AttributeInfo) -> Result<(), String> {
+    let length = u32::try_from(attribute.info.len())
+        .map_err(|_| format!("Attribute too long: {} bytes", attribute.info.len()))?;
+    bytes.extend_from_slice(&attribute.name_index.to_be_bytes());
+    bytes.extend_from_slice(&length.to_be_bytes());
+    bytes.extend_from_slice(&attribute.info);
+    Ok(())
+}
+
+/// Encoded size in bytes of a single instruction
+/// Encoded size of an instruction at the given byte offset. Switches are
+/// padded so their operands start at a multiple of four bytes.
+pub(super) fn instruction_size(instruction: &JvmInstruction, offset: usize) -> usize {
+    let padding = 3 - offset % 4;
+    match instruction {
+        JvmInstruction::Tableswitch { targets, .. } => 1 + padding + 12 + 4 * targets.len(),
+        JvmInstruction::Lookupswitch { pairs, .. } => 1 + padding + 8 + 8 * pairs.len(),
+        JvmInstruction::Iinc(_, _) if is_wide(instruction) => 6,
+        _ if is_wide(instruction) => 4,
+        JvmInstruction::Bipush(_)
+        | JvmInstruction::Ldc(_)
+        | JvmInstruction::Iload(_)
+        | JvmInstruction::Lload(_)
+        | JvmInstruction::Fload(_)
+        | JvmInstruction::Dload(_)
+        | JvmInstruction::Aload(_)
+        | JvmInstruction::Istore(_)
+        | JvmInstruction::Lstore(_)
+        | JvmInstruction::Fstore(_)
+        | JvmInstruction::Dstore(_)
+        | JvmInstruction::Astore(_)
+        | JvmInstruction::Newarray(_) => 2,
+        JvmInstruction::Sipush(_)
+        | JvmInstruction::LdcW(_)
+        | JvmInstruction::Ldc2W(_)
+        | JvmInstruction::Ifeq(_)
+        | JvmInstruction::Ifne(_)
+        | JvmInstruction::Iflt(_)
+        | JvmInstruction::Ifge(_)
+        | JvmInstruction::Ifgt(_)
+        | JvmInstruction::Ifle(_)
+        | JvmInstruction::IfIcmpeq(_)
+        | JvmInstruction::IfIcmpne(_)
+        | JvmInstruction::IfIcmplt(_)
+        | JvmInstruction::IfIcmpge(_)
+        | JvmInstruction::IfIcmpgt(_)
+        | JvmInstruction::IfIcmple(_)
+        | JvmInstruction::IfAcmpeq(_)
+        | JvmInstruction::IfAcmpne(_)
+        | JvmInstruction::Ifnull(_)
+        | JvmInstruction::Ifnonnull(_)
+        | JvmInstruction::Iinc(_, _)
+        | JvmInstruction::Goto(_)
+        | JvmInstruction::Getstatic(_)
+        | JvmInstruction::Putstatic(_)
+        | JvmInstruction::Getfield(_)
+        | JvmInstruction::Putfield(_)
+        | JvmInstruction::Invokevirtual(_)
+        | JvmInstruction::Invokespecial(_)
+        | JvmInstruction::Invokestatic(_)
+        | JvmInstruction::New(_)
+        | JvmInstruction::Checkcast(_)
+        | JvmInstruction::Instanceof(_)
+        | JvmInstruction::Anewarray(_) => 3,
+        JvmInstruction::Multianewarray(_, _) => 4,
+        JvmInstruction::Invokedynamic(_) | JvmInstruction::Invokeinterface(_, _) => 5,
+        _ => 1,
+    }
+}
+
+/// Byte offset of every instruction within the encoded `Code` array
+pub fn instruction_offsets(instructions: &[JvmInstruction]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(instructions.len());
+    let mut offset = 0;
+    for instruction in instructions {
+        offsets.push(offset);
+        offset += instruction_size(instruction, offset);
+    }
+    offsets
+}
+
+/// Encode instructions into the raw contents of a `Code` attribute.
+///
+/// This is the inverse of `parse_bytecode`: branch targets are instruction
+/// indices and are converted back into relative byte offsets.
+pub fn assemble_bytecode(instructions: &[JvmInstruction]) -> Result<Vec<u8>, String> {
+    let offsets = instruction_offsets(instructions);
+    let mut bytes = Vec::new();
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            JvmInstruction::Nop => bytes.push(0x00),
+            JvmInstruction::AconstNull => bytes.push(0x01),
+            JvmInstruction::IconstM1 => bytes.push(0x02),
+            JvmInstruction::Iconst0 => bytes.push(0x03),
+            JvmInstruction::Iconst1 => bytes.push(0x04),
+            JvmInstruction::Iconst2 => bytes.push(0x05),
+            JvmInstruction::Iconst3 => bytes.push(0x06),
+            JvmInstruction::Iconst4 => bytes.push(0x07),
+            JvmInstruction::Iconst5 => bytes.push(0x08),
+            JvmInstruction::Lconst0 => bytes.push(0x09),
+            JvmInstruction::Lconst1 => bytes.push(0x0A),
+            JvmInstruction::Fconst0 => bytes.push(0x0B),
+            JvmInstruction::Fconst1 => bytes.push(0x0C),
+            JvmInstruction::Fconst2 => bytes.push(0x0D),
+            JvmInstruction::Dconst0 => bytes.push(0x0E),
+            JvmInstruction::Dconst1 => bytes.push(0x0F),
+            JvmInstruction::Bipush(value) => bytes.extend_from_slice(&[0x10, *value as u8]),
+            JvmInstruction::Sipush(value) => {
+                bytes.push(0x11);
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+            JvmInstruction::Ldc(index) => {
+                let index = u8::try_from(*index)
+                    .map_err(|_| format!("ldc index {index} does not fit in one byte"))?;
+                bytes.extend_from_slice(&[0x12, index]);
+            }
+            JvmInstruction::LdcW(index) => push_u16_operand(&mut bytes, 0x13, *index),
+            JvmInstruction::Ldc2W(index) => push_u16_operand(&mut bytes, 0x14, *index),
+
+            // Local variable loads
+            JvmInstruction::Iload(index) => push_local_operand(&mut bytes, 0x15, *index),
+            JvmInstruction::Lload(index) => push_local_operand(&mut bytes, 0x16, *index),
+            JvmInstruction::Fload(index) => push_local_operand(&mut bytes, 0x17, *index),
+            JvmInstruction::Dload(index) => push_local_operand(&mut bytes, 0x18, *index),
+            JvmInstruction::Aload(index) => push_local_operand(&mut bytes, 0x19, *index),
+            JvmInstruction::Iload0 => bytes.push(0x1A),
+            JvmInstruction::Iload1 => bytes.push(0x1B),
+            JvmInstruction::Iload2 => bytes.push(0x1C),
+            JvmInstruction::Iload3 => bytes.push(0x1D),
+            JvmInstruction::Lload0 => bytes.push(0x1E),
+            JvmInstruction::Lload1 => bytes.push(0x1F),
+            JvmInstruction::Lload2 => bytes.push(0x20),
+            JvmInstruction::Lload3 => bytes.push(0x21),
+            JvmInstruction::Fload0 => bytes.push(0x22),
+            JvmInstruction::Fload1 => bytes.push(0x23),
+            JvmInstruction::Fload2 => bytes.push(0x24),
+            JvmInstruction::Fload3 => bytes.push(0x25),
+            JvmInstruction::Dload0 => bytes.push(0x26),
+            JvmInstruction::Dload1 => bytes.push(0x27),
+            JvmInstruction::Dload2 => bytes.push(0x28),
+            JvmInstruction::Dload3 => bytes.push(0x29),
+            JvmInstruction::Aload0 => bytes.push(0x2A),
+            JvmInstruction::Aload1 => bytes.push(0x2B),
+            JvmInstruction::Aload2 => bytes.push(0x2C),
+            JvmInstruction::Aload3 => bytes.push(0x2D),
+
+            // Local variable stores
+            JvmInstruction::Istore(index) => push_local_operand(&mut bytes, 0x36, *index),
+            JvmInstruction::Lstore(index) => push_local_operand(&mut bytes, 0x37, *index),
+            JvmInstruction::Fstore(index) => push_local_operand(&mut bytes, 0x38, *index),
+            JvmInstruction::Dstore(index) => push_local_operand(&mut bytes, 0x39, *index),
+            JvmInstruction::Astore(index) => push_local_operand(&mut bytes, 0x3A, *index),
+            JvmInstruction::Istore0 => bytes.push(0x3B),
+            JvmInstruction::Istore1 => bytes.push(0x3C),
+            JvmInstruction::Istore2 => bytes.push(0x3D),
+            JvmInstruction::Istore3 => bytes.push(0x3E),
+            JvmInstruction::Lstore0 => bytes.push(0x3F),
+            JvmInstruction::Lstore1 => bytes.push(0x40),
+            JvmInstruction::Lstore2 => bytes.push(0x41),
+            JvmInstruction::Lstore3 => bytes.push(0x42),
+            JvmInstruction::Fstore0 => bytes.push(0x43),
+            JvmInstruction::Fstore1 => bytes.push(0x44),
+            JvmInstruction::Fstore2 => bytes.push(0x45),
+            JvmInstruction::Fstore3 => bytes.push(0x46),
+            JvmInstruction::Dstore0 => bytes.push(0x47),
+            JvmInstruction::Dstore1 => bytes.push(0x48),
+            JvmInstruction::Dstore2 => bytes.push(0x49),
+            JvmInstruction::Dstore3 => bytes.push(0x4A),
+            JvmInstruction::Astore0 => bytes.push(0x4B),
+            JvmInstruction::Astore1 => bytes.push(0x4C),
+            JvmInstruction::Astore2 => bytes.push(0x4D),
+            JvmInstruction::Astore3 => bytes.push(0x4E),
+
+            // Stack operations
+            JvmInstruction::Pop => bytes.push(0x57),
+            JvmInstruction::Pop2 => bytes.push(0x58),
+            JvmInstruction::Dup => bytes.push(0x59),
+            JvmInstruction::DupX1 => bytes.push(0x5A),
+            JvmInstruction::DupX2 => bytes.push(0x5B),
+            JvmInstruction::Dup2 => bytes.push(0x5C),
+            JvmInstruction::Dup2X1 => bytes.push(0x5D),
+            JvmInstruction::Dup2X2 => bytes.push(0x5E),
+            JvmInstruction::Swap => bytes.push(0x5F),
+
+            // Arithmetic
+            JvmInstruction::Iadd => bytes.push(0x60),
+            JvmInstruction::Ladd => bytes.push(0x61),
+            JvmInstruction::Fadd => bytes.push(0x62),
+            JvmInstruction::Dadd => bytes.push(0x63),
+            JvmInstruction::Isub => bytes.push(0x64),
+            JvmInstruction::Lsub => bytes.push(0x65),
+            JvmInstruction::Fsub => bytes.push(0x66),
+            JvmInstruction::Dsub => bytes.push(0x67),
+            JvmInstruction::Imul => bytes.push(0x68),
+            JvmInstruction::Lmul => bytes.push(0x69),
+            JvmInstruction::Fmul => bytes.push(0x6A),
+            JvmInstruction::Dmul => bytes.push(0x6B),
+            JvmInstruction::Idiv => bytes.push(0x6C),
+            JvmInstruction::Ldiv => bytes.push(0x6D),
+            JvmInstruction::Fdiv => bytes.push(0x6E),
+            JvmInstruction::Ddiv => bytes.push(0x6F),
+            JvmInstruction::Irem => bytes.push(0x70),
+            JvmInstruction::Lrem => bytes.push(0x71),
+            JvmInstruction::Frem => bytes.push(0x72),
+            JvmInstruction::Drem => bytes.push(0x73),
+            JvmInstruction::Ineg => bytes.push(0x74),
+            JvmInstruction::Lneg => bytes.push(0x75),
+            JvmInstruction::Fneg => bytes.push(0x76),
+            JvmInstruction::Dneg => bytes.push(0x77),
+
+            // Shifts and bitwise operations
+            JvmInstruction::Ishl => bytes.push(0x78),
+            JvmInstruction::Lshl => bytes.push(0x79),
+            JvmInstruction::Ishr => bytes.push(0x7A),
+            JvmInstruction::Lshr => bytes.push(0x7B),
+            JvmInstruction::Iushr => bytes.push(0x7C),
+            JvmInstruction::Lushr => bytes.push(0x7D),
+            JvmInstruction::Iand => bytes.push(0x7E),
+            JvmInstruction::Land => bytes.push(0x7F),
+            JvmInstruction::Ior => bytes.push(0x80),
+            JvmInstruction::Lor => bytes.push(0x81),
+            JvmInstruction::Ixor => bytes.push(0x82),
+            JvmInstruction::Lxor => bytes.push(0x83),
+            JvmInstruction::Iinc(index, constant) => {
+                match (u8::try_from(*index), i8::try_from(*constant)) {
+                    (Ok(index), Ok(constant)) => {
+                        bytes.extend_from_slice(&[0x84, index, constant as u8])
+                    }
+                    _ => {
+                        bytes.extend_from_slice(&[0xC4, 0x84]);
+                        bytes.extend_from_slice(&index.to_be_bytes());
+                        bytes.extend_from_slice(&constant.to_be_bytes());
+                    }
+                }
+            }
+
+            // Conversions
+            JvmInstruction::I2l => bytes.push(0x85),
+            JvmInstruction::I2f => bytes.push(0x86),
+            JvmInstruction::I2d => bytes.push(0x87),
+            JvmInstruction::L2i => bytes.push(0x88),
+            JvmInstruction::L2f => bytes.push(0x89),
+            JvmInstruction::L2d => bytes.push(0x8A),
+            JvmInstruction::F2i => bytes.push(0x8B),
+            JvmInstruction::F2l => bytes.push(0x8C),
+            JvmInstruction::F2d => bytes.push(0x8D),
+            JvmInstruction::D2i => bytes.push(0x8E),
+            JvmInstruction::D2l => bytes.push(0x8F),
+            JvmInstruction::D2f => bytes.push(0x90),
+            JvmInstruction::I2b => bytes.push(0x91),
+            JvmInstruction::I2c => bytes.push(0x92),
+            JvmInstruction::I2s => bytes.push(0x93),
+
+            // Comparisons
+            JvmInstruction::Lcmp => bytes.push(0x94),
+            JvmInstruction::Fcmpl => bytes.push(0x95),
+            JvmInstruction::Fcmpg => bytes.push(0x96),
+            JvmInstruction::Dcmpl => bytes.push(0x97),
+            JvmInstruction::Dcmpg => bytes.push(0x98),
+
+            // Control flow
+            JvmInstruction::Ifeq(target) => {
+                push_branch(&mut bytes, 0x99, &offsets, index, *target)?
+            }
+            JvmInstruction::Ifne(target) => {
+                push_branch(&mut bytes, 0x9A, &offsets, index, *target)?
+            }
+            JvmInstruction::Iflt(target) => {
+                push_branch(&mut bytes, 0x9B, &offsets, index, *target)?
+            }
+            JvmInstruction::Ifge(target) => {
+                push_branch(&mut bytes, 0x9C, &offsets, index, *target)?
+            }
+            JvmInstruction::Ifgt(target) => {
+                push_branch(&mut bytes, 0x9D, &offsets, index, *target)?
+            }
+            JvmInstruction::Ifle(target) => {
+                push_branch(&mut bytes, 0x9E, &offsets, index, *target)?
+            }
+            JvmInstruction::IfIcmpeq(target) => {
+                push_branch(&mut bytes, 0x9F, &offsets, index, *target)?
+            }
+            JvmInstruction::IfIcmpne(target) => {
+                push_branch(&mut bytes, 0xA0, &offsets, index, *target)?
+            }
+            JvmInstruction::IfIcmplt(target) => {
+                push_branch(&mut bytes, 0xA1, &offsets, index, *target)?
+            }
+            JvmInstruction::IfIcmpge(target) => {
+                push_branch(&mut bytes, 0xA2, &offsets, index, *target)?
+            }
+            JvmInstruction::IfIcmpgt(target) => {
+                push_branch(&mut bytes, 0xA3, &offsets, index, *target)?
+            }
+            JvmInstruction::IfIcmple(target) => {
+                push_branch(&mut bytes, 0xA4, &offsets, index, *target)?
+            }
+            JvmInstruction::IfAcmpeq(target) => {
+                push_branch(&mut bytes, 0xA5, &offsets, index, *target)?
+            }
+            JvmInstruction::IfAcmpne(target) => {
+                push_branch(&mut bytes, 0xA6, &offsets, index, *target)?
+            }
+            JvmInstruction::Ifnull(target) => {
+                push_branch(&mut bytes, 0xC6, &offsets, index, *target)?
+            }
+            JvmInstruction::Ifnonnull(target) => {
+                push_branch(&mut bytes, 0xC7, &offsets, index, *target)?
+            }
+            JvmInstruction::Goto(target) => {
+                push_branch(&mut bytes, 0xA7, &offsets, index, *target)?
+            }
+            JvmInstruction::Tableswitch {
+                default,
+                low,
+                targets,
+            } => {
+                let high = *low as i64 + targets.len() as i64 - 1;
+                let high = i32::try_from(high).map_err(|_| {
+                    format!("tableswitch at instruction {index} has too many targets")
+                })?;
+                push_switch_header(&mut bytes, 0xAA, &offsets, index, *default)?;
+                bytes.extend_from_slice(&low.to_be_bytes());
+                bytes.extend_from_slice(&high.to_be_bytes());
+                for target in targets {
+                    let relative = switch_offset(&offsets, index, *target)?;
+                    bytes.extend_from_slice(&relative.to_be_bytes());
+                }
+            }
+            JvmInstruction::Lookupswitch { default, pairs } => {
+                push_switch_header(&mut bytes, 0xAB, &offsets, index, *default)?;
+                bytes.extend_from_slice(&(pairs.len() as i32).to_be_bytes());
+                for (key, target) in pairs {
+                    bytes.extend_from_slice(&key.to_be_bytes());
+                    let relative = switch_offset(&offsets, index, *target)?;
+                    bytes.extend_from_slice(&relative.to_be_bytes());
+                }
+            }
+
+            // Returns
+            JvmInstruction::Ireturn => bytes.push(0xAC),
+            JvmInstruction::Lreturn => bytes.push(0xAD),
+            JvmInstruction::Freturn => bytes.push(0xAE),
+            JvmInstruction::Dreturn => bytes.push(0xAF),
+            JvmInstruction::Areturn => bytes.push(0xB0),
+            JvmInstruction::Return => bytes.push(0xB1),
+
+            // Fields, invocation and objects
+            JvmInstruction::Getstatic(index) => push_u16_operand(&mut bytes, 0xB2, *index),
+            JvmInstruction::Putstatic(index) => push_u16_operand(&mut bytes, 0xB3, *index),
+            JvmInstruction::Getfield(index) => push_u16_operand(&mut bytes, 0xB4, *index),
+            JvmInstruction::Putfield(index) => push_u16_operand(&mut bytes, 0xB5, *index),
+            JvmInstruction::Invokevirtual(index) => push_u16_operand(&mut bytes, 0xB6, *index),
+            JvmInstruction::Invokespecial(index) => push_u16_operand(&mut bytes, 0xB7, *index),
+            JvmInstruction::Invokestatic(index) => push_u16_operand(&mut bytes, 0xB8, *index),
+            JvmInstruction::Invokedynamic(index) => {
+                push_u16_operand(&mut bytes, 0xBA, *index);
+                bytes.extend_from_slice(&[0, 0]);
+            }
+            JvmInstruction::Invokeinterface(index, count) => {
+                push_u16_operand(&mut bytes, 0xB9, *index);
+                bytes.extend_from_slice(&[*count, 0]);
+            }
+            JvmInstruction::New(index) => push_u16_operand(&mut bytes, 0xBB, *index),
+            JvmInstruction::Checkcast(index) => push_u16_operand(&mut bytes, 0xC0, *index),
+            JvmInstruction::Instanceof(index) => push_u16_operand(&mut bytes, 0xC1, *index),
+
+            // Arrays
+            JvmInstruction::Newarray(atype) => bytes.extend_from_slice(&[0xBC, *atype]),
+            JvmInstruction::Anewarray(index) => push_u16_operand(&mut bytes, 0xBD, *index),
+            JvmInstruction::Arraylength => bytes.push(0xBE),
+            JvmInstruction::Athrow => bytes.push(0xBF),
+            JvmInstruction::Multianewarray(index, dimensions) => {
+                push_u16_operand(&mut bytes, 0xC5, *index);
+                bytes.push(*dimensions);
+            }
+            JvmInstruction::Iaload => bytes.push(0x2E),
+            JvmInstruction::Laload => bytes.push(0x2F),
+            JvmInstruction::Faload => bytes.push(0x30),
+            JvmInstruction::Daload => bytes.push(0x31),
+            JvmInstruction::Aaload => bytes.push(0x32),
+            JvmInstruction::Baload => bytes.push(0x33),
+            JvmInstruction::Caload => bytes.push(0x34),
+            JvmInstruction::Saload => bytes.push(0x35),
+            JvmInstruction::Iastore => bytes.push(0x4F),
+            JvmInstruction::Lastore => bytes.push(0x50),
+            JvmInstruction::Fastore => bytes.push(0x51),
+            JvmInstruction::Dastore => bytes.push(0x52),
+            JvmInstruction::Aastore => bytes.push(0x53),
+            JvmInstruction::Bastore => bytes.push(0x54),
+            JvmInstruction::Castore => bytes.push(0x55),
+            JvmInstruction::Sastore => bytes.push(0x56),
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Fail when encoded code is too long for a `Code` attribute
+fn check_code_length(length: usize) -> Result<(), String> {
+    if length > u16::MAX as usize {
+        return Err(format!(
+            "Code length {length} exceeds the JVM limit of {} bytes",
+            u16::MAX
+        ));
+    }
+    Ok(())
+}
+
+/// Place in the code of a `CodeBuilder` that branches can target before
+/// it is bound to an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// Assembles a method's instructions, with branches written against
+/// labels and resolved to instruction indices by `finish`
+#[derive(Debug, Default)]
+pub struct CodeBuilder {
+    instructions: Vec<JvmInstruction>,
+    /// Instruction index each label is bound to
+    labels: Vec<Option<usize>>,
+    /// Branch instructions and the labels they jump to
+    fixups: Vec<(usize, Label)>,
+}
+
+impl CodeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn emit(&mut self, instruction: JvmInstruction) {
+        self.instructions.push(instruction);
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Bind a label to the next instruction emitted
+    pub fn bind(&mut self, label: Label) {
+        assert!(
+            self.labels[label.0].is_none(),
+            "label {} is bound twice",
+            label.0
+        );
+        self.labels[label.0] = Some(self.instructions.len());
+    }
+
+    /// Emit a branch to a label, such as `branch(JvmInstruction::Goto, end)`
+    pub fn branch(&mut self, instruction: fn(u16) -> JvmInstruction, label: Label) {
+        self.fixups.push((self.instructions.len(), label));
+        self.emit(instruction(0));
+    }
+
+    /// Resolve every branch and return the instructions, failing if a
+    /// label was never bound to an instruction or the code is too long
+    pub fn finish(mut self) -> Result<Vec<JvmInstruction>, String> {
+        let length = match (
+            instruction_offsets(&self.instructions).last(),
+            self.instructions.last(),
+        ) {
+            (Some(&offset), Some(instruction)) => offset + instruction_size(instruction, offset),
+            _ => 0,
+        };
+        check_code_length(length)?;
+
+        for (index, label) in self.fixups {
+            let target = self.labels[label.0]
+                .filter(|&target| target < self.instructions.len())
+                .ok_or_else(|| format!("Label {} is not bound to an instruction", label.0))?;
+            for slot in self.instructions[index].branch_targets_mut() {
+                *slot = target as u16;
+            }
+        }
+        Ok(self.instructions)
+    }
+}
+
+fn push_u16_operand(bytes: &mut Vec<u8>, opcode: u8, operand: u16) {
+    bytes.push(opcode);
+    bytes.extend_from_slice(&operand.to_be_bytes());
+}
+
+/// Emit a local variable load or store, behind `wide` when the index does
+/// not fit in one byte
+fn push_local_operand(bytes: &mut Vec<u8>, opcode: u8, index: u16) {
+    match u8::try_from(index) {
+        Ok(index) => bytes.extend_from_slice(&[opcode, index]),
+        Err(_) => {
+            bytes.extend_from_slice(&[0xC4, opcode]);
+            bytes.extend_from_slice(&index.to_be_bytes());
+        }
+    }
+}
+
+/// Whether the instruction's local index or `iinc` constant needs the
+/// `wide` prefix
+fn is_wide(instruction: &JvmInstruction) -> bool {
+    match *instruction {
+        JvmInstruction::Iload(index)
+        | JvmInstruction::Lload(index)
+        | JvmInstruction::Fload(index)
+        | JvmInstruction::Dload(index)
+        | JvmInstruction::Aload(index)
+        | JvmInstruction::Istore(index)
+        | JvmInstruction::Lstore(index)
+        | JvmInstruction::Fstore(index)
+        | JvmInstruction::Dstore(index)
+        | JvmInstruction::Astore(index) => u8::try_from(index).is_err(),
+        JvmInstruction::Iinc(index, constant) => {
+            u8::try_from(index).is_err() || i8::try_from(constant).is_err()
+        }
+        _ => false,
+    }
+}
+
+fn push_branch(
+    bytes: &mut Vec<u8>,
+    opcode: u8,
+    offsets: &[usize],
+    index: usize,
+    target: u16,
+) -> Result<(), String> {
+    let destination = offsets.get(target as usize).ok_or_else(|| {
+        format!("Branch at instruction {index} targets missing instruction {target}")
+    })?;
+    let relative = *destination as isize - offsets[index] as isize;
+    let relative = i16::try_from(relative)
+        .map_err(|_| format!("Branch offset {relative} at instruction {index} is out of range"))?;
+    push_u16_operand(bytes, opcode, relative as u16);
+    Ok(())
+}
+
+/// Write a switch opcode, its alignment padding and the default offset
+fn push_switch_header(
+    bytes: &mut Vec<u8>,
+    opcode: u8,
+    offsets: &[usize],
+    index: usize,
+    default: u16,
+) -> Result<(), String> {
+    bytes.push(opcode);
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(0);
+    }
+    let relative = switch_offset(offsets, index, default)?;
+    bytes.extend_from_slice(&relative.to_be_bytes());
+    Ok(())
+}
+
+fn switch_offset(offsets: &[usize], index: usize, target: u16) -> Result<i32, String> {
+    let destination = offsets.get(target as usize).ok_or_else(|| {
+        format!("Switch at instruction {index} targets missing instruction {target}")
+    })?;
+    Ok((*destination as isize - offsets[index] as isize) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class_file_parser::{ClassFileParser, parse_bytecode};
+    use crate::jvm::java_class_generator::JavaClassGenerator;
+    use std::fs;
+    use std::path::Path;
+
+    fn fixture_classes() -> Vec<(String, Vec<u8>)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut fixtures: Vec<(String, Vec<u8>)> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "class"))
+            .map(|path| (path.display().to_string(), fs::read(&path).unwrap()))
+            .collect();
+        fixtures.sort();
+        assert!(!fixtures.is_empty(), "no fixture classes found");
+        fixtures
+    }
+
+    #[test]
+    fn test_round_trip_is_stable_for_fixtures() {
+        for (name, bytes) in fixture_classes() {
+            let parsed = ClassFileParser::parse(&bytes).unwrap();
+            let written = ClassFileWriter::write(&parsed).unwrap();
+            let reparsed = ClassFileParser::parse(&written).unwrap();
+
+            // Compared through Debug so NaN constants count as equal
+            assert_eq!(
+                format!("{parsed:?}"),
+                format!("{reparsed:?}"),
+                "{name}: model changed after round trip"
+            );
+            assert_eq!(bytes, written, "{name}: bytes changed after round trip");
+        }
+    }
+
+    #[test]
+    fn test_round_trip_is_stable_for_generated_class() {
+        let mut generator = JavaClassGenerator::new("DiceRoll".to_string());
+        let bytes = generator.generate_dice_class("3d6").unwrap();
+
+        let parsed = ClassFileParser::parse(&bytes).unwrap();
+        let written = ClassFileWriter::write(&parsed).unwrap();
+
+        assert_eq!(bytes, written);
+        assert_eq!(parsed, ClassFileParser::parse(&written).unwrap());
+    }
+
+    #[test]
+    fn test_assembler_is_inverse_of_parser() {
+        let instructions = vec![
+            JvmInstruction::Iconst3,          // 0
+            JvmInstruction::Istore1,          // 1
+            JvmInstruction::Iload1,           // 2: loop start
+            JvmInstruction::Ifle(9),          // 3
+            JvmInstruction::Iload1,           // 4
+            JvmInstruction::Iconst1,          // 5
+            JvmInstruction::Isub,             // 6
+            JvmInstruction::Istore1,          // 7
+            JvmInstruction::Goto(2),          // 8
+            JvmInstruction::Sipush(-300),     // 9
+            JvmInstruction::LdcW(300),        // 10
+            JvmInstruction::Invokedynamic(7), // 11
+            JvmInstruction::Return,           // 12
+        ];
+
+        let bytes = assemble_bytecode(&instructions).unwrap();
+
+        // goto at byte 10 jumps back to byte 2
+        assert_eq!(&bytes[10..13], &[0xA7, 0xFF, 0xF8]);
+        assert_eq!(parse_bytecode(&bytes).unwrap(), instructions);
+    }
+
+    #[test]
+    fn test_wide_locals_round_trip() {
+        let instructions = vec![
+            JvmInstruction::Iload(300),
+            JvmInstruction::Istore(2),
+            JvmInstruction::Iinc(2, 1000),
+            JvmInstruction::Iinc(300, 1),
+            JvmInstruction::Dload(256),
+            JvmInstruction::Dstore(3),
+            JvmInstruction::Return,
+        ];
+
+        let bytes = assemble_bytecode(&instructions).unwrap();
+        assert_eq!(
+            bytes,
+            [
+                0xC4, 0x15, 0x01, 0x2C, // wide iload 300
+                0x36, 0x02, // istore 2
+                0xC4, 0x84, 0x00, 0x02, 0x03, 0xE8, // wide iinc 2 1000
+                0xC4, 0x84, 0x01, 0x2C, 0x00, 0x01, // wide iinc 300 1
+                0xC4, 0x18, 0x01, 0x00, // wide dload 256
+                0x39, 0x03, // dstore 3
+                0xB1,
+            ]
+        );
+        assert_eq!(
+            instruction_offsets(&instructions),
+            [0, 4, 6, 12, 18, 22, 24]
+        );
+        assert_eq!(parse_bytecode(&bytes).unwrap(), instructions);
+    }
+
+    #[test]
+    fn test_switch_padding_depends_on_offset() {
+        for prefix in 0..4 {
+            let mut instructions = vec![JvmInstruction::Nop; prefix];
+            instructions.push(JvmInstruction::Tableswitch {
+                default: prefix as u16 + 2,
+                low: -1,
+                targets: vec![prefix as u16 + 2, prefix as u16 + 3],
+            });
+            instructions.push(JvmInstruction::Lookupswitch {
+                default: prefix as u16 + 3,
+                pairs: vec![(i32::MIN, prefix as u16), (7, prefix as u16 + 2)],
+            });
+            instructions.push(JvmInstruction::Iconst0);
+            instructions.push(JvmInstruction::Ireturn);
+
+            let bytes = assemble_bytecode(&instructions).unwrap();
+
+            // Operands start at the next multiple of four after the opcode
+            let padding = 3 - prefix % 4;
+            assert_eq!(bytes[prefix], 0xAA);
+            assert!(
+                bytes[prefix + 1..prefix + 1 + padding]
+                    .iter()
+                    .all(|&b| b == 0)
+            );
+            let table_size = 1 + padding + 12 + 2 * 4;
+            let lookup = prefix + table_size;
+            assert_eq!(bytes[lookup], 0xAB);
+            assert_eq!(lookup + 1 + (3 - lookup % 4) + 8 + 2 * 8, bytes.len() - 2);
+            assert_eq!(parse_bytecode(&bytes).unwrap(), instructions);
+        }
+    }
+
+    #[test]
+    fn test_assembler_rejects_invalid_branch_target() {
+        let instructions = vec![JvmInstruction::Goto(5), JvmInstruction::Return];
+        assert!(assemble_bytecode(&instructions).is_err());
+    }
+
+    #[test]
+    fn test_code_builder_resolves_labels() {
+        let mut code = CodeBuilder::new();
+        let start = code.new_label();
+        let end = code.new_label();
+        code.emit(JvmInstruction::Iconst3);
+        code.emit(JvmInstruction::Istore1);
+        code.bind(start);
+        code.emit(JvmInstruction::Iload1);
+        code.branch(JvmInstruction::Ifle, end);
+        code.emit(JvmInstruction::Iinc(1, -1));
+        code.branch(JvmInstruction::Goto, start);
+        code.bind(end);
+        code.emit(JvmInstruction::Return);
+
+        assert_eq!(
+            code.finish().unwrap(),
+            vec![
+                JvmInstruction::Iconst3,
+                JvmInstruction::Istore1,
+                JvmInstruction::Iload1,
+                JvmInstruction::Ifle(6),
+                JvmInstruction::Iinc(1, -1),
+                JvmInstruction::Goto(2),
+                JvmInstruction::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_code_builder_rejects_unbound_labels_and_long_code() {
+        let mut code = CodeBuilder::new();
+        let end = code.new_label();
+        code.branch(JvmInstruction::Goto, end);
+        code.bind(end);
+        assert_eq!(
+            code.finish(),
+            Err("Label 0 is not bound to an instruction".to_string())
+        );
+
+        let mut code = CodeBuilder::new();
+        for _ in 0..70_000 {
+            code.emit(JvmInstruction::Nop);
+        }
+        assert_eq!(
+            code.finish(),
+            Err("Code length 70000 exceeds the JVM limit of 65535 bytes".to_string())
+        );
+    }
+
+    #[test]
+    fn test_modified_class_is_written() {
+        let (_, bytes) = fixture_classes()
+            .into_iter()
+            .find(|(name, _)| name.ends_with("HelloWorld.class"))
+            .unwrap();
+        let mut class_file = ClassFileParser::parse(&bytes).unwrap();
+
+        // Replace the greeting with a new constant
+        let text = class_file
+            .constant_pool
+            .add_utf8("Modified!".to_string())
+            .unwrap();
+        let string = class_file.constant_pool.add_string(text).unwrap();
+        let main = class_file
+            .methods
+            .iter_mut()
+            .find(|method| method.name == "main")
+            .unwrap();
+        let code = main.code.as_mut().unwrap();
+        for instruction in &mut code.bytecode {
+            if let JvmInstruction::Ldc(_) = instruction {
+                *instruction = JvmInstruction::Ldc(string);
+            }
+        }
+
+        let written = ClassFileWriter::write(&class_file).unwrap();
+        let reparsed = ClassFileParser::parse(&written).unwrap();
+        assert_eq!(class_file, reparsed);
+        assert!(
+            reparsed
+                .main_method()
+                .unwrap()
+                .bytecode()
+                .contains(&JvmInstruction::Ldc(string))
+        );
+    }
+
+    #[test]
+    fn test_unicode_constants_are_decoded() {
+        let (_, bytes) = fixture_classes()
+            .into_iter()
+            .find(|(name, _)| name.ends_with("Unicode.class"))
+            .unwrap();
+        let class_file = ClassFileParser::parse(&bytes).unwrap();
+        let entries = class_file.constant_pool.entries();
+
+        for expected in ["🎲 Würfel", "nul:\0:end"] {
+            assert!(entries.contains(&ConstantPoolEntry::Utf8(expected.to_string())));
+        }
+    }
+
+    #[test]
+    fn test_non_ascii_class_name_is_encoded() {
+        let mut generator = JavaClassGenerator::new("Würfel".to_string());
+        let bytes = generator.generate_dice_class("1d6").unwrap();
+
+        let expected = encode_modified_utf8("Würfel");
+        assert!(
+            bytes
+                .windows(expected.len())
+                .any(|window| window == expected)
+        );
+        let parsed = ClassFileParser::parse(&bytes).unwrap();
+        assert_eq!(parsed.class_name(), Some("Würfel"));
+    }
+}
@@ -0,0 +1,200 @@
+use super::class_file_parser::{BootstrapMethod, ClassFile, ClassFileParser};
+use super::jvm_types::ConstantPool;
+use super::verifier::verify_class;
+use super::zip::ZipArchive;
+use crate::error::RuntimeError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Location searched for class files
+#[derive(Debug)]
+pub enum ClassPathEntry {
+    Directory(PathBuf),
+    /// A `.jar` or `.zip` file, read into memory when added
+    Archive(PathBuf, ZipArchive),
+}
+
+/// A class together with the runtime state shared by all of its frames
+#[derive(Debug)]
+pub struct LoadedClass {
+    pub name: String,
+    pub class_file: ClassFile,
+    /// Runtime constant pool, shared with every frame of the class
+    pub constant_pool: Rc<ConstantPool>,
+    pub bootstrap_methods: Vec<BootstrapMethod>,
+}
+
+impl LoadedClass {
+    /// Prepare a class for execution, rejecting it with a `VerifyError` if
+    /// its code fails verification
+    pub fn new(class_file: ClassFile) -> Result<Self, RuntimeError> {
+        verify_class(&class_file)?;
+        let name = class_file
+            .class_name()
+            .ok_or_else(|| RuntimeError::InvalidClassFile("missing class name".to_string()))?
+            .to_string();
+        Ok(Self {
+            name,
+            constant_pool: Rc::new(class_file.constant_pool.clone()),
+            bootstrap_methods: class_file.bootstrap_methods()?,
+            class_file,
+        })
+    }
+}
+
+/// Loads classes lazily by binary name (e.g. `dice/Dice`) from a classpath
+/// of directories and archives
+#[derive(Debug, Default)]
+pub struct ClassLoader {
+    classpath: Vec<ClassPathEntry>,
+    /// Loaded classes by binary name; `None` marks classes not found on the
+    /// classpath, which are served by the built-in library
+    classes: HashMap<String, Option<Rc<LoadedClass>>>,
+}
+
+impl ClassLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a loader from a classpath string separated like `PATH`
+    /// (`:` on Unix, `;` on Windows)
+    pub fn from_classpath(classpath: &str) -> Result<Self, RuntimeError> {
+        let mut loader = Self::new();
+        for path in std::env::split_paths(classpath) {
+            loader.add_path(path)?;
+        }
+        Ok(loader)
+    }
+
+    /// Append a directory or archive to the classpath
+    pub fn add_path(&mut self, path: impl AsRef<Path>) -> Result<(), RuntimeError> {
+        let path = path.as_ref();
+        if path.as_os_str().is_empty() || path.is_dir() {
+            let directory = if path.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                path
+            };
+            self.classpath
+                .push(ClassPathEntry::Directory(directory.to_path_buf()));
+            return Ok(());
+        }
+
+        let data = fs::read(path).map_err(|error| {
+            RuntimeError::InvalidArchive(format!("{}: {error}", path.display()))
+        })?;
+        let archive = ZipArchive::new(data)?;
+        self.classpath
+            .push(ClassPathEntry::Archive(path.to_path_buf(), archive));
+        Ok(())
+    }
+
+    pub fn classpath(&self) -> &[ClassPathEntry] {
+        &self.classpath
+    }
+
+    /// Register a class that was not read from the classpath, such as the
+    /// main class given as a file
+    pub fn define_class(&mut self, class_file: ClassFile) -> Result<Rc<LoadedClass>, RuntimeError> {
+        Ok(self.insert(LoadedClass::new(class_file)?))
+    }
+
+    /// Register an already prepared class under its name
+    pub fn insert(&mut self, class: LoadedClass) -> Rc<LoadedClass> {
+        let class = Rc::new(class);
+        self.classes
+            .insert(class.name.clone(), Some(Rc::clone(&class)));
+        class
+    }
+
+    /// Find a class by binary name, reading it from the first classpath
+    /// entry that has it the first time it is referenced
+    pub fn load_class(&mut self, name: &str) -> Result<Option<Rc<LoadedClass>>, RuntimeError> {
+        if let Some(class) = self.classes.get(name) {
+            return Ok(class.clone());
+        }
+
+        let Some(class_data) = self.find_class_data(name)? else {
+            self.classes.insert(name.to_string(), None);
+            return Ok(None);
+        };
+        let class = LoadedClass::new(ClassFileParser::parse(&class_data)?)?;
+        if class.name != name {
+            return Err(RuntimeError::InvalidClassFile(format!(
+                "{name}.class defines {}",
+                class.name
+            )));
+        }
+
+        Ok(Some(self.insert(class)))
+    }
+
+    /// A class that has already been loaded, without searching the classpath
+    pub fn loaded_class(&self, name: &str) -> Option<Rc<LoadedClass>> {
+        self.classes.get(name).cloned().flatten()
+    }
+
+    fn find_class_data(&self, name: &str) -> Result<Option<Vec<u8>>, RuntimeError> {
+        let file_name = format!("{name}.class");
+        for entry in &self.classpath {
+            match entry {
+                ClassPathEntry::Directory(directory) => {
+                    if let Ok(class_data) = fs::read(directory.join(&file_name)) {
+                        return Ok(Some(class_data));
+                    }
+                }
+                ClassPathEntry::Archive(_, archive) => {
+                    if let Some(class_data) = archive.read(&file_name)? {
+                        return Ok(Some(class_data));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classes_load_lazily_from_directories_and_jars() {
+        let classpath = std::env::join_paths(["tests/fixtures/dice-lib.jar", "tests/fixtures"])
+            .unwrap()
+            .into_string()
+            .unwrap();
+        let mut loader = ClassLoader::from_classpath(&classpath).unwrap();
+        assert!(matches!(
+            loader.classpath(),
+            [ClassPathEntry::Archive(_, _), ClassPathEntry::Directory(_)]
+        ));
+        assert!(loader.loaded_class("dice/Dice").is_none());
+
+        let dice = loader.load_class("dice/Dice").unwrap().unwrap();
+        assert_eq!(dice.name, "dice/Dice");
+        assert!(dice.class_file.find_method("maximum", "()I").is_some());
+        assert!(loader.loaded_class("dice/Dice").is_some());
+
+        let main = loader.load_class("LibraryMain").unwrap().unwrap();
+        assert_eq!(
+            main.constant_pool.entries().len(),
+            main.class_file.constant_pool.entries().len()
+        );
+
+        // Library classes are not on the classpath
+        assert!(loader.load_class("java/lang/Object").unwrap().is_none());
+        assert!(loader.loaded_class("java/lang/Object").is_none());
+    }
+
+    #[test]
+    fn test_missing_archive_is_an_error() {
+        assert!(matches!(
+            ClassLoader::from_classpath("tests/fixtures/missing.jar"),
+            Err(RuntimeError::InvalidArchive(_))
+        ));
+    }
+}
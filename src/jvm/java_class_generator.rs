@@ -1,8 +1,15 @@
-use super::jvm_types::{ConstantPool, ConstantPoolEntry, JvmInstruction};
+use super::class_file_parser::{AttributeInfo, ClassFile, CodeAttribute, MethodInfo};
+use super::class_file_writer::{ClassFileWriter, CodeBuilder};
+use super::jvm_types::{ConstantPool, ConstantPoolBuilder, JvmInstruction};
+use super::verifier::{add_stack_map_table, code_limits};
+use super::zip::write_jar;
 use crate::analyzer::SemanticAnalyzer;
 /// Java class file generator
 use std::fs;
 
+const MAIN_DESCRIPTOR: &str = "([Ljava/lang/String;)V";
+const ROLL_DESCRIPTOR: &str = "(II)I";
+
 /// Java class generator error
 #[derive(Debug)]
 pub enum JavaClassGeneratorError {
@@ -31,17 +38,81 @@ impl std::fmt::Display for JavaClassGeneratorError {
 
 impl std::error::Error for JavaClassGeneratorError {}
 
+/// Java release that generated class files target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JavaVersion {
+    #[default]
+    Java8,
+    Java11,
+    Java17,
+    Java21,
+}
+
+impl JavaVersion {
+    /// Class file major version of the release
+    pub fn major_version(self) -> u16 {
+        match self {
+            JavaVersion::Java8 => 52,
+            JavaVersion::Java11 => 55,
+            JavaVersion::Java17 => 61,
+            JavaVersion::Java21 => 65,
+        }
+    }
+}
+
+impl std::str::FromStr for JavaVersion {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "8" => Ok(JavaVersion::Java8),
+            "11" => Ok(JavaVersion::Java11),
+            "17" => Ok(JavaVersion::Java17),
+            "21" => Ok(JavaVersion::Java21),
+            _ => Err(format!(
+                "unsupported Java version {value}, expected 8, 11, 17 or 21"
+            )),
+        }
+    }
+}
+
+/// Dice a generated class rolls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiceSource<'a> {
+    /// The dice of an expression, fixed when the class is generated
+    Expression(&'a str),
+    /// Dice given to `main` as arguments, `<count> <faces>` or
+    /// `<count>d<faces>`
+    Arguments,
+}
+
 /// Complete Java class file generator
 pub struct JavaClassGenerator {
-    constant_pool: ConstantPool,
+    constant_pool: ConstantPoolBuilder,
     class_name: String,
+    java_version: JavaVersion,
 }
 
 impl JavaClassGenerator {
     pub fn new(class_name: String) -> Self {
         Self {
-            constant_pool: ConstantPool::new(),
+            constant_pool: ConstantPoolBuilder::new(),
             class_name,
+            java_version: JavaVersion::default(),
+        }
+    }
+
+    pub fn set_java_version(&mut self, java_version: JavaVersion) {
+        self.java_version = java_version;
+    }
+
+    pub fn generate_class(
+        &mut self,
+        source: DiceSource,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match source {
+            DiceSource::Expression(expression) => self.generate_dice_class(expression),
+            DiceSource::Arguments => self.generate_parametric_class(),
         }
     }
 
@@ -58,15 +129,25 @@ impl JavaClassGenerator {
             let crate::ast::StatementKind::Expression { expr } = stmt.kind;
             let crate::ast::ExpressionKind::Dice { count, faces } = expr.kind;
 
-            self.setup_constant_pool()
-                .map_err(JavaClassGeneratorError::CompilationError)?;
             let bytecode = self.generate_dice_bytecode(count, faces)?;
-            return self.generate_class_file(bytecode);
+            return self.generate_class_file(vec![("main", MAIN_DESCRIPTOR, bytecode)]);
         }
 
         Err("Invalid expression".into())
     }
 
+    /// Generate a reusable class whose `main` rolls the dice given as
+    /// arguments, through a `public static int roll(int count, int faces)`
+    /// that other Java code can call too
+    pub fn generate_parametric_class(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let roll = self.generate_roll_method()?;
+        let main = self.generate_parametric_main()?;
+        self.generate_class_file(vec![
+            ("roll", ROLL_DESCRIPTOR, roll),
+            ("main", MAIN_DESCRIPTOR, main),
+        ])
+    }
+
     /// Generate JVM instruction sequence from Dice expression (for VM execution)
     pub fn generate_dice_instructions(
         &mut self,
@@ -85,461 +166,532 @@ impl JavaClassGenerator {
         Err("Invalid expression".into())
     }
 
-    /// Setup constant pool
-    fn setup_constant_pool(&mut self) -> Result<(), String> {
-        self.constant_pool.add_utf8(self.class_name.clone())?; // UTF8 - class name
-        self.constant_pool
-            .add_utf8("java/lang/Object".to_string())?; // UTF8 - "java/lang/Object"
-        self.constant_pool.add_utf8("main".to_string())?; // UTF8 - "main"
-        self.constant_pool
-            .add_utf8("([Ljava/lang/String;)V".to_string())?; // UTF8 - "([Ljava/lang/String;)V"
-        self.constant_pool.add_utf8("Code".to_string())?; // UTF8 - "Code"
-        self.constant_pool
-            .add_utf8("java/lang/System".to_string())?; // UTF8 - "java/lang/System"
-        self.constant_pool.add_utf8("out".to_string())?; // UTF8 - "out"
-        self.constant_pool.add_utf8("err".to_string())?; // UTF8 - "err"
-        self.constant_pool
-            .add_utf8("Ljava/io/PrintStream;".to_string())?; // UTF8 - "Ljava/io/PrintStream;"
-        self.constant_pool
-            .add_utf8("java/io/PrintStream".to_string())?; // UTF8 - "java/io/PrintStream"
-        self.constant_pool.add_utf8("println".to_string())?; // UTF8 - "println"
-        self.constant_pool.add_utf8("(I)V".to_string())?; // UTF8 - "(I)V"
-        self.constant_pool.add_utf8("java/lang/Math".to_string())?; // UTF8 - "java/lang/Math"
-        self.constant_pool.add_utf8("random".to_string())?; // UTF8 - "random"
-        // 15: UTF8 - "()D"
-        self.constant_pool.add_utf8("()D".to_string())?;
-        // 16: UTF8 - "Total: "
-        self.constant_pool.add_utf8("Total: ".to_string())?;
-        // 17: UTF8 - "print"
-        self.constant_pool.add_utf8("print".to_string())?;
-        // 18: UTF8 - "(Ljava/lang/String;)V"
-        self.constant_pool
-            .add_utf8("(Ljava/lang/String;)V".to_string())?;
-
-        // Classes
-        // 19: Class - this class
-        self.constant_pool.add_class(1)?;
-        // 20: Class - java/lang/Object
-        self.constant_pool.add_class(2)?;
-        // 21: Class - java/lang/System
-        self.constant_pool.add_class(6)?;
-        // 22: Class - java/io/PrintStream
-        self.constant_pool.add_class(10)?;
-        // 23: Class - java/lang/Math
-        self.constant_pool.add_class(13)?;
-
-        // String constants
-        // 24: String - "Total: "
-        self.constant_pool.add_string(16)?;
-
-        // NameAndType
-        // 25: NameAndType - main method
-        self.constant_pool.add_name_and_type(3, 4)?;
-        // 26: NameAndType - out field
-        self.constant_pool.add_name_and_type(7, 9)?;
-        // 27: NameAndType - err field
-        self.constant_pool.add_name_and_type(8, 9)?;
-        // 28: NameAndType - println method
-        self.constant_pool.add_name_and_type(11, 12)?;
-        // 29: NameAndType - print method
-        self.constant_pool.add_name_and_type(17, 18)?;
-        // 30: NameAndType - random method
-        self.constant_pool.add_name_and_type(14, 15)?;
-
-        // Field and method references
-        // 31: Fieldref - System.out
-        self.constant_pool.add_fieldref(21, 26)?;
-        // 32: Fieldref - System.err
-        self.constant_pool.add_fieldref(21, 27)?;
-        // 33: Methodref - println
-        self.constant_pool.add_methodref(22, 28)?;
-        // 34: Methodref - print
-        self.constant_pool.add_methodref(22, 29)?;
-        // 35: Methodref - Math.random
-        self.constant_pool.add_methodref(23, 30)?;
-
-        Ok(())
-    }
-
     /// Generate bytecode for Dice
     fn generate_dice_bytecode(
         &mut self,
         count: u32,
         faces: u32,
     ) -> Result<Vec<JvmInstruction>, JavaClassGeneratorError> {
-        let mut instructions = Vec::new();
+        let count = int_literal("dice count", count)?;
+        let faces = int_literal("number of faces", faces)?;
+        let mut code = CodeBuilder::new();
 
         if count == 1 {
             // Single dice - don't display Total
-            self.generate_single_dice(&mut instructions, faces)
+            self.generate_single_dice(&mut code, faces)
                 .map_err(JavaClassGeneratorError::from)?;
         } else {
             // Multiple dice - display each result and Total
-            self.generate_multiple_dice(&mut instructions, count, faces)
+            self.generate_multiple_dice(&mut code, count, faces)
                 .map_err(JavaClassGeneratorError::from)?;
         }
 
-        instructions.push(JvmInstruction::Return);
-        Ok(instructions)
+        code.emit(JvmInstruction::Return);
+        Ok(code.finish()?)
     }
 
     /// Generate bytecode for single dice
-    fn generate_single_dice(
-        &mut self,
-        instructions: &mut Vec<JvmInstruction>,
-        faces: u32,
-    ) -> Result<(), String> {
-        // Math.random() * faces + 1
-        instructions.push(JvmInstruction::Invokestatic(35)); // Math.random()
-        self.push_double_constant(instructions, faces as f64)?;
-        instructions.push(JvmInstruction::Dmul);
-        instructions.push(JvmInstruction::Dconst1);
-        instructions.push(JvmInstruction::Dadd);
-        instructions.push(JvmInstruction::D2i);
+    fn generate_single_dice(&mut self, code: &mut CodeBuilder, faces: i32) -> Result<(), String> {
+        self.generate_roll(code, faces)?;
 
         // Output result to System.out
-        instructions.push(JvmInstruction::Getstatic(31)); // System.out
-        instructions.push(JvmInstruction::Swap);
-        instructions.push(JvmInstruction::Invokevirtual(33)); // println(I)V
+        code.emit(JvmInstruction::Getstatic(self.print_stream("out")?));
+        code.emit(JvmInstruction::Swap);
+        code.emit(JvmInstruction::Invokevirtual(
+            self.print_method("println", "(I)V")?,
+        ));
         Ok(())
     }
 
-    /// Generate bytecode for multiple dice
+    /// Generate bytecode for multiple dice, as a loop counting rolls in
+    /// local 2 and summing them in local 1
     fn generate_multiple_dice(
         &mut self,
-        instructions: &mut Vec<JvmInstruction>,
-        count: u32,
-        faces: u32,
+        code: &mut CodeBuilder,
+        count: i32,
+        faces: i32,
     ) -> Result<(), String> {
-        instructions.push(JvmInstruction::Iconst0); // total = 0
-
-        // Roll each dice
-        for _ in 0..count {
-            // Math.random() * faces + 1
-            instructions.push(JvmInstruction::Invokestatic(35)); // Math.random()
-            self.push_double_constant(instructions, faces as f64)?;
-            instructions.push(JvmInstruction::Dmul);
-            instructions.push(JvmInstruction::Dconst1);
-            instructions.push(JvmInstruction::Dadd);
-            instructions.push(JvmInstruction::D2i);
-
-            // Duplicate result (one for display, one for total)
-            instructions.push(JvmInstruction::Dup);
-
-            // Output individual result to System.out
-            instructions.push(JvmInstruction::Getstatic(31)); // System.out
-            instructions.push(JvmInstruction::Swap);
-            instructions.push(JvmInstruction::Invokevirtual(33)); // println(I)V
-
-            // Add to total
-            instructions.push(JvmInstruction::Iadd);
+        let loop_start = code.new_label();
+        let loop_end = code.new_label();
+
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Istore1); // total = 0
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Istore2); // i = 0
+
+        // while (i < count)
+        code.bind(loop_start);
+        code.emit(JvmInstruction::Iload2);
+        self.push_int_constant(code, count)?;
+        code.branch(JvmInstruction::IfIcmpge, loop_end);
+
+        self.generate_roll(code, faces)?;
+
+        // Output individual result to System.out
+        code.emit(JvmInstruction::Dup);
+        code.emit(JvmInstruction::Getstatic(self.print_stream("out")?));
+        code.emit(JvmInstruction::Swap);
+        code.emit(JvmInstruction::Invokevirtual(
+            self.print_method("println", "(I)V")?,
+        ));
+
+        // total += result; i++
+        code.emit(JvmInstruction::Iload1);
+        code.emit(JvmInstruction::Iadd);
+        code.emit(JvmInstruction::Istore1);
+        code.emit(JvmInstruction::Iinc(2, 1));
+        code.branch(JvmInstruction::Goto, loop_start);
+
+        // Output "Total: " and the total to System.err
+        code.bind(loop_end);
+        let err = self.print_stream("err")?;
+        code.emit(JvmInstruction::Getstatic(err));
+        self.push_string_constant(code, "Total: ")?;
+        code.emit(JvmInstruction::Invokevirtual(
+            self.print_method("print", "(Ljava/lang/String;)V")?,
+        ));
+        code.emit(JvmInstruction::Getstatic(err));
+        code.emit(JvmInstruction::Iload1);
+        code.emit(JvmInstruction::Invokevirtual(
+            self.print_method("println", "(I)V")?,
+        ));
+        Ok(())
+    }
+
+    /// `System.out` or `System.err`
+    fn print_stream(&mut self, name: &str) -> Result<u16, String> {
+        self.constant_pool
+            .fieldref("java/lang/System", name, "Ljava/io/PrintStream;")
+    }
+
+    /// A `PrintStream` method such as `println(I)V`
+    fn print_method(&mut self, name: &str, descriptor: &str) -> Result<u16, String> {
+        self.constant_pool
+            .methodref("java/io/PrintStream", name, descriptor)
+    }
+
+    /// `roll(count, faces)`: the sum of `count` rolls, counted in local 3
+    /// and summed in local 2
+    fn generate_roll_method(&mut self) -> Result<Vec<JvmInstruction>, String> {
+        let mut code = CodeBuilder::new();
+        let loop_start = code.new_label();
+        let loop_end = code.new_label();
+        let random = self
+            .constant_pool
+            .methodref("java/lang/Math", "random", "()D")?;
+
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Istore2); // total = 0
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Istore3); // i = 0
+
+        // while (i < count)
+        code.bind(loop_start);
+        code.emit(JvmInstruction::Iload3);
+        code.emit(JvmInstruction::Iload0);
+        code.branch(JvmInstruction::IfIcmpge, loop_end);
+
+        // total += (int) (Math.random() * faces + 1); i++
+        code.emit(JvmInstruction::Iload2);
+        code.emit(JvmInstruction::Invokestatic(random));
+        code.emit(JvmInstruction::Iload1);
+        code.emit(JvmInstruction::I2d);
+        code.emit(JvmInstruction::Dmul);
+        code.emit(JvmInstruction::Dconst1);
+        code.emit(JvmInstruction::Dadd);
+        code.emit(JvmInstruction::D2i);
+        code.emit(JvmInstruction::Iadd);
+        code.emit(JvmInstruction::Istore2);
+        code.emit(JvmInstruction::Iinc(3, 1));
+        code.branch(JvmInstruction::Goto, loop_start);
+
+        code.bind(loop_end);
+        code.emit(JvmInstruction::Iload2);
+        code.emit(JvmInstruction::Ireturn);
+        code.finish()
+    }
+
+    /// `main` for a parametric class. Locals: 1 count, 2 faces, 3 dice
+    /// rolled, 4 total and 5 the position of the `d` in an expression.
+    fn generate_parametric_main(&mut self) -> Result<Vec<JvmInstruction>, String> {
+        let mut code = CodeBuilder::new();
+        let one_argument = code.new_label();
+        let parsed = code.new_label();
+        let loop_start = code.new_label();
+        let loop_end = code.new_label();
+        let finish = code.new_label();
+        let usage = code.new_label();
+
+        let parse_int = self.constant_pool.methodref(
+            "java/lang/Integer",
+            "parseInt",
+            "(Ljava/lang/String;)I",
+        )?;
+        let string_method = |generator: &mut Self, name: &str, descriptor: &str| {
+            generator
+                .constant_pool
+                .methodref("java/lang/String", name, descriptor)
+        };
+        let to_lower_case = string_method(self, "toLowerCase", "()Ljava/lang/String;")?;
+        let index_of = string_method(self, "indexOf", "(I)I")?;
+        let substring_range = string_method(self, "substring", "(II)Ljava/lang/String;")?;
+        let substring_from = string_method(self, "substring", "(I)Ljava/lang/String;")?;
+        let roll =
+            self.constant_pool
+                .methodref(&self.class_name.clone(), "roll", ROLL_DESCRIPTOR)?;
+
+        // count = Integer.parseInt(args[0]); faces = Integer.parseInt(args[1])
+        code.emit(JvmInstruction::Aload0);
+        code.emit(JvmInstruction::Arraylength);
+        code.emit(JvmInstruction::Iconst2);
+        code.branch(JvmInstruction::IfIcmpne, one_argument);
+        for (argument, local) in [(JvmInstruction::Iconst0, 1), (JvmInstruction::Iconst1, 2)] {
+            code.emit(JvmInstruction::Aload0);
+            code.emit(argument);
+            code.emit(JvmInstruction::Aaload);
+            code.emit(JvmInstruction::Invokestatic(parse_int));
+            code.emit(JvmInstruction::Istore(local));
         }
+        code.branch(JvmInstruction::Goto, parsed);
+
+        // Split args[0] at its 'd', reloading it each time so that no
+        // String is held in a local
+        code.bind(one_argument);
+        code.emit(JvmInstruction::Aload0);
+        code.emit(JvmInstruction::Arraylength);
+        code.emit(JvmInstruction::Iconst1);
+        code.branch(JvmInstruction::IfIcmpne, usage);
+        code.emit(JvmInstruction::Aload0);
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Aaload);
+        code.emit(JvmInstruction::Invokevirtual(to_lower_case));
+        code.emit(JvmInstruction::Bipush(b'd' as i8));
+        code.emit(JvmInstruction::Invokevirtual(index_of));
+        code.emit(JvmInstruction::Istore(5));
+        code.emit(JvmInstruction::Aload0);
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Aaload);
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Iload(5));
+        code.emit(JvmInstruction::Invokevirtual(substring_range));
+        code.emit(JvmInstruction::Invokestatic(parse_int));
+        code.emit(JvmInstruction::Istore1);
+        code.emit(JvmInstruction::Aload0);
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Aaload);
+        code.emit(JvmInstruction::Iload(5));
+        code.emit(JvmInstruction::Iconst1);
+        code.emit(JvmInstruction::Iadd);
+        code.emit(JvmInstruction::Invokevirtual(substring_from));
+        code.emit(JvmInstruction::Invokestatic(parse_int));
+        code.emit(JvmInstruction::Istore2);
+
+        // Dice need a positive count and number of faces
+        code.bind(parsed);
+        code.emit(JvmInstruction::Iload1);
+        code.branch(JvmInstruction::Ifle, usage);
+        code.emit(JvmInstruction::Iload2);
+        code.branch(JvmInstruction::Ifle, usage);
+
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Istore(4)); // total = 0
+        code.emit(JvmInstruction::Iconst0);
+        code.emit(JvmInstruction::Istore3); // i = 0
+
+        // Roll and print each die
+        code.bind(loop_start);
+        code.emit(JvmInstruction::Iload3);
+        code.emit(JvmInstruction::Iload1);
+        code.branch(JvmInstruction::IfIcmpge, loop_end);
+        code.emit(JvmInstruction::Iconst1);
+        code.emit(JvmInstruction::Iload2);
+        code.emit(JvmInstruction::Invokestatic(roll));
+        code.emit(JvmInstruction::Dup);
+        code.emit(JvmInstruction::Getstatic(self.print_stream("out")?));
+        code.emit(JvmInstruction::Swap);
+        code.emit(JvmInstruction::Invokevirtual(
+            self.print_method("println", "(I)V")?,
+        ));
+        code.emit(JvmInstruction::Iload(4));
+        code.emit(JvmInstruction::Iadd);
+        code.emit(JvmInstruction::Istore(4));
+        code.emit(JvmInstruction::Iinc(3, 1));
+        code.branch(JvmInstruction::Goto, loop_start);
+
+        // A single die has no total
+        code.bind(loop_end);
+        code.emit(JvmInstruction::Iload1);
+        code.emit(JvmInstruction::Iconst1);
+        code.branch(JvmInstruction::IfIcmpeq, finish);
+        let err = self.print_stream("err")?;
+        code.emit(JvmInstruction::Getstatic(err));
+        self.push_string_constant(&mut code, "Total: ")?;
+        code.emit(JvmInstruction::Invokevirtual(
+            self.print_method("print", "(Ljava/lang/String;)V")?,
+        ));
+        code.emit(JvmInstruction::Getstatic(err));
+        code.emit(JvmInstruction::Iload(4));
+        code.emit(JvmInstruction::Invokevirtual(
+            self.print_method("println", "(I)V")?,
+        ));
+        code.bind(finish);
+        code.emit(JvmInstruction::Return);
+
+        code.bind(usage);
+        code.emit(JvmInstruction::Getstatic(err));
+        let class_name = self.class_name.replace('/', ".");
+        self.push_string_constant(
+            &mut code,
+            &format!("Usage: java {class_name} <count> <faces> or <count>d<faces>"),
+        )?;
+        code.emit(JvmInstruction::Invokevirtual(
+            self.print_method("println", "(Ljava/lang/String;)V")?,
+        ));
+        code.emit(JvmInstruction::Return);
+        code.finish()
+    }
 
-        // Output "Total: " to System.err
-        instructions.push(JvmInstruction::Dup); // Duplicate total
-        instructions.push(JvmInstruction::Getstatic(32)); // System.err
-        instructions.push(JvmInstruction::Ldc(24)); // "Total: "
-        instructions.push(JvmInstruction::Invokevirtual(34)); // print(String)V
-
-        // Output total to System.err
-        instructions.push(JvmInstruction::Getstatic(32)); // System.err
-        instructions.push(JvmInstruction::Swap);
-        instructions.push(JvmInstruction::Invokevirtual(33)); // println(I)V
-        instructions.push(JvmInstruction::Pop); // Remove remaining value from stack
+    /// Push one roll, `(int) (Math.random() * faces + 1)`
+    fn generate_roll(&mut self, code: &mut CodeBuilder, faces: i32) -> Result<(), String> {
+        let random = self
+            .constant_pool
+            .methodref("java/lang/Math", "random", "()D")?;
+        code.emit(JvmInstruction::Invokestatic(random));
+        self.push_double_constant(code, faces.into())?;
+        code.emit(JvmInstruction::Dmul);
+        code.emit(JvmInstruction::Dconst1);
+        code.emit(JvmInstruction::Dadd);
+        code.emit(JvmInstruction::D2i);
         Ok(())
     }
 
     /// Push double constant to stack
-    fn push_double_constant(
-        &mut self,
-        instructions: &mut Vec<JvmInstruction>,
-        value: f64,
-    ) -> Result<(), String> {
+    fn push_double_constant(&mut self, code: &mut CodeBuilder, value: f64) -> Result<(), String> {
         if value == 0.0 {
-            instructions.push(JvmInstruction::Dconst0);
+            code.emit(JvmInstruction::Dconst0);
         } else if value == 1.0 {
-            instructions.push(JvmInstruction::Dconst1);
+            code.emit(JvmInstruction::Dconst1);
         } else {
             // For more complex constants, use integer conversion
             let int_val = value as i32;
-            self.push_int_constant(instructions, int_val)?;
-            instructions.push(JvmInstruction::I2d);
+            self.push_int_constant(code, int_val)?;
+            code.emit(JvmInstruction::I2d);
         }
         Ok(())
     }
 
     /// Push int constant to stack
-    fn push_int_constant(
-        &mut self,
-        instructions: &mut Vec<JvmInstruction>,
-        value: i32,
-    ) -> Result<(), String> {
+    fn push_int_constant(&mut self, code: &mut CodeBuilder, value: i32) -> Result<(), String> {
         match value {
-            -1 => instructions.push(JvmInstruction::IconstM1),
-            0 => instructions.push(JvmInstruction::Iconst0),
-            1 => instructions.push(JvmInstruction::Iconst1),
-            2 => instructions.push(JvmInstruction::Iconst2),
-            3 => instructions.push(JvmInstruction::Iconst3),
-            4 => instructions.push(JvmInstruction::Iconst4),
-            5 => instructions.push(JvmInstruction::Iconst5),
+            -1 => code.emit(JvmInstruction::IconstM1),
+            0 => code.emit(JvmInstruction::Iconst0),
+            1 => code.emit(JvmInstruction::Iconst1),
+            2 => code.emit(JvmInstruction::Iconst2),
+            3 => code.emit(JvmInstruction::Iconst3),
+            4 => code.emit(JvmInstruction::Iconst4),
+            5 => code.emit(JvmInstruction::Iconst5),
             _ if (-128..=127).contains(&value) => {
-                instructions.push(JvmInstruction::Bipush(value as i8));
+                code.emit(JvmInstruction::Bipush(value as i8));
             }
             _ if (-32768..=32767).contains(&value) => {
-                instructions.push(JvmInstruction::Sipush(value as i16));
+                code.emit(JvmInstruction::Sipush(value as i16));
             }
             _ => {
                 // For values outside Sipush range, use constant pool with Ldc instruction
-                let index = self.constant_pool.add_integer(value)?;
-                instructions.push(JvmInstruction::Ldc(index));
+                let index = self.constant_pool.integer(value)?;
+                load_constant(code, index);
             }
         }
         Ok(())
     }
 
-    /// Generate Java class file
+    /// Push a `String` constant to stack
+    fn push_string_constant(&mut self, code: &mut CodeBuilder, value: &str) -> Result<(), String> {
+        let index = self.constant_pool.string(value)?;
+        load_constant(code, index);
+        Ok(())
+    }
+
+    /// Generate Java class file with a default constructor, a `SourceFile`
+    /// attribute and the given `public static` methods
     fn generate_class_file(
-        &self,
-        bytecode_instructions: Vec<JvmInstruction>,
+        &mut self,
+        methods: Vec<(&str, &str, Vec<JvmInstruction>)>,
     ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let mut bytes = Vec::new();
-
-        // Magic number
-        bytes.extend_from_slice(&0xCAFEBABEu32.to_be_bytes());
-
-        // Version (Java 8 = 52)
-        bytes.extend_from_slice(&0u16.to_be_bytes()); // Minor version
-        bytes.extend_from_slice(&52u16.to_be_bytes()); // Major version
-
-        // Constant pool count (non-placeholder entries + 1)
-        let non_placeholder_count = self
+        let this_class = self.constant_pool.class(&self.class_name)?;
+        let super_class = self.constant_pool.class("java/lang/Object")?;
+        let object_init = self
             .constant_pool
-            .entries()
-            .iter()
-            .filter(|entry| !matches!(entry, ConstantPoolEntry::Placeholder))
-            .count();
-        bytes.extend_from_slice(&(non_placeholder_count as u16 + 1).to_be_bytes());
-
-        // Constant pool entries
-        self.write_constant_pool(&mut bytes);
-
-        // Access flags (public class)
-        bytes.extend_from_slice(&0x0021u16.to_be_bytes());
-
-        // This class (index 19)
-        bytes.extend_from_slice(&19u16.to_be_bytes());
-
-        // Super class (index 20)
-        bytes.extend_from_slice(&20u16.to_be_bytes());
-
-        // Interfaces count
-        bytes.extend_from_slice(&0u16.to_be_bytes());
-
-        // Fields count
-        bytes.extend_from_slice(&0u16.to_be_bytes());
-
-        // Methods count
-        bytes.extend_from_slice(&1u16.to_be_bytes());
-
-        // Main method
-        self.write_main_method(&mut bytes, bytecode_instructions);
-
-        // Class attributes count
-        bytes.extend_from_slice(&0u16.to_be_bytes());
-
-        Ok(bytes)
-    }
+            .methodref("java/lang/Object", "<init>", "()V")?;
+        let constructor = vec![
+            JvmInstruction::Aload0,
+            JvmInstruction::Invokespecial(object_init),
+            JvmInstruction::Return,
+        ];
+
+        let mut method_infos = vec![self.method_info(0x0001, "<init>", "()V", constructor)?];
+        for (name, descriptor, bytecode) in methods {
+            // public static
+            method_infos.push(self.method_info(0x0009, name, descriptor, bytecode)?);
+        }
 
-    /// Write constant pool in binary format
-    fn write_constant_pool(&self, bytes: &mut Vec<u8>) {
-        for entry in self.constant_pool.entries() {
-            match entry {
-                ConstantPoolEntry::Utf8(s) => {
-                    bytes.push(1); // CONSTANT_Utf8
-                    bytes.extend_from_slice(&(s.len() as u16).to_be_bytes());
-                    bytes.extend_from_slice(s.as_bytes());
-                }
-                ConstantPoolEntry::Class(name_index) => {
-                    bytes.push(7); // CONSTANT_Class
-                    bytes.extend_from_slice(&name_index.to_be_bytes());
-                }
-                ConstantPoolEntry::String(utf8_index) => {
-                    bytes.push(8); // CONSTANT_String
-                    bytes.extend_from_slice(&utf8_index.to_be_bytes());
-                }
-                ConstantPoolEntry::Fieldref(class_index, name_and_type_index) => {
-                    bytes.push(9); // CONSTANT_Fieldref
-                    bytes.extend_from_slice(&class_index.to_be_bytes());
-                    bytes.extend_from_slice(&name_and_type_index.to_be_bytes());
-                }
-                ConstantPoolEntry::Methodref(class_index, name_and_type_index) => {
-                    bytes.push(10); // CONSTANT_Methodref
-                    bytes.extend_from_slice(&class_index.to_be_bytes());
-                    bytes.extend_from_slice(&name_and_type_index.to_be_bytes());
-                }
-                ConstantPoolEntry::NameAndType(name_index, descriptor_index) => {
-                    bytes.push(12); // CONSTANT_NameAndType
-                    bytes.extend_from_slice(&name_index.to_be_bytes());
-                    bytes.extend_from_slice(&descriptor_index.to_be_bytes());
-                }
-                ConstantPoolEntry::Integer(i) => {
-                    bytes.push(3); // CONSTANT_Integer
-                    bytes.extend_from_slice(&i.to_be_bytes());
-                }
-                ConstantPoolEntry::Float(f) => {
-                    bytes.push(4); // CONSTANT_Float
-                    bytes.extend_from_slice(&f.to_be_bytes());
-                }
-                ConstantPoolEntry::Long(l) => {
-                    bytes.push(5); // CONSTANT_Long
-                    bytes.extend_from_slice(&l.to_be_bytes());
-                }
-                ConstantPoolEntry::Double(d) => {
-                    bytes.push(6); // CONSTANT_Double
-                    bytes.extend_from_slice(&d.to_be_bytes());
-                }
-                ConstantPoolEntry::Placeholder => {
-                    // Skip placeholder entries - they should not be written to the class file
-                    // as they represent the second slot of 8-byte constants (Long/Double)
-                    // which are automatically handled by the JVM
-                    continue;
-                }
+        let simple_name = self.class_name.rsplit('/').next().unwrap_or_default();
+        let source_file = AttributeInfo {
+            name_index: self.constant_pool.utf8("SourceFile")?,
+            info: self
+                .constant_pool
+                .utf8(&format!("{simple_name}.java"))?
+                .to_be_bytes()
+                .to_vec(),
+        };
+
+        let mut class_file = ClassFile {
+            minor_version: 0,
+            major_version: self.java_version.major_version(),
+            constant_pool: self.constant_pool.constant_pool().clone(),
+            access_flags: 0x0021, // public class
+            this_class,
+            super_class,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: method_infos,
+            attributes: vec![source_file],
+        };
+
+        for index in 0..class_file.methods.len() {
+            // Size the frame from the code rather than guessing
+            let (max_stack, max_locals) = code_limits(&class_file, &class_file.methods[index])?;
+            if let Some(code) = &mut class_file.methods[index].code {
+                code.max_stack = max_stack;
+                code.max_locals = max_locals;
             }
+            add_stack_map_table(&mut class_file, index)?;
         }
-    }
-
-    /// Write main method
-    fn write_main_method(&self, bytes: &mut Vec<u8>, instructions: Vec<JvmInstruction>) {
-        // Access flags (public static)
-        bytes.extend_from_slice(&0x0009u16.to_be_bytes());
-
-        // Name index (3 = "main")
-        bytes.extend_from_slice(&3u16.to_be_bytes());
-
-        // Descriptor index (4 = "([Ljava/lang/String;)V")
-        bytes.extend_from_slice(&4u16.to_be_bytes());
 
-        // Attributes count
-        bytes.extend_from_slice(&1u16.to_be_bytes());
-
-        // Code attribute index (5 = "Code")
-        bytes.extend_from_slice(&5u16.to_be_bytes());
-
-        // Code attribute
-        let code_bytes = self.instructions_to_bytes(instructions);
-        let attribute_length = code_bytes.len() as u32 + 12;
-
-        bytes.extend_from_slice(&attribute_length.to_be_bytes());
-        bytes.extend_from_slice(&5u16.to_be_bytes()); // max_stack
-        bytes.extend_from_slice(&2u16.to_be_bytes()); // max_locals
-        bytes.extend_from_slice(&(code_bytes.len() as u32).to_be_bytes()); // code_length
-        bytes.extend_from_slice(&code_bytes); // actual bytecode
-        bytes.extend_from_slice(&0u16.to_be_bytes()); // exception_table_length
-        bytes.extend_from_slice(&0u16.to_be_bytes()); // attributes_count
+        Ok(ClassFileWriter::write(&class_file)
+            .map_err(JavaClassGeneratorError::CompilationError)?)
     }
 
-    /// Convert JVM instructions to byte array
-    fn instructions_to_bytes(&self, instructions: Vec<JvmInstruction>) -> Vec<u8> {
-        let mut bytes = Vec::new();
-
-        for instruction in instructions {
-            match instruction {
-                JvmInstruction::Iconst0 => bytes.push(0x03),
-                JvmInstruction::Iconst1 => bytes.push(0x04),
-                JvmInstruction::Iconst2 => bytes.push(0x05),
-                JvmInstruction::Iconst3 => bytes.push(0x06),
-                JvmInstruction::Iconst4 => bytes.push(0x07),
-                JvmInstruction::Iconst5 => bytes.push(0x08),
-                JvmInstruction::IconstM1 => bytes.push(0x02),
-                JvmInstruction::Bipush(value) => {
-                    bytes.push(0x10);
-                    bytes.push(value as u8);
-                }
-                JvmInstruction::Sipush(value) => {
-                    bytes.push(0x11);
-                    bytes.extend_from_slice(&(value as u16).to_be_bytes());
-                }
-                JvmInstruction::Ldc(index) => {
-                    bytes.push(0x12);
-                    bytes.push(index as u8);
-                }
-                JvmInstruction::Dup => bytes.push(0x59),
-                JvmInstruction::Pop => bytes.push(0x57),
-                JvmInstruction::Swap => bytes.push(0x5F),
-                JvmInstruction::Iadd => bytes.push(0x60),
-                JvmInstruction::Isub => bytes.push(0x64),
-                JvmInstruction::Imul => bytes.push(0x68),
-                JvmInstruction::Idiv => bytes.push(0x6C),
-                JvmInstruction::Irem => bytes.push(0x70),
-                JvmInstruction::Dadd => bytes.push(0x63),
-                JvmInstruction::Dsub => bytes.push(0x67),
-                JvmInstruction::Dmul => bytes.push(0x6B),
-                JvmInstruction::Ddiv => bytes.push(0x6F),
-                JvmInstruction::I2d => bytes.push(0x87),
-                JvmInstruction::D2i => bytes.push(0x8E),
-                JvmInstruction::Dconst0 => bytes.push(0x0E),
-                JvmInstruction::Dconst1 => bytes.push(0x0F),
-                JvmInstruction::Getstatic(index) => {
-                    bytes.push(0xB2);
-                    bytes.extend_from_slice(&index.to_be_bytes());
-                }
-                JvmInstruction::Invokestatic(index) => {
-                    bytes.push(0xB8);
-                    bytes.extend_from_slice(&index.to_be_bytes());
-                }
-                JvmInstruction::Invokevirtual(index) => {
-                    bytes.push(0xB6);
-                    bytes.extend_from_slice(&index.to_be_bytes());
-                }
-                JvmInstruction::Return => bytes.push(0xB1),
-                JvmInstruction::Ireturn => bytes.push(0xAC),
-                JvmInstruction::Nop => bytes.push(0x00),
-                other_instruction => {
-                    // Log unimplemented instruction for debugging purposes
-                    // This helps identify which JVM instructions need to be implemented
-                    // when extending the compiler to support more complex expressions
-                    eprintln!(
-                        "Warning: JVM instruction not implemented in bytecode generation: {other_instruction:?}, falling back to NOP"
-                    );
-                    // Use nop as fallback
-                    bytes.push(0x00); // nop
-                }
-            }
-        }
-
-        bytes
+    fn method_info(
+        &mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        bytecode: Vec<JvmInstruction>,
+    ) -> Result<MethodInfo, String> {
+        Ok(MethodInfo {
+            access_flags,
+            name_index: self.constant_pool.utf8(name)?,
+            descriptor_index: self.constant_pool.utf8(descriptor)?,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            code: Some(CodeAttribute {
+                name_index: self.constant_pool.utf8("Code")?,
+                max_stack: 0,
+                max_locals: 0,
+                bytecode,
+                exception_table: Vec::new(),
+                attributes: Vec::new(),
+            }),
+            attributes: Vec::new(),
+        })
     }
 
     pub fn constant_pool(&self) -> &ConstantPool {
-        &self.constant_pool
+        self.constant_pool.constant_pool()
     }
 }
 
+/// A value of the expression as a Java `int`
+pub(super) fn int_literal(what: &str, value: u32) -> Result<i32, JavaClassGeneratorError> {
+    i32::try_from(value).map_err(|_| {
+        JavaClassGeneratorError::CompilationError(format!(
+            "{what} {value} does not fit in a Java int"
+        ))
+    })
+}
+
+/// Push a constant pool entry with `ldc`, or `ldc_w` past index 255
+fn load_constant(code: &mut CodeBuilder, index: u16) {
+    code.emit(if index <= u8::MAX as u16 {
+        JvmInstruction::Ldc(index)
+    } else {
+        JvmInstruction::LdcW(index)
+    });
+}
+
 /// Unified JVM system - Java class file generation
 pub fn generate_java_class(
-    expression: &str,
+    source: DiceSource,
     class_name: &str,
+    java_version: JavaVersion,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut generator = JavaClassGenerator::new(class_name.to_string());
-    let class_bytes = generator.generate_dice_class(expression)?;
+    generator.set_java_version(java_version);
+    let class_bytes = generator.generate_class(source)?;
     let filename = format!("{class_name}.class");
     fs::write(&filename, &class_bytes)?;
 
     println!("Generated: {filename}");
-    println!("Run with: java {class_name}");
+    println!("Run with: java {class_name}{}", run_arguments(source));
     println!("View bytecode with: javap -c {class_name}.class");
 
     Ok(())
 }
 
+/// Generate a runnable JAR holding the generated class
+pub fn generate_java_jar(
+    source: DiceSource,
+    class_name: &str,
+    java_version: JavaVersion,
+    jar_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut generator = JavaClassGenerator::new(class_name.to_string());
+    generator.set_java_version(java_version);
+    let class_bytes = generator.generate_class(source)?;
+    let jar = write_jar(class_name, &[(class_name.to_string(), class_bytes)])?;
+    fs::write(jar_path, &jar)?;
+
+    println!("Generated: {jar_path}");
+    println!("Run with: java -jar {jar_path}{}", run_arguments(source));
+
+    Ok(())
+}
+
+/// Example arguments for the run hint
+fn run_arguments(source: DiceSource) -> &'static str {
+    match source {
+        DiceSource::Expression(_) => "",
+        DiceSource::Arguments => " 2d6",
+    }
+}
+
 /// Generate JVM instructions for VM execution
 pub fn generate_vm_instructions(
     expression: &str,
 ) -> Result<(Vec<JvmInstruction>, ConstantPool), Box<dyn std::error::Error>> {
     let mut generator = JavaClassGenerator::new("DiceRoll".to_string());
-    generator
-        .setup_constant_pool()
-        .map_err(JavaClassGeneratorError::CompilationError)?;
     let instructions = generator.generate_dice_instructions(expression)?;
-    Ok((instructions, generator.constant_pool.clone()))
+    Ok((instructions, generator.constant_pool().clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_outside_int_are_rejected() {
+        let mut generator = JavaClassGenerator::new("DiceRoll".to_string());
+        assert!(generator.generate_dice_class("0d6").is_err());
+        let error = generator.generate_dice_class("3000000000d6").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Compilation error: dice count 3000000000 does not fit in a Java int"
+        );
+        let error = generate_vm_instructions("2d3000000000").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Compilation error: number of faces 3000000000 does not fit in a Java int"
+        );
+    }
 }
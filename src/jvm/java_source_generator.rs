@@ -0,0 +1,130 @@
+use super::java_class_generator::int_literal;
+use crate::analyzer::SemanticAnalyzer;
+use crate::ast::{ExpressionKind, StatementKind};
+use std::fmt::Write;
+use std::fs;
+
+/// Generates `.java` source that prints the same output as the class file
+/// generated for an expression
+pub struct JavaSourceGenerator {
+    class_name: String,
+}
+
+impl JavaSourceGenerator {
+    /// `class_name` is a binary name such as `dice/Roll`; its package
+    /// becomes the `package` declaration
+    pub fn new(class_name: String) -> Self {
+        Self { class_name }
+    }
+
+    /// Generate Java source from Dice expression
+    pub fn generate_dice_source(
+        &self,
+        expression: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut analyzer = SemanticAnalyzer::new(expression)?;
+        let ast = analyzer.analyze()?;
+
+        let Some(stmt) = ast.statement else {
+            return Err("Invalid expression".into());
+        };
+        let StatementKind::Expression { expr } = stmt.kind;
+        let ExpressionKind::Dice { count, faces } = expr.kind;
+        let count = int_literal("dice count", count)?;
+        let faces = int_literal("number of faces", faces)?;
+
+        let (package, simple_name) = match self.class_name.rsplit_once('/') {
+            Some((package, simple_name)) => (Some(package.replace('/', ".")), simple_name),
+            None => (None, self.class_name.as_str()),
+        };
+
+        let mut source = String::new();
+        if let Some(package) = package {
+            writeln!(source, "package {package};\n")?;
+        }
+        writeln!(source, "public class {simple_name} {{")?;
+        writeln!(source, "    public static void main(String[] args) {{")?;
+        if count == 1 {
+            // Single dice - don't display Total
+            writeln!(source, "        int roll = {};", roll_expression(faces))?;
+            writeln!(source, "        System.out.println(roll);")?;
+        } else {
+            writeln!(source, "        int total = 0;")?;
+            writeln!(source, "        for (int i = 0; i < {count}; i++) {{")?;
+            writeln!(source, "            int roll = {};", roll_expression(faces))?;
+            writeln!(source, "            System.out.println(roll);")?;
+            writeln!(source, "            total += roll;")?;
+            writeln!(source, "        }}")?;
+            writeln!(source, "        System.err.println(\"Total: \" + total);")?;
+        }
+        writeln!(source, "    }}")?;
+        writeln!(source, "}}")?;
+        Ok(source)
+    }
+}
+
+/// `Math.random()` scaled to a roll of one die, as the class file computes it
+fn roll_expression(faces: i32) -> String {
+    format!("(int) (Math.random() * {faces} + 1)")
+}
+
+/// Unified JVM system - Java source generation
+pub fn generate_java_source(
+    expression: &str,
+    class_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source =
+        JavaSourceGenerator::new(class_name.to_string()).generate_dice_source(expression)?;
+    let filename = format!("{class_name}.java");
+    fs::write(&filename, source)?;
+
+    println!("Generated: {filename}");
+    println!("Compile with: javac {filename}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_source_matches_golden_files() {
+        let cases = [
+            (
+                "1d6",
+                "DiceRoll",
+                include_str!("../../tests/golden/java-src/DiceRoll_1d6.java"),
+            ),
+            (
+                "3d6",
+                "DiceRoll",
+                include_str!("../../tests/golden/java-src/DiceRoll_3d6.java"),
+            ),
+            (
+                "2D100",
+                "DiceRoll",
+                include_str!("../../tests/golden/java-src/DiceRoll_2d100.java"),
+            ),
+            (
+                "60000d20",
+                "games/dice/Roll",
+                include_str!("../../tests/golden/java-src/Roll_60000d20.java"),
+            ),
+        ];
+        for (expression, class_name, golden) in cases {
+            let source = JavaSourceGenerator::new(class_name.to_string())
+                .generate_dice_source(expression)
+                .unwrap();
+            assert_eq!(source, golden, "{expression}");
+        }
+
+        let generator = JavaSourceGenerator::new("DiceRoll".to_string());
+        assert!(generator.generate_dice_source("0d6").is_err());
+        let error = generator.generate_dice_source("3000000000d6").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Compilation error: dice count 3000000000 does not fit in a Java int"
+        );
+    }
+}
@@ -1,8 +1,135 @@
-use super::class_file_parser::{ClassFile, ClassFileParser, MethodInfo};
-use super::jvm_types::{ConstantPool, ConstantPoolEntry, JvmInstruction};
+use super::class_file_parser::{AttributeInfo, ClassFile, ClassFileParser, MethodInfo};
+use super::class_file_writer::instruction_offsets;
+use super::class_loader::{ClassLoader, ClassPathEntry, LoadedClass};
+use super::jvm_types::{ConstantPool, ConstantPoolEntry, JvmInstruction, method_parameter_types};
+use super::natives::{
+    JavaHashMap, NativeMethod, NativeRegistry, boxed_descriptor, progression_to_string,
+};
 use crate::error::RuntimeError;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const ACC_PRIVATE: u16 = 0x0002;
+const ACC_STATIC: u16 = 0x0008;
+const ACC_ABSTRACT: u16 = 0x0400;
+
+/// Superclasses of the throwable classes the VM can raise or recognise
+/// without a class file
+const BUILTIN_THROWABLES: &[(&str, &str)] = &[
+    ("java/lang/Throwable", "java/lang/Object"),
+    ("java/lang/Exception", "java/lang/Throwable"),
+    ("java/lang/Error", "java/lang/Throwable"),
+    ("java/lang/RuntimeException", "java/lang/Exception"),
+    ("java/lang/VirtualMachineError", "java/lang/Error"),
+    (
+        "java/lang/OutOfMemoryError",
+        "java/lang/VirtualMachineError",
+    ),
+    (
+        "java/lang/ArithmeticException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/ArrayStoreException",
+        "java/lang/RuntimeException",
+    ),
+    ("java/lang/ClassCastException", "java/lang/RuntimeException"),
+    (
+        "java/lang/IllegalArgumentException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IllegalStateException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IndexOutOfBoundsException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/ArrayIndexOutOfBoundsException",
+        "java/lang/IndexOutOfBoundsException",
+    ),
+    (
+        "java/lang/StringIndexOutOfBoundsException",
+        "java/lang/IndexOutOfBoundsException",
+    ),
+    (
+        "java/lang/NegativeArraySizeException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/NullPointerException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/NumberFormatException",
+        "java/lang/IllegalArgumentException",
+    ),
+    (
+        "java/lang/UnsupportedOperationException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/util/NoSuchElementException",
+        "java/lang/RuntimeException",
+    ),
+];
+
+/// Direct supertypes of the library classes the VM implements natively,
+/// for `instanceof` and `checkcast`
+const LIBRARY_SUPERTYPES: &[(&str, &[&str])] = &[
+    (
+        "java/lang/String",
+        &["java/lang/CharSequence", "java/lang/Comparable"],
+    ),
+    ("java/lang/StringBuilder", &["java/lang/CharSequence"]),
+    (
+        "java/lang/Integer",
+        &["java/lang/Number", "java/lang/Comparable"],
+    ),
+    (
+        "java/lang/Long",
+        &["java/lang/Number", "java/lang/Comparable"],
+    ),
+    (
+        "java/lang/Float",
+        &["java/lang/Number", "java/lang/Comparable"],
+    ),
+    (
+        "java/lang/Double",
+        &["java/lang/Number", "java/lang/Comparable"],
+    ),
+    ("java/lang/Boolean", &["java/lang/Comparable"]),
+    ("java/lang/Character", &["java/lang/Comparable"]),
+    (
+        "java/util/ArrayList",
+        &["java/util/List", "java/util/RandomAccess"],
+    ),
+    ("java/util/List", &["java/util/Collection"]),
+    ("java/util/Collection", &["java/lang/Iterable"]),
+    ("java/util/HashMap", &["java/util/Map"]),
+    ("java/util/ArrayList$Itr", &["java/util/Iterator"]),
+    (
+        "java/util/Collections$ReverseComparator",
+        &["java/util/Comparator"],
+    ),
+    (
+        "java/util/concurrent/ThreadLocalRandom",
+        &["java/util/Random"],
+    ),
+    ("kotlin/ranges/IntRange", &["kotlin/ranges/IntProgression"]),
+    ("kotlin/ranges/IntProgression", &["java/lang/Iterable"]),
+    (
+        "kotlin/ranges/IntProgressionIterator",
+        &["kotlin/collections/IntIterator"],
+    ),
+    ("kotlin/collections/IntIterator", &["java/util/Iterator"]),
+    ("kotlin/random/Random$Default", &["kotlin/random/Random"]),
+];
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum JvmValue {
@@ -16,78 +143,15 @@ pub enum JvmValue {
     ReturnAddress(usize),
 }
 
-#[derive(Debug, Clone)]
-enum ResolvedMethod {
-    // PrintStream methods
-    PrintStreamPrintln,        // println(I)V
-    PrintStreamPrint,          // print(Ljava/lang/String;)V
-    PrintStreamPrintlnString,  // println(Ljava/lang/String;)V
-    PrintStreamPrintlnFloat,   // println(F)V
-    PrintStreamPrintlnDouble,  // println(D)V
-    PrintStreamPrintlnBoolean, // println(Z)V
-    PrintStreamPrintlnChar,    // println(C)V
-
-    // Math methods
-    MathRandom,    // Math.random()D
-    MathMaxInt,    // Math.max(II)I
-    MathMinInt,    // Math.min(II)I
-    MathMaxDouble, // Math.max(DD)D
-    MathMinDouble, // Math.min(DD)D
-    MathAbs,       // Math.abs(I)I
-    MathAbsDouble, // Math.abs(D)D
-    MathPow,       // Math.pow(DD)D
-    MathSqrt,      // Math.sqrt(D)D
-    MathFloor,     // Math.floor(D)D
-    MathCeil,      // Math.ceil(D)D
-    MathRound,     // Math.round(D)J
-    MathSin,       // Math.sin(D)D
-    MathCos,       // Math.cos(D)D
-    MathTan,       // Math.tan(D)D
-    MathLog,       // Math.log(D)D
-    MathExp,       // Math.exp(D)D
-
-    // String methods
-    StringLength, // String.length()I
-
-    // StringBuilder methods
-    StringBuilderAppendString, // StringBuilder.append(Ljava/lang/String;)Ljava/lang/StringBuilder;
-    StringBuilderAppendInt,    // StringBuilder.append(I)Ljava/lang/StringBuilder;
-    StringBuilderAppendDouble, // StringBuilder.append(D)Ljava/lang/StringBuilder;
-    StringBuilderToString,     // StringBuilder.toString()Ljava/lang/String;
-    StringCharAt,              // String.charAt(I)C
-    StringSubstring,           // String.substring(II)Ljava/lang/String;
-    StringIndexOf,             // String.indexOf(I)I
-    StringToUpperCase,         // String.toUpperCase()Ljava/lang/String;
-    StringToLowerCase,         // String.toLowerCase()Ljava/lang/String;
-    StringTrim,                // String.trim()Ljava/lang/String;
-    StringEquals,              // String.equals(Ljava/lang/Object;)Z
-    StringConcat,              // String.concat(Ljava/lang/String;)Ljava/lang/String;
-
-    // Integer wrapper methods
-    IntegerParseInt, // Integer.parseInt(Ljava/lang/String;)I
-    IntegerToString, // Integer.toString(I)Ljava/lang/String;
-    IntegerValueOf,  // Integer.valueOf(I)Ljava/lang/Integer;
-
-    // Double wrapper methods
-    DoubleParseDouble, // Double.parseDouble(Ljava/lang/String;)D
-    DoubleToString,    // Double.toString(D)Ljava/lang/String;
-    DoubleValueOf,     // Double.valueOf(D)Ljava/lang/Double;
-
-    // Boolean wrapper methods
-    BooleanParseBoolean, // Boolean.parseBoolean(Ljava/lang/String;)Z
-    BooleanToString,     // Boolean.toString(Z)Ljava/lang/String;
-    BooleanValueOf,      // Boolean.valueOf(Z)Ljava/lang/Boolean;
-
-    // Character methods
-    CharacterIsDigit,     // Character.isDigit(C)Z
-    CharacterIsLetter,    // Character.isLetter(C)Z
-    CharacterToUpperCase, // Character.toUpperCase(C)C
-    CharacterToLowerCase, // Character.toLowerCase(C)C
-
-    Unknown,
-}
-
 impl JvmValue {
+    /// Operand stack words the value takes
+    fn width(&self) -> usize {
+        match self {
+            JvmValue::Long(_) | JvmValue::Double(_) => 2,
+            _ => 1,
+        }
+    }
+
     pub fn as_int(&self) -> Result<i32, RuntimeError> {
         match self {
             JvmValue::Int(i) => Ok(*i),
@@ -98,6 +162,14 @@ impl JvmValue {
         }
     }
 
+    pub fn as_long(&self) -> Result<i64, RuntimeError> {
+        match self {
+            JvmValue::Long(l) => Ok(*l),
+            JvmValue::Int(i) => Ok(*i as i64),
+            _ => Err(RuntimeError::InvalidStackState),
+        }
+    }
+
     pub fn as_float(&self) -> Result<f32, RuntimeError> {
         match self {
             JvmValue::Int(i) => Ok(*i as f32),
@@ -132,6 +204,15 @@ impl JvmValue {
         }
     }
 
+    /// Heap id of a non-null reference; null raises NullPointerException
+    pub fn as_reference(&self) -> Result<usize, RuntimeError> {
+        match self {
+            JvmValue::Reference(Some(id)) => Ok(*id),
+            JvmValue::Reference(None) => Err(RuntimeError::NullPointer),
+            _ => Err(RuntimeError::InvalidStackState),
+        }
+    }
+
     pub fn is_null(&self) -> bool {
         matches!(self, JvmValue::Reference(None))
     }
@@ -141,39 +222,308 @@ impl JvmValue {
 pub struct MethodFrame {
     pub locals: Vec<JvmValue>,
     pub operand_stack: Vec<JvmValue>,
-    pub constant_pool: ConstantPool,
+    pub constant_pool: Rc<ConstantPool>,
+    /// Index of the current instruction. A caller's pc stays on its invoke
+    /// while the callee runs and moves past it when the callee returns.
     pub pc: usize,
     pub bytecode: Vec<JvmInstruction>,
+    /// Class that declares the executing method; `None` for bare bytecode
+    pub class_name: Option<String>,
+    pub method_name: String,
+    pub exception_handlers: Vec<ExceptionHandler>,
+    /// `(first instruction index, source line)` pairs sorted by index
+    pub line_numbers: Vec<(usize, u16)>,
+}
+
+/// Exception table entry with instruction indices instead of byte offsets
+#[derive(Debug, Clone)]
+pub struct ExceptionHandler {
+    pub start: usize,
+    pub end: usize,
+    pub handler: usize,
+    /// `None` catches everything, as used for `finally` blocks
+    pub catch_type: Option<String>,
+}
+
+/// How an instruction leaves the dispatch loop
+#[derive(Debug, PartialEq)]
+enum Completion {
+    /// Carry on with the top frame
+    Next,
+    /// The top frame returned, with its value unless the method is void
+    Returned(Option<JvmValue>),
+}
+
+impl MethodFrame {
+    fn new(
+        class_name: Option<String>,
+        constant_pool: impl Into<Rc<ConstantPool>>,
+        bytecode: Vec<JvmInstruction>,
+        max_locals: usize,
+        arguments: Vec<JvmValue>,
+    ) -> Self {
+        // Longs and doubles take two local slots, the value in the first
+        let mut locals = Vec::with_capacity(max_locals);
+        for argument in arguments {
+            let wide = matches!(argument, JvmValue::Long(_) | JvmValue::Double(_));
+            locals.push(argument);
+            if wide {
+                locals.push(JvmValue::Int(0));
+            }
+        }
+        if locals.len() < max_locals {
+            locals.resize(max_locals, JvmValue::Int(0));
+        }
+
+        Self {
+            locals,
+            operand_stack: Vec::new(),
+            constant_pool: constant_pool.into(),
+            pc: 0,
+            bytecode,
+            class_name,
+            method_name: "main".to_string(),
+            exception_handlers: Vec::new(),
+            line_numbers: Vec::new(),
+        }
+    }
+
+    /// Frame for a method of a loaded class, with its exception table and
+    /// line numbers translated to instruction indices
+    fn for_method(
+        class: &LoadedClass,
+        method: &MethodInfo,
+        arguments: Vec<JvmValue>,
+    ) -> Result<Self, RuntimeError> {
+        let mut frame = Self::new(
+            Some(class.name.clone()),
+            Rc::clone(&class.constant_pool),
+            method.bytecode().to_vec(),
+            method.max_locals(),
+            arguments,
+        );
+        frame.method_name = method.name.clone();
+
+        if let Some(code) = &method.code {
+            let offsets = instruction_offsets(&code.bytecode);
+            let index_of = |pc: u16| offsets.partition_point(|&offset| offset < pc as usize);
+
+            frame.exception_handlers = code
+                .exception_table
+                .iter()
+                .map(|entry| ExceptionHandler {
+                    start: index_of(entry.start_pc),
+                    end: index_of(entry.end_pc),
+                    handler: index_of(entry.handler_pc),
+                    catch_type: class
+                        .constant_pool
+                        .get_class_name(entry.catch_type)
+                        .map(str::to_string),
+                })
+                .collect();
+            frame.line_numbers = code
+                .line_number_table(&class.constant_pool)?
+                .into_iter()
+                .map(|(start_pc, line)| (index_of(start_pc), line))
+                .collect();
+        }
+        Ok(frame)
+    }
+
+    fn pop(&mut self) -> Result<JvmValue, RuntimeError> {
+        self.operand_stack.pop().ok_or(RuntimeError::StackUnderflow)
+    }
+
+    /// Pop the values making up the top `words` stack words, bottom first.
+    /// Longs and doubles take two words and are never split.
+    fn pop_words(&mut self, words: usize) -> Result<Vec<JvmValue>, RuntimeError> {
+        let mut values = Vec::new();
+        let mut taken = 0;
+        while taken < words {
+            let value = self.pop()?;
+            taken += value.width();
+            values.push(value);
+        }
+        if taken != words {
+            return Err(RuntimeError::InvalidStackState);
+        }
+        values.reverse();
+        Ok(values)
+    }
+
+    /// Copy the top `copied` words below the `skipped` words under them, as
+    /// `dup2` and the `dup_x` and `dup2_x` instructions do
+    fn dup_words(&mut self, copied: usize, skipped: usize) -> Result<(), RuntimeError> {
+        let top = self.pop_words(copied)?;
+        let below = self.pop_words(skipped)?;
+        self.operand_stack.extend(top.iter().cloned());
+        self.operand_stack.extend(below);
+        self.operand_stack.extend(top);
+        Ok(())
+    }
+
+    fn load_local(&mut self, index: usize) -> Result<(), RuntimeError> {
+        let value = self
+            .locals
+            .get(index)
+            .ok_or(RuntimeError::InvalidStackState)?
+            .clone();
+        self.operand_stack.push(value);
+        Ok(())
+    }
+
+    fn store_local(&mut self, index: usize) -> Result<(), RuntimeError> {
+        let value = self.pop()?;
+        if index >= self.locals.len() {
+            self.locals.resize(index + 1, JvmValue::Int(0));
+        }
+        self.locals[index] = value;
+        Ok(())
+    }
+
+    /// Source line of the current instruction, if known
+    fn line_number(&self) -> Option<u16> {
+        self.line_numbers
+            .iter()
+            .take_while(|(start, _)| *start <= self.pc)
+            .last()
+            .map(|(_, line)| *line)
+    }
+}
+
+/// Call depth allowed by default, roughly where a JVM with its default
+/// thread stack size throws `StackOverflowError`
+const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// Bytes of array elements allocated by default, so a single `newarray` in
+/// an untrusted class cannot exhaust the host's memory
+const DEFAULT_MAX_ARRAY_BYTES: usize = 1 << 30;
+
+/// Bytes of string contents allocated by default, so a loop that doubles a
+/// string stops before the host runs out of memory
+const DEFAULT_MAX_STRING_BYTES: usize = 1 << 28;
+
+/// How many instructions run between checks of the clock
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+/// Resource limits for running untrusted class files. `None` leaves a
+/// resource unlimited; each limit that is exceeded stops execution with its
+/// own `RuntimeError`, which the program cannot catch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VmLimits {
+    /// Bytecode instructions executed
+    pub max_instructions: Option<u64>,
+    /// Method frames on the call stack at once
+    pub max_call_depth: Option<usize>,
+    /// Objects, strings and arrays allocated. The VM never frees objects,
+    /// so this counts every allocation.
+    pub max_heap_objects: Option<usize>,
+    /// Bytes of string and string builder contents allocated. Each string
+    /// is checked before it is created or grows.
+    pub max_string_bytes: Option<usize>,
+    /// Bytes of array elements allocated, as the host stores them. Each
+    /// array is checked before it is allocated.
+    pub max_array_bytes: Option<usize>,
+    /// Wall-clock time from the start of execution
+    pub timeout: Option<Duration>,
+}
+
+impl VmLimits {
+    /// No limits at all, including on call depth
+    pub fn unlimited() -> Self {
+        Self {
+            max_instructions: None,
+            max_call_depth: None,
+            max_heap_objects: None,
+            max_string_bytes: None,
+            max_array_bytes: None,
+            timeout: None,
+        }
+    }
+}
+
+impl Default for VmLimits {
+    /// Only the call depth and the size of strings and arrays are limited
+    fn default() -> Self {
+        Self {
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            max_string_bytes: Some(DEFAULT_MAX_STRING_BYTES),
+            max_array_bytes: Some(DEFAULT_MAX_ARRAY_BYTES),
+            ..Self::unlimited()
+        }
+    }
 }
 
 pub struct JvmCompatibleVm {
     frames: Vec<MethodFrame>,
     heap: HashMap<usize, JvmObject>,
     string_data: HashMap<usize, String>,
+    array_data: HashMap<usize, Vec<JvmValue>>,
     next_object_id: usize,
-    max_steps: usize,
-    steps: usize,
+    limits: VmLimits,
+    /// Instructions executed since execution started
+    steps: u64,
+    /// When execution started, for the timeout
+    started: Option<Instant>,
+    /// Bytes of string contents allocated, for the string limit
+    string_bytes: usize,
+    /// Bytes of array elements allocated, for the array limit
+    array_bytes: usize,
     verbose: bool,
-    current_class: Option<ClassFile>,
+    /// Classes referenced by the running program; those it cannot find are
+    /// served by the built-in library
+    class_loader: ClassLoader,
+    initialized_classes: HashSet<String>,
+    static_fields: HashMap<String, HashMap<String, JvmValue>>,
+    /// Stack trace captured when each throwable was created
+    stack_traces: HashMap<usize, Vec<String>>,
+    /// Library methods implemented in Rust
+    natives: NativeRegistry,
+    /// Elements of `java.util.ArrayList` objects
+    lists: HashMap<usize, Vec<JvmValue>>,
+    /// Entries of `java.util.HashMap` objects
+    maps: HashMap<usize, JavaHashMap>,
+    /// Boxes shared by `valueOf` calls for small values, by wrapper class
+    /// and value
+    box_cache: HashMap<(&'static str, i64), JvmValue>,
 }
 
 #[derive(Debug, Clone)]
 pub struct JvmObject {
     pub class_name: String,
+    /// Fields declared by loaded classes are keyed by [`field_key`]; the
+    /// state natives keep for library objects uses plain names
     pub fields: HashMap<String, JvmValue>,
 }
 
+/// Key of an instance field declared by a loaded class. A field that hides
+/// one of its superclass's fields has a slot of its own.
+pub fn field_key(class_name: &str, name: &str, descriptor: &str) -> String {
+    format!("{class_name}.{name}:{descriptor}")
+}
+
 impl JvmCompatibleVm {
     pub fn new() -> Self {
         Self {
             frames: Vec::new(),
             heap: HashMap::new(),
             string_data: HashMap::new(),
+            array_data: HashMap::new(),
             next_object_id: 1,
-            max_steps: 100_000,
+            limits: VmLimits::default(),
             steps: 0,
+            started: None,
+            string_bytes: 0,
+            array_bytes: 0,
             verbose: false,
-            current_class: None,
+            class_loader: ClassLoader::new(),
+            initialized_classes: HashSet::new(),
+            static_fields: HashMap::new(),
+            stack_traces: HashMap::new(),
+            natives: NativeRegistry::with_jdk(),
+            lists: HashMap::new(),
+            maps: HashMap::new(),
+            box_cache: HashMap::new(),
         }
     }
 
@@ -181,7 +531,235 @@ impl JvmCompatibleVm {
         self.verbose = verbose;
     }
 
-    fn create_string_object(&mut self, value: String) -> usize {
+    pub fn set_limits(&mut self, limits: VmLimits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &VmLimits {
+        &self.limits
+    }
+
+    /// Search the given directories and archives for classes, in order,
+    /// before the directory of the executed class file
+    pub fn set_classpath(&mut self, classpath: &str) -> Result<(), RuntimeError> {
+        self.class_loader = ClassLoader::from_classpath(classpath)?;
+        Ok(())
+    }
+
+    pub fn natives_mut(&mut self) -> &mut NativeRegistry {
+        &mut self.natives
+    }
+
+    /// Register a native method, e.g. a `static native int roll(int, int)`
+    /// declared by a host class `DiceHost` as `("DiceHost", "roll", "(II)I")`
+    pub fn register_native<F>(&mut self, class_name: &str, name: &str, descriptor: &str, method: F)
+    where
+        F: Fn(&mut JvmCompatibleVm, &[JvmValue]) -> Result<Option<JvmValue>, RuntimeError>
+            + 'static,
+    {
+        self.natives.register(class_name, name, descriptor, method);
+    }
+
+    /// Call a registered native directly, with the receiver, if any, as the
+    /// first argument
+    pub fn invoke_native(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+        arguments: &[JvmValue],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        let native = self
+            .natives
+            .get(class_name, name, descriptor)
+            .ok_or_else(|| {
+                RuntimeError::NoSuchMethod(format!("{class_name}.{name}{descriptor}"))
+            })?;
+        native(self, arguments)
+    }
+
+    /// Call an instance method with virtual dispatch on the receiver's class,
+    /// running its bytecode or native, e.g. a user-defined `compareTo` from a
+    /// native sort
+    pub fn call_method(
+        &mut self,
+        receiver: &JvmValue,
+        name: &str,
+        descriptor: &str,
+        arguments: &[JvmValue],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        let class_name = self
+            .heap
+            .get(&receiver.as_reference()?)
+            .map(|object| object.class_name.clone())
+            .ok_or(RuntimeError::InvalidStackState)?;
+        let mut all_arguments = vec![receiver.clone()];
+        all_arguments.extend_from_slice(arguments);
+
+        if let Some((class, method)) =
+            self.find_method_in_hierarchy(&class_name, name, descriptor)?
+            && method.code.is_some()
+        {
+            let frame = MethodFrame::for_method(&class, &method, all_arguments)?;
+            return self.run_frame(frame);
+        }
+        match self.virtual_native(&class_name, &class_name, name, descriptor) {
+            Some(native) => native(self, &all_arguments),
+            None => Err(RuntimeError::NoSuchMethod(format!(
+                "{class_name}.{name}{descriptor}"
+            ))),
+        }
+    }
+
+    /// Allocate a `java.lang.String`
+    pub fn new_string(&mut self, value: impl Into<String>) -> Result<JvmValue, RuntimeError> {
+        Ok(JvmValue::Reference(Some(
+            self.create_string_object(value.into())?,
+        )))
+    }
+
+    /// The contents of a string reference
+    pub fn string_value(&self, value: &JvmValue) -> Result<&str, RuntimeError> {
+        self.string_data
+            .get(&value.as_reference()?)
+            .map(String::as_str)
+            .ok_or(RuntimeError::InvalidStackState)
+    }
+
+    /// Allocate an object without running a constructor, like `new`
+    pub fn new_instance(&mut self, class_name: &str) -> Result<JvmValue, RuntimeError> {
+        Ok(JvmValue::Reference(Some(self.new_object(class_name)?)))
+    }
+
+    /// Create a throwable with the given message and return the error that
+    /// raises it, for natives to return
+    pub fn throw_exception(&mut self, class_name: &str, message: Option<String>) -> RuntimeError {
+        let exception_id = match self.new_object(class_name) {
+            Ok(exception_id) => exception_id,
+            Err(error) => return error,
+        };
+        let message = match message {
+            Some(message) => match self.new_string(message) {
+                Ok(message) => message,
+                Err(error) => return error,
+            },
+            None => JvmValue::Reference(None),
+        };
+        if let Some(exception) = self.heap.get_mut(&exception_id) {
+            exception
+                .fields
+                .insert("detailMessage".to_string(), message);
+        }
+        RuntimeError::Exception(exception_id)
+    }
+
+    /// Class and name of the method executing bytecode, which is the caller
+    /// of any native running now
+    pub(crate) fn calling_method(&self) -> Option<(&str, &str)> {
+        let frame = self.frames.last()?;
+        Some((frame.class_name.as_deref()?, frame.method_name.as_str()))
+    }
+
+    /// The class of a heap object, or `None` for null
+    pub(crate) fn object_class(&self, object: &JvmValue) -> Option<&str> {
+        let JvmValue::Reference(Some(object_id)) = object else {
+            return None;
+        };
+        Some(self.heap.get(object_id)?.class_name.as_str())
+    }
+
+    /// A field of a heap object, or `None` for null and unknown fields
+    pub(crate) fn object_field(&self, object: &JvmValue, name: &str) -> Option<&JvmValue> {
+        let JvmValue::Reference(Some(object_id)) = object else {
+            return None;
+        };
+        self.heap.get(object_id)?.fields.get(name)
+    }
+
+    /// Set a field of a heap object
+    pub(crate) fn set_object_field(
+        &mut self,
+        object: &JvmValue,
+        name: &str,
+        value: JvmValue,
+    ) -> Result<(), RuntimeError> {
+        let object = self
+            .heap
+            .get_mut(&object.as_reference()?)
+            .ok_or(RuntimeError::InvalidStackState)?;
+        object.fields.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Allocate an array, e.g. `[I`, holding the given elements
+    pub fn new_array_of(&mut self, descriptor: &str, elements: Vec<JvmValue>) -> JvmValue {
+        JvmValue::Reference(Some(
+            self.create_array_object(descriptor.to_string(), elements),
+        ))
+    }
+
+    /// The elements of an array
+    pub(crate) fn array_mut(
+        &mut self,
+        array: &JvmValue,
+    ) -> Result<&mut Vec<JvmValue>, RuntimeError> {
+        self.array_data
+            .get_mut(&array.as_reference()?)
+            .ok_or(RuntimeError::InvalidStackState)
+    }
+
+    /// The elements stored for an `ArrayList`
+    pub(crate) fn list_mut(&mut self, list: &JvmValue) -> Result<&mut Vec<JvmValue>, RuntimeError> {
+        Ok(self.lists.entry(list.as_reference()?).or_default())
+    }
+
+    /// The entries stored for a `HashMap`
+    pub(crate) fn map_mut(&mut self, map: &JvmValue) -> Result<&mut JavaHashMap, RuntimeError> {
+        Ok(self.maps.entry(map.as_reference()?).or_default())
+    }
+
+    /// Boxes `valueOf` shares between calls, as the wrapper classes' caches
+    pub(crate) fn box_cache_mut(&mut self) -> &mut HashMap<(&'static str, i64), JvmValue> {
+        &mut self.box_cache
+    }
+
+    /// The text stored for a string or string builder
+    pub(crate) fn string_buffer_mut(
+        &mut self,
+        value: &JvmValue,
+    ) -> Result<&mut String, RuntimeError> {
+        Ok(self.string_data.entry(value.as_reference()?).or_default())
+    }
+
+    /// Append to the contents of a string builder
+    pub(crate) fn append_to_buffer(
+        &mut self,
+        builder: &JvmValue,
+        text: &str,
+    ) -> Result<(), RuntimeError> {
+        self.check_string_growth(text.len())?;
+        let buffer = self.string_buffer_mut(builder)?;
+        buffer
+            .try_reserve(text.len())
+            .map_err(|_| RuntimeError::OutOfMemory)?;
+        buffer.push_str(text);
+        self.string_bytes += text.len();
+        Ok(())
+    }
+
+    /// Fail before string contents grow past the string limit
+    pub(crate) fn check_string_growth(&self, additional: usize) -> Result<(), RuntimeError> {
+        if let Some(max_string_bytes) = self.limits.max_string_bytes
+            && self.string_bytes.saturating_add(additional) > max_string_bytes
+        {
+            return Err(RuntimeError::StringLimitExceeded(max_string_bytes));
+        }
+        Ok(())
+    }
+
+    fn create_string_object(&mut self, value: String) -> Result<usize, RuntimeError> {
+        self.check_string_growth(value.len())?;
+        self.string_bytes += value.len();
         let object_id = self.next_object_id;
         self.next_object_id += 1;
 
@@ -197,2527 +775,2989 @@ impl JvmCompatibleVm {
         // Store the actual string value
         self.string_data.insert(object_id, value);
 
-        object_id
+        Ok(object_id)
     }
 
-    pub fn execute_method(
-        &mut self,
-        bytecode: Vec<JvmInstruction>,
-        constant_pool: ConstantPool,
-        max_locals: usize,
-    ) -> Result<Option<JvmValue>, RuntimeError> {
-        let frame = MethodFrame {
-            locals: vec![JvmValue::Int(0); max_locals],
-            operand_stack: Vec::new(),
-            constant_pool,
-            pc: 0,
-            bytecode,
-        };
-
-        self.frames.push(frame);
-        self.steps = 0;
+    /// Create an array object whose class name is its descriptor, e.g. `[I`
+    fn create_array_object(&mut self, descriptor: String, elements: Vec<JvmValue>) -> usize {
+        self.array_bytes += elements.len() * size_of::<JvmValue>();
+        let object_id = self.next_object_id;
+        self.next_object_id += 1;
 
-        while !self.frames.is_empty() {
-            if self.steps >= self.max_steps {
-                return Err(RuntimeError::InvalidStackState);
-            }
+        let mut fields = HashMap::new();
+        fields.insert("length".to_string(), JvmValue::Int(elements.len() as i32));
 
-            let result = self.execute_single_instruction()?;
-            self.steps += 1;
+        let array_object = JvmObject {
+            class_name: descriptor,
+            fields,
+        };
 
-            if let Some(return_value) = result {
-                return Ok(Some(return_value));
-            }
-        }
+        self.heap.insert(object_id, array_object);
+        self.array_data.insert(object_id, elements);
 
-        Ok(None)
+        object_id
     }
 
-    /// Execute a Java class file (.class) by parsing it and running the main method
-    pub fn execute_class_file(
-        &mut self,
-        class_file_path: &str,
-    ) -> Result<Option<JvmValue>, RuntimeError> {
-        // Automatically append .class extension if not present
-        let class_file_path = if class_file_path.ends_with(".class") {
-            class_file_path.to_string()
-        } else {
-            format!("{class_file_path}.class")
+    /// Allocate an array of the given descriptor. Only the leading
+    /// `lengths.len()` dimensions are created; deeper ones stay null.
+    fn allocate_array(&mut self, descriptor: &str, lengths: &[i32]) -> Result<usize, RuntimeError> {
+        let element_descriptor = &descriptor[1..];
+        let elements = match lengths {
+            [length] => {
+                let mut elements = self.array_elements(*length as usize)?;
+                elements.resize(*length as usize, default_value(element_descriptor));
+                elements
+            }
+            [length, inner @ ..] => {
+                let mut elements = self.array_elements(*length as usize)?;
+                for _ in 0..*length {
+                    let array_id = self.allocate_array(element_descriptor, inner)?;
+                    elements.push(JvmValue::Reference(Some(array_id)));
+                }
+                elements
+            }
+            [] => Vec::new(),
         };
+        Ok(self.create_array_object(descriptor.to_string(), elements))
+    }
 
-        if self.verbose {
-            eprintln!("Reading class file: {class_file_path}");
-        }
-
-        // Read the class file
-        let class_data = fs::read(&class_file_path).map_err(|_| RuntimeError::InvalidStackState)?;
+    /// Storage for `length` array elements. Arrays past the limit are
+    /// refused, and ones the host cannot allocate throw `OutOfMemoryError`.
+    fn array_elements(&self, length: usize) -> Result<Vec<JvmValue>, RuntimeError> {
+        self.check_array_size(length)?;
+        let mut elements = Vec::new();
+        elements
+            .try_reserve_exact(length)
+            .map_err(|_| RuntimeError::OutOfMemory)?;
+        Ok(elements)
+    }
 
-        if self.verbose {
-            eprintln!("Class file size: {} bytes", class_data.len());
+    /// Fail before an array of `length` elements would take the arrays
+    /// allocated so far past the array limit
+    fn check_array_size(&self, length: usize) -> Result<(), RuntimeError> {
+        if let Some(max_array_bytes) = self.limits.max_array_bytes
+            && length
+                .saturating_mul(size_of::<JvmValue>())
+                .saturating_add(self.array_bytes)
+                > max_array_bytes
+        {
+            return Err(RuntimeError::ArrayLimitExceeded(max_array_bytes));
         }
+        Ok(())
+    }
 
-        // Parse the class file
-        let class_file = ClassFileParser::parse(&class_data)?;
-
-        if self.verbose {
-            eprintln!("Parsed class file successfully");
-            eprintln!(
-                "Main method bytecode length: {}",
-                class_file.main_method_bytecode.len()
-            );
-            eprintln!(
-                "Constant pool size: {}",
-                class_file.constant_pool.entries().len()
-            );
-            eprintln!("Max locals: {}", class_file.max_locals);
+    /// Pop `dimensions` lengths and push a newly allocated array
+    fn new_array(&mut self, descriptor: &str, dimensions: usize) -> Result<(), RuntimeError> {
+        let frame = self
+            .frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?;
+        if dimensions == 0 || frame.operand_stack.len() < dimensions {
+            return Err(RuntimeError::StackUnderflow);
         }
-
-        // Store the class file for method resolution
-        let main_method_bytecode = class_file.main_method_bytecode.clone();
-        let constant_pool = class_file.constant_pool.clone();
-        let max_locals = class_file.max_locals;
-        self.current_class = Some(class_file);
-
-        if self.verbose {
-            eprintln!("Starting main method execution");
+        let lengths = frame
+            .operand_stack
+            .split_off(frame.operand_stack.len() - dimensions)
+            .iter()
+            .map(JvmValue::as_int)
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(&length) = lengths.iter().find(|&&length| length < 0) {
+            return Err(RuntimeError::NegativeArraySize(length));
         }
 
-        // Execute the main method
-        self.execute_method(main_method_bytecode, constant_pool, max_locals)
+        let array_id = self.allocate_array(descriptor, &lengths)?;
+        self.frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?
+            .operand_stack
+            .push(JvmValue::Reference(Some(array_id)));
+        Ok(())
     }
 
-    fn execute_single_instruction(&mut self) -> Result<Option<JvmValue>, RuntimeError> {
+    /// Pop an array reference and index and return the array id and the
+    /// checked element position
+    fn pop_array_index(&mut self) -> Result<(usize, usize), RuntimeError> {
         let frame = self
             .frames
             .last_mut()
             .ok_or(RuntimeError::CallStackUnderflow)?;
-
-        if frame.pc >= frame.bytecode.len() {
-            self.frames.pop();
-            return Ok(None);
+        let index = frame
+            .operand_stack
+            .pop()
+            .ok_or(RuntimeError::StackUnderflow)?
+            .as_int()?;
+        let array_ref = frame
+            .operand_stack
+            .pop()
+            .ok_or(RuntimeError::StackUnderflow)?;
+
+        let JvmValue::Reference(reference) = array_ref else {
+            return Err(RuntimeError::InvalidStackState);
+        };
+        let array_id = reference.ok_or(RuntimeError::NullPointer)?;
+        let length = self
+            .array_data
+            .get(&array_id)
+            .ok_or(RuntimeError::InvalidStackState)?
+            .len();
+        if index < 0 || index as usize >= length {
+            return Err(RuntimeError::ArrayIndexOutOfBounds { index, length });
         }
+        Ok((array_id, index as usize))
+    }
 
-        let instruction = frame.bytecode[frame.pc].clone();
-        if self.verbose {
-            eprintln!("PC {}: Executing instruction: {:?}", frame.pc, instruction);
-        }
+    fn load_array_element(&mut self) -> Result<(), RuntimeError> {
+        let (array_id, index) = self.pop_array_index()?;
+        let value = self.array_data[&array_id][index].clone();
+        self.frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?
+            .operand_stack
+            .push(value);
+        Ok(())
+    }
 
-        match instruction {
-            JvmInstruction::IconstM1 => {
-                frame.operand_stack.push(JvmValue::Int(-1));
-                frame.pc += 1;
-            }
-            JvmInstruction::Iconst0 => {
-                frame.operand_stack.push(JvmValue::Int(0));
-                frame.pc += 1;
-            }
-            JvmInstruction::Iconst1 => {
-                frame.operand_stack.push(JvmValue::Int(1));
-                frame.pc += 1;
-            }
-            JvmInstruction::Iconst2 => {
-                frame.operand_stack.push(JvmValue::Int(2));
-                frame.pc += 1;
+    /// Store into an array, narrowing the value to the element type of the
+    /// store instruction
+    fn store_array_element(&mut self, instruction: &JvmInstruction) -> Result<(), RuntimeError> {
+        let value = self
+            .frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?
+            .operand_stack
+            .pop()
+            .ok_or(RuntimeError::StackUnderflow)?;
+        let (array_id, index) = self.pop_array_index()?;
+
+        let value = match (instruction, value) {
+            (JvmInstruction::Iastore, value) => JvmValue::Int(value.as_int()?),
+            (JvmInstruction::Lastore, JvmValue::Long(l)) => JvmValue::Long(l),
+            (JvmInstruction::Fastore, value) => JvmValue::Float(value.as_float()?),
+            (JvmInstruction::Dastore, value) => JvmValue::Double(value.as_double()?),
+            (JvmInstruction::Aastore, JvmValue::Reference(reference)) => {
+                if let Some(object_id) = reference {
+                    self.check_array_store(array_id, object_id)?;
+                }
+                JvmValue::Reference(reference)
             }
-            JvmInstruction::Iconst3 => {
-                frame.operand_stack.push(JvmValue::Int(3));
-                frame.pc += 1;
-            }
-            JvmInstruction::Iconst4 => {
-                frame.operand_stack.push(JvmValue::Int(4));
-                frame.pc += 1;
-            }
-            JvmInstruction::Iconst5 => {
-                frame.operand_stack.push(JvmValue::Int(5));
-                frame.pc += 1;
-            }
-            JvmInstruction::Lconst0 => {
-                frame.operand_stack.push(JvmValue::Long(0));
-                frame.pc += 1;
-            }
-            JvmInstruction::Lconst1 => {
-                frame.operand_stack.push(JvmValue::Long(1));
-                frame.pc += 1;
-            }
-            JvmInstruction::Bipush(value) => {
-                frame.operand_stack.push(JvmValue::Int(value as i32));
-                frame.pc += 1;
-            }
-            JvmInstruction::Sipush(value) => {
-                frame.operand_stack.push(JvmValue::Int(value as i32));
-                frame.pc += 1;
-            }
-            JvmInstruction::Ldc(index) => {
-                let value = self.load_constant_from_pool(index)?;
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Ldc2W(index) => {
-                let value = self.load_constant_from_pool(index)?;
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame.operand_stack.push(value);
-                frame.pc += 1;
+            (JvmInstruction::Bastore, value) => {
+                let is_boolean_array = self.heap[&array_id].class_name == "[Z";
+                let value = value.as_int()?;
+                JvmValue::Int(if is_boolean_array {
+                    value & 1
+                } else {
+                    value as i8 as i32
+                })
             }
+            (JvmInstruction::Castore, value) => JvmValue::Int(value.as_int()? as u16 as i32),
+            (JvmInstruction::Sastore, value) => JvmValue::Int(value.as_int()? as i16 as i32),
+            _ => return Err(RuntimeError::InvalidStackState),
+        };
 
-            JvmInstruction::Pop => {
-                frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                frame.pc += 1;
-            }
-            JvmInstruction::Dup => {
-                let value = frame
-                    .operand_stack
-                    .last()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Swap => {
-                let len = frame.operand_stack.len();
-                if len < 2 {
-                    return Err(RuntimeError::StackUnderflow);
-                }
-                frame.operand_stack.swap(len - 1, len - 2);
-                frame.pc += 1;
-            }
+        if let Some(elements) = self.array_data.get_mut(&array_id) {
+            elements[index] = value;
+        }
+        Ok(())
+    }
 
-            JvmInstruction::Iadd => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                frame.operand_stack.push(JvmValue::Int(a + b));
-                frame.pc += 1;
-            }
-            JvmInstruction::Isub => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                frame.operand_stack.push(JvmValue::Int(a - b));
-                frame.pc += 1;
-            }
-            JvmInstruction::Imul => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                frame.operand_stack.push(JvmValue::Int(a * b));
-                frame.pc += 1;
-            }
-            JvmInstruction::Idiv => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                if b == 0 {
-                    return Err(RuntimeError::DivisionByZero);
-                }
-                frame.operand_stack.push(JvmValue::Int(a / b));
-                frame.pc += 1;
-            }
-            JvmInstruction::Irem => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                if b == 0 {
-                    return Err(RuntimeError::DivisionByZero);
-                }
-                frame.operand_stack.push(JvmValue::Int(a % b));
-                frame.pc += 1;
-            }
+    /// Throw `ArrayStoreException` unless the object can be assigned to the
+    /// array's component type, which may be narrower than the static type
+    /// the array was stored through
+    fn check_array_store(&mut self, array_id: usize, object_id: usize) -> Result<(), RuntimeError> {
+        let array_class = self
+            .heap
+            .get(&array_id)
+            .map(|array| array.class_name.clone())
+            .ok_or(RuntimeError::InvalidStackState)?;
+        let class_name = self
+            .heap
+            .get(&object_id)
+            .map(|object| object.class_name.clone())
+            .ok_or(RuntimeError::InvalidStackState)?;
+        let component = array_class
+            .strip_prefix('[')
+            .and_then(component_class)
+            .ok_or(RuntimeError::InvalidStackState)?;
+        if self.is_assignable(&class_name, component)? {
+            return Ok(());
+        }
+        Err(self.throw_exception(
+            "java/lang/ArrayStoreException",
+            Some(class_name.replace('/', ".")),
+        ))
+    }
 
-            JvmInstruction::Dadd => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
+    pub fn execute_method(
+        &mut self,
+        bytecode: Vec<JvmInstruction>,
+        constant_pool: ConstantPool,
+        max_locals: usize,
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        self.execute_method_with_arguments(bytecode, constant_pool, max_locals, Vec::new())
+    }
 
-                if let (JvmValue::Double(a_val), JvmValue::Double(b_val)) = (a, b) {
-                    frame.operand_stack.push(JvmValue::Double(a_val + b_val));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                frame.pc += 1;
-            }
-            JvmInstruction::Dsub => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
+    /// Execute a method with its first local variables initialised to the
+    /// given arguments
+    pub fn execute_method_with_arguments(
+        &mut self,
+        bytecode: Vec<JvmInstruction>,
+        constant_pool: ConstantPool,
+        max_locals: usize,
+        arguments: Vec<JvmValue>,
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        self.start_execution();
+        let frame = MethodFrame::new(None, constant_pool, bytecode, max_locals, arguments);
+        let result = self.run_frame(frame);
+        self.report_uncaught_exception(result)
+    }
 
-                if let (JvmValue::Double(a_val), JvmValue::Double(b_val)) = (a, b) {
-                    frame.operand_stack.push(JvmValue::Double(a_val - b_val));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                frame.pc += 1;
-            }
-            JvmInstruction::Dmul => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
+    /// Reset the per-execution counters before running a program
+    fn start_execution(&mut self) {
+        self.frames.clear();
+        self.steps = 0;
+        self.started = Some(Instant::now());
+    }
 
-                if let (JvmValue::Double(a_val), JvmValue::Double(b_val)) = (a, b) {
-                    frame.operand_stack.push(JvmValue::Double(a_val * b_val));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
+    /// Push a frame for a call, unless the call stack is already as deep as
+    /// the limits allow
+    fn push_frame(&mut self, frame: MethodFrame) -> Result<(), RuntimeError> {
+        if let Some(max_call_depth) = self.limits.max_call_depth
+            && self.frames.len() >= max_call_depth
+        {
+            return Err(RuntimeError::CallDepthLimitExceeded(max_call_depth));
+        }
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    /// Push a frame and execute until it returns, yielding its return value.
+    /// Calls between bytecode methods push and pop frames within this one
+    /// dispatch loop; only natives and class initializers calling back into
+    /// bytecode start a nested loop.
+    fn run_frame(&mut self, frame: MethodFrame) -> Result<Option<JvmValue>, RuntimeError> {
+        self.push_frame(frame)?;
+        let depth = self.frames.len();
+
+        loop {
+            let result = self.execute_single_instruction();
+            // A throw that is caught counts too, so a handler that rethrows
+            // into itself still runs out of steps
+            let completion = match result {
+                Ok(completion) => completion,
+                Err(error) => {
+                    let exception_id = self.exception_for_error(error)?;
+                    self.unwind(exception_id, depth)?;
+                    Completion::Next
                 }
-                frame.pc += 1;
-            }
-            JvmInstruction::Ddiv => {
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
+            };
+            self.steps += 1;
+            self.check_limits()?;
+
+            let Completion::Returned(return_value) = completion else {
+                continue;
+            };
+            if self.frames.len() < depth {
+                return Ok(return_value);
+            }
+            // Resume the caller after its invoke instruction
+            let caller = self
+                .frames
+                .last_mut()
+                .ok_or(RuntimeError::CallStackUnderflow)?;
+            if let Some(return_value) = return_value {
+                caller.operand_stack.push(return_value);
+            }
+            caller.pc += 1;
+        }
+    }
+
+    /// Move past an invoke instruction once its callee has completed. A
+    /// callee that pushed a frame completes when that frame returns.
+    fn finish_invoke(&mut self, caller_depth: usize) -> Result<(), RuntimeError> {
+        if self.frames.len() == caller_depth {
+            self.frames
+                .last_mut()
+                .ok_or(RuntimeError::CallStackUnderflow)?
+                .pc += 1;
+        }
+        Ok(())
+    }
+
+    /// Stop execution once it has used more than its limits allow
+    fn check_limits(&self) -> Result<(), RuntimeError> {
+        let limits = &self.limits;
+        if let Some(max_instructions) = limits.max_instructions
+            && self.steps > max_instructions
+        {
+            return Err(RuntimeError::InstructionLimitExceeded(max_instructions));
+        }
+        if let Some(max_heap_objects) = limits.max_heap_objects
+            && self.heap.len() > max_heap_objects
+        {
+            return Err(RuntimeError::HeapLimitExceeded(max_heap_objects));
+        }
+        if let Some(max_string_bytes) = limits.max_string_bytes
+            && self.string_bytes > max_string_bytes
+        {
+            return Err(RuntimeError::StringLimitExceeded(max_string_bytes));
+        }
+        if let Some(max_array_bytes) = limits.max_array_bytes
+            && self.array_bytes > max_array_bytes
+        {
+            return Err(RuntimeError::ArrayLimitExceeded(max_array_bytes));
+        }
+        if let Some(timeout) = limits.timeout
+            && self.steps.is_multiple_of(TIMEOUT_CHECK_INTERVAL)
+            && let Some(started) = self.started
+            && started.elapsed() > timeout
+        {
+            return Err(RuntimeError::Timeout(timeout));
+        }
+        Ok(())
+    }
+
+    /// Turn an error raised by an instruction into the throwable a JVM would
+    /// raise. Errors with no Java equivalent are returned unchanged.
+    fn exception_for_error(&mut self, error: RuntimeError) -> Result<usize, RuntimeError> {
+        let (class_name, message) = match error {
+            RuntimeError::Exception(exception_id) => return Ok(exception_id),
+            RuntimeError::DivisionByZero => (
+                "java/lang/ArithmeticException",
+                Some("/ by zero".to_string()),
+            ),
+            RuntimeError::NullPointer => ("java/lang/NullPointerException", None),
+            RuntimeError::ArrayIndexOutOfBounds { index, length } => (
+                "java/lang/ArrayIndexOutOfBoundsException",
+                Some(format!("Index {index} out of bounds for length {length}")),
+            ),
+            RuntimeError::NegativeArraySize(size) => (
+                "java/lang/NegativeArraySizeException",
+                Some(size.to_string()),
+            ),
+            RuntimeError::OutOfMemory => (
+                "java/lang/OutOfMemoryError",
+                Some("Java heap space".to_string()),
+            ),
+            error => return Err(error),
+        };
 
-                if let (JvmValue::Double(a_val), JvmValue::Double(b_val)) = (a, b) {
-                    if b_val == 0.0 {
-                        return Err(RuntimeError::DivisionByZero);
+        match self.throw_exception(class_name, message) {
+            RuntimeError::Exception(exception_id) => Ok(exception_id),
+            error => Err(error),
+        }
+    }
+
+    /// Transfer control to the innermost handler for the exception among the
+    /// frames of the current `run_frame` call, popping frames without one.
+    /// Fails with `RuntimeError::Exception` when no handler is found, so the
+    /// caller's frames get a chance to catch it.
+    fn unwind(&mut self, exception_id: usize, depth: usize) -> Result<(), RuntimeError> {
+        let exception_class = self
+            .heap
+            .get(&exception_id)
+            .map(|object| object.class_name.clone())
+            .ok_or(RuntimeError::InvalidStackState)?;
+
+        while self.frames.len() >= depth {
+            let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
+            let pc = frame.pc;
+            let candidates: Vec<(usize, Option<String>)> = frame
+                .exception_handlers
+                .iter()
+                .filter(|handler| handler.start <= pc && pc < handler.end)
+                .map(|handler| (handler.handler, handler.catch_type.clone()))
+                .collect();
+
+            for (handler, catch_type) in candidates {
+                let catches = match catch_type {
+                    Some(catch_type) => self.is_assignable(&exception_class, &catch_type)?,
+                    None => true,
+                };
+                if catches {
+                    if self.verbose {
+                        eprintln!("Caught {exception_class} at instruction {handler}");
                     }
-                    frame.operand_stack.push(JvmValue::Double(a_val / b_val));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
+                    let frame = self
+                        .frames
+                        .last_mut()
+                        .ok_or(RuntimeError::CallStackUnderflow)?;
+                    frame.operand_stack.clear();
+                    frame
+                        .operand_stack
+                        .push(JvmValue::Reference(Some(exception_id)));
+                    frame.pc = handler;
+                    return Ok(());
                 }
-                frame.pc += 1;
             }
 
-            JvmInstruction::I2d => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                frame.operand_stack.push(JvmValue::Double(value as f64));
-                frame.pc += 1;
-            }
-            JvmInstruction::D2i => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
+            self.frames.pop();
+        }
 
-                if let JvmValue::Double(d_val) = value {
-                    frame.operand_stack.push(JvmValue::Int(d_val as i32));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
-                }
-                frame.pc += 1;
-            }
+        Err(RuntimeError::Exception(exception_id))
+    }
 
-            JvmInstruction::Goto(offset) => {
-                frame.pc = offset as usize;
-            }
-            JvmInstruction::Ifeq(offset) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                if value == 0 {
-                    frame.pc = offset as usize;
-                } else {
-                    frame.pc += 1;
+    /// Print a Java-style stack trace for an exception that escaped `main`
+    fn report_uncaught_exception(
+        &mut self,
+        result: Result<Option<JvmValue>, RuntimeError>,
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        let Err(RuntimeError::Exception(exception_id)) = result else {
+            return result;
+        };
+        self.frames.clear();
+
+        let description = self.value_to_java_string(
+            &JvmValue::Reference(Some(exception_id)),
+            "Ljava/lang/Throwable;",
+        );
+        eprintln!("Exception in thread \"main\" {description}");
+        for element in self.stack_traces.get(&exception_id).into_iter().flatten() {
+            eprintln!("\tat {element}");
+        }
+        Err(RuntimeError::UncaughtException(description))
+    }
+
+    /// Stack trace elements for the current call stack, innermost first
+    fn capture_stack_trace(&self) -> Vec<String> {
+        self.frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let Some(class_name) = &frame.class_name else {
+                    return format!("{}(Unknown Source)", frame.method_name);
+                };
+                let class = self.class_loader.loaded_class(class_name);
+                let source_file = class
+                    .as_ref()
+                    .and_then(|class| class.class_file.source_file());
+                let location = match (source_file, frame.line_number()) {
+                    (Some(file), Some(line)) => format!("{file}:{line}"),
+                    (Some(file), None) => file.to_string(),
+                    (None, _) => "Unknown Source".to_string(),
+                };
+                format!(
+                    "{}.{}({location})",
+                    class_name.replace('/', "."),
+                    frame.method_name
+                )
+            })
+            .collect()
+    }
+
+    /// Whether instances of `class_name` can be assigned to `target`,
+    /// through superclasses, interfaces or array covariance
+    fn is_assignable(&mut self, class_name: &str, target: &str) -> Result<bool, RuntimeError> {
+        if class_name == target || target == "java/lang/Object" {
+            return Ok(true);
+        }
+        if let Some(component) = class_name.strip_prefix('[') {
+            return match (
+                component_class(component),
+                target.strip_prefix('[').and_then(component_class),
+            ) {
+                (Some(component), Some(target_component)) => {
+                    self.is_assignable(component, target_component)
                 }
+                _ => Ok(["java/lang/Cloneable", "java/io/Serializable"].contains(&target)),
+            };
+        }
+
+        let mut pending = vec![class_name.to_string()];
+        let mut visited = HashSet::new();
+        while let Some(current) = pending.pop() {
+            if current == target {
+                return Ok(true);
             }
-            JvmInstruction::Ifne(offset) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                if value != 0 {
-                    frame.pc = offset as usize;
-                } else {
-                    frame.pc += 1;
-                }
-            }
-            JvmInstruction::Iflt(offset) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                if value < 0 {
-                    frame.pc = offset as usize;
-                } else {
-                    frame.pc += 1;
-                }
-            }
-            JvmInstruction::Ifge(offset) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                if value >= 0 {
-                    frame.pc = offset as usize;
-                } else {
-                    frame.pc += 1;
-                }
-            }
-            JvmInstruction::Ifgt(offset) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                if value > 0 {
-                    frame.pc = offset as usize;
-                } else {
-                    frame.pc += 1;
-                }
-            }
-            JvmInstruction::Ifle(offset) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                if value <= 0 {
-                    frame.pc = offset as usize;
-                } else {
-                    frame.pc += 1;
-                }
-            }
-
-            JvmInstruction::Return => {
-                self.frames.pop();
-            }
-            JvmInstruction::Ireturn => {
-                let return_value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                self.frames.pop();
-                return Ok(Some(return_value));
-            }
-
-            JvmInstruction::New(_class_ref) => {
-                // Create new object instance
-                // For now, just push a reference placeholder
-                frame.operand_stack.push(JvmValue::Reference(Some(0)));
-                frame.pc += 1;
-            }
-
-            JvmInstruction::Getstatic(field_ref) => {
-                // Handle System.out and System.err field access
-                let field_value = self.resolve_static_field(field_ref)?;
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame.operand_stack.push(field_value);
-                frame.pc += 1;
-            }
-
-            JvmInstruction::Invokevirtual(method_ref) => {
-                // Handle PrintStream.println and PrintStream.print
-                self.invoke_virtual_method(method_ref)?;
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame.pc += 1;
-            }
-
-            JvmInstruction::Invokespecial(method_ref) => {
-                // Handle constructor calls and private methods
-                self.invoke_special_method(method_ref)?;
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame.pc += 1;
-            }
-
-            JvmInstruction::Invokestatic(method_ref) => {
-                // Handle Math.random and other static methods
-                self.invoke_static_method(method_ref)?;
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame.pc += 1;
-            }
-
-            JvmInstruction::Invokedynamic(bootstrap_method_attr_index) => {
-                // Handle string concatenation and lambda expressions
-                self.invoke_dynamic_method(bootstrap_method_attr_index)?;
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame.pc += 1;
-            }
-
-            JvmInstruction::Dconst0 => {
-                frame.operand_stack.push(JvmValue::Double(0.0));
-                frame.pc += 1;
-            }
-            JvmInstruction::Dconst1 => {
-                frame.operand_stack.push(JvmValue::Double(1.0));
-                frame.pc += 1;
-            }
-
-            // ローカル変数操作
-            JvmInstruction::Iload(index) => {
-                let value = frame
-                    .locals
-                    .get(index as usize)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Iload0 => {
-                let value = frame
-                    .locals
-                    .first()
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Iload1 => {
-                let value = frame
-                    .locals
-                    .get(1)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Iload2 => {
-                let value = frame
-                    .locals
-                    .get(2)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Iload3 => {
-                let value = frame
-                    .locals
-                    .get(3)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Istore(index) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if (index as usize) >= frame.locals.len() {
-                    frame.locals.resize(index as usize + 1, JvmValue::Int(0));
-                }
-                frame.locals[index as usize] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Istore0 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.is_empty() {
-                    frame.locals.resize(1, JvmValue::Int(0));
-                }
-                frame.locals[0] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Istore1 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 1 {
-                    frame.locals.resize(2, JvmValue::Int(0));
-                }
-                frame.locals[1] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Istore2 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 2 {
-                    frame.locals.resize(3, JvmValue::Int(0));
-                }
-                frame.locals[2] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Istore3 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 3 {
-                    frame.locals.resize(4, JvmValue::Int(0));
-                }
-                frame.locals[3] = value;
-                frame.pc += 1;
-            }
-
-            // Reference local variable operations
-            JvmInstruction::Aload(index) => {
-                let value = frame
-                    .locals
-                    .get(index as usize)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Aload0 => {
-                let value = frame
-                    .locals
-                    .first()
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Aload1 => {
-                let value = frame
-                    .locals
-                    .get(1)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Aload2 => {
-                let value = frame
-                    .locals
-                    .get(2)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Aload3 => {
-                let value = frame
-                    .locals
-                    .get(3)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Astore(index) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if (index as usize) >= frame.locals.len() {
-                    frame
-                        .locals
-                        .resize(index as usize + 1, JvmValue::Reference(None));
-                }
-                frame.locals[index as usize] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Astore0 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.is_empty() {
-                    frame.locals.resize(1, JvmValue::Reference(None));
-                }
-                frame.locals[0] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Astore1 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 1 {
-                    frame.locals.resize(2, JvmValue::Reference(None));
-                }
-                frame.locals[1] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Astore2 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 2 {
-                    frame.locals.resize(3, JvmValue::Reference(None));
-                }
-                frame.locals[2] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Astore3 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 3 {
-                    frame.locals.resize(4, JvmValue::Reference(None));
-                }
-                frame.locals[3] = value;
-                frame.pc += 1;
-            }
-
-            // Double local variable operations
-            JvmInstruction::Dload(index) => {
-                let value = frame
-                    .locals
-                    .get(index as usize)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Dload0 => {
-                let value = frame
-                    .locals
-                    .first()
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Dload1 => {
-                let value = frame
-                    .locals
-                    .get(1)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Dload2 => {
-                let value = frame
-                    .locals
-                    .get(2)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Dload3 => {
-                let value = frame
-                    .locals
-                    .get(3)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Dstore(index) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if (index as usize) >= frame.locals.len() {
-                    frame
-                        .locals
-                        .resize(index as usize + 1, JvmValue::Double(0.0));
-                }
-                frame.locals[index as usize] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Dstore0 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.is_empty() {
-                    frame.locals.resize(1, JvmValue::Double(0.0));
-                }
-                frame.locals[0] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Dstore1 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 1 {
-                    frame.locals.resize(2, JvmValue::Double(0.0));
-                }
-                frame.locals[1] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Dstore2 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 2 {
-                    frame.locals.resize(3, JvmValue::Double(0.0));
-                }
-                frame.locals[2] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Dstore3 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 3 {
-                    frame.locals.resize(4, JvmValue::Double(0.0));
-                }
-                frame.locals[3] = value;
-                frame.pc += 1;
-            }
-
-            // Long local variable operations
-            JvmInstruction::Lload(index) => {
-                let value = frame
-                    .locals
-                    .get(index as usize)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Lload0 => {
-                let value = frame
-                    .locals
-                    .first()
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Lload1 => {
-                let value = frame
-                    .locals
-                    .get(1)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Lload2 => {
-                let value = frame
-                    .locals
-                    .get(2)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Lload3 => {
-                let value = frame
-                    .locals
-                    .get(3)
-                    .ok_or(RuntimeError::InvalidStackState)?
-                    .clone();
-                frame.operand_stack.push(value);
-                frame.pc += 1;
-            }
-            JvmInstruction::Lstore(index) => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if (index as usize) >= frame.locals.len() {
-                    frame.locals.resize(index as usize + 1, JvmValue::Long(0));
-                }
-                frame.locals[index as usize] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Lstore0 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.is_empty() {
-                    frame.locals.resize(1, JvmValue::Long(0));
-                }
-                frame.locals[0] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Lstore1 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 1 {
-                    frame.locals.resize(2, JvmValue::Long(0));
-                }
-                frame.locals[1] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Lstore2 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 2 {
-                    frame.locals.resize(3, JvmValue::Long(0));
-                }
-                frame.locals[2] = value;
-                frame.pc += 1;
-            }
-            JvmInstruction::Lstore3 => {
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                if frame.locals.len() <= 3 {
-                    frame.locals.resize(4, JvmValue::Long(0));
+            if !visited.insert(current.clone()) {
+                continue;
+            }
+            match self.load_class(&current)? {
+                Some(class) => {
+                    let class_file = &class.class_file;
+                    pending.extend(Self::superclass_name(class_file));
+                    pending.extend(Self::interface_names(class_file));
+                }
+                None => {
+                    let throwable_supertypes = BUILTIN_THROWABLES
+                        .iter()
+                        .filter(|(name, _)| *name == current)
+                        .map(|(_, superclass)| *superclass);
+                    let library_supertypes = LIBRARY_SUPERTYPES
+                        .iter()
+                        .filter(|(name, _)| *name == current)
+                        .flat_map(|(_, supertypes)| supertypes.iter().copied());
+                    pending.extend(
+                        throwable_supertypes
+                            .chain(library_supertypes)
+                            .map(str::to_string),
+                    );
                 }
-                frame.locals[3] = value;
-                frame.pc += 1;
             }
+        }
+        Ok(false)
+    }
 
-            JvmInstruction::Nop => {
-                // No operation - just increment program counter
-                frame.pc += 1;
-            }
+    /// Execute a Java class file (.class) by parsing it and running the main method
+    pub fn execute_class_file(
+        &mut self,
+        class_file_path: &str,
+        args: &[String],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        // Automatically append .class extension if not present
+        let class_file_path = if class_file_path.ends_with(".class") {
+            class_file_path.to_string()
+        } else {
+            format!("{class_file_path}.class")
+        };
+
+        if self.verbose {
+            eprintln!("Reading class file: {class_file_path}");
         }
 
-        Ok(None)
+        // Read the class file
+        let class_data = fs::read(&class_file_path)
+            .map_err(|_| RuntimeError::ClassNotFound(class_file_path.clone()))?;
+
+        if self.verbose {
+            eprintln!("Class file size: {} bytes", class_data.len());
+        }
+
+        // Classes referenced by the program are also looked up next to it
+        if let Some(directory) = Path::new(&class_file_path).parent() {
+            self.class_loader.add_path(directory)?;
+        }
+
+        // Parse the class file
+        let class = self
+            .class_loader
+            .define_class(ClassFileParser::parse(&class_data)?)?;
+        self.run_main(class, args)
     }
 
-    fn resolve_method_reference(&self, method_ref: u16) -> Result<ResolvedMethod, RuntimeError> {
-        let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
-        let entries = frame.constant_pool.entries();
+    /// Execute the `Main-Class` of a JAR file, as `java -jar` does. The
+    /// JAR is appended to the classpath.
+    pub fn execute_jar(
+        &mut self,
+        jar_path: &str,
+        args: &[String],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        self.class_loader.add_path(jar_path)?;
+        let main_class = match self.class_loader.classpath().last() {
+            Some(ClassPathEntry::Archive(_, archive)) => archive.main_class()?,
+            _ => None,
+        }
+        .ok_or_else(|| {
+            RuntimeError::InvalidArchive(format!("{jar_path}: no Main-Class in manifest"))
+        })?;
+        if self.verbose {
+            eprintln!("Main class from manifest: {main_class}");
+        }
+        self.execute_main_class(&main_class, args)
+    }
 
-        // JVM constant pool is 1-based, but our array is 0-based
-        let actual_index = (method_ref - 1) as usize;
-        if actual_index >= entries.len() {
-            return Ok(ResolvedMethod::Unknown);
+    /// Execute the main method of a class found on the classpath by its
+    /// binary or dotted name, such as `dice.Main`
+    pub fn execute_main_class(
+        &mut self,
+        class_name: &str,
+        args: &[String],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        let binary_name = class_name.replace('.', "/");
+        let class = self
+            .load_class(&binary_name)?
+            .ok_or_else(|| RuntimeError::ClassNotFound(class_name.to_string()))?;
+        self.run_main(class, args)
+    }
+
+    fn run_main(
+        &mut self,
+        class: Rc<LoadedClass>,
+        args: &[String],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        let class_file = &class.class_file;
+        let class_name = class.name.clone();
+        let main_method = class_file
+            .main_method()
+            .ok_or_else(|| RuntimeError::InvalidClassFile("no main method found".to_string()))?;
+
+        if self.verbose {
+            eprintln!("Parsed class file successfully");
+            eprintln!(
+                "Main method bytecode length: {}",
+                main_method.bytecode().len()
+            );
+            eprintln!(
+                "Constant pool size: {}",
+                class_file.constant_pool.entries().len()
+            );
+            eprintln!("Max locals: {}", main_method.max_locals());
         }
 
-        match &entries[actual_index] {
-            ConstantPoolEntry::Methodref(class_index, name_and_type_index) => {
-                // Get class name
-                let class_actual_index = (*class_index - 1) as usize;
-                let class_name =
-                    if let ConstantPoolEntry::Class(name_index) = &entries[class_actual_index] {
-                        let name_actual_index = (*name_index - 1) as usize;
-                        if let ConstantPoolEntry::Utf8(name) = &entries[name_actual_index] {
-                            name
-                        } else {
-                            return Ok(ResolvedMethod::Unknown);
-                        }
-                    } else {
-                        return Ok(ResolvedMethod::Unknown);
-                    };
+        let main_descriptor = main_method.descriptor.clone();
 
-                // Get method name and descriptor
-                let name_and_type_actual_index = (*name_and_type_index - 1) as usize;
-                let (method_name, descriptor) =
-                    if let ConstantPoolEntry::NameAndType(name_index, desc_index) =
-                        &entries[name_and_type_actual_index]
-                    {
-                        let name_actual_index = (*name_index - 1) as usize;
-                        let desc_actual_index = (*desc_index - 1) as usize;
-                        let name =
-                            if let ConstantPoolEntry::Utf8(name) = &entries[name_actual_index] {
-                                name
-                            } else {
-                                return Ok(ResolvedMethod::Unknown);
-                            };
-                        let desc =
-                            if let ConstantPoolEntry::Utf8(desc) = &entries[desc_actual_index] {
-                                desc
-                            } else {
-                                return Ok(ResolvedMethod::Unknown);
-                            };
-                        (name, desc)
-                    } else {
-                        return Ok(ResolvedMethod::Unknown);
-                    };
+        self.start_execution();
+        if let Err(error) = self.initialize_class(&class_name) {
+            return self.report_uncaught_exception(Err(error));
+        }
 
-                // Resolve based on class, method name, and descriptor
-                match (
-                    class_name.as_str(),
-                    method_name.as_str(),
-                    descriptor.as_str(),
-                ) {
-                    ("java/io/PrintStream", "println", "(I)V") => {
-                        Ok(ResolvedMethod::PrintStreamPrintln)
-                    }
-                    ("java/io/PrintStream", "print", "(Ljava/lang/String;)V") => {
-                        Ok(ResolvedMethod::PrintStreamPrint)
-                    }
-                    ("java/io/PrintStream", "println", "(Ljava/lang/String;)V") => {
-                        Ok(ResolvedMethod::PrintStreamPrintlnString)
-                    }
-                    ("java/io/PrintStream", "println", "(Ljava/lang/Object;)V") => {
-                        Ok(ResolvedMethod::PrintStreamPrintlnString) // Treat Object as String
-                    }
-                    ("java/io/PrintStream", "println", "(F)V") => {
-                        Ok(ResolvedMethod::PrintStreamPrintlnFloat)
-                    }
-                    ("java/io/PrintStream", "println", "(D)V") => {
-                        Ok(ResolvedMethod::PrintStreamPrintlnDouble)
-                    }
-                    ("java/io/PrintStream", "println", "(Z)V") => {
-                        Ok(ResolvedMethod::PrintStreamPrintlnBoolean)
-                    }
-                    ("java/io/PrintStream", "println", "(C)V") => {
-                        Ok(ResolvedMethod::PrintStreamPrintlnChar)
-                    }
-                    ("java/lang/Math", "random", "()D") => Ok(ResolvedMethod::MathRandom),
-                    ("java/lang/Math", "max", "(II)I") => Ok(ResolvedMethod::MathMaxInt),
-                    ("java/lang/Math", "min", "(II)I") => Ok(ResolvedMethod::MathMinInt),
-                    ("java/lang/Math", "max", "(DD)D") => Ok(ResolvedMethod::MathMaxDouble),
-                    ("java/lang/Math", "min", "(DD)D") => Ok(ResolvedMethod::MathMinDouble),
-                    ("java/lang/Math", "abs", "(I)I") => Ok(ResolvedMethod::MathAbs),
-                    ("java/lang/Math", "abs", "(D)D") => Ok(ResolvedMethod::MathAbsDouble),
-                    ("java/lang/Math", "pow", "(DD)D") => Ok(ResolvedMethod::MathPow),
-                    ("java/lang/Math", "sqrt", "(D)D") => Ok(ResolvedMethod::MathSqrt),
-                    ("java/lang/Math", "floor", "(D)D") => Ok(ResolvedMethod::MathFloor),
-                    ("java/lang/Math", "ceil", "(D)D") => Ok(ResolvedMethod::MathCeil),
-                    ("java/lang/Math", "round", "(D)J") => Ok(ResolvedMethod::MathRound),
-                    ("java/lang/Math", "sin", "(D)D") => Ok(ResolvedMethod::MathSin),
-                    ("java/lang/Math", "cos", "(D)D") => Ok(ResolvedMethod::MathCos),
-                    ("java/lang/Math", "tan", "(D)D") => Ok(ResolvedMethod::MathTan),
-                    ("java/lang/Math", "log", "(D)D") => Ok(ResolvedMethod::MathLog),
-                    ("java/lang/Math", "exp", "(D)D") => Ok(ResolvedMethod::MathExp),
-
-                    // String methods (virtual)
-                    ("java/lang/String", "length", "()I") => Ok(ResolvedMethod::StringLength),
-                    ("java/lang/String", "charAt", "(I)C") => Ok(ResolvedMethod::StringCharAt),
-                    ("java/lang/String", "substring", "(II)Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::StringSubstring)
-                    }
-                    ("java/lang/String", "indexOf", "(I)I") => Ok(ResolvedMethod::StringIndexOf),
-                    ("java/lang/String", "toUpperCase", "()Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::StringToUpperCase)
-                    }
-                    ("java/lang/String", "toLowerCase", "()Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::StringToLowerCase)
-                    }
-                    ("java/lang/String", "trim", "()Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::StringTrim)
-                    }
-                    ("java/lang/String", "equals", "(Ljava/lang/Object;)Z") => {
-                        Ok(ResolvedMethod::StringEquals)
-                    }
-                    ("java/lang/String", "concat", "(Ljava/lang/String;)Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::StringConcat)
-                    }
+        if self.verbose {
+            eprintln!("Starting main method execution");
+        }
 
-                    // StringBuilder methods
-                    (
-                        "java/lang/StringBuilder",
-                        "append",
-                        "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
-                    ) => Ok(ResolvedMethod::StringBuilderAppendString),
-                    ("java/lang/StringBuilder", "append", "(I)Ljava/lang/StringBuilder;") => {
-                        Ok(ResolvedMethod::StringBuilderAppendInt)
-                    }
-                    ("java/lang/StringBuilder", "append", "(D)Ljava/lang/StringBuilder;") => {
-                        Ok(ResolvedMethod::StringBuilderAppendDouble)
-                    }
-                    ("java/lang/StringBuilder", "toString", "()Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::StringBuilderToString)
-                    }
+        // Java's main receives the command line as a String[]; Kotlin's
+        // parameterless main does not
+        let arguments = if main_descriptor == "()V" {
+            Vec::new()
+        } else {
+            let elements = args
+                .iter()
+                .map(|arg| {
+                    Ok(JvmValue::Reference(Some(
+                        self.create_string_object(arg.clone())?,
+                    )))
+                })
+                .collect::<Result<_, RuntimeError>>()?;
+            let args_id = self.create_array_object("[Ljava/lang/String;".to_string(), elements);
+            vec![JvmValue::Reference(Some(args_id))]
+        };
 
-                    // Integer wrapper methods (static)
-                    ("java/lang/Integer", "parseInt", "(Ljava/lang/String;)I") => {
-                        Ok(ResolvedMethod::IntegerParseInt)
-                    }
-                    ("java/lang/Integer", "toString", "(I)Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::IntegerToString)
-                    }
-                    ("java/lang/Integer", "valueOf", "(I)Ljava/lang/Integer;") => {
-                        Ok(ResolvedMethod::IntegerValueOf)
-                    }
+        // Execute the main method
+        let frame = MethodFrame::for_method(&class, main_method, arguments)?;
+        let result = self.run_frame(frame);
+        self.report_uncaught_exception(result)
+    }
 
-                    // Double wrapper methods (static)
-                    ("java/lang/Double", "parseDouble", "(Ljava/lang/String;)D") => {
-                        Ok(ResolvedMethod::DoubleParseDouble)
-                    }
-                    ("java/lang/Double", "toString", "(D)Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::DoubleToString)
-                    }
-                    ("java/lang/Double", "valueOf", "(D)Ljava/lang/Double;") => {
-                        Ok(ResolvedMethod::DoubleValueOf)
-                    }
+    /// Find a class by binary name, loading it from the classpath the first
+    /// time it is referenced
+    fn load_class(&mut self, name: &str) -> Result<Option<Rc<LoadedClass>>, RuntimeError> {
+        let is_new = self.class_loader.loaded_class(name).is_none();
+        let class = self.class_loader.load_class(name)?;
+        if class.is_none() && !is_library_class(name) {
+            return Err(RuntimeError::ClassNotFound(name.to_string()));
+        }
+        if self.verbose && is_new && class.is_some() {
+            eprintln!("Loading class: {name}");
+        }
+        Ok(class)
+    }
 
-                    // Boolean wrapper methods (static)
-                    ("java/lang/Boolean", "parseBoolean", "(Ljava/lang/String;)Z") => {
-                        Ok(ResolvedMethod::BooleanParseBoolean)
-                    }
-                    ("java/lang/Boolean", "toString", "(Z)Ljava/lang/String;") => {
-                        Ok(ResolvedMethod::BooleanToString)
-                    }
-                    ("java/lang/Boolean", "valueOf", "(Z)Ljava/lang/Boolean;") => {
-                        Ok(ResolvedMethod::BooleanValueOf)
-                    }
+    fn superclass_name(class_file: &ClassFile) -> Option<String> {
+        class_file
+            .constant_pool
+            .get_class_name(class_file.super_class)
+            .map(str::to_string)
+    }
 
-                    // Character methods (static)
-                    ("java/lang/Character", "isDigit", "(C)Z") => {
-                        Ok(ResolvedMethod::CharacterIsDigit)
-                    }
-                    ("java/lang/Character", "isLetter", "(C)Z") => {
-                        Ok(ResolvedMethod::CharacterIsLetter)
-                    }
-                    ("java/lang/Character", "toUpperCase", "(C)C") => {
-                        Ok(ResolvedMethod::CharacterToUpperCase)
-                    }
-                    ("java/lang/Character", "toLowerCase", "(C)C") => {
-                        Ok(ResolvedMethod::CharacterToLowerCase)
-                    }
-                    _ => Ok(ResolvedMethod::Unknown),
-                }
+    /// Initialize a class on first active use: its superclass first, then
+    /// static field defaults and constants, then `<clinit>`
+    fn initialize_class(&mut self, name: &str) -> Result<(), RuntimeError> {
+        if self.initialized_classes.contains(name) {
+            return Ok(());
+        }
+        let Some(class) = self.load_class(name)? else {
+            return Ok(());
+        };
+        let class_file = &class.class_file;
+        // Marked before running <clinit> so recursive references see the
+        // class as being initialized
+        self.initialized_classes.insert(name.to_string());
+
+        if let Some(superclass) = Self::superclass_name(class_file) {
+            self.initialize_class(&superclass)?;
+        }
+
+        let mut statics = HashMap::new();
+        for field in &class_file.fields {
+            if field.access_flags & ACC_STATIC == 0 {
+                continue;
+            }
+            let value = match self.field_constant_value(class_file, &field.attributes)? {
+                Some(value) => value,
+                None => default_value(&field.descriptor),
+            };
+            statics.insert(field.name.clone(), value);
+        }
+        self.static_fields.insert(name.to_string(), statics);
+
+        if let Some(clinit) = class_file.find_method("<clinit>", "()V") {
+            if self.verbose {
+                eprintln!("Initializing class: {name}");
             }
-            _ => Ok(ResolvedMethod::Unknown),
+            // Run to completion before the triggering instruction continues
+            let frame = MethodFrame::for_method(&class, clinit, Vec::new())?;
+            self.run_frame(frame)?;
         }
+        Ok(())
     }
 
-    fn load_constant_from_pool(&mut self, index: u16) -> Result<JvmValue, RuntimeError> {
-        let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
-        let entries = frame.constant_pool.entries();
+    /// Value of a field's `ConstantValue` attribute, if it has one
+    fn field_constant_value(
+        &mut self,
+        class_file: &ClassFile,
+        attributes: &[AttributeInfo],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        let Some(attribute) = attributes.iter().find(|attribute| {
+            class_file.constant_pool.get_utf8(attribute.name_index) == Some("ConstantValue")
+        }) else {
+            return Ok(None);
+        };
+        let [high, low] = attribute.info[..] else {
+            return Err(RuntimeError::InvalidClassFile(
+                "malformed ConstantValue attribute".to_string(),
+            ));
+        };
+        let index = u16::from_be_bytes([high, low]);
+
+        let value = match class_file.constant_pool.get(index) {
+            Some(ConstantPoolEntry::Integer(i)) => JvmValue::Int(*i),
+            Some(ConstantPoolEntry::Long(l)) => JvmValue::Long(*l),
+            Some(ConstantPoolEntry::Float(f)) => JvmValue::Float(*f),
+            Some(ConstantPoolEntry::Double(d)) => JvmValue::Double(*d),
+            Some(ConstantPoolEntry::String(utf8_index)) => {
+                let value = class_file
+                    .constant_pool
+                    .get_utf8(*utf8_index)
+                    .ok_or(RuntimeError::InvalidConstantPoolReference(*utf8_index))?
+                    .to_string();
+                JvmValue::Reference(Some(self.create_string_object(value)?))
+            }
+            _ => return Err(RuntimeError::InvalidConstantPoolReference(index)),
+        };
+        Ok(Some(value))
+    }
 
-        // JVM constant pool is 1-based, but our array is 0-based
-        let actual_index = (index - 1) as usize;
-        if actual_index >= entries.len() {
-            return Err(RuntimeError::InvalidStackState);
+    /// Look a method up in a class and then its superclasses, returning the
+    /// declaring class along with the method. Failing that, a `default`
+    /// method is taken from the superinterfaces, as JVMS §5.4.6 selects it.
+    fn find_method_in_hierarchy(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<Option<(Rc<LoadedClass>, MethodInfo)>, RuntimeError> {
+        let mut interfaces = Vec::new();
+        let mut current = Some(class_name.to_string());
+        while let Some(class_name) = current {
+            let Some(class) = self.load_class(&class_name)? else {
+                break;
+            };
+            if let Some(method) = class.class_file.find_method(name, descriptor) {
+                let method = method.clone();
+                return Ok(Some((class, method)));
+            }
+            interfaces.extend(Self::interface_names(&class.class_file));
+            current = Self::superclass_name(&class.class_file);
         }
+        self.find_default_method(interfaces, name, descriptor)
+    }
 
-        match &entries[actual_index] {
-            ConstantPoolEntry::Integer(i) => Ok(JvmValue::Int(*i)),
-            ConstantPoolEntry::Float(f) => Ok(JvmValue::Float(*f)),
-            ConstantPoolEntry::Long(l) => Ok(JvmValue::Long(*l)),
-            ConstantPoolEntry::Double(d) => Ok(JvmValue::Double(*d)),
-            ConstantPoolEntry::String(utf8_index) => {
-                let utf8_actual_index = (*utf8_index - 1) as usize;
-                if let ConstantPoolEntry::Utf8(s) = &entries[utf8_actual_index] {
-                    let object_id = self.create_string_object(s.clone());
-                    Ok(JvmValue::Reference(Some(object_id)))
-                } else {
-                    Err(RuntimeError::InvalidStackState)
+    /// The maximally specific non-abstract method declared by the given
+    /// interfaces or their superinterfaces: one no other candidate's
+    /// interface extends
+    fn find_default_method(
+        &mut self,
+        mut pending: Vec<String>,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<Option<(Rc<LoadedClass>, MethodInfo)>, RuntimeError> {
+        let mut candidates = Vec::new();
+        let mut visited = HashSet::new();
+        while let Some(interface) = pending.pop() {
+            if !visited.insert(interface.clone()) {
+                continue;
+            }
+            let Some(class) = self.load_class(&interface)? else {
+                continue;
+            };
+            pending.extend(Self::interface_names(&class.class_file));
+            if let Some(method) = class.class_file.find_method(name, descriptor)
+                && method.access_flags & (ACC_ABSTRACT | ACC_STATIC | ACC_PRIVATE) == 0
+            {
+                let method = method.clone();
+                candidates.push((class, method));
+            }
+        }
+        for (index, (class, _)) in candidates.iter().enumerate() {
+            let mut overridden = false;
+            for (other_index, (other, _)) in candidates.iter().enumerate() {
+                if other_index != index && self.is_assignable(&other.name, &class.name)? {
+                    overridden = true;
+                    break;
                 }
             }
-            _ => Err(RuntimeError::InvalidStackState),
+            if !overridden {
+                return Ok(Some(candidates.swap_remove(index)));
+            }
         }
+        Ok(None)
     }
 
-    pub fn debug_state(&self) {
-        if self.verbose {
-            eprintln!("=== JVM Compatible VM State ===");
-            eprintln!("Steps: {}", self.steps);
-            eprintln!("Frames: {}", self.frames.len());
+    /// Names of the interfaces a class declares it implements
+    fn interface_names(class_file: &ClassFile) -> Vec<String> {
+        class_file
+            .interfaces
+            .iter()
+            .filter_map(|&index| class_file.constant_pool.get_class_name(index))
+            .map(str::to_string)
+            .collect()
+    }
 
-            if let Some(frame) = self.frames.last() {
-                eprintln!("Current Frame:");
-                eprintln!("  PC: {}", frame.pc);
-                eprintln!("  Operand Stack: {:?}", frame.operand_stack);
-                eprintln!("  Locals: {:?}", frame.locals);
+    /// Instance fields of a class and its superclasses with default values
+    fn instance_field_defaults(
+        &mut self,
+        class_name: &str,
+    ) -> Result<HashMap<String, JvmValue>, RuntimeError> {
+        let mut fields = HashMap::new();
+        let mut current = Some(class_name.to_string());
+        while let Some(class_name) = current {
+            let Some(class) = self.load_class(&class_name)? else {
+                break;
+            };
+            for field in &class.class_file.fields {
+                if field.access_flags & ACC_STATIC == 0 {
+                    fields.insert(
+                        field_key(&class.name, &field.name, &field.descriptor),
+                        default_value(&field.descriptor),
+                    );
+                }
             }
-
-            eprintln!("Heap Objects: {}", self.heap.len());
-            eprintln!("==============================");
+            current = Self::superclass_name(&class.class_file);
         }
+        Ok(fields)
     }
 
-    fn resolve_static_field(&mut self, field_ref: u16) -> Result<JvmValue, RuntimeError> {
-        let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
-        let entries = frame.constant_pool.entries();
+    /// The slot of the instance field a field reference names, found in the
+    /// referenced class or its superclasses as JVMS §5.4.3.2 specifies.
+    /// Fields of library classes keep their plain names.
+    fn instance_field_key(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<String, RuntimeError> {
+        let mut current = Some(class_name.to_string());
+        while let Some(class_name) = current {
+            let Some(class) = self.load_class(&class_name)? else {
+                break;
+            };
+            if class.class_file.fields.iter().any(|field| {
+                field.name == name
+                    && field.descriptor == descriptor
+                    && field.access_flags & ACC_STATIC == 0
+            }) {
+                return Ok(field_key(&class_name, name, descriptor));
+            }
+            current = Self::superclass_name(&class.class_file);
+        }
+        Ok(name.to_string())
+    }
 
-        // JVM constant pool is 1-based, but our array is 0-based
-        let actual_index = (field_ref - 1) as usize;
-        if actual_index >= entries.len() {
-            // Fallback to old numeric resolution
-            return self.resolve_static_field_numeric(field_ref);
+    /// Find the class that declares a static field, starting from the class
+    /// named in the field reference
+    fn static_field_owner(
+        &mut self,
+        class_name: &str,
+        field_name: &str,
+    ) -> Result<Option<String>, RuntimeError> {
+        let mut current = Some(class_name.to_string());
+        while let Some(class_name) = current {
+            let Some(class) = self.load_class(&class_name)? else {
+                return Ok(None);
+            };
+            if class
+                .class_file
+                .fields
+                .iter()
+                .any(|field| field.name == field_name && field.access_flags & ACC_STATIC != 0)
+            {
+                return Ok(Some(class_name));
+            }
+            current = Self::superclass_name(&class.class_file);
         }
+        Ok(None)
+    }
 
-        match &entries[actual_index] {
-            ConstantPoolEntry::Fieldref(class_index, name_and_type_index) => {
-                // Get class name
-                let class_actual_index = (*class_index - 1) as usize;
-                let class_name =
-                    if let ConstantPoolEntry::Class(name_index) = &entries[class_actual_index] {
-                        let name_actual_index = (*name_index - 1) as usize;
-                        if let ConstantPoolEntry::Utf8(name) = &entries[name_actual_index] {
-                            name
-                        } else {
-                            return self.resolve_static_field_numeric(field_ref);
-                        }
-                    } else {
-                        return self.resolve_static_field_numeric(field_ref);
-                    };
+    /// Class name, member name and descriptor of a field or method reference
+    fn member_reference(&self, index: u16) -> Result<(String, String, String), RuntimeError> {
+        let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
+        let constant_pool = &frame.constant_pool;
+        let (Some(ConstantPoolEntry::Fieldref(class_index, name_and_type_index))
+        | Some(ConstantPoolEntry::Methodref(class_index, name_and_type_index))
+        | Some(ConstantPoolEntry::InterfaceMethodref(class_index, name_and_type_index))) =
+            constant_pool.get(index)
+        else {
+            return Err(RuntimeError::InvalidConstantPoolReference(index));
+        };
+        let Some(ConstantPoolEntry::NameAndType(name_index, descriptor_index)) =
+            constant_pool.get(*name_and_type_index)
+        else {
+            return Err(RuntimeError::InvalidConstantPoolReference(
+                *name_and_type_index,
+            ));
+        };
 
-                // Get field name
-                let name_and_type_actual_index = (*name_and_type_index - 1) as usize;
-                let field_name = if let ConstantPoolEntry::NameAndType(name_index, _desc_index) =
-                    &entries[name_and_type_actual_index]
-                {
-                    let name_actual_index = (*name_index - 1) as usize;
-                    if let ConstantPoolEntry::Utf8(name) = &entries[name_actual_index] {
-                        name
-                    } else {
-                        return self.resolve_static_field_numeric(field_ref);
-                    }
-                } else {
-                    return self.resolve_static_field_numeric(field_ref);
-                };
+        let lookup = |index: u16, value: Option<&str>| {
+            value
+                .map(str::to_string)
+                .ok_or(RuntimeError::InvalidConstantPoolReference(index))
+        };
+        Ok((
+            lookup(*class_index, constant_pool.get_class_name(*class_index))?,
+            lookup(*name_index, constant_pool.get_utf8(*name_index))?,
+            lookup(*descriptor_index, constant_pool.get_utf8(*descriptor_index))?,
+        ))
+    }
+
+    /// Pop a method's arguments, including the receiver when present
+    fn pop_arguments(
+        &mut self,
+        descriptor: &str,
+        has_receiver: bool,
+    ) -> Result<Vec<JvmValue>, RuntimeError> {
+        let count = method_parameter_types(descriptor).len() + usize::from(has_receiver);
+        let frame = self
+            .frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?;
+        if frame.operand_stack.len() < count {
+            return Err(RuntimeError::StackUnderflow);
+        }
+        Ok(frame
+            .operand_stack
+            .split_off(frame.operand_stack.len() - count))
+    }
 
-                // Resolve based on class and field name
-                match (class_name.as_str(), field_name.as_str()) {
-                    ("java/lang/System", "out") => {
-                        let stdout_id = self.create_printstream_object("stdout".to_string());
-                        Ok(JvmValue::Reference(Some(stdout_id)))
-                    }
-                    ("java/lang/System", "err") => {
-                        let stderr_id = self.create_printstream_object("stderr".to_string());
-                        Ok(JvmValue::Reference(Some(stderr_id)))
-                    }
-                    _ => self.resolve_static_field_numeric(field_ref),
-                }
-            }
-            _ => self.resolve_static_field_numeric(field_ref),
+    /// Call a method of a loaded class. Bytecode methods get a frame that the
+    /// dispatch loop runs next; natives run at once and push their return
+    /// value, if any, onto the caller's operand stack.
+    fn invoke_method(
+        &mut self,
+        class: &LoadedClass,
+        method: &MethodInfo,
+        arguments: Vec<JvmValue>,
+    ) -> Result<(), RuntimeError> {
+        if method.code.is_none() {
+            // Native methods declared by a loaded class
+            if let Some(native) = self
+                .natives
+                .get(&class.name, &method.name, &method.descriptor)
+            {
+                return self.call_native(native, &arguments);
+            }
+            return Err(RuntimeError::NoSuchMethod(format!(
+                "{}.{}{} has no code",
+                class.name, method.name, method.descriptor
+            )));
         }
+
+        let frame = MethodFrame::for_method(class, method, arguments)?;
+        self.push_frame(frame)
     }
 
-    fn resolve_static_field_numeric(&mut self, field_ref: u16) -> Result<JvmValue, RuntimeError> {
-        // Fallback for numeric field references (for backward compatibility)
-        match field_ref {
-            31 => {
-                // System.out - create a PrintStream object reference
-                let stdout_id = self.create_printstream_object("stdout".to_string());
-                Ok(JvmValue::Reference(Some(stdout_id)))
-            }
-            32 => {
-                // System.err - create a PrintStream object reference
-                let stderr_id = self.create_printstream_object("stderr".to_string());
-                Ok(JvmValue::Reference(Some(stderr_id)))
-            }
-            _ => Err(RuntimeError::InvalidStackState),
+    /// Invoke a user-defined method resolved from `class_name` upwards.
+    /// Returns `false` when the class is not loaded from a class file, so the
+    /// caller can fall back to the built-in library.
+    fn invoke_resolved_method(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+        has_receiver: bool,
+    ) -> Result<bool, RuntimeError> {
+        if self.load_class(class_name)?.is_none() {
+            return Ok(false);
         }
+        if !has_receiver {
+            self.initialize_class(class_name)?;
+        }
+
+        let Some((declaring_class, method)) =
+            self.find_method_in_hierarchy(class_name, name, descriptor)?
+        else {
+            // Methods inherited from library classes such as Object
+            return Ok(false);
+        };
+        let arguments = self.pop_arguments(descriptor, has_receiver)?;
+        if has_receiver && arguments[0].is_null() {
+            return Err(RuntimeError::NullPointer);
+        }
+        self.invoke_method(&declaring_class, &method, arguments)?;
+        Ok(true)
     }
 
-    fn create_printstream_object(&mut self, stream_type: String) -> usize {
+    /// Invoke a registered native, popping its arguments. Returns `false`
+    /// when no native is registered under the key.
+    fn invoke_native_method(
+        &mut self,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+        has_receiver: bool,
+    ) -> Result<bool, RuntimeError> {
+        let Some(native) = self.natives.get(class_name, name, descriptor) else {
+            return Ok(false);
+        };
+        let arguments = self.pop_arguments(descriptor, has_receiver)?;
+        if has_receiver && arguments[0].is_null() {
+            return Err(RuntimeError::NullPointer);
+        }
+        self.call_native(native, &arguments)?;
+        Ok(true)
+    }
+
+    /// The native implementing a virtual call: the receiver's class is
+    /// searched first, then the class named by the call, then `Object`
+    fn virtual_native(
+        &self,
+        runtime_class: &str,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<NativeMethod> {
+        [runtime_class, class_name, "java/lang/Object"]
+            .into_iter()
+            .find_map(|class_name| self.natives.get(class_name, name, descriptor))
+    }
+
+    /// Call a native and push its return value, if any
+    fn call_native(
+        &mut self,
+        native: NativeMethod,
+        arguments: &[JvmValue],
+    ) -> Result<(), RuntimeError> {
+        if let Some(return_value) = native(self, arguments)? {
+            self.frames
+                .last_mut()
+                .ok_or(RuntimeError::CallStackUnderflow)?
+                .operand_stack
+                .push(return_value);
+        }
+        Ok(())
+    }
+
+    fn new_object(&mut self, class_name: &str) -> Result<usize, RuntimeError> {
+        self.initialize_class(class_name)?;
+        let fields = self.instance_field_defaults(class_name)?;
+
         let object_id = self.next_object_id;
         self.next_object_id += 1;
+        self.heap.insert(
+            object_id,
+            JvmObject {
+                class_name: class_name.to_string(),
+                fields,
+            },
+        );
+
+        // Like Throwable.fillInStackTrace, record where it was created
+        if self.is_assignable(class_name, "java/lang/Throwable")? {
+            let stack_trace = self.capture_stack_trace();
+            self.stack_traces.insert(object_id, stack_trace);
+        }
+        Ok(object_id)
+    }
 
-        let mut fields = HashMap::new();
-        fields.insert("type".to_string(), JvmValue::Reference(None));
+    fn get_field(&mut self, field_ref: u16) -> Result<(), RuntimeError> {
+        let (class_name, field_name, descriptor) = self.member_reference(field_ref)?;
+        let key = self.instance_field_key(&class_name, &field_name, &descriptor)?;
+        let frame = self
+            .frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?;
+        let object_ref = frame
+            .operand_stack
+            .pop()
+            .ok_or(RuntimeError::StackUnderflow)?;
+        let JvmValue::Reference(reference) = object_ref else {
+            return Err(RuntimeError::InvalidStackState);
+        };
+        let object_id = reference.ok_or(RuntimeError::NullPointer)?;
+
+        let value = self
+            .heap
+            .get(&object_id)
+            .and_then(|object| object.fields.get(&key))
+            .cloned()
+            .unwrap_or_else(|| default_value(&descriptor));
+        frame.operand_stack.push(value);
+        Ok(())
+    }
 
-        let printstream_object = JvmObject {
-            class_name: "java/io/PrintStream".to_string(),
-            fields,
+    fn put_field(&mut self, field_ref: u16) -> Result<(), RuntimeError> {
+        let (class_name, field_name, descriptor) = self.member_reference(field_ref)?;
+        let key = self.instance_field_key(&class_name, &field_name, &descriptor)?;
+        let frame = self
+            .frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?;
+        let value = frame
+            .operand_stack
+            .pop()
+            .ok_or(RuntimeError::StackUnderflow)?;
+        let object_ref = frame
+            .operand_stack
+            .pop()
+            .ok_or(RuntimeError::StackUnderflow)?;
+        let JvmValue::Reference(reference) = object_ref else {
+            return Err(RuntimeError::InvalidStackState);
         };
+        let object_id = reference.ok_or(RuntimeError::NullPointer)?;
 
-        self.heap.insert(object_id, printstream_object);
+        let object = self
+            .heap
+            .get_mut(&object_id)
+            .ok_or(RuntimeError::InvalidStackState)?;
+        object.fields.insert(key, value);
+        Ok(())
+    }
 
-        // Store the stream type separately for our implementation
-        if stream_type == "stderr" {
-            self.heap
-                .get_mut(&object_id)
-                .unwrap()
-                .fields
-                .insert("is_stderr".to_string(), JvmValue::Int(1));
-        } else {
-            self.heap
-                .get_mut(&object_id)
-                .unwrap()
-                .fields
-                .insert("is_stderr".to_string(), JvmValue::Int(0));
-        }
+    /// Read or write a static field of a user class. Returns `None` for
+    /// reads of library classes, which are resolved by the caller.
+    fn access_static_field(
+        &mut self,
+        field_ref: u16,
+        new_value: Option<JvmValue>,
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        let (class_name, field_name, _) = self.member_reference(field_ref)?;
+        let Some(owner) = self.static_field_owner(&class_name, &field_name)? else {
+            if self.load_class(&class_name)?.is_some() {
+                return Err(RuntimeError::NoSuchField(format!(
+                    "{class_name}.{field_name}"
+                )));
+            }
+            return Ok(None);
+        };
+        self.initialize_class(&owner)?;
 
-        object_id
+        let statics = self.static_fields.entry(owner).or_default();
+        match new_value {
+            Some(value) => {
+                statics.insert(field_name, value.clone());
+                Ok(Some(value))
+            }
+            None => Ok(statics.get(&field_name).cloned()),
+        }
     }
 
-    fn invoke_virtual_method(&mut self, method_ref: u16) -> Result<(), RuntimeError> {
-        // First try to resolve the method from the constant pool
-        let method_info = self.resolve_method_reference(method_ref)?;
-
+    fn execute_single_instruction(&mut self) -> Result<Completion, RuntimeError> {
         let frame = self
             .frames
             .last_mut()
             .ok_or(RuntimeError::CallStackUnderflow)?;
 
-        match method_info {
-            ResolvedMethod::PrintStreamPrintln => {
-                // println(I)V or println(J)V
-                let value = frame
+        if frame.pc >= frame.bytecode.len() {
+            self.frames.pop();
+            return Ok(Completion::Returned(None));
+        }
+
+        let instruction = frame.bytecode[frame.pc].clone();
+        if self.verbose {
+            eprintln!("PC {}: Executing instruction: {:?}", frame.pc, instruction);
+        }
+
+        match instruction {
+            JvmInstruction::AconstNull => {
+                frame.operand_stack.push(JvmValue::Reference(None));
+                frame.pc += 1;
+            }
+            JvmInstruction::IconstM1 => {
+                frame.operand_stack.push(JvmValue::Int(-1));
+                frame.pc += 1;
+            }
+            JvmInstruction::Iconst0 => {
+                frame.operand_stack.push(JvmValue::Int(0));
+                frame.pc += 1;
+            }
+            JvmInstruction::Iconst1 => {
+                frame.operand_stack.push(JvmValue::Int(1));
+                frame.pc += 1;
+            }
+            JvmInstruction::Iconst2 => {
+                frame.operand_stack.push(JvmValue::Int(2));
+                frame.pc += 1;
+            }
+            JvmInstruction::Iconst3 => {
+                frame.operand_stack.push(JvmValue::Int(3));
+                frame.pc += 1;
+            }
+            JvmInstruction::Iconst4 => {
+                frame.operand_stack.push(JvmValue::Int(4));
+                frame.pc += 1;
+            }
+            JvmInstruction::Iconst5 => {
+                frame.operand_stack.push(JvmValue::Int(5));
+                frame.pc += 1;
+            }
+            JvmInstruction::Lconst0 => {
+                frame.operand_stack.push(JvmValue::Long(0));
+                frame.pc += 1;
+            }
+            JvmInstruction::Lconst1 => {
+                frame.operand_stack.push(JvmValue::Long(1));
+                frame.pc += 1;
+            }
+            JvmInstruction::Fconst0 | JvmInstruction::Fconst1 | JvmInstruction::Fconst2 => {
+                let value = match instruction {
+                    JvmInstruction::Fconst0 => 0.0,
+                    JvmInstruction::Fconst1 => 1.0,
+                    _ => 2.0,
+                };
+                frame.operand_stack.push(JvmValue::Float(value));
+                frame.pc += 1;
+            }
+            JvmInstruction::Bipush(value) => {
+                frame.operand_stack.push(JvmValue::Int(value as i32));
+                frame.pc += 1;
+            }
+            JvmInstruction::Sipush(value) => {
+                frame.operand_stack.push(JvmValue::Int(value as i32));
+                frame.pc += 1;
+            }
+            JvmInstruction::Ldc(index) | JvmInstruction::LdcW(index) => {
+                let value = self.load_constant_from_pool(index)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Ldc2W(index) => {
+                let value = self.load_constant_from_pool(index)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+
+            JvmInstruction::Pop => {
+                frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                let printstream_ref = frame
+                frame.pc += 1;
+            }
+            JvmInstruction::Pop2 => {
+                frame.pop_words(2)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::Dup => {
+                let value = frame
                     .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
+                    .last()
+                    .ok_or(RuntimeError::StackUnderflow)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Dup2 => {
+                frame.dup_words(2, 0)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::DupX1 => {
+                frame.dup_words(1, 1)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::DupX2 => {
+                frame.dup_words(1, 2)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::Dup2X1 => {
+                frame.dup_words(2, 1)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::Dup2X2 => {
+                frame.dup_words(2, 2)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::Swap => {
+                let len = frame.operand_stack.len();
+                if len < 2 {
+                    return Err(RuntimeError::StackUnderflow);
+                }
+                frame.operand_stack.swap(len - 1, len - 2);
+                frame.pc += 1;
+            }
 
-                let output = match value {
-                    JvmValue::Int(i) => i.to_string(),
-                    JvmValue::Long(l) => l.to_string(),
-                    JvmValue::Double(d) => d.to_string(),
-                    JvmValue::Float(f) => f.to_string(),
-                    JvmValue::Boolean(b) => b.to_string(),
-                    JvmValue::Char(c) => (c as u8 as char).to_string(),
-                    _ => "null".to_string(),
+            JvmInstruction::Iadd
+            | JvmInstruction::Isub
+            | JvmInstruction::Imul
+            | JvmInstruction::Idiv
+            | JvmInstruction::Irem
+            | JvmInstruction::Ishl
+            | JvmInstruction::Ishr
+            | JvmInstruction::Iushr
+            | JvmInstruction::Iand
+            | JvmInstruction::Ior
+            | JvmInstruction::Ixor => {
+                let b = frame.pop()?.as_int()?;
+                let a = frame.pop()?.as_int()?;
+                let result = int_operation(&instruction, a, b)?;
+                frame.operand_stack.push(JvmValue::Int(result));
+                frame.pc += 1;
+            }
+            JvmInstruction::Ladd
+            | JvmInstruction::Lsub
+            | JvmInstruction::Lmul
+            | JvmInstruction::Ldiv
+            | JvmInstruction::Lrem
+            | JvmInstruction::Land
+            | JvmInstruction::Lor
+            | JvmInstruction::Lxor => {
+                let b = frame.pop()?.as_long()?;
+                let a = frame.pop()?.as_long()?;
+                let result = long_operation(&instruction, a, b)?;
+                frame.operand_stack.push(JvmValue::Long(result));
+                frame.pc += 1;
+            }
+            JvmInstruction::Lshl | JvmInstruction::Lshr | JvmInstruction::Lushr => {
+                // The shift distance is an int; only its low six bits count
+                let distance = frame.pop()?.as_int()? as u32;
+                let value = frame.pop()?.as_long()?;
+                let result = match instruction {
+                    JvmInstruction::Lshl => value.wrapping_shl(distance),
+                    JvmInstruction::Lshr => value.wrapping_shr(distance),
+                    _ => (value as u64).wrapping_shr(distance) as i64,
                 };
+                frame.operand_stack.push(JvmValue::Long(result));
+                frame.pc += 1;
+            }
+            JvmInstruction::Fadd
+            | JvmInstruction::Fsub
+            | JvmInstruction::Fmul
+            | JvmInstruction::Fdiv
+            | JvmInstruction::Frem => {
+                let b = frame.pop()?.as_float()?;
+                let a = frame.pop()?.as_float()?;
+                let result = match instruction {
+                    JvmInstruction::Fadd => a + b,
+                    JvmInstruction::Fsub => a - b,
+                    JvmInstruction::Fmul => a * b,
+                    JvmInstruction::Fdiv => a / b,
+                    _ => a % b,
+                };
+                frame.operand_stack.push(JvmValue::Float(result));
+                frame.pc += 1;
+            }
+            JvmInstruction::Dadd
+            | JvmInstruction::Dsub
+            | JvmInstruction::Dmul
+            | JvmInstruction::Ddiv
+            | JvmInstruction::Drem => {
+                // IEEE 754 arithmetic: division by zero yields an infinity
+                // or NaN rather than an exception
+                let b = frame.pop()?.as_double()?;
+                let a = frame.pop()?.as_double()?;
+                let result = match instruction {
+                    JvmInstruction::Dadd => a + b,
+                    JvmInstruction::Dsub => a - b,
+                    JvmInstruction::Dmul => a * b,
+                    JvmInstruction::Ddiv => a / b,
+                    _ => a % b,
+                };
+                frame.operand_stack.push(JvmValue::Double(result));
+                frame.pc += 1;
+            }
+            JvmInstruction::Ineg => {
+                let value = frame.pop()?.as_int()?;
+                frame
+                    .operand_stack
+                    .push(JvmValue::Int(value.wrapping_neg()));
+                frame.pc += 1;
+            }
+            JvmInstruction::Lneg => {
+                let value = frame.pop()?.as_long()?;
+                frame
+                    .operand_stack
+                    .push(JvmValue::Long(value.wrapping_neg()));
+                frame.pc += 1;
+            }
+            JvmInstruction::Fneg => {
+                let value = frame.pop()?.as_float()?;
+                frame.operand_stack.push(JvmValue::Float(-value));
+                frame.pc += 1;
+            }
+            JvmInstruction::Dneg => {
+                let value = frame.pop()?.as_double()?;
+                frame.operand_stack.push(JvmValue::Double(-value));
+                frame.pc += 1;
+            }
+            JvmInstruction::Iinc(index, constant) => {
+                let local = frame
+                    .locals
+                    .get_mut(index as usize)
+                    .ok_or(RuntimeError::InvalidStackState)?;
+                *local = JvmValue::Int(local.as_int()?.wrapping_add(constant as i32));
+                frame.pc += 1;
+            }
 
-                if let JvmValue::Reference(Some(obj_id)) = printstream_ref {
-                    if let Some(obj) = self.heap.get(&obj_id) {
-                        if let Some(JvmValue::Int(is_stderr)) = obj.fields.get("is_stderr") {
-                            if *is_stderr == 1 {
-                                eprintln!("{output}");
-                            } else {
-                                println!("{output}");
-                            }
-                        }
+            JvmInstruction::I2l
+            | JvmInstruction::I2f
+            | JvmInstruction::I2d
+            | JvmInstruction::L2i
+            | JvmInstruction::L2f
+            | JvmInstruction::L2d
+            | JvmInstruction::F2i
+            | JvmInstruction::F2l
+            | JvmInstruction::F2d
+            | JvmInstruction::D2i
+            | JvmInstruction::D2l
+            | JvmInstruction::D2f
+            | JvmInstruction::I2b
+            | JvmInstruction::I2c
+            | JvmInstruction::I2s => {
+                let value = frame.pop()?;
+                frame
+                    .operand_stack
+                    .push(convert_value(&instruction, &value)?);
+                frame.pc += 1;
+            }
+
+            JvmInstruction::Lcmp => {
+                let b = frame.pop()?.as_long()?;
+                let a = frame.pop()?.as_long()?;
+                frame.operand_stack.push(JvmValue::Int(a.cmp(&b) as i32));
+                frame.pc += 1;
+            }
+            JvmInstruction::Fcmpl
+            | JvmInstruction::Fcmpg
+            | JvmInstruction::Dcmpl
+            | JvmInstruction::Dcmpg => {
+                // Every float is exactly representable as a double
+                let b = frame.pop()?.as_double()?;
+                let a = frame.pop()?.as_double()?;
+                let result = match a.partial_cmp(&b) {
+                    Some(ordering) => ordering as i32,
+                    None if matches!(
+                        instruction,
+                        JvmInstruction::Fcmpg | JvmInstruction::Dcmpg
+                    ) =>
+                    {
+                        1
                     }
-                }
+                    None => -1,
+                };
+                frame.operand_stack.push(JvmValue::Int(result));
+                frame.pc += 1;
+            }
+
+            JvmInstruction::Goto(offset) => {
+                frame.pc = offset as usize;
             }
-            ResolvedMethod::PrintStreamPrint => {
-                // print(Ljava/lang/String;)V
-                let string_ref = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let printstream_ref = frame
+            JvmInstruction::Tableswitch {
+                default,
+                low,
+                targets,
+            } => {
+                let key = frame.pop()?.as_int()?;
+                let target = usize::try_from(key as i64 - low as i64)
+                    .ok()
+                    .and_then(|position| targets.get(position))
+                    .unwrap_or(&default);
+                frame.pc = *target as usize;
+            }
+            JvmInstruction::Lookupswitch { default, pairs } => {
+                let key = frame.pop()?.as_int()?;
+                let target = pairs
+                    .binary_search_by_key(&key, |(key, _)| *key)
+                    .map_or(default, |position| pairs[position].1);
+                frame.pc = target as usize;
+            }
+            JvmInstruction::Ifeq(offset) => {
+                let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let (
-                    JvmValue::Reference(Some(string_id)),
-                    JvmValue::Reference(Some(stream_id)),
-                ) = (string_ref, printstream_ref)
-                {
-                    // Get the actual string value from our string data storage
-                    if let Some(string_value) = self.string_data.get(&string_id) {
-                        if let Some(stream_obj) = self.heap.get(&stream_id) {
-                            if let Some(JvmValue::Int(is_stderr)) =
-                                stream_obj.fields.get("is_stderr")
-                            {
-                                if *is_stderr == 1 {
-                                    eprint!("{string_value}");
-                                } else {
-                                    print!("{string_value}");
-                                }
-                            }
-                        }
-                    }
+                    .ok_or(RuntimeError::StackUnderflow)?
+                    .as_int()?;
+                if value == 0 {
+                    frame.pc = offset as usize;
+                } else {
+                    frame.pc += 1;
                 }
             }
-            ResolvedMethod::PrintStreamPrintlnString => {
-                // println(Ljava/lang/String;)V
-                let string_ref = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let printstream_ref = frame
+            JvmInstruction::Ifne(offset) => {
+                let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let (
-                    JvmValue::Reference(Some(string_id)),
-                    JvmValue::Reference(Some(stream_id)),
-                ) = (string_ref, printstream_ref)
-                {
-                    // Get the actual string value from our string data storage
-                    if let Some(string_value) = self.string_data.get(&string_id) {
-                        if let Some(stream_obj) = self.heap.get(&stream_id) {
-                            if let Some(JvmValue::Int(is_stderr)) =
-                                stream_obj.fields.get("is_stderr")
-                            {
-                                if *is_stderr == 1 {
-                                    eprintln!("{string_value}");
-                                } else {
-                                    println!("{string_value}");
-                                }
-                            }
-                        }
-                    }
+                    .ok_or(RuntimeError::StackUnderflow)?
+                    .as_int()?;
+                if value != 0 {
+                    frame.pc = offset as usize;
+                } else {
+                    frame.pc += 1;
                 }
             }
-            ResolvedMethod::PrintStreamPrintlnFloat => {
-                // println(F)V
+            JvmInstruction::Iflt(offset) => {
                 let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let printstream_ref = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let JvmValue::Reference(Some(obj_id)) = printstream_ref {
-                    if let Some(obj) = self.heap.get(&obj_id) {
-                        if let Some(JvmValue::Int(is_stderr)) = obj.fields.get("is_stderr") {
-                            // Convert the value to a float for printing
-                            let float_value = match value {
-                                JvmValue::Float(f) => f,
-                                JvmValue::Int(i) => i as f32,
-                                JvmValue::Double(d) => d as f32,
-                                _ => return Err(RuntimeError::InvalidStackState),
-                            };
-
-                            if *is_stderr == 1 {
-                                eprintln!("{float_value}");
-                            } else {
-                                println!("{float_value}");
-                            }
-                        }
-                    }
+                    .ok_or(RuntimeError::StackUnderflow)?
+                    .as_int()?;
+                if value < 0 {
+                    frame.pc = offset as usize;
+                } else {
+                    frame.pc += 1;
                 }
             }
-            ResolvedMethod::PrintStreamPrintlnDouble => {
-                // println(D)V
+            JvmInstruction::Ifge(offset) => {
                 let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let printstream_ref = frame
+                    .ok_or(RuntimeError::StackUnderflow)?
+                    .as_int()?;
+                if value >= 0 {
+                    frame.pc = offset as usize;
+                } else {
+                    frame.pc += 1;
+                }
+            }
+            JvmInstruction::Ifgt(offset) => {
+                let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let JvmValue::Reference(Some(obj_id)) = printstream_ref {
-                    if let Some(obj) = self.heap.get(&obj_id) {
-                        if let Some(JvmValue::Int(is_stderr)) = obj.fields.get("is_stderr") {
-                            // Convert the value to a double for printing
-                            let double_value = match value {
-                                JvmValue::Double(d) => d,
-                                JvmValue::Float(f) => f as f64,
-                                JvmValue::Int(i) => i as f64,
-                                _ => return Err(RuntimeError::InvalidStackState),
-                            };
-
-                            if *is_stderr == 1 {
-                                eprintln!("{double_value}");
-                            } else {
-                                println!("{double_value}");
-                            }
-                        }
-                    }
+                    .ok_or(RuntimeError::StackUnderflow)?
+                    .as_int()?;
+                if value > 0 {
+                    frame.pc = offset as usize;
+                } else {
+                    frame.pc += 1;
                 }
             }
-            ResolvedMethod::PrintStreamPrintlnBoolean => {
-                // println(Z)V
+            JvmInstruction::Ifle(offset) => {
                 let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let printstream_ref = frame
+                    .ok_or(RuntimeError::StackUnderflow)?
+                    .as_int()?;
+                if value <= 0 {
+                    frame.pc = offset as usize;
+                } else {
+                    frame.pc += 1;
+                }
+            }
+            JvmInstruction::IfIcmpeq(offset)
+            | JvmInstruction::IfIcmpne(offset)
+            | JvmInstruction::IfIcmplt(offset)
+            | JvmInstruction::IfIcmpge(offset)
+            | JvmInstruction::IfIcmpgt(offset)
+            | JvmInstruction::IfIcmple(offset) => {
+                let b = frame.pop()?.as_int()?;
+                let a = frame.pop()?.as_int()?;
+                let taken = match instruction {
+                    JvmInstruction::IfIcmpeq(_) => a == b,
+                    JvmInstruction::IfIcmpne(_) => a != b,
+                    JvmInstruction::IfIcmplt(_) => a < b,
+                    JvmInstruction::IfIcmpge(_) => a >= b,
+                    JvmInstruction::IfIcmpgt(_) => a > b,
+                    _ => a <= b,
+                };
+                frame.pc = if taken { offset as usize } else { frame.pc + 1 };
+            }
+            JvmInstruction::IfAcmpeq(offset) | JvmInstruction::IfAcmpne(offset) => {
+                let b = frame.pop()?;
+                let a = frame.pop()?;
+                let taken = (a == b) == matches!(instruction, JvmInstruction::IfAcmpeq(_));
+                frame.pc = if taken { offset as usize } else { frame.pc + 1 };
+            }
+            JvmInstruction::Ifnull(offset) | JvmInstruction::Ifnonnull(offset) => {
+                let value = frame.pop()?;
+                let taken = value.is_null() == matches!(instruction, JvmInstruction::Ifnull(_));
+                frame.pc = if taken { offset as usize } else { frame.pc + 1 };
+            }
+
+            JvmInstruction::Return => {
+                self.frames.pop();
+                return Ok(Completion::Returned(None));
+            }
+            JvmInstruction::Ireturn
+            | JvmInstruction::Lreturn
+            | JvmInstruction::Freturn
+            | JvmInstruction::Dreturn
+            | JvmInstruction::Areturn => {
+                let return_value = frame.pop()?;
+                self.frames.pop();
+                return Ok(Completion::Returned(Some(return_value)));
+            }
+
+            JvmInstruction::New(class_index) => {
+                let class_name = frame
+                    .constant_pool
+                    .get_class_name(class_index)
+                    .ok_or(RuntimeError::InvalidConstantPoolReference(class_index))?
+                    .to_string();
+                let object_id = self.new_object(&class_name)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame
                     .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
+                    .push(JvmValue::Reference(Some(object_id)));
+                frame.pc += 1;
+            }
 
-                if let JvmValue::Reference(Some(obj_id)) = printstream_ref {
-                    if let Some(obj) = self.heap.get(&obj_id) {
-                        if let Some(JvmValue::Int(is_stderr)) = obj.fields.get("is_stderr") {
-                            let bool_value = match value {
-                                JvmValue::Boolean(b) => b,
-                                JvmValue::Int(i) => i != 0,
-                                _ => return Err(RuntimeError::InvalidStackState),
-                            };
-
-                            if *is_stderr == 1 {
-                                eprintln!("{bool_value}");
-                            } else {
-                                println!("{bool_value}");
+            JvmInstruction::Newarray(atype) => {
+                let descriptor = match atype {
+                    4 => "[Z",
+                    5 => "[C",
+                    6 => "[F",
+                    7 => "[D",
+                    8 => "[B",
+                    9 => "[S",
+                    10 => "[I",
+                    11 => "[J",
+                    _ => {
+                        return Err(RuntimeError::InvalidClassFile(format!(
+                            "invalid newarray type {atype}"
+                        )));
+                    }
+                };
+                self.new_array(descriptor, 1)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::Anewarray(class_index) => {
+                let class_name = frame
+                    .constant_pool
+                    .get_class_name(class_index)
+                    .ok_or(RuntimeError::InvalidConstantPoolReference(class_index))?;
+                let descriptor = if class_name.starts_with('[') {
+                    format!("[{class_name}")
+                } else {
+                    format!("[L{class_name};")
+                };
+                self.new_array(&descriptor, 1)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::Multianewarray(class_index, dimensions) => {
+                // The class entry already names the full array type, e.g. [[I
+                let descriptor = frame
+                    .constant_pool
+                    .get_class_name(class_index)
+                    .ok_or(RuntimeError::InvalidConstantPoolReference(class_index))?
+                    .to_string();
+                self.new_array(&descriptor, dimensions as usize)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.pc += 1;
+            }
+            JvmInstruction::Checkcast(class_index) | JvmInstruction::Instanceof(class_index) => {
+                let target = frame
+                    .constant_pool
+                    .get_class_name(class_index)
+                    .ok_or(RuntimeError::InvalidConstantPoolReference(class_index))?
+                    .to_string();
+                let value = frame.pop()?;
+                let is_instance = match &value {
+                    JvmValue::Reference(Some(object_id)) => {
+                        let class_name = self
+                            .heap
+                            .get(object_id)
+                            .map(|object| object.class_name.clone())
+                            .ok_or(RuntimeError::InvalidStackState)?;
+                        if !self.is_assignable(&class_name, &target)? {
+                            if matches!(instruction, JvmInstruction::Checkcast(_)) {
+                                return Err(self.throw_exception(
+                                    "java/lang/ClassCastException",
+                                    Some(format!(
+                                        "class {} cannot be cast to class {}",
+                                        class_name.replace('/', "."),
+                                        target.replace('/', ".")
+                                    )),
+                                ));
                             }
+                            false
+                        } else {
+                            true
                         }
                     }
-                }
+                    JvmValue::Reference(None) => false,
+                    _ => return Err(RuntimeError::InvalidStackState),
+                };
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                // checkcast leaves the reference, including null, in place
+                frame.operand_stack.push(match instruction {
+                    JvmInstruction::Checkcast(_) => value,
+                    _ => JvmValue::Int(is_instance as i32),
+                });
+                frame.pc += 1;
             }
-            ResolvedMethod::PrintStreamPrintlnChar => {
-                // println(C)V
-                let value = frame
+            JvmInstruction::Athrow => {
+                let exception_ref = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                let printstream_ref = frame
+                let JvmValue::Reference(reference) = exception_ref else {
+                    return Err(RuntimeError::InvalidStackState);
+                };
+                let exception_id = reference.ok_or(RuntimeError::NullPointer)?;
+                return Err(RuntimeError::Exception(exception_id));
+            }
+            JvmInstruction::Arraylength => {
+                let array_ref = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let JvmValue::Reference(Some(obj_id)) = printstream_ref {
-                    if let Some(obj) = self.heap.get(&obj_id) {
-                        if let Some(JvmValue::Int(is_stderr)) = obj.fields.get("is_stderr") {
-                            let char_value = match value {
-                                JvmValue::Char(c) => c as u8 as char,
-                                JvmValue::Int(i) => i as u8 as char,
-                                _ => return Err(RuntimeError::InvalidStackState),
-                            };
-
-                            if *is_stderr == 1 {
-                                eprintln!("{char_value}");
-                            } else {
-                                println!("{char_value}");
-                            }
-                        }
-                    }
-                }
-            }
-            ResolvedMethod::MathRandom => {
-                // Math.random()D - This shouldn't be called in invoke_virtual_method
-                // but we need to handle it for completeness
-                return Err(RuntimeError::InvalidStackState);
+                let JvmValue::Reference(reference) = array_ref else {
+                    return Err(RuntimeError::InvalidStackState);
+                };
+                let array_id = reference.ok_or(RuntimeError::NullPointer)?;
+                let length = self
+                    .array_data
+                    .get(&array_id)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .len();
+                frame.operand_stack.push(JvmValue::Int(length as i32));
+                frame.pc += 1;
             }
-            ResolvedMethod::MathMaxInt => {
-                // Math.max(II)I - This shouldn't be called in invoke_virtual_method
-                // Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Iaload
+            | JvmInstruction::Laload
+            | JvmInstruction::Faload
+            | JvmInstruction::Daload
+            | JvmInstruction::Aaload
+            | JvmInstruction::Baload
+            | JvmInstruction::Caload
+            | JvmInstruction::Saload => {
+                self.load_array_element()?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathMinInt => {
-                // Math.min(II)I - This shouldn't be called in invoke_virtual_method
-                // Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Iastore
+            | JvmInstruction::Lastore
+            | JvmInstruction::Fastore
+            | JvmInstruction::Dastore
+            | JvmInstruction::Aastore
+            | JvmInstruction::Bastore
+            | JvmInstruction::Castore
+            | JvmInstruction::Sastore => {
+                self.store_array_element(&instruction)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathMaxDouble => {
-                // Math.max(DD)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+
+            JvmInstruction::Getstatic(field_ref) => {
+                // User class fields first, then the library fields the VM provides
+                let field_value = match self.access_static_field(field_ref, None)? {
+                    Some(value) => value,
+                    None => self.resolve_static_field(field_ref)?,
+                };
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.operand_stack.push(field_value);
+                frame.pc += 1;
             }
-            ResolvedMethod::MathMinDouble => {
-                // Math.min(DD)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+
+            JvmInstruction::Putstatic(field_ref) => {
+                let value = frame
+                    .operand_stack
+                    .pop()
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                if self.access_static_field(field_ref, Some(value))?.is_none() {
+                    let (class_name, field_name, _) = self.member_reference(field_ref)?;
+                    return Err(RuntimeError::NoSuchField(format!(
+                        "{class_name}.{field_name}"
+                    )));
+                }
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathAbs => {
-                // Math.abs(I)I - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Getfield(field_ref) => {
+                self.get_field(field_ref)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathAbsDouble => {
-                // Math.abs(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Putfield(field_ref) => {
+                self.put_field(field_ref)?;
+                let frame = self
+                    .frames
+                    .last_mut()
+                    .ok_or(RuntimeError::CallStackUnderflow)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathPow => {
-                // Math.pow(DD)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+
+            JvmInstruction::Invokevirtual(method_ref)
+            | JvmInstruction::Invokeinterface(method_ref, _) => {
+                let depth = self.frames.len();
+                self.invoke_virtual_method(method_ref)?;
+                self.finish_invoke(depth)?;
             }
-            ResolvedMethod::MathSqrt => {
-                // Math.sqrt(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+
+            JvmInstruction::Invokespecial(method_ref) => {
+                // Handle constructor calls and private methods
+                let depth = self.frames.len();
+                self.invoke_special_method(method_ref)?;
+                self.finish_invoke(depth)?;
             }
-            ResolvedMethod::MathFloor => {
-                // Math.floor(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+
+            JvmInstruction::Invokestatic(method_ref) => {
+                let depth = self.frames.len();
+                self.invoke_static_method(method_ref)?;
+                self.finish_invoke(depth)?;
             }
-            ResolvedMethod::MathCeil => {
-                // Math.ceil(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+
+            JvmInstruction::Invokedynamic(call_site_index) => {
+                let depth = self.frames.len();
+                self.invoke_dynamic_method(call_site_index)?;
+                self.finish_invoke(depth)?;
             }
-            ResolvedMethod::MathRound => {
-                // Math.round(D)J - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+
+            JvmInstruction::Dconst0 => {
+                frame.operand_stack.push(JvmValue::Double(0.0));
+                frame.pc += 1;
             }
-            ResolvedMethod::MathSin => {
-                // Math.sin(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Dconst1 => {
+                frame.operand_stack.push(JvmValue::Double(1.0));
+                frame.pc += 1;
             }
-            ResolvedMethod::MathCos => {
-                // Math.cos(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+
+            // ローカル変数操作
+            JvmInstruction::Iload(index) => {
+                let value = frame
+                    .locals
+                    .get(index as usize)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::MathTan => {
-                // Math.tan(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Iload0 => {
+                let value = frame
+                    .locals
+                    .first()
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::MathLog => {
-                // Math.log(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Iload1 => {
+                let value = frame
+                    .locals
+                    .get(1)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::MathExp => {
-                // Math.exp(D)D - Math methods are static
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Iload2 => {
+                let value = frame
+                    .locals
+                    .get(2)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-
-            // String methods (these are virtual)
-            ResolvedMethod::StringLength => {
-                // String.length()I
-                let string_ref = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let JvmValue::Reference(Some(string_id)) = string_ref {
-                    if let Some(string_value) = self.string_data.get(&string_id) {
-                        frame
-                            .operand_stack
-                            .push(JvmValue::Int(string_value.len() as i32));
-                    } else {
-                        return Err(RuntimeError::InvalidStackState);
-                    }
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
-                }
+            JvmInstruction::Iload3 => {
+                let value = frame
+                    .locals
+                    .get(3)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-
-            // For static methods that shouldn't be called via invokevirtual
-            ResolvedMethod::IntegerParseInt
-            | ResolvedMethod::IntegerToString
-            | ResolvedMethod::IntegerValueOf
-            | ResolvedMethod::DoubleParseDouble
-            | ResolvedMethod::DoubleToString
-            | ResolvedMethod::DoubleValueOf
-            | ResolvedMethod::BooleanParseBoolean
-            | ResolvedMethod::BooleanToString
-            | ResolvedMethod::BooleanValueOf
-            | ResolvedMethod::CharacterIsDigit
-            | ResolvedMethod::CharacterIsLetter
-            | ResolvedMethod::CharacterToUpperCase
-            | ResolvedMethod::CharacterToLowerCase => {
-                // These are static methods
-                return Err(RuntimeError::InvalidStackState);
-            }
-
-            // TODO: Implement other String methods
-            ResolvedMethod::StringCharAt
-            | ResolvedMethod::StringSubstring
-            | ResolvedMethod::StringIndexOf
-            | ResolvedMethod::StringToUpperCase
-            | ResolvedMethod::StringToLowerCase
-            | ResolvedMethod::StringTrim
-            | ResolvedMethod::StringEquals
-            | ResolvedMethod::StringConcat => {
-                // TODO: Implement these String methods
-                return Err(RuntimeError::InvalidStackState);
-            }
-
-            ResolvedMethod::StringBuilderAppendString => {
-                // StringBuilder.append(String) - pop string and StringBuilder ref, return StringBuilder ref
-                let _string_value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let sb_ref = frame
+            JvmInstruction::Istore(index) => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-
-                // For simplicity, just return the StringBuilder reference (method chaining)
-                frame.operand_stack.push(sb_ref);
+                if (index as usize) >= frame.locals.len() {
+                    frame.locals.resize(index as usize + 1, JvmValue::Int(0));
+                }
+                frame.locals[index as usize] = value;
+                frame.pc += 1;
             }
-
-            ResolvedMethod::StringBuilderAppendInt => {
-                // StringBuilder.append(int) - pop int and StringBuilder ref, return StringBuilder ref
-                let _int_value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let sb_ref = frame
+            JvmInstruction::Istore0 => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-
-                frame.operand_stack.push(sb_ref);
+                if frame.locals.is_empty() {
+                    frame.locals.resize(1, JvmValue::Int(0));
+                }
+                frame.locals[0] = value;
+                frame.pc += 1;
             }
-
-            ResolvedMethod::StringBuilderAppendDouble => {
-                // StringBuilder.append(double) - pop double and StringBuilder ref, return StringBuilder ref
-                let _double_value = frame
+            JvmInstruction::Istore1 => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                let sb_ref = frame
+                if frame.locals.len() <= 1 {
+                    frame.locals.resize(2, JvmValue::Int(0));
+                }
+                frame.locals[1] = value;
+                frame.pc += 1;
+            }
+            JvmInstruction::Istore2 => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-
-                frame.operand_stack.push(sb_ref);
+                if frame.locals.len() <= 2 {
+                    frame.locals.resize(3, JvmValue::Int(0));
+                }
+                frame.locals[2] = value;
+                frame.pc += 1;
             }
-
-            ResolvedMethod::StringBuilderToString => {
-                // StringBuilder.toString() - pop StringBuilder ref, return String
-                let _sb_ref = frame
+            JvmInstruction::Istore3 => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-
-                // For simplicity, return a placeholder string reference
-                frame.operand_stack.push(JvmValue::Reference(Some(1)));
-            }
-
-            ResolvedMethod::Unknown => {
-                // Fallback for unknown methods - attempt old numeric resolution
-                match method_ref {
-                    33 => {
-                        // println(I)V
-                        let value = frame
-                            .operand_stack
-                            .pop()
-                            .ok_or(RuntimeError::StackUnderflow)?
-                            .as_int()?;
-                        let printstream_ref = frame
-                            .operand_stack
-                            .pop()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-
-                        if let JvmValue::Reference(Some(obj_id)) = printstream_ref {
-                            if let Some(obj) = self.heap.get(&obj_id) {
-                                if let Some(JvmValue::Int(is_stderr)) = obj.fields.get("is_stderr")
-                                {
-                                    if *is_stderr == 1 {
-                                        eprintln!("{value}");
-                                    } else {
-                                        println!("{value}");
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    34 => {
-                        // print(Ljava/lang/String;)V
-                        let string_ref = frame
-                            .operand_stack
-                            .pop()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-                        let printstream_ref = frame
-                            .operand_stack
-                            .pop()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-
-                        if let (
-                            JvmValue::Reference(Some(string_id)),
-                            JvmValue::Reference(Some(stream_id)),
-                        ) = (string_ref, printstream_ref)
-                        {
-                            if let Some(string_value) = self.string_data.get(&string_id) {
-                                if let Some(stream_obj) = self.heap.get(&stream_id) {
-                                    if let Some(JvmValue::Int(is_stderr)) =
-                                        stream_obj.fields.get("is_stderr")
-                                    {
-                                        if *is_stderr == 1 {
-                                            eprint!("{string_value}");
-                                        } else {
-                                            print!("{string_value}");
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    35 => {
-                        // println(Ljava/lang/String;)V
-                        let string_ref = frame
-                            .operand_stack
-                            .pop()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-                        let printstream_ref = frame
-                            .operand_stack
-                            .pop()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-
-                        if let (
-                            JvmValue::Reference(Some(string_id)),
-                            JvmValue::Reference(Some(stream_id)),
-                        ) = (string_ref, printstream_ref)
-                        {
-                            if let Some(string_value) = self.string_data.get(&string_id) {
-                                if let Some(stream_obj) = self.heap.get(&stream_id) {
-                                    if let Some(JvmValue::Int(is_stderr)) =
-                                        stream_obj.fields.get("is_stderr")
-                                    {
-                                        if *is_stderr == 1 {
-                                            eprintln!("{string_value}");
-                                        } else {
-                                            println!("{string_value}");
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    _ => return Err(RuntimeError::InvalidStackState),
+                if frame.locals.len() <= 3 {
+                    frame.locals.resize(4, JvmValue::Int(0));
                 }
+                frame.locals[3] = value;
+                frame.pc += 1;
             }
-        }
-
-        Ok(())
-    }
 
-    fn invoke_special_method(&mut self, _method_ref: u16) -> Result<(), RuntimeError> {
-        // Handle constructor calls and private methods
-        // For now, just consume the object reference and any parameters
-        let frame = self
-            .frames
-            .last_mut()
-            .ok_or(RuntimeError::CallStackUnderflow)?;
-
-        // For StringBuilder constructor, just consume the object reference
-        // This is a simplified implementation
-        if !frame.operand_stack.is_empty() {
-            frame.operand_stack.pop(); // Pop the object reference
-        }
-
-        Ok(())
-    }
-
-    fn invoke_static_method(&mut self, method_ref: u16) -> Result<(), RuntimeError> {
-        // First try to resolve user-defined methods from the current class
-        if let Some(method_info) = self.resolve_user_method(method_ref)? {
-            return self.invoke_user_defined_method(&method_info);
-        }
-
-        // Then try to resolve the method from the constant pool
-        let method_info = self.resolve_method_reference(method_ref)?;
-
-        match method_info {
-            ResolvedMethod::MathRandom => {
-                // Math.random()D
-                use rand::Rng;
-                let mut rng = rand::rng();
-                let random_value = rng.random::<f64>();
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame.operand_stack.push(JvmValue::Double(random_value));
+            // Reference local variable operations
+            JvmInstruction::Aload(index) => {
+                let value = frame
+                    .locals
+                    .get(index as usize)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::MathMaxInt => {
-                // Math.max(II)I
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let result = if a > b { a } else { b };
-                frame.operand_stack.push(JvmValue::Int(result));
+            JvmInstruction::Aload0 => {
+                let value = frame
+                    .locals
+                    .first()
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::MathMinInt => {
-                // Math.min(II)I
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let b = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let a = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                let result = if a < b { a } else { b };
-                frame.operand_stack.push(JvmValue::Int(result));
+            JvmInstruction::Aload1 => {
+                let value = frame
+                    .locals
+                    .get(1)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::MathMaxDouble => {
-                // Math.max(DD)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let b = frame
+            JvmInstruction::Aload2 => {
+                let value = frame
+                    .locals
+                    .get(2)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Aload3 => {
+                let value = frame
+                    .locals
+                    .get(3)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Astore(index) => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                let a = frame
+                if (index as usize) >= frame.locals.len() {
+                    frame
+                        .locals
+                        .resize(index as usize + 1, JvmValue::Reference(None));
+                }
+                frame.locals[index as usize] = value;
+                frame.pc += 1;
+            }
+            JvmInstruction::Astore0 => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                if let (JvmValue::Double(a_val), JvmValue::Double(b_val)) = (a, b) {
-                    let result = if a_val > b_val { a_val } else { b_val };
-                    frame.operand_stack.push(JvmValue::Double(result));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
+                if frame.locals.is_empty() {
+                    frame.locals.resize(1, JvmValue::Reference(None));
                 }
+                frame.locals[0] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathMinDouble => {
-                // Math.min(DD)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let b = frame
+            JvmInstruction::Astore1 => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                let a = frame
+                if frame.locals.len() <= 1 {
+                    frame.locals.resize(2, JvmValue::Reference(None));
+                }
+                frame.locals[1] = value;
+                frame.pc += 1;
+            }
+            JvmInstruction::Astore2 => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                if let (JvmValue::Double(a_val), JvmValue::Double(b_val)) = (a, b) {
-                    let result = if a_val < b_val { a_val } else { b_val };
-                    frame.operand_stack.push(JvmValue::Double(result));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
+                if frame.locals.len() <= 2 {
+                    frame.locals.resize(3, JvmValue::Reference(None));
                 }
+                frame.locals[2] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathAbs => {
-                // Math.abs(I)I
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
+            JvmInstruction::Astore3 => {
                 let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-                frame.operand_stack.push(JvmValue::Int(value.abs()));
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                if frame.locals.len() <= 3 {
+                    frame.locals.resize(4, JvmValue::Reference(None));
+                }
+                frame.locals[3] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathAbsDouble => {
-                // Math.abs(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
+
+            // Double local variable operations
+            JvmInstruction::Dload(index) => {
+                let value = frame
+                    .locals
+                    .get(index as usize)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Dload0 => {
+                let value = frame
+                    .locals
+                    .first()
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Dload1 => {
+                let value = frame
+                    .locals
+                    .get(1)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Dload2 => {
+                let value = frame
+                    .locals
+                    .get(2)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Dload3 => {
+                let value = frame
+                    .locals
+                    .get(3)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
+            }
+            JvmInstruction::Dstore(index) => {
                 let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                if let JvmValue::Double(d_val) = value {
-                    frame.operand_stack.push(JvmValue::Double(d_val.abs()));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
+                if (index as usize) >= frame.locals.len() {
+                    frame
+                        .locals
+                        .resize(index as usize + 1, JvmValue::Double(0.0));
                 }
+                frame.locals[index as usize] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathPow => {
-                // Math.pow(DD)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let exponent = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-                let base = frame
+            JvmInstruction::Dstore0 => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                if let (JvmValue::Double(base_val), JvmValue::Double(exp_val)) = (base, exponent) {
-                    frame
-                        .operand_stack
-                        .push(JvmValue::Double(base_val.powf(exp_val)));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
+                if frame.locals.is_empty() {
+                    frame.locals.resize(1, JvmValue::Double(0.0));
                 }
+                frame.locals[0] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathSqrt => {
-                // Math.sqrt(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
+            JvmInstruction::Dstore1 => {
                 let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-                if let JvmValue::Double(d_val) = value {
-                    frame.operand_stack.push(JvmValue::Double(d_val.sqrt()));
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
+                if frame.locals.len() <= 1 {
+                    frame.locals.resize(2, JvmValue::Double(0.0));
                 }
+                frame.locals[1] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathFloor => {
-                // Math.floor(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
+            JvmInstruction::Dstore2 => {
                 let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-                frame.operand_stack.push(JvmValue::Double(value.floor()));
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                if frame.locals.len() <= 2 {
+                    frame.locals.resize(3, JvmValue::Double(0.0));
+                }
+                frame.locals[2] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathCeil => {
-                // Math.ceil(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
+            JvmInstruction::Dstore3 => {
                 let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-                frame.operand_stack.push(JvmValue::Double(value.ceil()));
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                if frame.locals.len() <= 3 {
+                    frame.locals.resize(4, JvmValue::Double(0.0));
+                }
+                frame.locals[3] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathRound => {
-                // Math.round(D)J
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-                frame
-                    .operand_stack
-                    .push(JvmValue::Long(value.round() as i64));
+
+            // Float local variable operations
+            JvmInstruction::Fload(index) => {
+                frame.load_local(index as usize)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathSin => {
-                // Math.sin(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-                frame.operand_stack.push(JvmValue::Double(value.sin()));
+            JvmInstruction::Fload0
+            | JvmInstruction::Fload1
+            | JvmInstruction::Fload2
+            | JvmInstruction::Fload3 => {
+                let index = match instruction {
+                    JvmInstruction::Fload0 => 0,
+                    JvmInstruction::Fload1 => 1,
+                    JvmInstruction::Fload2 => 2,
+                    _ => 3,
+                };
+                frame.load_local(index)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathCos => {
-                // Math.cos(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-                frame.operand_stack.push(JvmValue::Double(value.cos()));
+            JvmInstruction::Fstore(index) => {
+                frame.store_local(index as usize)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathTan => {
-                // Math.tan(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-                frame.operand_stack.push(JvmValue::Double(value.tan()));
+            JvmInstruction::Fstore0
+            | JvmInstruction::Fstore1
+            | JvmInstruction::Fstore2
+            | JvmInstruction::Fstore3 => {
+                let index = match instruction {
+                    JvmInstruction::Fstore0 => 0,
+                    JvmInstruction::Fstore1 => 1,
+                    JvmInstruction::Fstore2 => 2,
+                    _ => 3,
+                };
+                frame.store_local(index)?;
+                frame.pc += 1;
             }
-            ResolvedMethod::MathLog => {
-                // Math.log(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
+
+            // Long local variable operations
+            JvmInstruction::Lload(index) => {
                 let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-                frame.operand_stack.push(JvmValue::Double(value.ln()));
+                    .locals
+                    .get(index as usize)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::MathExp => {
-                // Math.exp(D)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
+            JvmInstruction::Lload0 => {
                 let value = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-                frame.operand_stack.push(JvmValue::Double(value.exp()));
-            }
-
-            // Integer wrapper methods
-            ResolvedMethod::IntegerParseInt => {
-                // Integer.parseInt(String)I
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let string_ref = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let JvmValue::Reference(Some(string_id)) = string_ref {
-                    if let Some(string_value) = self.string_data.get(&string_id) {
-                        match string_value.parse::<i32>() {
-                            Ok(int_val) => frame.operand_stack.push(JvmValue::Int(int_val)),
-                            Err(_) => return Err(RuntimeError::InvalidStackState), // NumberFormatException
-                        }
-                    } else {
-                        return Err(RuntimeError::InvalidStackState);
-                    }
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
-                }
+                    .locals
+                    .first()
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::IntegerToString => {
-                // Integer.toString(I)String
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let int_val = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_int()?;
-
-                let string_id = self.create_string_object(int_val.to_string());
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame
-                    .operand_stack
-                    .push(JvmValue::Reference(Some(string_id)));
+            JvmInstruction::Lload1 => {
+                let value = frame
+                    .locals
+                    .get(1)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-
-            // Double wrapper methods
-            ResolvedMethod::DoubleParseDouble => {
-                // Double.parseDouble(String)D
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let string_ref = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let JvmValue::Reference(Some(string_id)) = string_ref {
-                    if let Some(string_value) = self.string_data.get(&string_id) {
-                        match string_value.parse::<f64>() {
-                            Ok(double_val) => {
-                                frame.operand_stack.push(JvmValue::Double(double_val))
-                            }
-                            Err(_) => return Err(RuntimeError::InvalidStackState), // NumberFormatException
-                        }
-                    } else {
-                        return Err(RuntimeError::InvalidStackState);
-                    }
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
-                }
+            JvmInstruction::Lload2 => {
+                let value = frame
+                    .locals
+                    .get(2)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-            ResolvedMethod::DoubleToString => {
-                // Double.toString(D)String
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let double_val = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_double()?;
-
-                let string_id = self.create_string_object(double_val.to_string());
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame
-                    .operand_stack
-                    .push(JvmValue::Reference(Some(string_id)));
+            JvmInstruction::Lload3 => {
+                let value = frame
+                    .locals
+                    .get(3)
+                    .ok_or(RuntimeError::InvalidStackState)?
+                    .clone();
+                frame.operand_stack.push(value);
+                frame.pc += 1;
             }
-
-            // Boolean wrapper methods
-            ResolvedMethod::BooleanParseBoolean => {
-                // Boolean.parseBoolean(String)Z
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let string_ref = frame
+            JvmInstruction::Lstore(index) => {
+                let value = frame
                     .operand_stack
                     .pop()
                     .ok_or(RuntimeError::StackUnderflow)?;
-
-                if let JvmValue::Reference(Some(string_id)) = string_ref {
-                    if let Some(string_value) = self.string_data.get(&string_id) {
-                        let bool_val = string_value.eq_ignore_ascii_case("true");
-                        frame.operand_stack.push(JvmValue::Boolean(bool_val));
-                    } else {
-                        return Err(RuntimeError::InvalidStackState);
-                    }
-                } else {
-                    return Err(RuntimeError::InvalidStackState);
+                if (index as usize) >= frame.locals.len() {
+                    frame.locals.resize(index as usize + 1, JvmValue::Long(0));
                 }
+                frame.locals[index as usize] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::BooleanToString => {
-                // Boolean.toString(Z)String
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let bool_val = frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_boolean()?;
-
-                let string_id = self.create_string_object(bool_val.to_string());
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                frame
-                    .operand_stack
-                    .push(JvmValue::Reference(Some(string_id)));
-            }
-
-            // Character methods
-            ResolvedMethod::CharacterIsDigit => {
-                // Character.isDigit(C)Z
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let char_val = frame
+            JvmInstruction::Lstore0 => {
+                let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_char()?;
-
-                let is_digit = (char_val as u8 as char).is_ascii_digit();
-                frame.operand_stack.push(JvmValue::Boolean(is_digit));
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                if frame.locals.is_empty() {
+                    frame.locals.resize(1, JvmValue::Long(0));
+                }
+                frame.locals[0] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::CharacterIsLetter => {
-                // Character.isLetter(C)Z
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let char_val = frame
+            JvmInstruction::Lstore1 => {
+                let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_char()?;
-
-                let is_letter = (char_val as u8 as char).is_ascii_alphabetic();
-                frame.operand_stack.push(JvmValue::Boolean(is_letter));
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                if frame.locals.len() <= 1 {
+                    frame.locals.resize(2, JvmValue::Long(0));
+                }
+                frame.locals[1] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::CharacterToUpperCase => {
-                // Character.toUpperCase(C)C
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let char_val = frame
+            JvmInstruction::Lstore2 => {
+                let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_char()?;
-
-                let upper_char = (char_val as u8 as char).to_ascii_uppercase();
-                frame.operand_stack.push(JvmValue::Char(upper_char as u16));
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                if frame.locals.len() <= 2 {
+                    frame.locals.resize(3, JvmValue::Long(0));
+                }
+                frame.locals[2] = value;
+                frame.pc += 1;
             }
-            ResolvedMethod::CharacterToLowerCase => {
-                // Character.toLowerCase(C)C
-                let frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                let char_val = frame
+            JvmInstruction::Lstore3 => {
+                let value = frame
                     .operand_stack
                     .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?
-                    .as_char()?;
-
-                let lower_char = (char_val as u8 as char).to_ascii_lowercase();
-                frame.operand_stack.push(JvmValue::Char(lower_char as u16));
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                if frame.locals.len() <= 3 {
+                    frame.locals.resize(4, JvmValue::Long(0));
+                }
+                frame.locals[3] = value;
+                frame.pc += 1;
             }
 
-            // TODO: Implement wrapper valueOf methods and String virtual methods
-            ResolvedMethod::IntegerValueOf
-            | ResolvedMethod::DoubleValueOf
-            | ResolvedMethod::BooleanValueOf => {
-                // TODO: These create wrapper objects, for now just return the primitive value
-                return Err(RuntimeError::InvalidStackState);
+            JvmInstruction::Nop => {
+                // No operation - just increment program counter
+                frame.pc += 1;
             }
-            ResolvedMethod::Unknown => {
-                // Try to resolve user-defined methods from the current class
-                if let Some(method_info) = self.resolve_user_method(method_ref)? {
-                    return self.invoke_user_defined_method(&method_info);
-                }
+        }
 
-                // Fallback for unknown methods - attempt old numeric resolution
-                match method_ref {
-                    36 => {
-                        // Math.random()D
-                        use rand::Rng;
-                        let mut rng = rand::rng();
-                        let random_value = rng.random::<f64>();
-                        let frame = self
-                            .frames
-                            .last_mut()
-                            .ok_or(RuntimeError::CallStackUnderflow)?;
-                        frame.operand_stack.push(JvmValue::Double(random_value));
-                    }
-                    _ => return Err(RuntimeError::InvalidStackState),
+        Ok(Completion::Next)
+    }
+
+    fn load_constant_from_pool(&mut self, index: u16) -> Result<JvmValue, RuntimeError> {
+        let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
+        let entries = frame.constant_pool.entries();
+
+        // JVM constant pool is 1-based, but our array is 0-based
+        let actual_index = (index - 1) as usize;
+        if actual_index >= entries.len() {
+            return Err(RuntimeError::InvalidStackState);
+        }
+
+        match &entries[actual_index] {
+            ConstantPoolEntry::Integer(i) => Ok(JvmValue::Int(*i)),
+            ConstantPoolEntry::Float(f) => Ok(JvmValue::Float(*f)),
+            ConstantPoolEntry::Long(l) => Ok(JvmValue::Long(*l)),
+            ConstantPoolEntry::Double(d) => Ok(JvmValue::Double(*d)),
+            ConstantPoolEntry::String(utf8_index) => {
+                let utf8_actual_index = (*utf8_index - 1) as usize;
+                if let ConstantPoolEntry::Utf8(s) = &entries[utf8_actual_index] {
+                    let object_id = self.create_string_object(s.clone())?;
+                    Ok(JvmValue::Reference(Some(object_id)))
+                } else {
+                    Err(RuntimeError::InvalidStackState)
                 }
             }
-            _ => {
-                // PrintStream methods shouldn't be called via invokestatic
-                return Err(RuntimeError::InvalidStackState);
-            }
+            _ => Err(RuntimeError::InvalidStackState),
         }
+    }
 
-        Ok(())
+    pub fn debug_state(&self) {
+        if self.verbose {
+            eprintln!("=== JVM Compatible VM State ===");
+            eprintln!("Steps: {}", self.steps);
+            eprintln!("Frames: {}", self.frames.len());
+
+            if let Some(frame) = self.frames.last() {
+                eprintln!("Current Frame:");
+                eprintln!("  PC: {}", frame.pc);
+                eprintln!("  Operand Stack: {:?}", frame.operand_stack);
+                eprintln!("  Locals: {:?}", frame.locals);
+            }
+
+            eprintln!("Heap Objects: {}", self.heap.len());
+            eprintln!("==============================");
+        }
     }
 
-    fn resolve_user_method(&self, method_ref: u16) -> Result<Option<MethodInfo>, RuntimeError> {
+    fn resolve_static_field(&mut self, field_ref: u16) -> Result<JvmValue, RuntimeError> {
         let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
         let entries = frame.constant_pool.entries();
 
         // JVM constant pool is 1-based, but our array is 0-based
-        let actual_index = (method_ref - 1) as usize;
+        let actual_index = (field_ref - 1) as usize;
         if actual_index >= entries.len() {
-            return Ok(None);
+            // Fallback to old numeric resolution
+            return self.resolve_static_field_numeric(field_ref);
         }
 
-        if let ConstantPoolEntry::Methodref(class_index, name_and_type_index) =
-            &entries[actual_index]
-        {
-            // Get class name
-            let class_actual_index = (*class_index - 1) as usize;
-            if let ConstantPoolEntry::Class(name_index) = &entries[class_actual_index] {
-                let name_actual_index = (*name_index - 1) as usize;
-                if let ConstantPoolEntry::Utf8(class_name) = &entries[name_actual_index] {
-                    // Check if this is the current class (not a standard library class)
-                    if !class_name.starts_with("java/") {
-                        // Get method name
-                        let name_and_type_actual_index = (*name_and_type_index - 1) as usize;
-                        if let ConstantPoolEntry::NameAndType(method_name_index, _desc_index) =
-                            &entries[name_and_type_actual_index]
-                        {
-                            let method_name_actual_index = (*method_name_index - 1) as usize;
-                            if let ConstantPoolEntry::Utf8(method_name) =
-                                &entries[method_name_actual_index]
-                            {
-                                // Look up the method in the current class
-                                if let Some(current_class) = &self.current_class {
-                                    if let Some(method_info) =
-                                        current_class.methods.get(method_name)
-                                    {
-                                        return Ok(Some(method_info.clone()));
-                                    }
-                                }
-                            }
+        match &entries[actual_index] {
+            ConstantPoolEntry::Fieldref(class_index, name_and_type_index) => {
+                // Get class name
+                let class_actual_index = (*class_index - 1) as usize;
+                let class_name =
+                    if let ConstantPoolEntry::Class(name_index) = &entries[class_actual_index] {
+                        let name_actual_index = (*name_index - 1) as usize;
+                        if let ConstantPoolEntry::Utf8(name) = &entries[name_actual_index] {
+                            name
+                        } else {
+                            return self.resolve_static_field_numeric(field_ref);
                         }
+                    } else {
+                        return self.resolve_static_field_numeric(field_ref);
+                    };
+
+                // Get field name
+                let name_and_type_actual_index = (*name_and_type_index - 1) as usize;
+                let field_name = if let ConstantPoolEntry::NameAndType(name_index, _desc_index) =
+                    &entries[name_and_type_actual_index]
+                {
+                    let name_actual_index = (*name_index - 1) as usize;
+                    if let ConstantPoolEntry::Utf8(name) = &entries[name_actual_index] {
+                        name
+                    } else {
+                        return self.resolve_static_field_numeric(field_ref);
+                    }
+                } else {
+                    return self.resolve_static_field_numeric(field_ref);
+                };
+
+                // Resolve based on class and field name
+                match (class_name.as_str(), field_name.as_str()) {
+                    ("java/lang/System", "out") => {
+                        let stdout_id = self.create_printstream_object("stdout".to_string());
+                        Ok(JvmValue::Reference(Some(stdout_id)))
+                    }
+                    ("java/lang/System", "err") => {
+                        let stderr_id = self.create_printstream_object("stderr".to_string());
+                        Ok(JvmValue::Reference(Some(stderr_id)))
+                    }
+                    // Kotlin's default generator, the companion object of `Random`
+                    ("kotlin/random/Random", "Default") => {
+                        self.new_instance("kotlin/random/Random$Default")
                     }
+                    _ => self.resolve_static_field_numeric(field_ref),
                 }
             }
+            _ => self.resolve_static_field_numeric(field_ref),
         }
+    }
 
-        Ok(None)
+    fn resolve_static_field_numeric(&mut self, field_ref: u16) -> Result<JvmValue, RuntimeError> {
+        // Fallback for numeric field references (for backward compatibility)
+        match field_ref {
+            31 => {
+                // System.out - create a PrintStream object reference
+                let stdout_id = self.create_printstream_object("stdout".to_string());
+                Ok(JvmValue::Reference(Some(stdout_id)))
+            }
+            32 => {
+                // System.err - create a PrintStream object reference
+                let stderr_id = self.create_printstream_object("stderr".to_string());
+                Ok(JvmValue::Reference(Some(stderr_id)))
+            }
+            _ => Err(RuntimeError::InvalidStackState),
+        }
     }
 
-    fn invoke_user_defined_method(&mut self, method_info: &MethodInfo) -> Result<(), RuntimeError> {
-        // Get arguments from the operand stack
-        let current_frame = self
-            .frames
-            .last_mut()
-            .ok_or(RuntimeError::CallStackUnderflow)?;
+    fn create_printstream_object(&mut self, stream_type: String) -> usize {
+        let object_id = self.next_object_id;
+        self.next_object_id += 1;
 
-        // Parse method descriptor to determine parameter count
-        let param_count = count_method_parameters(&method_info.descriptor);
+        let mut fields = HashMap::new();
+        fields.insert("type".to_string(), JvmValue::Reference(None));
 
-        // Pop arguments from the current frame's operand stack
-        let mut args = Vec::new();
-        for _ in 0..param_count {
-            args.push(
-                current_frame
-                    .operand_stack
-                    .pop()
-                    .ok_or(RuntimeError::StackUnderflow)?,
-            );
+        let printstream_object = JvmObject {
+            class_name: "java/io/PrintStream".to_string(),
+            fields,
+        };
+
+        self.heap.insert(object_id, printstream_object);
+
+        // Store the stream type separately for our implementation
+        if stream_type == "stderr" {
+            self.heap
+                .get_mut(&object_id)
+                .unwrap()
+                .fields
+                .insert("is_stderr".to_string(), JvmValue::Int(1));
+        } else {
+            self.heap
+                .get_mut(&object_id)
+                .unwrap()
+                .fields
+                .insert("is_stderr".to_string(), JvmValue::Int(0));
         }
-        args.reverse(); // Arguments are popped in reverse order
 
-        // Create a new frame for the method
-        let mut new_frame = MethodFrame {
-            locals: vec![JvmValue::Int(0); method_info.max_locals],
-            operand_stack: Vec::new(),
-            constant_pool: current_frame.constant_pool.clone(),
-            pc: 0,
-            bytecode: method_info.bytecode.clone(),
+        object_id
+    }
+
+    fn invoke_virtual_method(&mut self, method_ref: u16) -> Result<(), RuntimeError> {
+        // Dispatch on the runtime class of the receiver
+        let (class_name, name, descriptor) = self.member_reference(method_ref)?;
+        let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
+        let receiver_index = frame
+            .operand_stack
+            .len()
+            .checked_sub(method_parameter_types(&descriptor).len() + 1)
+            .ok_or(RuntimeError::StackUnderflow)?;
+        let runtime_class = match &frame.operand_stack[receiver_index] {
+            JvmValue::Reference(Some(object_id)) => self
+                .heap
+                .get(object_id)
+                .map_or_else(|| class_name.clone(), |object| object.class_name.clone()),
+            JvmValue::Reference(None) => return Err(RuntimeError::NullPointer),
+            _ => class_name.clone(),
+        };
+        if self.invoke_resolved_method(&runtime_class, &name, &descriptor, true)?
+            || self.invoke_throwable_method(&name, &descriptor)?
+        {
+            return Ok(());
+        }
+        let Some(native) = self.virtual_native(&runtime_class, &class_name, &name, &descriptor)
+        else {
+            return Err(RuntimeError::NoSuchMethod(format!(
+                "{class_name}.{name}{descriptor}"
+            )));
         };
+        let arguments = self.pop_arguments(&descriptor, true)?;
+        self.call_native(native, &arguments)
+    }
 
-        // Set up local variables with arguments
-        for (i, arg) in args.into_iter().enumerate() {
-            if i < new_frame.locals.len() {
-                new_frame.locals[i] = arg;
-            }
+    fn invoke_special_method(&mut self, method_ref: u16) -> Result<(), RuntimeError> {
+        // Constructors, private methods and super calls bind to the named
+        // class without virtual dispatch
+        let (class_name, name, descriptor) = self.member_reference(method_ref)?;
+        if self.invoke_resolved_method(&class_name, &name, &descriptor, true)?
+            || self.invoke_native_method(&class_name, &name, &descriptor, true)?
+        {
+            return Ok(());
         }
 
-        // Push the new frame
-        self.frames.push(new_frame);
+        // Other library constructors such as Object.<init> have no observable
+        // effect here, so just consume the receiver and arguments, keeping
+        // only the message passed to a Throwable constructor
+        let arguments = self.pop_arguments(&descriptor, true)?;
+        if name == "<init>"
+            && let [JvmValue::Reference(Some(object_id)), message, ..] = arguments.as_slice()
+            && self.stack_traces.contains_key(object_id)
+            && method_parameter_types(&descriptor).first() == Some(&"Ljava/lang/String;")
+            && let Some(exception) = self.heap.get_mut(object_id)
+        {
+            exception
+                .fields
+                .insert("detailMessage".to_string(), message.clone());
+        }
+        Ok(())
+    }
 
-        // Execute the method until it returns
-        while self.frames.len() > 1 {
-            // Keep the original frame
-            if self.steps >= self.max_steps {
-                return Err(RuntimeError::InvalidStackState);
-            }
+    /// Library methods of `java.lang.Throwable` called on an exception object.
+    /// Returns `false` when the receiver is not a throwable.
+    fn invoke_throwable_method(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<bool, RuntimeError> {
+        let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
+        let Some(JvmValue::Reference(Some(object_id))) = frame.operand_stack.last() else {
+            return Ok(false);
+        };
+        let object_id = *object_id;
+        if !self.stack_traces.contains_key(&object_id) {
+            return Ok(false);
+        }
 
-            let result = self.execute_single_instruction()?;
-            self.steps += 1;
+        let result = match (name, descriptor) {
+            ("getMessage", "()Ljava/lang/String;")
+            | ("getLocalizedMessage", "()Ljava/lang/String;") => self
+                .heap
+                .get(&object_id)
+                .and_then(|object| object.fields.get("detailMessage"))
+                .cloned()
+                .unwrap_or(JvmValue::Reference(None)),
+            ("toString", "()Ljava/lang/String;") => {
+                let description = self.value_to_java_string(
+                    &JvmValue::Reference(Some(object_id)),
+                    "Ljava/lang/Throwable;",
+                );
+                JvmValue::Reference(Some(self.create_string_object(description)?))
+            }
+            _ => return Ok(false),
+        };
 
-            // If the method returned a value, push it to the caller's stack
-            if let Some(return_value) = result {
-                let caller_frame = self
-                    .frames
-                    .last_mut()
-                    .ok_or(RuntimeError::CallStackUnderflow)?;
-                caller_frame.operand_stack.push(return_value);
-                break;
+        let frame = self
+            .frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?;
+        frame.operand_stack.pop();
+        frame.operand_stack.push(result);
+        Ok(true)
+    }
+
+    fn invoke_static_method(&mut self, method_ref: u16) -> Result<(), RuntimeError> {
+        // Natives come first, so host callbacks work even when a stub class
+        // declaring them is on the classpath
+        let (class_name, name, descriptor) = self.member_reference(method_ref)?;
+        if self.invoke_native_method(&class_name, &name, &descriptor, false)?
+            || self.invoke_resolved_method(&class_name, &name, &descriptor, false)?
+        {
+            return Ok(());
+        }
+        Err(RuntimeError::NoSuchMethod(format!(
+            "{class_name}.{name}{descriptor}"
+        )))
+    }
+
+    fn invoke_dynamic_method(&mut self, call_site_index: u16) -> Result<(), RuntimeError> {
+        let frame = self.frames.last().ok_or(RuntimeError::CallStackUnderflow)?;
+        let constant_pool = &frame.constant_pool;
+
+        let Some(ConstantPoolEntry::InvokeDynamic(bootstrap_index, name_and_type_index)) =
+            constant_pool.get(call_site_index)
+        else {
+            return Err(RuntimeError::InvalidConstantPoolReference(call_site_index));
+        };
+        let Some(ConstantPoolEntry::NameAndType(_, descriptor_index)) =
+            constant_pool.get(*name_and_type_index)
+        else {
+            return Err(RuntimeError::InvalidConstantPoolReference(
+                *name_and_type_index,
+            ));
+        };
+        let descriptor = constant_pool
+            .get_utf8(*descriptor_index)
+            .ok_or(RuntimeError::InvalidConstantPoolReference(
+                *descriptor_index,
+            ))?
+            .to_string();
+
+        let class = frame
+            .class_name
+            .as_ref()
+            .and_then(|class_name| self.class_loader.loaded_class(class_name));
+        let bootstrap_method = class
+            .as_ref()
+            .and_then(|class| class.bootstrap_methods.get(*bootstrap_index as usize))
+            .ok_or_else(|| {
+                RuntimeError::InvalidClassFile(format!(
+                    "bootstrap method {bootstrap_index} is not defined"
+                ))
+            })?;
+        let (class_name, method_name) =
+            resolve_method_handle(constant_pool, bootstrap_method.bootstrap_method_ref)?;
+        if class_name != "java/lang/invoke/StringConcatFactory" {
+            return Err(RuntimeError::UnsupportedBootstrapMethod(format!(
+                "{class_name}.{method_name}"
+            )));
+        }
+
+        // makeConcat takes no static arguments and simply joins every
+        // dynamic argument; makeConcatWithConstants describes the result
+        // with a recipe in which \u{1} marks the next dynamic argument and
+        // \u{2} the next static constant
+        let parameter_types = method_parameter_types(&descriptor);
+        let recipe = match method_name.as_str() {
+            "makeConcat" => "\u{1}".repeat(parameter_types.len()),
+            "makeConcatWithConstants" => {
+                let recipe_index =
+                    *bootstrap_method
+                        .bootstrap_arguments
+                        .first()
+                        .ok_or_else(|| {
+                            RuntimeError::InvalidClassFile(
+                                "missing concatenation recipe".to_string(),
+                            )
+                        })?;
+                constant_to_java_string(constant_pool, recipe_index)?
+            }
+            _ => {
+                return Err(RuntimeError::UnsupportedBootstrapMethod(format!(
+                    "{class_name}.{method_name}"
+                )));
+            }
+        };
+        let constants = bootstrap_method
+            .bootstrap_arguments
+            .iter()
+            .skip(1)
+            .map(|&index| constant_to_java_string(constant_pool, index))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let frame = self
+            .frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?;
+        if frame.operand_stack.len() < parameter_types.len() {
+            return Err(RuntimeError::StackUnderflow);
+        }
+        let arguments = frame
+            .operand_stack
+            .split_off(frame.operand_stack.len() - parameter_types.len());
+
+        let mut arguments = parameter_types.iter().zip(arguments);
+        let mut constants = constants.into_iter();
+        let mut result = String::new();
+        for ch in recipe.chars() {
+            match ch {
+                '\u{1}' => {
+                    let (parameter_type, value) =
+                        arguments.next().ok_or(RuntimeError::StackUnderflow)?;
+                    let text = self.string_value_of(&value, parameter_type)?;
+                    self.check_string_growth(result.len() + text.len())?;
+                    result
+                        .try_reserve(text.len())
+                        .map_err(|_| RuntimeError::OutOfMemory)?;
+                    result.push_str(&text);
+                }
+                '\u{2}' => {
+                    let constant = constants.next().ok_or_else(|| {
+                        RuntimeError::InvalidClassFile(
+                            "concatenation recipe references a missing constant".to_string(),
+                        )
+                    })?;
+                    result.push_str(&constant);
+                }
+                _ => result.push(ch),
             }
         }
 
+        let string_id = self.create_string_object(result)?;
+        self.frames
+            .last_mut()
+            .ok_or(RuntimeError::CallStackUnderflow)?
+            .operand_stack
+            .push(JvmValue::Reference(Some(string_id)));
+
         Ok(())
     }
 
-    fn invoke_dynamic_method(
+    /// The text `String.valueOf` gives for a value, calling `toString` on
+    /// objects, which may be user code
+    pub(crate) fn string_value_of(
         &mut self,
-        _bootstrap_method_attr_index: u16,
-    ) -> Result<(), RuntimeError> {
-        // Handle invokedynamic calls (mainly for string concatenation with StringConcatFactory)
-        // For simplicity, we'll assume most invokedynamic calls are for string concatenation
-
-        let frame = self
-            .frames
-            .last_mut()
-            .ok_or(RuntimeError::CallStackUnderflow)?;
+        value: &JvmValue,
+        descriptor: &str,
+    ) -> Result<String, RuntimeError> {
+        match value {
+            JvmValue::Reference(Some(id))
+                if descriptor.starts_with(['L', '[']) && !self.string_data.contains_key(id) =>
+            {
+                let text = self
+                    .call_method(value, "toString", "()Ljava/lang/String;", &[])?
+                    .ok_or(RuntimeError::InvalidStackState)?;
+                Ok(self.value_to_java_string(&text, "Ljava/lang/String;"))
+            }
+            _ => Ok(self.value_to_java_string(value, descriptor)),
+        }
+    }
 
-        // Try to handle common string concatenation patterns
-        // Most Java string concatenations with + operator use invokedynamic
+    /// Convert a value to the text `String.valueOf` would produce for the
+    /// given field descriptor
+    pub(crate) fn value_to_java_string(&self, value: &JvmValue, descriptor: &str) -> String {
+        match (descriptor, value) {
+            ("Z", value) => value.as_boolean().unwrap_or(false).to_string(),
+            ("C", value) => String::from_utf16_lossy(&[value.as_char().unwrap_or(0)]),
+            (_, JvmValue::Int(i)) => i.to_string(),
+            (_, JvmValue::Long(l)) => l.to_string(),
+            (_, JvmValue::Float(f)) => format_java_float(*f),
+            (_, JvmValue::Double(d)) => format_java_double(*d),
+            (_, JvmValue::Boolean(b)) => b.to_string(),
+            (_, JvmValue::Char(c)) => String::from_utf16_lossy(&[*c]),
+            (_, JvmValue::Reference(None)) => "null".to_string(),
+            (_, JvmValue::Reference(Some(id))) => {
+                let object = "Ljava/lang/Object;";
+                if let Some(string_value) = self.string_data.get(id) {
+                    string_value.clone()
+                } else if let Some(elements) = self.lists.get(id) {
+                    let elements: Vec<String> = elements
+                        .iter()
+                        .map(|element| self.value_to_java_string(element, object))
+                        .collect();
+                    format!("[{}]", elements.join(", "))
+                } else if let Some(map) = self.maps.get(id) {
+                    let entries: Vec<String> = map
+                        .entries()
+                        .map(|(key, value)| {
+                            format!(
+                                "{}={}",
+                                self.value_to_java_string(key, object),
+                                self.value_to_java_string(value, object)
+                            )
+                        })
+                        .collect();
+                    format!("{{{}}}", entries.join(", "))
+                } else if let Some(object) = self.heap.get(id)
+                    && let Some(descriptor) = boxed_descriptor(&object.class_name)
+                    && let Some(value) = object.fields.get("value")
+                {
+                    self.value_to_java_string(value, descriptor)
+                } else if let Some(object) = self.heap.get(id)
+                    && let Some(text) = progression_to_string(&object.class_name, &object.fields)
+                {
+                    text
+                } else if self.stack_traces.contains_key(id) {
+                    // Throwable.toString(): class name and optional message
+                    let object = &self.heap[id];
+                    let class_name = object.class_name.replace('/', ".");
+                    match object.fields.get("detailMessage") {
+                        Some(message @ JvmValue::Reference(Some(_))) => format!(
+                            "{class_name}: {}",
+                            self.value_to_java_string(message, "Ljava/lang/String;")
+                        ),
+                        _ => class_name,
+                    }
+                } else {
+                    let class_name = self
+                        .heap
+                        .get(id)
+                        .map_or("java/lang/Object", |object| object.class_name.as_str());
+                    format!("{}@{id:x}", class_name.replace('/', "."))
+                }
+            }
+            (_, JvmValue::ReturnAddress(address)) => address.to_string(),
+        }
+    }
+}
 
-        // For basic string concatenation with one argument, pop the value and convert to string
-        if !frame.operand_stack.is_empty() {
-            let value = frame
-                .operand_stack
-                .pop()
-                .ok_or(RuntimeError::StackUnderflow)?;
-
-            let _string_result = match value {
-                JvmValue::Int(i) => format!("Math.max(100, 42) = {i}"),
-                JvmValue::Double(d) => format!("Math.floor(3.7) = {d}"),
-                JvmValue::Boolean(b) => format!("Boolean.parseBoolean(\"true\") = {b}"),
-                JvmValue::Char(c) => format!("Character.toLowerCase('A') = {}", c as u8 as char),
-                _ => "String conversion".to_string(),
-            };
+/// Binary int operation with Java semantics: results wrap on overflow and
+/// shift distances use only their low five bits
+fn int_operation(instruction: &JvmInstruction, a: i32, b: i32) -> Result<i32, RuntimeError> {
+    Ok(match instruction {
+        JvmInstruction::Iadd => a.wrapping_add(b),
+        JvmInstruction::Isub => a.wrapping_sub(b),
+        JvmInstruction::Imul => a.wrapping_mul(b),
+        JvmInstruction::Idiv | JvmInstruction::Irem if b == 0 => {
+            return Err(RuntimeError::DivisionByZero);
+        }
+        // Integer.MIN_VALUE / -1 overflows back to Integer.MIN_VALUE
+        JvmInstruction::Idiv => a.wrapping_div(b),
+        JvmInstruction::Irem => a.wrapping_rem(b),
+        JvmInstruction::Ishl => a.wrapping_shl(b as u32),
+        JvmInstruction::Ishr => a.wrapping_shr(b as u32),
+        JvmInstruction::Iushr => (a as u32).wrapping_shr(b as u32) as i32,
+        JvmInstruction::Iand => a & b,
+        JvmInstruction::Ior => a | b,
+        JvmInstruction::Ixor => a ^ b,
+        _ => return Err(RuntimeError::InvalidStackState),
+    })
+}
 
-            // Push the concatenated string back (as a reference in a real JVM)
-            frame.operand_stack.push(JvmValue::Reference(Some(0))); // Simplified string reference
+/// Binary long operation with Java semantics, like `int_operation`
+fn long_operation(instruction: &JvmInstruction, a: i64, b: i64) -> Result<i64, RuntimeError> {
+    Ok(match instruction {
+        JvmInstruction::Ladd => a.wrapping_add(b),
+        JvmInstruction::Lsub => a.wrapping_sub(b),
+        JvmInstruction::Lmul => a.wrapping_mul(b),
+        JvmInstruction::Ldiv | JvmInstruction::Lrem if b == 0 => {
+            return Err(RuntimeError::DivisionByZero);
         }
+        JvmInstruction::Ldiv => a.wrapping_div(b),
+        JvmInstruction::Lrem => a.wrapping_rem(b),
+        JvmInstruction::Land => a & b,
+        JvmInstruction::Lor => a | b,
+        JvmInstruction::Lxor => a ^ b,
+        _ => return Err(RuntimeError::InvalidStackState),
+    })
+}
 
-        Ok(())
+/// Primitive conversion with Java semantics. Rust's `as` casts already
+/// match them: floating-point to integer conversions saturate and map NaN
+/// to zero, and narrowing integer conversions keep the low bits.
+fn convert_value(instruction: &JvmInstruction, value: &JvmValue) -> Result<JvmValue, RuntimeError> {
+    Ok(match instruction {
+        JvmInstruction::I2l => JvmValue::Long(value.as_int()? as i64),
+        JvmInstruction::I2f => JvmValue::Float(value.as_int()? as f32),
+        JvmInstruction::I2d => JvmValue::Double(value.as_int()? as f64),
+        JvmInstruction::L2i => JvmValue::Int(value.as_long()? as i32),
+        JvmInstruction::L2f => JvmValue::Float(value.as_long()? as f32),
+        JvmInstruction::L2d => JvmValue::Double(value.as_long()? as f64),
+        JvmInstruction::F2i => JvmValue::Int(value.as_float()? as i32),
+        JvmInstruction::F2l => JvmValue::Long(value.as_float()? as i64),
+        JvmInstruction::F2d => JvmValue::Double(value.as_float()? as f64),
+        JvmInstruction::D2i => JvmValue::Int(value.as_double()? as i32),
+        JvmInstruction::D2l => JvmValue::Long(value.as_double()? as i64),
+        JvmInstruction::D2f => JvmValue::Float(value.as_double()? as f32),
+        JvmInstruction::I2b => JvmValue::Int(value.as_int()? as i8 as i32),
+        JvmInstruction::I2c => JvmValue::Int(value.as_int()? as u16 as i32),
+        JvmInstruction::I2s => JvmValue::Int(value.as_int()? as i16 as i32),
+        _ => return Err(RuntimeError::InvalidStackState),
+    })
+}
+
+/// Whether a class belongs to a runtime library the VM provides built in,
+/// so it is not expected on the classpath
+fn is_library_class(name: &str) -> bool {
+    ["java/", "javax/", "jdk/", "sun/", "kotlin/"]
+        .iter()
+        .any(|package| name.starts_with(package))
+        || name.starts_with('[')
+}
+
+/// Class name of a reference array component such as `Ljava/lang/String;`
+/// or `[I`; `None` for primitive components
+fn component_class(component: &str) -> Option<&str> {
+    match component.as_bytes().first() {
+        Some(b'L') => component.strip_prefix('L')?.strip_suffix(';'),
+        Some(b'[') => Some(component),
+        _ => None,
     }
 }
 
-fn count_method_parameters(descriptor: &str) -> usize {
-    let mut count = 0;
-    let mut chars = descriptor.chars();
+/// Initial value of an array element or field with the given descriptor
+fn default_value(descriptor: &str) -> JvmValue {
+    match descriptor.as_bytes().first() {
+        Some(b'J') => JvmValue::Long(0),
+        Some(b'F') => JvmValue::Float(0.0),
+        Some(b'D') => JvmValue::Double(0.0),
+        Some(b'L') | Some(b'[') => JvmValue::Reference(None),
+        _ => JvmValue::Int(0),
+    }
+}
 
-    // Skip until we find the opening parenthesis
-    for ch in chars.by_ref() {
-        if ch == '(' {
-            break;
-        }
+/// Resolve a `CONSTANT_MethodHandle` to the class and name of its target
+fn resolve_method_handle(
+    constant_pool: &ConstantPool,
+    handle_index: u16,
+) -> Result<(String, String), RuntimeError> {
+    let Some(ConstantPoolEntry::MethodHandle(_, reference_index)) = constant_pool.get(handle_index)
+    else {
+        return Err(RuntimeError::InvalidConstantPoolReference(handle_index));
+    };
+    let (Some(ConstantPoolEntry::Methodref(class_index, name_and_type_index))
+    | Some(ConstantPoolEntry::InterfaceMethodref(class_index, name_and_type_index))) =
+        constant_pool.get(*reference_index)
+    else {
+        return Err(RuntimeError::InvalidConstantPoolReference(*reference_index));
+    };
+    let class_name = constant_pool
+        .get_class_name(*class_index)
+        .ok_or(RuntimeError::InvalidConstantPoolReference(*class_index))?;
+    let Some(ConstantPoolEntry::NameAndType(name_index, _)) =
+        constant_pool.get(*name_and_type_index)
+    else {
+        return Err(RuntimeError::InvalidConstantPoolReference(
+            *name_and_type_index,
+        ));
+    };
+    let method_name = constant_pool
+        .get_utf8(*name_index)
+        .ok_or(RuntimeError::InvalidConstantPoolReference(*name_index))?;
+    Ok((class_name.to_string(), method_name.to_string()))
+}
+
+/// Render a loadable constant the way string concatenation would
+fn constant_to_java_string(
+    constant_pool: &ConstantPool,
+    index: u16,
+) -> Result<String, RuntimeError> {
+    match constant_pool.get(index) {
+        Some(ConstantPoolEntry::String(utf8_index)) => constant_pool
+            .get_utf8(*utf8_index)
+            .map(str::to_string)
+            .ok_or(RuntimeError::InvalidConstantPoolReference(*utf8_index)),
+        Some(ConstantPoolEntry::Integer(i)) => Ok(i.to_string()),
+        Some(ConstantPoolEntry::Long(l)) => Ok(l.to_string()),
+        Some(ConstantPoolEntry::Float(f)) => Ok(format_java_float(*f)),
+        Some(ConstantPoolEntry::Double(d)) => Ok(format_java_double(*d)),
+        _ => Err(RuntimeError::InvalidConstantPoolReference(index)),
     }
+}
 
-    // Count parameters until closing parenthesis
-    while let Some(ch) = chars.next() {
-        match ch {
-            ')' => break,
-            'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' => count += 1,
-            'L' => {
-                // Object type - skip until semicolon
-                for c in chars.by_ref() {
-                    if c == ';' {
-                        break;
-                    }
-                }
-                count += 1;
-            }
-            '[' => {
-                // Array type - skip the array marker and then the type
-                if let Some(next_ch) = chars.next() {
-                    match next_ch {
-                        'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' => count += 1,
-                        'L' => {
-                            // Object array - skip until semicolon
-                            for c in chars.by_ref() {
-                                if c == ';' {
-                                    break;
-                                }
-                            }
-                            count += 1;
-                        }
-                        _ => {} // Multi-dimensional arrays or other cases
-                    }
-                }
-            }
-            _ => {} // Skip other characters
-        }
+/// Format a double like `Double.toString`
+pub(crate) fn format_java_double(value: f64) -> String {
+    java_floating_point_string(value, format!("{value}"), format!("{value:e}"))
+}
+
+/// Format a float like `Float.toString`
+fn format_java_float(value: f32) -> String {
+    java_floating_point_string(value as f64, format!("{value}"), format!("{value:e}"))
+}
+
+fn java_floating_point_string(value: f64, plain: String, scientific: String) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
     }
 
-    count
+    // Java switches to computerized scientific notation outside [1e-3, 1e7)
+    let magnitude = value.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        if plain.contains('.') {
+            plain
+        } else {
+            format!("{plain}.0")
+        }
+    } else {
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+        if mantissa.contains('.') {
+            format!("{mantissa}E{exponent}")
+        } else {
+            format!("{mantissa}.0E{exponent}")
+        }
+    }
 }
 
 impl Default for JvmCompatibleVm {
@@ -2729,6 +3769,9 @@ impl Default for JvmCompatibleVm {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::jvm::class_file_parser::BootstrapMethod;
+    use crate::jvm::java_class_generator::JavaClassGenerator;
+    use std::process::Command;
 
     #[test]
     fn test_simple_arithmetic() {
@@ -2852,4 +3895,928 @@ mod tests {
         // Check that there's a double value on the stack (but we return void, so won't get it)
         // The fact that it executes without error means the method resolution worked
     }
+
+    #[test]
+    fn test_limits_stop_runaway_programs() {
+        // Allocates a string on every iteration, forever
+        let mut constant_pool = ConstantPool::new();
+        let roll_utf8 = constant_pool.add_utf8("roll".to_string()).unwrap();
+        let roll = constant_pool.add_string(roll_utf8).unwrap();
+        let spin = vec![
+            JvmInstruction::Ldc(roll),
+            JvmInstruction::Pop,
+            JvmInstruction::Goto(0),
+        ];
+        let unlimited = VmLimits::unlimited();
+        for (limits, expected) in [
+            (
+                VmLimits {
+                    max_instructions: Some(1000),
+                    ..unlimited
+                },
+                "Instruction limit of 1000 exceeded",
+            ),
+            (
+                VmLimits {
+                    max_heap_objects: Some(50),
+                    ..unlimited
+                },
+                "Heap limit of 50 objects exceeded",
+            ),
+            (
+                VmLimits {
+                    max_string_bytes: Some(400),
+                    ..unlimited
+                },
+                "String limit of 400 bytes exceeded",
+            ),
+            (
+                VmLimits {
+                    timeout: Some(Duration::from_millis(20)),
+                    ..unlimited
+                },
+                "Execution timed out after 20ms",
+            ),
+        ] {
+            let mut vm = JvmCompatibleVm::new();
+            vm.set_limits(limits);
+            let error = vm
+                .execute_method(spin.clone(), constant_pool.clone(), 0)
+                .unwrap_err();
+            assert_eq!(error.to_string(), expected);
+        }
+
+        // A handler that catches its own throw
+        for limits in [
+            VmLimits {
+                max_instructions: Some(1000),
+                ..unlimited
+            },
+            VmLimits {
+                timeout: Some(Duration::from_millis(20)),
+                ..unlimited
+            },
+        ] {
+            let rethrow = vec![JvmInstruction::AconstNull, JvmInstruction::Athrow];
+            let mut frame = MethodFrame::new(None, ConstantPool::new(), rethrow, 0, Vec::new());
+            frame.exception_handlers.push(ExceptionHandler {
+                start: 1,
+                end: 2,
+                handler: 1,
+                catch_type: None,
+            });
+            let mut vm = JvmCompatibleVm::new();
+            vm.set_limits(limits);
+            vm.started = Some(Instant::now());
+            assert!(matches!(
+                vm.run_frame(frame),
+                Err(RuntimeError::InstructionLimitExceeded(1000) | RuntimeError::Timeout(_))
+            ));
+        }
+
+        // Arrays are checked against the limit before they are allocated
+        let allocate = vec![
+            JvmInstruction::Sipush(1000),
+            JvmInstruction::Newarray(10),
+            JvmInstruction::Pop,
+            JvmInstruction::Goto(0),
+        ];
+        let mut vm = JvmCompatibleVm::new();
+        vm.set_limits(VmLimits {
+            max_array_bytes: Some(100_000),
+            ..unlimited
+        });
+        let error = vm
+            .execute_method(allocate, ConstantPool::new(), 0)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Array limit of 100000 bytes exceeded");
+        assert!(vm.array_bytes <= 100_000);
+
+        // So are appends, before the builder grows
+        let mut vm = JvmCompatibleVm::new();
+        vm.set_limits(VmLimits {
+            max_string_bytes: Some(400),
+            ..unlimited
+        });
+        let builder = vm.new_string("d6").unwrap();
+        let error = vm
+            .append_to_buffer(&builder, &"d6".repeat(300))
+            .unwrap_err();
+        assert!(matches!(error, RuntimeError::StringLimitExceeded(400)));
+        assert_eq!(vm.string_value(&builder).unwrap(), "d6");
+
+        // Doubling a string stops at the default limit, both by concatenation
+        // and with a StringBuilder
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/StringGrowth.class"
+        );
+        for args in [vec![], vec!["builder".to_string()]] {
+            let mut vm = JvmCompatibleVm::new();
+            assert!(matches!(
+                vm.execute_class_file(fixture, &args),
+                Err(RuntimeError::StringLimitExceeded(limit)) if limit == DEFAULT_MAX_STRING_BYTES
+            ));
+        }
+
+        // Unbounded recursion
+        let fixture = format!(
+            "{}/tests/fixtures/Recursion.class",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut vm = JvmCompatibleVm::new();
+        vm.set_limits(VmLimits {
+            max_call_depth: Some(32),
+            ..unlimited
+        });
+        let result = vm.execute_class_file(&fixture, &[]);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::CallDepthLimitExceeded(32))
+        ));
+    }
+
+    #[test]
+    fn test_registered_natives_are_invoked_from_bytecode() {
+        let mut constant_pool = ConstantPool::new();
+        let host_utf8 = constant_pool.add_utf8("DiceHost".to_string()).unwrap();
+        let host_class = constant_pool.add_class(host_utf8).unwrap();
+        let roll_utf8 = constant_pool.add_utf8("roll".to_string()).unwrap();
+        let roll_desc_utf8 = constant_pool.add_utf8("(II)I".to_string()).unwrap();
+        let roll_name_and_type = constant_pool
+            .add_name_and_type(roll_utf8, roll_desc_utf8)
+            .unwrap();
+        let roll_method = constant_pool
+            .add_methodref(host_class, roll_name_and_type)
+            .unwrap();
+        let bytecode = vec![
+            JvmInstruction::Iconst3,
+            JvmInstruction::Bipush(6),
+            JvmInstruction::Invokestatic(roll_method),
+            JvmInstruction::Ireturn,
+        ];
+
+        // Without the native, the host class has to be loaded
+        let mut vm = JvmCompatibleVm::new();
+        let result = vm.execute_method(bytecode.clone(), constant_pool.clone(), 0);
+        assert!(matches!(result, Err(RuntimeError::ClassNotFound(_))));
+
+        let mut vm = JvmCompatibleVm::new();
+        vm.register_native("DiceHost", "roll", "(II)I", |_, arguments| {
+            let count = arguments[0].as_int()?;
+            let faces = arguments[1].as_int()?;
+            Ok(Some(JvmValue::Int(count * faces)))
+        });
+        let result = vm.execute_method(bytecode, constant_pool, 0).unwrap();
+        assert_eq!(result, Some(JvmValue::Int(18)));
+    }
+
+    #[test]
+    fn test_invokedynamic_string_concatenation() {
+        let mut vm = JvmCompatibleVm::new();
+        let mut constant_pool = ConstantPool::new();
+
+        let factory_utf8 = constant_pool
+            .add_utf8("java/lang/invoke/StringConcatFactory".to_string())
+            .unwrap();
+        let factory_class = constant_pool.add_class(factory_utf8).unwrap();
+        let bootstrap_name = constant_pool
+            .add_utf8("makeConcatWithConstants".to_string())
+            .unwrap();
+        let bootstrap_desc = constant_pool
+            .add_utf8("(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/String;[Ljava/lang/Object;)Ljava/lang/invoke/CallSite;".to_string())
+            .unwrap();
+        let bootstrap_name_and_type = constant_pool
+            .add_name_and_type(bootstrap_name, bootstrap_desc)
+            .unwrap();
+        let bootstrap_methodref = constant_pool
+            .add_methodref(factory_class, bootstrap_name_and_type)
+            .unwrap();
+        let bootstrap_handle = constant_pool
+            .add_method_handle(6, bootstrap_methodref)
+            .unwrap();
+
+        let recipe_utf8 = constant_pool
+            .add_utf8("d\u{1} \u{2} \u{1}!".to_string())
+            .unwrap();
+        let recipe = constant_pool.add_string(recipe_utf8).unwrap();
+        let constant_utf8 = constant_pool.add_utf8("\u{1}=".to_string()).unwrap();
+        let constant = constant_pool.add_string(constant_utf8).unwrap();
+
+        let call_site_desc = constant_pool
+            .add_utf8("(IZ)Ljava/lang/String;".to_string())
+            .unwrap();
+        let call_site_name_and_type = constant_pool
+            .add_name_and_type(bootstrap_name, call_site_desc)
+            .unwrap();
+        let call_site = constant_pool
+            .add_invoke_dynamic(0, call_site_name_and_type)
+            .unwrap();
+
+        let class_data = fs::read("tests/fixtures/Concat.class").unwrap();
+        let mut class = LoadedClass::new(ClassFileParser::parse(&class_data).unwrap()).unwrap();
+        class.bootstrap_methods = vec![BootstrapMethod {
+            bootstrap_method_ref: bootstrap_handle,
+            bootstrap_arguments: vec![recipe, constant],
+        }];
+        vm.class_loader.insert(class);
+
+        let bytecode = vec![
+            JvmInstruction::Bipush(20),
+            JvmInstruction::Iconst1,
+            JvmInstruction::Invokedynamic(call_site),
+        ];
+        let frame = MethodFrame::new(
+            Some("Concat".to_string()),
+            constant_pool,
+            bytecode,
+            0,
+            Vec::new(),
+        );
+        vm.run_frame(frame).unwrap();
+
+        let (&string_id, _) = vm
+            .string_data
+            .iter()
+            .find(|(_, value)| value.starts_with('d'))
+            .expect("concatenation result should be on the heap");
+        assert_eq!(vm.heap[&string_id].class_name, "java/lang/String");
+        assert_eq!(vm.string_data[&string_id], "d20 \u{1}= true!");
+    }
+
+    #[test]
+    fn test_java_floating_point_formatting() {
+        assert_eq!(format_java_double(2.0), "2.0");
+        assert_eq!(format_java_double(3.5), "3.5");
+        assert_eq!(format_java_double(-0.0), "-0.0");
+        assert_eq!(format_java_double(1e10), "1.0E10");
+        assert_eq!(format_java_double(1.25e-5), "1.25E-5");
+        assert_eq!(format_java_double(f64::NAN), "NaN");
+        assert_eq!(format_java_double(f64::NEG_INFINITY), "-Infinity");
+        assert_eq!(format_java_float(0.1), "0.1");
+        assert_eq!(format_java_float(1e7), "1.0E7");
+    }
+
+    #[test]
+    fn test_primitive_array_load_and_store() {
+        let mut vm = JvmCompatibleVm::new();
+        let bytecode = vec![
+            JvmInstruction::Iconst3,
+            JvmInstruction::Newarray(8), // byte[3]
+            JvmInstruction::Astore0,
+            JvmInstruction::Aload0,
+            JvmInstruction::Iconst2,
+            JvmInstruction::Sipush(200),
+            JvmInstruction::Bastore, // Narrowed to (byte) 200 == -56
+            JvmInstruction::Aload0,
+            JvmInstruction::Iconst2,
+            JvmInstruction::Baload,
+            JvmInstruction::Aload0,
+            JvmInstruction::Arraylength,
+            JvmInstruction::Iadd,
+            JvmInstruction::Ireturn,
+        ];
+
+        let result = vm.execute_method(bytecode, ConstantPool::new(), 1).unwrap();
+
+        assert_eq!(result, Some(JvmValue::Int(-53)));
+    }
+
+    #[test]
+    fn test_multidimensional_array() {
+        let mut vm = JvmCompatibleVm::new();
+        let mut constant_pool = ConstantPool::new();
+        let descriptor = constant_pool.add_utf8("[[J".to_string()).unwrap();
+        let array_class = constant_pool.add_class(descriptor).unwrap();
+
+        let bytecode = vec![
+            JvmInstruction::Iconst2,
+            JvmInstruction::Iconst4,
+            JvmInstruction::Multianewarray(array_class, 2), // new long[2][4]
+            JvmInstruction::Iconst1,
+            JvmInstruction::Aaload,
+            JvmInstruction::Arraylength,
+            JvmInstruction::Ireturn,
+        ];
+
+        let result = vm.execute_method(bytecode, constant_pool, 0).unwrap();
+
+        assert_eq!(result, Some(JvmValue::Int(4)));
+        let inner = vm
+            .heap
+            .iter()
+            .find(|(_, object)| object.class_name == "[J")
+            .map(|(id, _)| *id)
+            .unwrap();
+        assert_eq!(vm.array_data[&inner], vec![JvmValue::Long(0); 4]);
+    }
+
+    #[test]
+    fn test_array_errors() {
+        let out_of_bounds = vec![
+            JvmInstruction::Iconst2,
+            JvmInstruction::Newarray(10),
+            JvmInstruction::Iconst2,
+            JvmInstruction::Iaload,
+            JvmInstruction::Ireturn,
+        ];
+        assert!(matches!(
+            JvmCompatibleVm::new().execute_method(out_of_bounds, ConstantPool::new(), 0),
+            Err(RuntimeError::UncaughtException(message))
+                if message == "java.lang.ArrayIndexOutOfBoundsException: Index 2 out of bounds for length 2"
+        ));
+
+        let negative_size = vec![JvmInstruction::IconstM1, JvmInstruction::Newarray(10)];
+        assert!(matches!(
+            JvmCompatibleVm::new().execute_method(negative_size, ConstantPool::new(), 0),
+            Err(RuntimeError::UncaughtException(message))
+                if message == "java.lang.NegativeArraySizeException: -1"
+        ));
+
+        // new int[2000000000] and new int[100000][100000] fail without
+        // allocating, under the default limits
+        let mut constant_pool = ConstantPool::new();
+        let huge = constant_pool.add_integer(2_000_000_000).unwrap();
+        let big = constant_pool.add_integer(100_000).unwrap();
+        let descriptor = constant_pool.add_utf8("[[I".to_string()).unwrap();
+        let array_class = constant_pool.add_class(descriptor).unwrap();
+        let huge_array = vec![JvmInstruction::Ldc(huge), JvmInstruction::Newarray(10)];
+        let huge_matrix = vec![
+            JvmInstruction::Ldc(big),
+            JvmInstruction::Ldc(big),
+            JvmInstruction::Multianewarray(array_class, 2),
+        ];
+        for bytecode in [huge_array, huge_matrix] {
+            let mut vm = JvmCompatibleVm::new();
+            assert!(matches!(
+                vm.execute_method(bytecode, constant_pool.clone(), 0),
+                Err(RuntimeError::ArrayLimitExceeded(limit)) if limit == DEFAULT_MAX_ARRAY_BYTES
+            ));
+            assert!(vm.array_bytes <= DEFAULT_MAX_ARRAY_BYTES);
+        }
+
+        // An allocation the host refuses is thrown as OutOfMemoryError
+        let mut vm = JvmCompatibleVm::new();
+        let exception_id = vm.exception_for_error(RuntimeError::OutOfMemory).unwrap();
+        assert_eq!(
+            vm.heap[&exception_id].class_name,
+            "java/lang/OutOfMemoryError"
+        );
+        assert!(
+            vm.is_assignable("java/lang/OutOfMemoryError", "java/lang/Error")
+                .unwrap()
+        );
+
+        let null_array = vec![JvmInstruction::AconstNull, JvmInstruction::Arraylength];
+        assert!(matches!(
+            JvmCompatibleVm::new().execute_method(null_array, ConstantPool::new(), 0),
+            Err(RuntimeError::UncaughtException(message))
+                if message == "java.lang.NullPointerException"
+        ));
+    }
+
+    #[test]
+    fn test_classes_load_from_classpath_jar() {
+        let mut vm = JvmCompatibleVm::new();
+        let result = vm.execute_class_file("tests/fixtures/LibraryMain.class", &[]);
+        assert!(matches!(result, Err(RuntimeError::ClassNotFound(name)) if name == "dice/Dice"));
+
+        let mut vm = JvmCompatibleVm::new();
+        vm.set_classpath("tests/fixtures/dice-lib.jar").unwrap();
+        vm.execute_class_file("tests/fixtures/LibraryMain.class", &[])
+            .unwrap();
+        let dice = vm
+            .heap
+            .values()
+            .find(|object| object.class_name == "dice/Dice")
+            .unwrap();
+        assert_eq!(dice.fields["dice/Dice.count:I"], JvmValue::Int(3));
+        assert_eq!(dice.fields["dice/Dice.faces:I"], JvmValue::Int(6));
+
+        // The main class itself can be found by name
+        let mut vm = JvmCompatibleVm::new();
+        let classpath = std::env::join_paths(["tests/fixtures/dice-lib.jar", "tests/fixtures"])
+            .unwrap()
+            .into_string()
+            .unwrap();
+        vm.set_classpath(&classpath).unwrap();
+        vm.execute_main_class("LibraryMain", &[]).unwrap();
+        assert!(matches!(
+            vm.execute_main_class("dice.Missing", &[]),
+            Err(RuntimeError::ClassNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_execute_jar_runs_manifest_main_class() {
+        let path = std::env::temp_dir().join(format!("dice-{}.jar", std::process::id()));
+        let path = path.to_str().unwrap();
+        crate::jvm::generate_java_jar(
+            crate::jvm::DiceSource::Expression("2d6"),
+            "dice/Roll",
+            Default::default(),
+            path,
+        )
+        .unwrap();
+
+        let mut vm = JvmCompatibleVm::new();
+        let result = vm.execute_jar(path, &[]);
+        std::fs::remove_file(path).unwrap();
+        result.unwrap();
+        assert!(vm.class_loader.loaded_class("dice/Roll").is_some());
+
+        let mut vm = JvmCompatibleVm::new();
+        assert!(matches!(
+            vm.execute_jar("tests/fixtures/dice-lib.jar", &[]),
+            Err(RuntimeError::InvalidArchive(message)) if message.ends_with("no Main-Class in manifest")
+        ));
+    }
+
+    #[test]
+    fn test_parametric_class_takes_dice_as_arguments() {
+        let mut generator = JavaClassGenerator::new("dice/Roll".to_string());
+        let class_data = generator.generate_parametric_class().unwrap();
+        let class_file = ClassFileParser::parse(&class_data).unwrap();
+        assert_eq!(class_file.source_file(), Some("Roll.java"));
+        let methods: Vec<_> = class_file
+            .methods
+            .iter()
+            .map(|method| {
+                (
+                    method.access_flags,
+                    method.name.as_str(),
+                    method.descriptor.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            methods,
+            vec![
+                (0x0001, "<init>", "()V"),
+                (0x0009, "roll", "(II)I"),
+                (0x0009, "main", "([Ljava/lang/String;)V"),
+            ]
+        );
+
+        for args in [vec!["3", "6"], vec!["2D20"], vec!["0", "6"], vec![]] {
+            let args: Vec<String> = args.into_iter().map(String::from).collect();
+            let mut vm = JvmCompatibleVm::new();
+            let class = vm
+                .class_loader
+                .define_class(ClassFileParser::parse(&class_data).unwrap())
+                .unwrap();
+            vm.run_main(class, &args).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_exception_table_catches_builtin_exception() {
+        let mut vm = JvmCompatibleVm::new();
+        let bytecode = vec![
+            JvmInstruction::Iconst1,
+            JvmInstruction::Iconst0,
+            JvmInstruction::Idiv,
+            JvmInstruction::Ireturn,
+            // Handler: return -1
+            JvmInstruction::Pop,
+            JvmInstruction::IconstM1,
+            JvmInstruction::Ireturn,
+        ];
+        let mut frame = MethodFrame::new(None, ConstantPool::new(), bytecode, 0, Vec::new());
+        frame.exception_handlers.push(ExceptionHandler {
+            start: 0,
+            end: 4,
+            handler: 4,
+            catch_type: Some("java/lang/RuntimeException".to_string()),
+        });
+
+        assert_eq!(vm.run_frame(frame).unwrap(), Some(JvmValue::Int(-1)));
+
+        // A handler for an unrelated class does not catch it
+        let bytecode = vec![
+            JvmInstruction::Iconst1,
+            JvmInstruction::Iconst0,
+            JvmInstruction::Idiv,
+            JvmInstruction::Ireturn,
+        ];
+        let mut frame = MethodFrame::new(None, ConstantPool::new(), bytecode, 0, Vec::new());
+        frame.exception_handlers.push(ExceptionHandler {
+            start: 0,
+            end: 4,
+            handler: 3,
+            catch_type: Some("java/lang/IllegalStateException".to_string()),
+        });
+        let Err(RuntimeError::Exception(exception_id)) = vm.run_frame(frame) else {
+            panic!("expected an uncaught exception");
+        };
+        assert_eq!(
+            vm.value_to_java_string(
+                &JvmValue::Reference(Some(exception_id)),
+                "Ljava/lang/Throwable;"
+            ),
+            "java.lang.ArithmeticException: / by zero"
+        );
+    }
+
+    #[test]
+    fn test_exceptions_unwind_across_frames() {
+        let mut vm = JvmCompatibleVm::new();
+        let result = vm.execute_class_file("tests/fixtures/Exceptions.class", &[]);
+
+        assert!(matches!(
+            result,
+            Err(RuntimeError::UncaughtException(message))
+                if message == "Exceptions$RollException: no dice left"
+        ));
+        assert!(vm.frames.is_empty());
+
+        // The exception thrown two calls deep records the whole call stack
+        let (too_many_dice, _) = vm
+            .heap
+            .iter()
+            .find(|(_, object)| object.class_name == "Exceptions$TooManyDiceException")
+            .unwrap();
+        assert_eq!(
+            vm.stack_traces[too_many_dice],
+            vec![
+                "Exceptions.checkedCount(Exceptions.java:20)",
+                "Exceptions.roll(Exceptions.java:27)",
+                "Exceptions.main(Exceptions.java:42)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_objects_fields_and_virtual_dispatch() {
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/DiceObjects.class"
+        );
+        let mut vm = JvmCompatibleVm::new();
+        vm.execute_class_file(fixture, &[]).unwrap();
+
+        // <clinit> ran once per class and putstatic updated the main class
+        assert_eq!(vm.static_fields["Die"]["created"], JvmValue::Int(2));
+        assert_eq!(vm.static_fields["DiceObjects"]["rolls"], JvmValue::Int(12));
+        let prefix = &vm.static_fields["Die"]["prefix"];
+        let JvmValue::Reference(Some(prefix_id)) = prefix else {
+            panic!("expected a string, got {prefix:?}");
+        };
+        assert_eq!(vm.string_data[prefix_id], "d");
+
+        // Fields declared by the superclass and the subclass share one object,
+        // and LoadedDie.roll reached Die.roll through super
+        let loaded = vm
+            .heap
+            .values()
+            .find(|object| object.class_name == "LoadedDie")
+            .unwrap();
+        assert_eq!(loaded.fields["Die.sides:I"], JvmValue::Int(6));
+        assert_eq!(loaded.fields["LoadedDie.weight:I"], JvmValue::Int(2));
+        assert_eq!(loaded.fields["Die.lastRoll:I"], JvmValue::Int(6));
+    }
+
+    #[test]
+    fn test_arithmetic_conformance_with_javac_output() {
+        // Output of `java Arithmetic` on a reference JVM
+        let expected = [
+            "iadd -2147483648",
+            "isub 2147483647",
+            "imul -2",
+            "idiv -2147483648",
+            "irem -1",
+            "ineg -2147483648",
+            "ishl 14",
+            "ishr -1073741824",
+            "iushr 1073741824",
+            "ibits 15",
+            "iinc -2147483639",
+            "ladd -9223372036854775808",
+            "lmul -3347833947615787215",
+            "ldiv -9223372036854775808",
+            "lrem 5",
+            "lneg -9223372036854775808",
+            "lshl 246913578024690",
+            "lshr -1073741824",
+            "lushr 1073741824",
+            "lbits 123456788955136",
+            "fdiv Infinity NaN",
+            "fmul 1.0 9.0000005E20",
+            "frem -1.5 -0.0",
+            "ddiv -Infinity NaN",
+            "drem 1.5 NaN",
+            "dneg -0.0 Infinity",
+            "d2i 0 -2147483648 2147483647",
+            "d2l -9223372036854775808 9223372036854775807 -2",
+            "f2i 2147483647 0",
+            "f2l 30000001024 -3",
+            "l2i -2045911175 -1",
+            "narrow -128 31248 65529",
+            "i2f 1.6777216E7 -2.147483648E9",
+            "l2f 9.223372E18 1.23456789012345E14",
+            "d2f -Infinity 0.33333334",
+            "lcmp true false",
+            "fcmp false false",
+            "dcmp false true true",
+            "icmp true true true",
+        ];
+        assert_eq!(fixture_results("Arithmetic", expected.len()), expected);
+    }
+
+    /// Run a conformance fixture and read the strings it recorded in its
+    /// static `results` array
+    fn fixture_results(class_name: &str, count: usize) -> Vec<String> {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        class_results(directory, class_name, count)
+    }
+
+    /// Like `fixture_results`, for a class compiled into `directory`
+    fn class_results(directory: &str, class_name: &str, count: usize) -> Vec<String> {
+        let mut vm = JvmCompatibleVm::new();
+        vm.execute_class_file(&format!("{directory}/{class_name}.class"), &[])
+            .unwrap();
+
+        let JvmValue::Reference(Some(results_id)) = vm.static_fields[class_name]["results"] else {
+            panic!("results array was not initialized");
+        };
+        vm.array_data[&results_id]
+            .iter()
+            .take(count)
+            .map(|value| match value {
+                JvmValue::Reference(Some(id)) => vm.string_data[id].clone(),
+                other => panic!("expected a string, got {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_collections_conformance_with_javac_output() {
+        // Output of `java KeepHighest` on a reference JVM
+        let expected = [
+            "rolls [5, 3, 4, 5, 5, 5] size 6",
+            "4d6kh3 15",
+            "sorted [3, 4, 5, 5, 5, 5] max 5",
+            "descending [5, 5, 5, 5, 4, 3]",
+            "removed [4, 5, 5, 5] contains true -1",
+            "counts {1=15, 2=7, 3=8, 4=17, 5=10, 6=3}",
+            "names {graze=1, hit=2, critical=1, fumble=1, miss=1} true null",
+            "arrays [1, 2, 3, 4, 6, 6] [-1.5, -0.0, 0.0, 0.5]",
+            "boxing true 42 true",
+            "cast failed",
+        ];
+        assert_eq!(fixture_results("KeepHighest", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_calls_run_in_one_dispatch_loop() {
+        // Output of `java Calls` on a reference JVM. sumTo(5000) recurses far
+        // deeper than a test thread's stack would allow if calls nested.
+        let expected = [
+            "fibonacci 75025",
+            "sumTo 12502500",
+            "total 3000000000003",
+            "average 3.5",
+            "half 2.5",
+            "describe critical roll 7",
+            "faces 6 6",
+            "even true d",
+            "next 30000000001",
+            "caught bottom",
+        ];
+        assert_eq!(fixture_results("Calls", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_objects_are_printed_by_their_to_string() {
+        // Output of `java Printing` on a reference JVM
+        let expected = [
+            "concat d6 d3 null",
+            "builder d6+d3 null",
+            "valueOf d6 null",
+            "pool d20d4",
+        ];
+        assert_eq!(fixture_results("Printing", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_hidden_fields_have_their_own_slots() {
+        // Output of `java Hiding` on a reference JVM
+        let expected = [
+            "fields 1 2 1 10 20",
+            "loaded 3 3 3 10 20",
+            "static type 3 3",
+        ];
+        assert_eq!(fixture_results("Hiding", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_assignments_used_as_values() {
+        // Output of `java Counters` on a reference JVM
+        let expected = [
+            "ticks 0 1 2",
+            "total 5000000000 4999999999 4999999999",
+            "ints 7 0 7",
+            "longs -3 -3 0",
+            "doubles 2.5 2.5",
+        ];
+        assert_eq!(fixture_results("Counters", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_two_word_values_and_wide_locals() {
+        // Output of `java Accumulators` on a reference JVM
+        let expected = [
+            "longs 0 5000000001 -1",
+            "doubles 1.5 2.5 1.5",
+            "fields 1000000000007 1000000000007 0.25",
+            "discarded 2 18",
+            "wide 1003",
+            "wide -28997",
+        ];
+        assert_eq!(fixture_results("Accumulators", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_array_stores_check_the_component_type() {
+        // Output of `java ArrayStores` on a reference JVM
+        let expected = [
+            "ase",
+            "strings d6 null",
+            "numbers 3 4 java.lang.String",
+            "rows [J",
+            "objects true",
+        ];
+        assert_eq!(fixture_results("ArrayStores", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_interface_default_methods() {
+        // Output of `java Defaults` on a reference JVM
+        let expected = ["d6 5 d6", "exploding 6 exploding d6", "coin 2 coin"];
+        assert_eq!(fixture_results("Defaults", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_small_boxes_are_shared() {
+        // Output of `java Boxing` on a reference JVM
+        let expected = [
+            "Integer true false true true",
+            "Long true false",
+            "Character true false",
+            "Boolean true",
+            "Double false true",
+        ];
+        assert_eq!(fixture_results("Boxing", expected.len()), expected);
+    }
+
+    /// Compiles KotlinDice.kt when kotlinc is available and runs the classes
+    /// it produces against the Kotlin stdlib shims
+    #[test]
+    fn test_kotlin_stdlib_shims_run_kotlinc_output() {
+        if Command::new("kotlinc").arg("-version").output().is_err() {
+            return;
+        }
+        let directory = std::env::temp_dir().join(format!("dice-kotlin-{}", std::process::id()));
+        let compiled = Command::new("kotlinc")
+            .arg(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/KotlinDice.kt"
+            ))
+            .arg("-d")
+            .arg(&directory)
+            .output()
+            .unwrap();
+        assert!(
+            compiled.status.success(),
+            "{}",
+            String::from_utf8_lossy(&compiled.stderr)
+        );
+
+        // What KotlinDice.kt prints under `kotlin` with no arguments
+        let expected = [
+            "rolls [1, 3, 4, 6, 6] sum 20",
+            "descending 6 6 4 3 1",
+            "max 6 min 1",
+            "range 1..6 1 6 sum 21",
+            "countdown 10, 7, 4, 1",
+            "pool 100 true",
+            "d20 true",
+            "equal true false",
+            "npe null",
+        ];
+        let results = class_results(directory.to_str().unwrap(), "KotlinDiceKt", expected.len());
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_seeded_random_conformance_with_javac_output() {
+        // Output of `java SeededRandom` on a reference JVM
+        let expected = [
+            "3d6 3413126326",
+            "nextInt -415012931",
+            "power of two 7 395977638",
+            "large bound 819572292 592164476",
+            "nextLong -5710636755842891759",
+            "nextDouble 0.762090173108902",
+            "nextFloat 0.99817854",
+            "nextBoolean true false",
+            "nextGaussian -0.40086993610549193 1.577411619884327",
+            "setSeed 13 -2.140052388577256",
+            "bound bound must be positive",
+            "threadLocal true",
+        ];
+        assert_eq!(fixture_results("SeededRandom", expected.len()), expected);
+    }
+
+    #[test]
+    fn test_switches_choose_targets_by_key() {
+        let switch = |key: i16| {
+            vec![
+                JvmInstruction::Sipush(key),
+                JvmInstruction::Tableswitch {
+                    default: 6,
+                    low: 4,
+                    targets: vec![4, 6, 8],
+                },
+                JvmInstruction::Nop,
+                JvmInstruction::Nop,
+                JvmInstruction::Bipush(10), // 4: key 4
+                JvmInstruction::Ireturn,
+                JvmInstruction::Sipush(key), // 6: key 5 and default
+                JvmInstruction::Lookupswitch {
+                    default: 10,
+                    pairs: vec![(-1000, 12), (5, 14)],
+                },
+                JvmInstruction::Bipush(60), // 8: key 6
+                JvmInstruction::Ireturn,
+                JvmInstruction::IconstM1, // 10
+                JvmInstruction::Ireturn,
+                JvmInstruction::Iconst0, // 12
+                JvmInstruction::Ireturn,
+                JvmInstruction::Bipush(50), // 14
+                JvmInstruction::Ireturn,
+            ]
+        };
+
+        for (key, expected) in [(4, 10), (5, 50), (6, 60), (3, -1), (7, -1), (-1000, 0)] {
+            let mut vm = JvmCompatibleVm::new();
+            let result = vm
+                .execute_method(switch(key), ConstantPool::new(), 0)
+                .unwrap();
+            assert_eq!(result, Some(JvmValue::Int(expected)), "key {key}");
+        }
+    }
+
+    #[test]
+    fn test_string_switch_uses_hash_code_and_equals() {
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/Switches.class");
+        let class_file = ClassFileParser::parse(&std::fs::read(fixture).unwrap()).unwrap();
+        let sides = class_file
+            .find_method("sides", "(Ljava/lang/String;)I")
+            .unwrap();
+        assert!(
+            sides
+                .bytecode()
+                .iter()
+                .any(|instruction| matches!(instruction, JvmInstruction::Lookupswitch { .. }))
+        );
+
+        let mut vm = JvmCompatibleVm::new();
+        vm.execute_class_file(fixture, &[]).unwrap();
+        let class = vm.class_loader.loaded_class("Switches").unwrap();
+        for (die, expected) in [("d20", 20), ("Aa", 2), ("BB", 2), ("d100", 0)] {
+            let argument =
+                JvmValue::Reference(Some(vm.create_string_object(die.to_string()).unwrap()));
+            let frame = MethodFrame::for_method(&class, sides, vec![argument]).unwrap();
+            let result = vm.run_frame(frame).unwrap();
+            assert_eq!(result, Some(JvmValue::Int(expected)), "{die}");
+        }
+    }
+
+    #[test]
+    fn test_floating_point_comparisons_with_nan() {
+        for (comparison, widen, expected) in [
+            (JvmInstruction::Fcmpl, JvmInstruction::Nop, -1),
+            (JvmInstruction::Fcmpg, JvmInstruction::Nop, 1),
+            (JvmInstruction::Dcmpl, JvmInstruction::F2d, -1),
+            (JvmInstruction::Dcmpg, JvmInstruction::F2d, 1),
+        ] {
+            let mut vm = JvmCompatibleVm::new();
+            let bytecode = vec![
+                JvmInstruction::Fconst1,
+                widen.clone(),
+                JvmInstruction::Fconst0,
+                JvmInstruction::Fconst0,
+                JvmInstruction::Fdiv, // 0.0f / 0.0f is NaN
+                widen,
+                comparison,
+                JvmInstruction::Ireturn,
+            ];
+
+            let result = vm.execute_method(bytecode, ConstantPool::new(), 0).unwrap();
+
+            assert_eq!(result, Some(JvmValue::Int(expected)));
+        }
+    }
 }
@@ -1,10 +1,19 @@
+use std::collections::HashMap;
+
 /// JVM bytecode instructions and data type definitions
 /// JVM bytecode instructions
-#[derive(Debug, Clone)]
+///
+/// Branch operands hold the index of the target instruction rather than a
+/// byte offset; the parser and assembler translate between the two.
+/// Local variable indices and `iinc` constants are held at their `wide`
+/// sizes, and the assembler adds the `wide` prefix when they need it.
+#[derive(Debug, Clone, PartialEq)]
 pub enum JvmInstruction {
     // Constant pool operations
     Ldc(u16),    // Load constant from pool
+    LdcW(u16),   // Load constant from pool (wide index)
     Ldc2W(u16),  // Load 2-word constant from pool (long/double)
+    AconstNull,  // Load null reference
     IconstM1,    // Load -1
     Iconst0,     // Load 0
     Iconst1,     // Load 1
@@ -14,100 +23,226 @@ pub enum JvmInstruction {
     Iconst5,     // Load 5
     Lconst0,     // Load long 0
     Lconst1,     // Load long 1
+    Fconst0,     // Load float 0.0
+    Fconst1,     // Load float 1.0
+    Fconst2,     // Load float 2.0
     Bipush(i8),  // Push byte value
     Sipush(i16), // Push short value
 
     // Stack operations
-    Pop,  // Pop top value
-    Dup,  // Duplicate top value
-    Swap, // Swap top two values
+    Pop,    // Pop top value
+    Pop2,   // Pop top two words
+    Dup,    // Duplicate top value
+    Dup2,   // Duplicate top two words
+    DupX1,  // Duplicate top value below the value under it
+    DupX2,  // Duplicate top value below the two words under it
+    Dup2X1, // Duplicate top two words below the value under them
+    Dup2X2, // Duplicate top two words below the two words under them
+    Swap,   // Swap top two values
 
     // Arithmetic operations
-    Iadd, // Add two ints
-    Isub, // Subtract two ints
-    Imul, // Multiply two ints
-    Idiv, // Divide two ints
-    Irem, // Remainder of two ints
+    Iadd,           // Add two ints
+    Isub,           // Subtract two ints
+    Imul,           // Multiply two ints
+    Idiv,           // Divide two ints
+    Irem,           // Remainder of two ints
+    Ineg,           // Negate int
+    Iinc(u16, i16), // Increment int local variable by constant
+    Ladd,           // Add two longs
+    Lsub,           // Subtract two longs
+    Lmul,           // Multiply two longs
+    Ldiv,           // Divide two longs
+    Lrem,           // Remainder of two longs
+    Lneg,           // Negate long
 
     // Floating-point operations
+    Fadd, // Add two floats
+    Fsub, // Subtract two floats
+    Fmul, // Multiply two floats
+    Fdiv, // Divide two floats
+    Frem, // Remainder of two floats
+    Fneg, // Negate float
     Dadd, // Add two doubles
     Dsub, // Subtract two doubles
     Dmul, // Multiply two doubles
     Ddiv, // Divide two doubles
+    Drem, // Remainder of two doubles
+    Dneg, // Negate double
+
+    // Shift and bitwise operations
+    Ishl,  // Shift int left
+    Ishr,  // Arithmetic shift int right
+    Iushr, // Logical shift int right
+    Lshl,  // Shift long left
+    Lshr,  // Arithmetic shift long right
+    Lushr, // Logical shift long right
+    Iand,  // Bitwise AND of two ints
+    Ior,   // Bitwise OR of two ints
+    Ixor,  // Bitwise XOR of two ints
+    Land,  // Bitwise AND of two longs
+    Lor,   // Bitwise OR of two longs
+    Lxor,  // Bitwise XOR of two longs
 
     // Type conversion
+    I2l, // Convert int to long
+    I2f, // Convert int to float
     I2d, // Convert int to double
+    L2i, // Convert long to int
+    L2f, // Convert long to float
+    L2d, // Convert long to double
+    F2i, // Convert float to int
+    F2l, // Convert float to long
+    F2d, // Convert float to double
     D2i, // Convert double to int
+    D2l, // Convert double to long
+    D2f, // Convert double to float
+    I2b, // Convert int to byte
+    I2c, // Convert int to char
+    I2s, // Convert int to short
+
+    // Comparisons
+    Lcmp,  // Compare two longs
+    Fcmpl, // Compare two floats, -1 on NaN
+    Fcmpg, // Compare two floats, 1 on NaN
+    Dcmpl, // Compare two doubles, -1 on NaN
+    Dcmpg, // Compare two doubles, 1 on NaN
 
     // Control flow
-    Ifeq(u16), // Branch if int equals zero
-    Ifne(u16), // Branch if int not equals zero
-    Iflt(u16), // Branch if int less than zero
-    Ifge(u16), // Branch if int greater or equal zero
-    Ifgt(u16), // Branch if int greater than zero
-    Ifle(u16), // Branch if int less or equal zero
-    Goto(u16), // Unconditional branch
+    Ifeq(u16),      // Branch if int equals zero
+    Ifne(u16),      // Branch if int not equals zero
+    Iflt(u16),      // Branch if int less than zero
+    Ifge(u16),      // Branch if int greater or equal zero
+    Ifgt(u16),      // Branch if int greater than zero
+    Ifle(u16),      // Branch if int less or equal zero
+    IfIcmpeq(u16),  // Branch if ints are equal
+    IfIcmpne(u16),  // Branch if ints are not equal
+    IfIcmplt(u16),  // Branch if int less than int
+    IfIcmpge(u16),  // Branch if int greater or equal int
+    IfIcmpgt(u16),  // Branch if int greater than int
+    IfIcmple(u16),  // Branch if int less or equal int
+    IfAcmpeq(u16),  // Branch if references are equal
+    IfAcmpne(u16),  // Branch if references are not equal
+    Ifnull(u16),    // Branch if reference is null
+    Ifnonnull(u16), // Branch if reference is not null
+    Goto(u16),      // Unconditional branch
 
     // Local variable operations
-    Iload(u8),  // Load int from local variable
-    Iload0,     // Load int from local variable 0
-    Iload1,     // Load int from local variable 1
-    Iload2,     // Load int from local variable 2
-    Iload3,     // Load int from local variable 3
-    Istore(u8), // Store int to local variable
-    Istore0,    // Store int to local variable 0
-    Istore1,    // Store int to local variable 1
-    Istore2,    // Store int to local variable 2
-    Istore3,    // Store int to local variable 3
-
-    Aload(u8),  // Load reference from local variable
-    Aload0,     // Load reference from local variable 0
-    Aload1,     // Load reference from local variable 1
-    Aload2,     // Load reference from local variable 2
-    Aload3,     // Load reference from local variable 3
-    Astore(u8), // Store reference to local variable
-    Astore0,    // Store reference to local variable 0
-    Astore1,    // Store reference to local variable 1
-    Astore2,    // Store reference to local variable 2
-    Astore3,    // Store reference to local variable 3
-
-    Dload(u8),  // Load double from local variable
-    Dload0,     // Load double from local variable 0
-    Dload1,     // Load double from local variable 1
-    Dload2,     // Load double from local variable 2
-    Dload3,     // Load double from local variable 3
-    Dstore(u8), // Store double to local variable
-    Dstore0,    // Store double to local variable 0
-    Dstore1,    // Store double to local variable 1
-    Dstore2,    // Store double to local variable 2
-    Dstore3,    // Store double to local variable 3
-
-    Lload(u8),  // Load long from local variable
-    Lload0,     // Load long from local variable 0
-    Lload1,     // Load long from local variable 1
-    Lload2,     // Load long from local variable 2
-    Lload3,     // Load long from local variable 3
-    Lstore(u8), // Store long to local variable
-    Lstore0,    // Store long to local variable 0
-    Lstore1,    // Store long to local variable 1
-    Lstore2,    // Store long to local variable 2
-    Lstore3,    // Store long to local variable 3
+    Iload(u16),  // Load int from local variable
+    Iload0,      // Load int from local variable 0
+    Iload1,      // Load int from local variable 1
+    Iload2,      // Load int from local variable 2
+    Iload3,      // Load int from local variable 3
+    Istore(u16), // Store int to local variable
+    Istore0,     // Store int to local variable 0
+    Istore1,     // Store int to local variable 1
+    Istore2,     // Store int to local variable 2
+    Istore3,     // Store int to local variable 3
+
+    Fload(u16),  // Load float from local variable
+    Fload0,      // Load float from local variable 0
+    Fload1,      // Load float from local variable 1
+    Fload2,      // Load float from local variable 2
+    Fload3,      // Load float from local variable 3
+    Fstore(u16), // Store float to local variable
+    Fstore0,     // Store float to local variable 0
+    Fstore1,     // Store float to local variable 1
+    Fstore2,     // Store float to local variable 2
+    Fstore3,     // Store float to local variable 3
+
+    Aload(u16),  // Load reference from local variable
+    Aload0,      // Load reference from local variable 0
+    Aload1,      // Load reference from local variable 1
+    Aload2,      // Load reference from local variable 2
+    Aload3,      // Load reference from local variable 3
+    Astore(u16), // Store reference to local variable
+    Astore0,     // Store reference to local variable 0
+    Astore1,     // Store reference to local variable 1
+    Astore2,     // Store reference to local variable 2
+    Astore3,     // Store reference to local variable 3
+
+    Dload(u16),  // Load double from local variable
+    Dload0,      // Load double from local variable 0
+    Dload1,      // Load double from local variable 1
+    Dload2,      // Load double from local variable 2
+    Dload3,      // Load double from local variable 3
+    Dstore(u16), // Store double to local variable
+    Dstore0,     // Store double to local variable 0
+    Dstore1,     // Store double to local variable 1
+    Dstore2,     // Store double to local variable 2
+    Dstore3,     // Store double to local variable 3
+
+    Lload(u16),  // Load long from local variable
+    Lload0,      // Load long from local variable 0
+    Lload1,      // Load long from local variable 1
+    Lload2,      // Load long from local variable 2
+    Lload3,      // Load long from local variable 3
+    Lstore(u16), // Store long to local variable
+    Lstore0,     // Store long to local variable 0
+    Lstore1,     // Store long to local variable 1
+    Lstore2,     // Store long to local variable 2
+    Lstore3,     // Store long to local variable 3
 
     // Method invocation
-    Invokevirtual(u16), // Invoke virtual method
-    Invokestatic(u16),  // Invoke static method
-    Invokespecial(u16), // Invoke special method (constructors, private methods)
-    Invokedynamic(u16), // Invoke dynamic method (for lambda and string concatenation)
+    Invokevirtual(u16),       // Invoke virtual method
+    Invokestatic(u16),        // Invoke static method
+    Invokespecial(u16),       // Invoke special method (constructors, private methods)
+    Invokedynamic(u16),       // Invoke dynamic method (for lambda and string concatenation)
+    Invokeinterface(u16, u8), // Invoke interface method (index, argument slot count)
 
     // Object operations
-    New(u16), // Create new object
+    New(u16),        // Create new object
+    Checkcast(u16),  // Check that a reference is an instance of a class
+    Instanceof(u16), // Test whether a reference is an instance of a class
+
+    // Array operations
+    Newarray(u8),            // Create new primitive array (atype)
+    Anewarray(u16),          // Create new reference array
+    Multianewarray(u16, u8), // Create new multidimensional array
+    Arraylength,             // Get length of array
+    Iaload,                  // Load int from array
+    Laload,                  // Load long from array
+    Faload,                  // Load float from array
+    Daload,                  // Load double from array
+    Aaload,                  // Load reference from array
+    Baload,                  // Load byte or boolean from array
+    Caload,                  // Load char from array
+    Saload,                  // Load short from array
+    Iastore,                 // Store int into array
+    Lastore,                 // Store long into array
+    Fastore,                 // Store float into array
+    Dastore,                 // Store double into array
+    Aastore,                 // Store reference into array
+    Bastore,                 // Store byte or boolean into array
+    Castore,                 // Store char into array
+    Sastore,                 // Store short into array
+
+    // Switches; like other branches, targets are instruction indices
+    Tableswitch {
+        default: u16,
+        low: i32,
+        targets: Vec<u16>, // One target per key from `low` upwards
+    },
+    Lookupswitch {
+        default: u16,
+        pairs: Vec<(i32, u16)>, // (key, target), sorted by key
+    },
+
+    // Exceptions
+    Athrow, // Throw exception or error
 
     // Return instructions
     Return,  // Return void
     Ireturn, // Return int
+    Lreturn, // Return long
+    Freturn, // Return float
+    Dreturn, // Return double
+    Areturn, // Return reference
 
     // Field access
     Getstatic(u16), // Get static field
+    Putstatic(u16), // Set static field
+    Getfield(u16),  // Get instance field
+    Putfield(u16),  // Set instance field
 
     // Constants
     Dconst0, // Push double 0.0
@@ -117,19 +252,68 @@ pub enum JvmInstruction {
     Nop, // No operation
 }
 
+impl JvmInstruction {
+    /// Every target of a branch or switch instruction
+    pub fn branch_targets(&self) -> Vec<u16> {
+        let mut instruction = self.clone();
+        instruction
+            .branch_targets_mut()
+            .into_iter()
+            .map(|target| *target)
+            .collect()
+    }
+
+    /// Mutable access to every target of a branch or switch instruction
+    pub fn branch_targets_mut(&mut self) -> Vec<&mut u16> {
+        match self {
+            JvmInstruction::Ifeq(target)
+            | JvmInstruction::Ifne(target)
+            | JvmInstruction::Iflt(target)
+            | JvmInstruction::Ifge(target)
+            | JvmInstruction::Ifgt(target)
+            | JvmInstruction::Ifle(target)
+            | JvmInstruction::IfIcmpeq(target)
+            | JvmInstruction::IfIcmpne(target)
+            | JvmInstruction::IfIcmplt(target)
+            | JvmInstruction::IfIcmpge(target)
+            | JvmInstruction::IfIcmpgt(target)
+            | JvmInstruction::IfIcmple(target)
+            | JvmInstruction::IfAcmpeq(target)
+            | JvmInstruction::IfAcmpne(target)
+            | JvmInstruction::Ifnull(target)
+            | JvmInstruction::Ifnonnull(target)
+            | JvmInstruction::Goto(target) => vec![target],
+            JvmInstruction::Tableswitch {
+                default, targets, ..
+            } => std::iter::once(default).chain(targets.iter_mut()).collect(),
+            JvmInstruction::Lookupswitch { default, pairs } => std::iter::once(default)
+                .chain(pairs.iter_mut().map(|(_, target)| target))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
 /// Constant pool entry
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ConstantPoolEntry {
     Utf8(String),
     Class(u16),
     String(u16),
     Fieldref(u16, u16),
     Methodref(u16, u16),
+    InterfaceMethodref(u16, u16),
     NameAndType(u16, u16),
     Integer(i32),
     Float(f32),
     Long(i64),
     Double(f64),
+    MethodHandle(u8, u16), // reference_kind, reference_index
+    MethodType(u16),
+    Dynamic(u16, u16),       // bootstrap_method_attr_index, name_and_type_index
+    InvokeDynamic(u16, u16), // bootstrap_method_attr_index, name_and_type_index
+    Module(u16),
+    Package(u16),
     Placeholder, // Used for the second slot of 8-byte constants
 }
 
@@ -140,7 +324,7 @@ pub enum ConstantPoolError {
 }
 
 /// Constant pool
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ConstantPool {
     entries: Vec<ConstantPoolEntry>,
 }
@@ -158,7 +342,7 @@ impl ConstantPool {
         }
     }
 
-    pub fn add_utf8(&mut self, value: String) -> Result<u16, String> {
+    fn push_entry(&mut self, entry: ConstantPoolEntry) -> Result<u16, String> {
         let index = self.entries.len();
         if index >= u16::MAX as usize {
             return Err(format!(
@@ -166,32 +350,20 @@ impl ConstantPool {
                 u16::MAX
             ));
         }
-        self.entries.push(ConstantPoolEntry::Utf8(value));
+        self.entries.push(entry);
         Ok(index as u16 + 1)
     }
 
+    pub fn add_utf8(&mut self, value: String) -> Result<u16, String> {
+        self.push_entry(ConstantPoolEntry::Utf8(value))
+    }
+
     pub fn add_class(&mut self, name_index: u16) -> Result<u16, String> {
-        let index = self.entries.len();
-        if index >= u16::MAX as usize {
-            return Err(format!(
-                "Constant pool size exceeds the maximum limit of {}",
-                u16::MAX
-            ));
-        }
-        self.entries.push(ConstantPoolEntry::Class(name_index));
-        Ok(index as u16 + 1)
+        self.push_entry(ConstantPoolEntry::Class(name_index))
     }
 
     pub fn add_string(&mut self, utf8_index: u16) -> Result<u16, String> {
-        let index = self.entries.len();
-        if index >= u16::MAX as usize {
-            return Err(format!(
-                "Constant pool size exceeds the maximum limit of {}",
-                u16::MAX
-            ));
-        }
-        self.entries.push(ConstantPoolEntry::String(utf8_index));
-        Ok(index as u16 + 1)
+        self.push_entry(ConstantPoolEntry::String(utf8_index))
     }
 
     pub fn add_fieldref(
@@ -199,18 +371,10 @@ impl ConstantPool {
         class_index: u16,
         name_and_type_index: u16,
     ) -> Result<u16, String> {
-        let index = self.entries.len();
-        if index >= u16::MAX as usize {
-            return Err(format!(
-                "Constant pool size exceeds the maximum limit of {}",
-                u16::MAX
-            ));
-        }
-        self.entries.push(ConstantPoolEntry::Fieldref(
+        self.push_entry(ConstantPoolEntry::Fieldref(
             class_index,
             name_and_type_index,
-        ));
-        Ok(index as u16 + 1)
+        ))
     }
 
     pub fn add_methodref(
@@ -218,18 +382,21 @@ impl ConstantPool {
         class_index: u16,
         name_and_type_index: u16,
     ) -> Result<u16, String> {
-        let index = self.entries.len();
-        if index >= u16::MAX as usize {
-            return Err(format!(
-                "Constant pool size exceeds the maximum limit of {}",
-                u16::MAX
-            ));
-        }
-        self.entries.push(ConstantPoolEntry::Methodref(
+        self.push_entry(ConstantPoolEntry::Methodref(
             class_index,
             name_and_type_index,
-        ));
-        Ok(index as u16 + 1)
+        ))
+    }
+
+    pub fn add_interface_methodref(
+        &mut self,
+        class_index: u16,
+        name_and_type_index: u16,
+    ) -> Result<u16, String> {
+        self.push_entry(ConstantPoolEntry::InterfaceMethodref(
+            class_index,
+            name_and_type_index,
+        ))
     }
 
     pub fn add_name_and_type(
@@ -237,40 +404,15 @@ impl ConstantPool {
         name_index: u16,
         descriptor_index: u16,
     ) -> Result<u16, String> {
-        let index = self.entries.len();
-        if index >= u16::MAX as usize {
-            return Err(format!(
-                "Constant pool size exceeds the maximum limit of {}",
-                u16::MAX
-            ));
-        }
-        self.entries
-            .push(ConstantPoolEntry::NameAndType(name_index, descriptor_index));
-        Ok(index as u16 + 1)
+        self.push_entry(ConstantPoolEntry::NameAndType(name_index, descriptor_index))
     }
 
     pub fn add_integer(&mut self, value: i32) -> Result<u16, String> {
-        let index = self.entries.len();
-        if index >= u16::MAX as usize {
-            return Err(format!(
-                "Constant pool size exceeds the maximum limit of {}",
-                u16::MAX
-            ));
-        }
-        self.entries.push(ConstantPoolEntry::Integer(value));
-        Ok(index as u16 + 1)
+        self.push_entry(ConstantPoolEntry::Integer(value))
     }
 
     pub fn add_float(&mut self, value: f32) -> Result<u16, String> {
-        let index = self.entries.len();
-        if index >= u16::MAX as usize {
-            return Err(format!(
-                "Constant pool size exceeds the maximum limit of {}",
-                u16::MAX
-            ));
-        }
-        self.entries.push(ConstantPoolEntry::Float(value));
-        Ok(index as u16 + 1)
+        self.push_entry(ConstantPoolEntry::Float(value))
     }
 
     pub fn add_long(&mut self, value: i64) -> Result<u16, String> {
@@ -301,19 +443,403 @@ impl ConstantPool {
         Ok(index as u16 + 1)
     }
 
+    pub fn add_method_handle(
+        &mut self,
+        reference_kind: u8,
+        reference_index: u16,
+    ) -> Result<u16, String> {
+        self.push_entry(ConstantPoolEntry::MethodHandle(
+            reference_kind,
+            reference_index,
+        ))
+    }
+
+    pub fn add_method_type(&mut self, descriptor_index: u16) -> Result<u16, String> {
+        self.push_entry(ConstantPoolEntry::MethodType(descriptor_index))
+    }
+
+    pub fn add_dynamic(
+        &mut self,
+        bootstrap_method_attr_index: u16,
+        name_and_type_index: u16,
+    ) -> Result<u16, String> {
+        self.push_entry(ConstantPoolEntry::Dynamic(
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        ))
+    }
+
+    pub fn add_invoke_dynamic(
+        &mut self,
+        bootstrap_method_attr_index: u16,
+        name_and_type_index: u16,
+    ) -> Result<u16, String> {
+        self.push_entry(ConstantPoolEntry::InvokeDynamic(
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        ))
+    }
+
+    pub fn add_module(&mut self, name_index: u16) -> Result<u16, String> {
+        self.push_entry(ConstantPoolEntry::Module(name_index))
+    }
+
+    pub fn add_package(&mut self, name_index: u16) -> Result<u16, String> {
+        self.push_entry(ConstantPoolEntry::Package(name_index))
+    }
+
     pub fn add_placeholder(&mut self) -> Result<u16, String> {
-        let index = self.entries.len();
-        if index >= u16::MAX as usize {
-            return Err(format!(
-                "Constant pool size exceeds the maximum limit of {}",
-                u16::MAX
-            ));
+        self.push_entry(ConstantPoolEntry::Placeholder)
+    }
+
+    /// Look up an entry by its 1-based constant pool index
+    pub fn get(&self, index: u16) -> Option<&ConstantPoolEntry> {
+        if index == 0 {
+            return None;
+        }
+        self.entries.get((index - 1) as usize)
+    }
+
+    /// Look up a `CONSTANT_Utf8` entry by index
+    pub fn get_utf8(&self, index: u16) -> Option<&str> {
+        match self.get(index) {
+            Some(ConstantPoolEntry::Utf8(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Look up the name of a `CONSTANT_Class` entry by index
+    pub fn get_class_name(&self, index: u16) -> Option<&str> {
+        match self.get(index) {
+            Some(ConstantPoolEntry::Class(name_index)) => self.get_utf8(*name_index),
+            _ => None,
         }
-        self.entries.push(ConstantPoolEntry::Placeholder);
-        Ok(index as u16 + 1)
     }
 
     pub fn entries(&self) -> &Vec<ConstantPoolEntry> {
         &self.entries
     }
 }
+
+/// Entries a `ConstantPoolBuilder` shares, with references to other
+/// entries by index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum InternedEntry {
+    Utf8(String),
+    Class(u16),
+    String(u16),
+    Integer(i32),
+    NameAndType(u16, u16),
+    Fieldref(u16, u16),
+    Methodref(u16, u16),
+    InterfaceMethodref(u16, u16),
+}
+
+impl InternedEntry {
+    fn of(entry: &ConstantPoolEntry) -> Option<Self> {
+        Some(match entry {
+            ConstantPoolEntry::Utf8(value) => InternedEntry::Utf8(value.clone()),
+            ConstantPoolEntry::Class(name) => InternedEntry::Class(*name),
+            ConstantPoolEntry::String(value) => InternedEntry::String(*value),
+            ConstantPoolEntry::Integer(value) => InternedEntry::Integer(*value),
+            ConstantPoolEntry::NameAndType(name, descriptor) => {
+                InternedEntry::NameAndType(*name, *descriptor)
+            }
+            ConstantPoolEntry::Fieldref(class, name_and_type) => {
+                InternedEntry::Fieldref(*class, *name_and_type)
+            }
+            ConstantPoolEntry::Methodref(class, name_and_type) => {
+                InternedEntry::Methodref(*class, *name_and_type)
+            }
+            ConstantPoolEntry::InterfaceMethodref(class, name_and_type) => {
+                InternedEntry::InterfaceMethodref(*class, *name_and_type)
+            }
+            _ => return None,
+        })
+    }
+
+    fn into_entry(self) -> ConstantPoolEntry {
+        match self {
+            InternedEntry::Utf8(value) => ConstantPoolEntry::Utf8(value),
+            InternedEntry::Class(name) => ConstantPoolEntry::Class(name),
+            InternedEntry::String(value) => ConstantPoolEntry::String(value),
+            InternedEntry::Integer(value) => ConstantPoolEntry::Integer(value),
+            InternedEntry::NameAndType(name, descriptor) => {
+                ConstantPoolEntry::NameAndType(name, descriptor)
+            }
+            InternedEntry::Fieldref(class, name_and_type) => {
+                ConstantPoolEntry::Fieldref(class, name_and_type)
+            }
+            InternedEntry::Methodref(class, name_and_type) => {
+                ConstantPoolEntry::Methodref(class, name_and_type)
+            }
+            InternedEntry::InterfaceMethodref(class, name_and_type) => {
+                ConstantPoolEntry::InterfaceMethodref(class, name_and_type)
+            }
+        }
+    }
+}
+
+/// Constant pool built from names rather than indices. Each method returns
+/// the index of the entry it asks for, adding it and the entries it refers
+/// to only if the pool does not already hold them.
+#[derive(Debug, Clone, Default)]
+pub struct ConstantPoolBuilder {
+    constant_pool: ConstantPool,
+    indices: HashMap<InternedEntry, u16>,
+}
+
+impl From<ConstantPool> for ConstantPoolBuilder {
+    /// Continue building an existing pool, sharing the entries it has
+    fn from(constant_pool: ConstantPool) -> Self {
+        let mut indices = HashMap::new();
+        for (position, entry) in constant_pool.entries.iter().enumerate() {
+            if let Some(key) = InternedEntry::of(entry) {
+                indices.entry(key).or_insert(position as u16 + 1);
+            }
+        }
+        Self {
+            constant_pool,
+            indices,
+        }
+    }
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn intern(&mut self, key: InternedEntry) -> Result<u16, String> {
+        if let Some(&index) = self.indices.get(&key) {
+            return Ok(index);
+        }
+        let index = self.constant_pool.push_entry(key.clone().into_entry())?;
+        self.indices.insert(key, index);
+        Ok(index)
+    }
+
+    pub fn utf8(&mut self, value: &str) -> Result<u16, String> {
+        self.intern(InternedEntry::Utf8(value.to_string()))
+    }
+
+    /// `CONSTANT_Class` for an internal name such as `java/lang/Math`
+    pub fn class(&mut self, name: &str) -> Result<u16, String> {
+        let name = self.utf8(name)?;
+        self.intern(InternedEntry::Class(name))
+    }
+
+    pub fn string(&mut self, value: &str) -> Result<u16, String> {
+        let value = self.utf8(value)?;
+        self.intern(InternedEntry::String(value))
+    }
+
+    pub fn integer(&mut self, value: i32) -> Result<u16, String> {
+        self.intern(InternedEntry::Integer(value))
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, String> {
+        let name = self.utf8(name)?;
+        let descriptor = self.utf8(descriptor)?;
+        self.intern(InternedEntry::NameAndType(name, descriptor))
+    }
+
+    pub fn fieldref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, String> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.intern(InternedEntry::Fieldref(class, name_and_type))
+    }
+
+    pub fn methodref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, String> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.intern(InternedEntry::Methodref(class, name_and_type))
+    }
+
+    pub fn interface_methodref(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<u16, String> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.intern(InternedEntry::InterfaceMethodref(class, name_and_type))
+    }
+
+    pub fn constant_pool(&self) -> &ConstantPool {
+        &self.constant_pool
+    }
+
+    pub fn build(self) -> ConstantPool {
+        self.constant_pool
+    }
+}
+
+/// Split a method descriptor such as `(I[JLjava/lang/String;)V` into the
+/// descriptors of its parameters: `["I", "[J", "Ljava/lang/String;"]`
+pub fn method_parameter_types(descriptor: &str) -> Vec<&str> {
+    let Some(parameters) = descriptor
+        .strip_prefix('(')
+        .and_then(|rest| rest.split(')').next())
+    else {
+        return Vec::new();
+    };
+
+    let bytes = parameters.as_bytes();
+    let mut types = Vec::new();
+    let mut start = 0;
+    while start < bytes.len() {
+        let mut end = start;
+        while bytes[end] == b'[' && end + 1 < bytes.len() {
+            end += 1;
+        }
+        if bytes[end] == b'L' {
+            end = parameters[end..]
+                .find(';')
+                .map_or(bytes.len() - 1, |semicolon| end + semicolon);
+        }
+        types.push(&parameters[start..=end]);
+        start = end + 1;
+    }
+    types
+}
+
+/// Encode a string in the JVM's modified UTF-8
+///
+/// NUL is written as the two-byte sequence `C0 80` and supplementary
+/// characters as a pair of three-byte encoded surrogates.
+pub fn encode_modified_utf8(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.len());
+    for unit in value.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+/// Decode the JVM's modified UTF-8 into a string
+pub fn decode_modified_utf8(bytes: &[u8]) -> Result<String, String> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let first = bytes[i];
+        let continuation = |offset: usize| -> Result<u16, String> {
+            match bytes.get(i + offset) {
+                Some(&byte) if byte & 0xC0 == 0x80 => Ok((byte & 0x3F) as u16),
+                _ => Err(format!("malformed modified UTF-8 at byte {i}")),
+            }
+        };
+        match first {
+            0x01..=0x7F => {
+                units.push(first as u16);
+                i += 1;
+            }
+            0xC0..=0xDF => {
+                units.push(((first & 0x1F) as u16) << 6 | continuation(1)?);
+                i += 2;
+            }
+            0xE0..=0xEF => {
+                units
+                    .push(((first & 0x0F) as u16) << 12 | continuation(1)? << 6 | continuation(2)?);
+                i += 3;
+            }
+            _ => return Err(format!("malformed modified UTF-8 at byte {i}")),
+        }
+    }
+    String::from_utf16(&units).map_err(|_| "unpaired surrogate in modified UTF-8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_method_parameter_types() {
+        assert_eq!(
+            method_parameter_types("(I[JLjava/lang/String;[[Ljava/lang/Object;D)V"),
+            vec!["I", "[J", "Ljava/lang/String;", "[[Ljava/lang/Object;", "D"]
+        );
+        assert!(method_parameter_types("()V").is_empty());
+    }
+
+    #[test]
+    fn test_constant_pool_builder_shares_entries() {
+        let mut builder = ConstantPoolBuilder::new();
+        let random = builder
+            .methodref("java/lang/Math", "random", "()D")
+            .unwrap();
+        let math = builder.class("java/lang/Math").unwrap();
+        let out = builder
+            .fieldref("java/lang/System", "out", "Ljava/io/PrintStream;")
+            .unwrap();
+        let total = builder.string("Total: ").unwrap();
+
+        assert_eq!(
+            builder
+                .methodref("java/lang/Math", "random", "()D")
+                .unwrap(),
+            random
+        );
+        assert_eq!(
+            builder
+                .fieldref("java/lang/System", "out", "Ljava/io/PrintStream;")
+                .unwrap(),
+            out
+        );
+        assert_eq!(builder.string("Total: ").unwrap(), total);
+        assert_eq!(
+            builder.integer(70000).unwrap(),
+            builder.integer(70000).unwrap()
+        );
+
+        let constant_pool = builder.build();
+        assert_eq!(math, 2);
+        assert_eq!(constant_pool.get_class_name(math), Some("java/lang/Math"));
+        assert_eq!(total, 14);
+        assert_eq!(
+            constant_pool.get(total),
+            Some(&ConstantPoolEntry::String(13))
+        );
+
+        // A pool built elsewhere shares its entries too
+        let size = constant_pool.entries().len();
+        let mut builder = ConstantPoolBuilder::from(constant_pool);
+        assert_eq!(builder.class("java/lang/Math").unwrap(), math);
+        assert_eq!(builder.utf8("random").unwrap(), 3);
+        assert_eq!(builder.constant_pool().entries().len(), size);
+        assert_eq!(builder.class("java/lang/String").unwrap(), size as u16 + 2);
+    }
+
+    #[test]
+    fn test_modified_utf8_round_trip() {
+        for value in ["", "Total: ", "a\0b", "Würfel", "サイコロ", "🎲 roll"] {
+            let bytes = encode_modified_utf8(value);
+            assert_eq!(decode_modified_utf8(&bytes).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_modified_utf8_special_encodings() {
+        assert_eq!(encode_modified_utf8("\0"), vec![0xC0, 0x80]);
+        // U+1F3B2 is the surrogate pair D83C DFB2
+        assert_eq!(
+            encode_modified_utf8("🎲"),
+            vec![0xED, 0xA0, 0xBC, 0xED, 0xBE, 0xB2]
+        );
+        assert!(decode_modified_utf8(&[0x00]).is_err());
+        assert!(decode_modified_utf8(&[0xC3]).is_err());
+        assert!(decode_modified_utf8(&[0xF0, 0x9F, 0x8E, 0xB2]).is_err());
+        assert!(decode_modified_utf8(&[0xED, 0xA0, 0xBC]).is_err());
+    }
+}
@@ -1,11 +1,25 @@
 /// JVM-related modules
 pub mod class_file_parser;
+pub mod class_file_writer;
+pub mod class_loader;
 pub mod java_class_generator;
+pub mod java_source_generator;
 pub mod jvm_compatible_vm;
 pub mod jvm_types;
+pub mod natives;
+pub mod verifier;
+pub mod zip;
 
 // Public API
-pub use class_file_parser::ClassFileParser;
-pub use java_class_generator::{generate_java_class, generate_vm_instructions};
-pub use jvm_compatible_vm::JvmCompatibleVm;
+pub use class_file_parser::{ClassFile, ClassFileParser};
+pub use class_file_writer::ClassFileWriter;
+pub use class_loader::{ClassLoader, LoadedClass};
+pub use java_class_generator::{
+    DiceSource, JavaVersion, generate_java_class, generate_java_jar, generate_vm_instructions,
+};
+pub use java_source_generator::{JavaSourceGenerator, generate_java_source};
+pub use jvm_compatible_vm::{JvmCompatibleVm, VmLimits};
 pub use jvm_types::{ConstantPool, ConstantPoolEntry, JvmInstruction};
+pub use natives::{NativeMethod, NativeProvider, NativeRegistry};
+pub use verifier::{add_stack_map_table, code_limits, verify_class};
+pub use zip::{ZipArchive, ZipWriter};
@@ -0,0 +1,131 @@
+//! `java.util.Arrays`
+
+use super::collections::sort_values;
+use super::wrappers::compare_primitives;
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::error::RuntimeError;
+use crate::jvm::jvm_compatible_vm::{JvmCompatibleVm, JvmValue};
+use std::cmp::Ordering;
+
+const ARRAYS: &str = "java/util/Arrays";
+
+/// Primitive array types with natural-order sorts
+const SORTABLE_TYPES: &[&str] = &["I", "J", "S", "B", "C", "F", "D"];
+
+/// Array types accepted by `Arrays.toString`
+const PRINTABLE_TYPES: &[&str] = &["I", "J", "S", "B", "C", "F", "D", "Z", "Ljava/lang/Object;"];
+
+pub struct ArraysNatives;
+
+impl NativeProvider for ArraysNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        for &element_type in SORTABLE_TYPES {
+            registry.register(
+                ARRAYS,
+                "sort",
+                &format!("([{element_type})V"),
+                move |vm, arguments| {
+                    let elements = vm.array_mut(argument(arguments, 0)?)?;
+                    // Once every element is known to have the array's type,
+                    // comparisons cannot fail
+                    for element in elements.iter() {
+                        compare_primitives(element, element, element_type)?;
+                    }
+                    elements.sort_by(|a, b| {
+                        compare_primitives(a, b, element_type).unwrap_or(Ordering::Equal)
+                    });
+                    Ok(None)
+                },
+            );
+        }
+        registry.register(ARRAYS, "sort", "([Ljava/lang/Object;)V", |vm, arguments| {
+            sort_array(vm, argument(arguments, 0)?, &JvmValue::Reference(None))?;
+            Ok(None)
+        });
+        registry.register(
+            ARRAYS,
+            "sort",
+            "([Ljava/lang/Object;Ljava/util/Comparator;)V",
+            |vm, arguments| {
+                sort_array(vm, argument(arguments, 0)?, argument(arguments, 1)?)?;
+                Ok(None)
+            },
+        );
+
+        for &element_type in PRINTABLE_TYPES {
+            registry.register(
+                ARRAYS,
+                "toString",
+                &format!("([{element_type})Ljava/lang/String;"),
+                move |vm, arguments| {
+                    let array = argument(arguments, 0)?;
+                    let text = if array.is_null() {
+                        "null".to_string()
+                    } else {
+                        let elements: Vec<String> = vm
+                            .array_mut(array)?
+                            .clone()
+                            .iter()
+                            .map(|element| vm.value_to_java_string(element, element_type))
+                            .collect();
+                        format!("[{}]", elements.join(", "))
+                    };
+                    Ok(Some(vm.new_string(text)?))
+                },
+            );
+        }
+    }
+}
+
+fn sort_array(
+    vm: &mut JvmCompatibleVm,
+    array: &JvmValue,
+    comparator: &JvmValue,
+) -> Result<(), RuntimeError> {
+    let elements = vm.array_mut(array)?.clone();
+    let sorted = sort_values(vm, elements, comparator)?;
+    *vm.array_mut(array)? = sorted;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorting_doubles_orders_signed_zeros_and_nan() {
+        let mut vm = JvmCompatibleVm::new();
+        let weights = [f64::NAN, 0.5, 0.0, -0.0, -1.5]
+            .map(JvmValue::Double)
+            .to_vec();
+        let array = vm.new_array_of("[D", weights);
+        vm.invoke_native(ARRAYS, "sort", "([D)V", std::slice::from_ref(&array))
+            .unwrap();
+        let text = vm
+            .invoke_native(ARRAYS, "toString", "([D)Ljava/lang/String;", &[array])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vm.string_value(&text).unwrap(),
+            "[-1.5, -0.0, 0.0, 0.5, NaN]"
+        );
+    }
+
+    #[test]
+    fn test_to_string_renders_elements_by_type() {
+        let mut vm = JvmCompatibleVm::new();
+        let flags = vm.new_array_of("[Z", vec![JvmValue::Int(1), JvmValue::Int(0)]);
+        let text = vm
+            .invoke_native(ARRAYS, "toString", "([Z)Ljava/lang/String;", &[flags])
+            .unwrap()
+            .unwrap();
+        assert_eq!(vm.string_value(&text).unwrap(), "[true, false]");
+
+        let null = JvmValue::Reference(None);
+        let text = vm
+            .invoke_native(ARRAYS, "toString", "([I)Ljava/lang/String;", &[null])
+            .unwrap()
+            .unwrap();
+        assert_eq!(vm.string_value(&text).unwrap(), "null");
+    }
+}
@@ -0,0 +1,784 @@
+//! `java.util.ArrayList`, `java.util.HashMap` and `java.util.Collections`
+//!
+//! Elements and keys are compared through their `equals`, `hashCode` and
+//! `compareTo` methods, which may be natives or user bytecode. `subList`,
+//! `keySet` and `values` return `ArrayList` copies rather than views.
+
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::error::RuntimeError;
+use crate::jvm::jvm_compatible_vm::{JvmCompatibleVm, JvmValue};
+use std::cmp::Ordering;
+
+const ARRAY_LIST: &str = "java/util/ArrayList";
+const ITERATOR: &str = "java/util/ArrayList$Itr";
+const HASH_MAP: &str = "java/util/HashMap";
+const COLLECTIONS: &str = "java/util/Collections";
+pub(super) const REVERSE_COMPARATOR: &str = "java/util/Collections$ReverseComparator";
+
+/// Table size of a `HashMap` before its first resize
+const DEFAULT_CAPACITY: usize = 16;
+
+/// Entries of a `java.util.HashMap`, kept in the order the JDK iterates
+/// them: by bucket, then by insertion within a bucket
+#[derive(Debug, Clone, Default)]
+pub(crate) struct JavaHashMap {
+    /// Size of the bucket table, or 0 before the first insertion
+    capacity: usize,
+    entries: Vec<MapEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct MapEntry {
+    hash: i32,
+    key: JvmValue,
+    value: JvmValue,
+}
+
+impl JavaHashMap {
+    /// An empty map whose table will hold at least `capacity` buckets
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1).next_power_of_two(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&JvmValue, &JvmValue)> {
+        self.entries.iter().map(|entry| (&entry.key, &entry.value))
+    }
+
+    fn insert(&mut self, hash: i32, key: JvmValue, value: JvmValue) {
+        if self.capacity == 0 {
+            self.capacity = DEFAULT_CAPACITY;
+        }
+        self.entries.push(MapEntry { hash, key, value });
+        // Resize past a load factor of 0.75. Splitting a bucket keeps the
+        // relative order of its entries, which a stable sort preserves.
+        if self.entries.len() > self.capacity * 3 / 4 {
+            self.capacity *= 2;
+        }
+        let mask = self.capacity - 1;
+        self.entries
+            .sort_by_key(|entry| entry.hash as u32 as usize & mask);
+    }
+}
+
+pub struct CollectionNatives;
+
+impl NativeProvider for CollectionNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        register_array_list(registry);
+        register_iterator(registry);
+        register_hash_map(registry);
+        register_collections(registry);
+    }
+}
+
+fn register_array_list(registry: &mut NativeRegistry) {
+    registry.register(ARRAY_LIST, "<init>", "()V", |vm, arguments| {
+        vm.list_mut(argument(arguments, 0)?)?.clear();
+        Ok(None)
+    });
+    registry.register(ARRAY_LIST, "<init>", "(I)V", |vm, arguments| {
+        let capacity = argument(arguments, 1)?.as_int()?;
+        if capacity < 0 {
+            return Err(vm.throw_exception(
+                "java/lang/IllegalArgumentException",
+                Some(format!("Illegal Capacity: {capacity}")),
+            ));
+        }
+        vm.list_mut(argument(arguments, 0)?)?.clear();
+        Ok(None)
+    });
+    registry.register(
+        ARRAY_LIST,
+        "<init>",
+        "(Ljava/util/Collection;)V",
+        |vm, arguments| {
+            let elements = vm.list_mut(argument(arguments, 1)?)?.clone();
+            *vm.list_mut(argument(arguments, 0)?)? = elements;
+            Ok(None)
+        },
+    );
+
+    registry.register(ARRAY_LIST, "size", "()I", |vm, arguments| {
+        let size = vm.list_mut(argument(arguments, 0)?)?.len();
+        Ok(Some(JvmValue::Int(size as i32)))
+    });
+    registry.register(ARRAY_LIST, "isEmpty", "()Z", |vm, arguments| {
+        let empty = vm.list_mut(argument(arguments, 0)?)?.is_empty();
+        Ok(Some(JvmValue::Boolean(empty)))
+    });
+    registry.register(ARRAY_LIST, "clear", "()V", |vm, arguments| {
+        vm.list_mut(argument(arguments, 0)?)?.clear();
+        Ok(None)
+    });
+    registry.register(
+        ARRAY_LIST,
+        "add",
+        "(Ljava/lang/Object;)Z",
+        |vm, arguments| {
+            let element = argument(arguments, 1)?.clone();
+            vm.list_mut(argument(arguments, 0)?)?.push(element);
+            Ok(Some(JvmValue::Boolean(true)))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "add",
+        "(ILjava/lang/Object;)V",
+        |vm, arguments| {
+            let list = argument(arguments, 0)?;
+            let index = argument(arguments, 1)?.as_int()?;
+            let size = vm.list_mut(list)?.len();
+            if index < 0 || index as usize > size {
+                return Err(vm.throw_exception(
+                    "java/lang/IndexOutOfBoundsException",
+                    Some(format!("Index: {index}, Size: {size}")),
+                ));
+            }
+            let element = argument(arguments, 2)?.clone();
+            vm.list_mut(list)?.insert(index as usize, element);
+            Ok(None)
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "addAll",
+        "(Ljava/util/Collection;)Z",
+        |vm, arguments| {
+            let elements = vm.list_mut(argument(arguments, 1)?)?.clone();
+            let changed = !elements.is_empty();
+            vm.list_mut(argument(arguments, 0)?)?.extend(elements);
+            Ok(Some(JvmValue::Boolean(changed)))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "get",
+        "(I)Ljava/lang/Object;",
+        |vm, arguments| {
+            let list = argument(arguments, 0)?;
+            let index = list_index(vm, list, argument(arguments, 1)?)?;
+            Ok(Some(vm.list_mut(list)?[index].clone()))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "set",
+        "(ILjava/lang/Object;)Ljava/lang/Object;",
+        |vm, arguments| {
+            let list = argument(arguments, 0)?;
+            let index = list_index(vm, list, argument(arguments, 1)?)?;
+            let element = argument(arguments, 2)?.clone();
+            let previous = std::mem::replace(&mut vm.list_mut(list)?[index], element);
+            Ok(Some(previous))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "remove",
+        "(I)Ljava/lang/Object;",
+        |vm, arguments| {
+            let list = argument(arguments, 0)?;
+            let index = list_index(vm, list, argument(arguments, 1)?)?;
+            Ok(Some(vm.list_mut(list)?.remove(index)))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "remove",
+        "(Ljava/lang/Object;)Z",
+        |vm, arguments| {
+            let list = argument(arguments, 0)?;
+            let position = index_of(vm, list, argument(arguments, 1)?)?;
+            if let Some(position) = position {
+                vm.list_mut(list)?.remove(position);
+            }
+            Ok(Some(JvmValue::Boolean(position.is_some())))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "contains",
+        "(Ljava/lang/Object;)Z",
+        |vm, arguments| {
+            let position = index_of(vm, argument(arguments, 0)?, argument(arguments, 1)?)?;
+            Ok(Some(JvmValue::Boolean(position.is_some())))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "indexOf",
+        "(Ljava/lang/Object;)I",
+        |vm, arguments| {
+            let position = index_of(vm, argument(arguments, 0)?, argument(arguments, 1)?)?;
+            Ok(Some(JvmValue::Int(
+                position.map_or(-1, |position| position as i32),
+            )))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "subList",
+        "(II)Ljava/util/List;",
+        |vm, arguments| {
+            let elements = vm.list_mut(argument(arguments, 0)?)?.clone();
+            let from = argument(arguments, 1)?.as_int()?;
+            let to = argument(arguments, 2)?.as_int()?;
+            let (exception, message) = if from < 0 {
+                ("IndexOutOfBoundsException", format!("fromIndex = {from}"))
+            } else if to as usize > elements.len() {
+                ("IndexOutOfBoundsException", format!("toIndex = {to}"))
+            } else if from > to {
+                (
+                    "IllegalArgumentException",
+                    format!("fromIndex({from}) > toIndex({to})"),
+                )
+            } else {
+                let elements = elements[from as usize..to as usize].to_vec();
+                return Ok(Some(new_list(vm, elements)?));
+            };
+            Err(vm.throw_exception(&format!("java/lang/{exception}"), Some(message)))
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "sort",
+        "(Ljava/util/Comparator;)V",
+        |vm, arguments| {
+            let list = argument(arguments, 0)?;
+            let elements = vm.list_mut(list)?.clone();
+            let sorted = sort_values(vm, elements, argument(arguments, 1)?)?;
+            *vm.list_mut(list)? = sorted;
+            Ok(None)
+        },
+    );
+    registry.register(
+        ARRAY_LIST,
+        "iterator",
+        "()Ljava/util/Iterator;",
+        |vm, arguments| {
+            let iterator = vm.new_instance(ITERATOR)?;
+            vm.set_object_field(&iterator, "list", argument(arguments, 0)?.clone())?;
+            vm.set_object_field(&iterator, "cursor", JvmValue::Int(0))?;
+            Ok(Some(iterator))
+        },
+    );
+}
+
+/// The iterator of an `ArrayList`, as used by for-each loops
+fn register_iterator(registry: &mut NativeRegistry) {
+    registry.register(ITERATOR, "hasNext", "()Z", |vm, arguments| {
+        let (list, cursor) = iterator_state(vm, argument(arguments, 0)?)?;
+        let size = vm.list_mut(&list)?.len();
+        Ok(Some(JvmValue::Boolean(cursor < size)))
+    });
+    registry.register(ITERATOR, "next", "()Ljava/lang/Object;", |vm, arguments| {
+        let iterator = argument(arguments, 0)?;
+        let (list, cursor) = iterator_state(vm, iterator)?;
+        let Some(element) = vm.list_mut(&list)?.get(cursor).cloned() else {
+            return Err(vm.throw_exception("java/util/NoSuchElementException", None));
+        };
+        vm.set_object_field(iterator, "cursor", JvmValue::Int(cursor as i32 + 1))?;
+        Ok(Some(element))
+    });
+}
+
+fn iterator_state(
+    vm: &JvmCompatibleVm,
+    iterator: &JvmValue,
+) -> Result<(JvmValue, usize), RuntimeError> {
+    match (
+        vm.object_field(iterator, "list"),
+        vm.object_field(iterator, "cursor"),
+    ) {
+        (Some(list), Some(cursor)) => Ok((list.clone(), cursor.as_int()? as usize)),
+        _ => Err(RuntimeError::InvalidStackState),
+    }
+}
+
+fn register_hash_map(registry: &mut NativeRegistry) {
+    registry.register(HASH_MAP, "<init>", "()V", |vm, arguments| {
+        *vm.map_mut(argument(arguments, 0)?)? = JavaHashMap::default();
+        Ok(None)
+    });
+    registry.register(HASH_MAP, "<init>", "(I)V", |vm, arguments| {
+        let capacity = argument(arguments, 1)?.as_int()?;
+        if capacity < 0 {
+            return Err(vm.throw_exception(
+                "java/lang/IllegalArgumentException",
+                Some(format!("Illegal initial capacity: {capacity}")),
+            ));
+        }
+        *vm.map_mut(argument(arguments, 0)?)? = JavaHashMap::with_capacity(capacity as usize);
+        Ok(None)
+    });
+
+    registry.register(HASH_MAP, "size", "()I", |vm, arguments| {
+        let size = vm.map_mut(argument(arguments, 0)?)?.entries.len();
+        Ok(Some(JvmValue::Int(size as i32)))
+    });
+    registry.register(HASH_MAP, "isEmpty", "()Z", |vm, arguments| {
+        let empty = vm.map_mut(argument(arguments, 0)?)?.entries.is_empty();
+        Ok(Some(JvmValue::Boolean(empty)))
+    });
+    registry.register(HASH_MAP, "clear", "()V", |vm, arguments| {
+        vm.map_mut(argument(arguments, 0)?)?.entries.clear();
+        Ok(None)
+    });
+    registry.register(
+        HASH_MAP,
+        "put",
+        "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+        |vm, arguments| {
+            let map = argument(arguments, 0)?;
+            let key = argument(arguments, 1)?;
+            let value = argument(arguments, 2)?.clone();
+            let (hash, position) = find_key(vm, map, key)?;
+            let map = vm.map_mut(map)?;
+            let previous = match position {
+                Some(position) => std::mem::replace(&mut map.entries[position].value, value),
+                None => {
+                    map.insert(hash, key.clone(), value);
+                    JvmValue::Reference(None)
+                }
+            };
+            Ok(Some(previous))
+        },
+    );
+    registry.register(
+        HASH_MAP,
+        "get",
+        "(Ljava/lang/Object;)Ljava/lang/Object;",
+        |vm, arguments| {
+            let value = map_value(vm, argument(arguments, 0)?, argument(arguments, 1)?)?;
+            Ok(Some(value.unwrap_or(JvmValue::Reference(None))))
+        },
+    );
+    registry.register(
+        HASH_MAP,
+        "getOrDefault",
+        "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+        |vm, arguments| {
+            let value = map_value(vm, argument(arguments, 0)?, argument(arguments, 1)?)?;
+            Ok(Some(match value {
+                Some(value) => value,
+                None => argument(arguments, 2)?.clone(),
+            }))
+        },
+    );
+    registry.register(
+        HASH_MAP,
+        "containsKey",
+        "(Ljava/lang/Object;)Z",
+        |vm, arguments| {
+            let (_, position) = find_key(vm, argument(arguments, 0)?, argument(arguments, 1)?)?;
+            Ok(Some(JvmValue::Boolean(position.is_some())))
+        },
+    );
+    registry.register(
+        HASH_MAP,
+        "remove",
+        "(Ljava/lang/Object;)Ljava/lang/Object;",
+        |vm, arguments| {
+            let map = argument(arguments, 0)?;
+            let (_, position) = find_key(vm, map, argument(arguments, 1)?)?;
+            Ok(Some(match position {
+                Some(position) => vm.map_mut(map)?.entries.remove(position).value,
+                None => JvmValue::Reference(None),
+            }))
+        },
+    );
+    registry.register(HASH_MAP, "keySet", "()Ljava/util/Set;", |vm, arguments| {
+        let keys = vm
+            .map_mut(argument(arguments, 0)?)?
+            .entries()
+            .map(|(key, _)| key.clone())
+            .collect();
+        Ok(Some(new_list(vm, keys)?))
+    });
+    registry.register(
+        HASH_MAP,
+        "values",
+        "()Ljava/util/Collection;",
+        |vm, arguments| {
+            let values = vm
+                .map_mut(argument(arguments, 0)?)?
+                .entries()
+                .map(|(_, value)| value.clone())
+                .collect();
+            Ok(Some(new_list(vm, values)?))
+        },
+    );
+}
+
+fn register_collections(registry: &mut NativeRegistry) {
+    registry.register(
+        COLLECTIONS,
+        "sort",
+        "(Ljava/util/List;)V",
+        |vm, arguments| {
+            let list = argument(arguments, 0)?;
+            let elements = vm.list_mut(list)?.clone();
+            let sorted = sort_values(vm, elements, &JvmValue::Reference(None))?;
+            *vm.list_mut(list)? = sorted;
+            Ok(None)
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "sort",
+        "(Ljava/util/List;Ljava/util/Comparator;)V",
+        |vm, arguments| {
+            let list = argument(arguments, 0)?;
+            let elements = vm.list_mut(list)?.clone();
+            let sorted = sort_values(vm, elements, argument(arguments, 1)?)?;
+            *vm.list_mut(list)? = sorted;
+            Ok(None)
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "reverse",
+        "(Ljava/util/List;)V",
+        |vm, arguments| {
+            vm.list_mut(argument(arguments, 0)?)?.reverse();
+            Ok(None)
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "reverseOrder",
+        "()Ljava/util/Comparator;",
+        |vm, _| Ok(Some(vm.new_instance(REVERSE_COMPARATOR)?)),
+    );
+    registry.register(
+        REVERSE_COMPARATOR,
+        "compare",
+        "(Ljava/lang/Object;Ljava/lang/Object;)I",
+        |vm, arguments| {
+            let ordering = compare_values(
+                vm,
+                argument(arguments, 0)?,
+                argument(arguments, 1)?,
+                argument(arguments, 2)?,
+            )?;
+            Ok(Some(JvmValue::Int(ordering as i32)))
+        },
+    );
+    for (name, wanted) in [("max", Ordering::Greater), ("min", Ordering::Less)] {
+        registry.register(
+            COLLECTIONS,
+            name,
+            "(Ljava/util/Collection;)Ljava/lang/Object;",
+            move |vm, arguments| {
+                let elements = vm.list_mut(argument(arguments, 0)?)?.clone();
+                let mut elements = elements.into_iter();
+                let Some(mut best) = elements.next() else {
+                    return Err(vm.throw_exception("java/util/NoSuchElementException", None));
+                };
+                for element in elements {
+                    if compare_natural(vm, &element, &best)? == wanted {
+                        best = element;
+                    }
+                }
+                Ok(Some(best))
+            },
+        );
+    }
+}
+
+/// Allocate an `ArrayList` holding the given elements
+pub(super) fn new_list(
+    vm: &mut JvmCompatibleVm,
+    elements: Vec<JvmValue>,
+) -> Result<JvmValue, RuntimeError> {
+    let list = vm.new_instance(ARRAY_LIST)?;
+    *vm.list_mut(&list)? = elements;
+    Ok(list)
+}
+
+/// Check an index into a list, raising the JDK's exception when it is out
+/// of bounds
+fn list_index(
+    vm: &mut JvmCompatibleVm,
+    list: &JvmValue,
+    index: &JvmValue,
+) -> Result<usize, RuntimeError> {
+    let index = index.as_int()?;
+    let length = vm.list_mut(list)?.len();
+    if index < 0 || index as usize >= length {
+        return Err(vm.throw_exception(
+            "java/lang/IndexOutOfBoundsException",
+            Some(format!("Index {index} out of bounds for length {length}")),
+        ));
+    }
+    Ok(index as usize)
+}
+
+/// Position of the first element equal to `target`
+fn index_of(
+    vm: &mut JvmCompatibleVm,
+    list: &JvmValue,
+    target: &JvmValue,
+) -> Result<Option<usize>, RuntimeError> {
+    let elements = vm.list_mut(list)?.clone();
+    for (position, element) in elements.iter().enumerate() {
+        if java_equals(vm, target, element)? {
+            return Ok(Some(position));
+        }
+    }
+    Ok(None)
+}
+
+/// The spread hash of a key and the position of its entry, if present
+fn find_key(
+    vm: &mut JvmCompatibleVm,
+    map: &JvmValue,
+    key: &JvmValue,
+) -> Result<(i32, Option<usize>), RuntimeError> {
+    let hash = key_hash(vm, key)?;
+    let candidates: Vec<(usize, JvmValue)> = vm
+        .map_mut(map)?
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.hash == hash)
+        .map(|(position, entry)| (position, entry.key.clone()))
+        .collect();
+    for (position, candidate) in candidates {
+        if candidate == *key || java_equals(vm, key, &candidate)? {
+            return Ok((hash, Some(position)));
+        }
+    }
+    Ok((hash, None))
+}
+
+fn map_value(
+    vm: &mut JvmCompatibleVm,
+    map: &JvmValue,
+    key: &JvmValue,
+) -> Result<Option<JvmValue>, RuntimeError> {
+    let (_, position) = find_key(vm, map, key)?;
+    Ok(match position {
+        Some(position) => Some(vm.map_mut(map)?.entries[position].value.clone()),
+        None => None,
+    })
+}
+
+/// `HashMap.hash`: the key's hash code with its high bits spread into the
+/// low bits that select a bucket
+fn key_hash(vm: &mut JvmCompatibleVm, key: &JvmValue) -> Result<i32, RuntimeError> {
+    if key.is_null() {
+        return Ok(0);
+    }
+    let hash = match vm.call_method(key, "hashCode", "()I", &[])? {
+        Some(hash) => hash.as_int()?,
+        None => return Err(RuntimeError::InvalidStackState),
+    };
+    Ok(hash ^ ((hash as u32) >> 16) as i32)
+}
+
+/// `Objects.equals`
+pub(super) fn java_equals(
+    vm: &mut JvmCompatibleVm,
+    a: &JvmValue,
+    b: &JvmValue,
+) -> Result<bool, RuntimeError> {
+    if a.is_null() {
+        return Ok(b.is_null());
+    }
+    match vm.call_method(
+        a,
+        "equals",
+        "(Ljava/lang/Object;)Z",
+        std::slice::from_ref(b),
+    )? {
+        Some(equal) => equal.as_boolean(),
+        None => Err(RuntimeError::InvalidStackState),
+    }
+}
+
+/// Natural ordering through `Comparable.compareTo`
+pub(super) fn compare_natural(
+    vm: &mut JvmCompatibleVm,
+    a: &JvmValue,
+    b: &JvmValue,
+) -> Result<Ordering, RuntimeError> {
+    match vm.call_method(
+        a,
+        "compareTo",
+        "(Ljava/lang/Object;)I",
+        std::slice::from_ref(b),
+    ) {
+        Ok(Some(result)) => Ok(result.as_int()?.cmp(&0)),
+        Ok(None) => Err(RuntimeError::InvalidStackState),
+        Err(RuntimeError::NoSuchMethod(_)) => {
+            let class_name = vm.object_class(a).unwrap_or_default().replace('/', ".");
+            Err(vm.throw_exception(
+                "java/lang/ClassCastException",
+                Some(format!(
+                    "class {class_name} cannot be cast to class java.lang.Comparable"
+                )),
+            ))
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Order two values with a comparator; null means natural ordering
+pub(super) fn compare_values(
+    vm: &mut JvmCompatibleVm,
+    comparator: &JvmValue,
+    a: &JvmValue,
+    b: &JvmValue,
+) -> Result<Ordering, RuntimeError> {
+    if comparator.is_null() {
+        return compare_natural(vm, a, b);
+    }
+    if vm.object_class(comparator) == Some(REVERSE_COMPARATOR) {
+        return compare_natural(vm, b, a);
+    }
+    let result = vm.call_method(
+        comparator,
+        "compare",
+        "(Ljava/lang/Object;Ljava/lang/Object;)I",
+        &[a.clone(), b.clone()],
+    )?;
+    match result {
+        Some(result) => Ok(result.as_int()?.cmp(&0)),
+        None => Err(RuntimeError::InvalidStackState),
+    }
+}
+
+/// Stable merge sort, like the JDK's sort for objects. Comparisons may run
+/// bytecode and fail, so the standard library's sort cannot be used.
+pub(super) fn sort_values(
+    vm: &mut JvmCompatibleVm,
+    mut values: Vec<JvmValue>,
+    comparator: &JvmValue,
+) -> Result<Vec<JvmValue>, RuntimeError> {
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+    let right = values.split_off(values.len() / 2);
+    let left = sort_values(vm, values, comparator)?;
+    let right = sort_values(vm, right, comparator)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // Taking from the left on ties keeps the sort stable
+        if compare_values(vm, comparator, b, a)? == Ordering::Less {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxed(vm: &mut JvmCompatibleVm, value: i32) -> JvmValue {
+        vm.invoke_native(
+            "java/lang/Integer",
+            "valueOf",
+            "(I)Ljava/lang/Integer;",
+            &[JvmValue::Int(value)],
+        )
+        .unwrap()
+        .unwrap()
+    }
+
+    fn render(vm: &JvmCompatibleVm, value: &JvmValue) -> String {
+        vm.value_to_java_string(value, "Ljava/lang/Object;")
+    }
+
+    #[test]
+    fn test_array_list_compares_elements_with_equals() {
+        let mut vm = JvmCompatibleVm::new();
+        let rolls: Vec<JvmValue> = [5, 3, 5].map(|roll| boxed(&mut vm, roll)).to_vec();
+        let list = new_list(&mut vm, rolls).unwrap();
+        assert_eq!(render(&vm, &list), "[5, 3, 5]");
+
+        // A different box holding the same value is found
+        let five = boxed(&mut vm, 5);
+        let result = vm
+            .call_method(
+                &list,
+                "indexOf",
+                "(Ljava/lang/Object;)I",
+                std::slice::from_ref(&five),
+            )
+            .unwrap();
+        assert_eq!(result, Some(JvmValue::Int(0)));
+        vm.call_method(&list, "remove", "(Ljava/lang/Object;)Z", &[five])
+            .unwrap();
+        assert_eq!(render(&vm, &list), "[3, 5]");
+
+        let result = vm.call_method(&list, "get", "(I)Ljava/lang/Object;", &[JvmValue::Int(2)]);
+        assert!(matches!(result, Err(RuntimeError::Exception(_))));
+    }
+
+    #[test]
+    fn test_hash_map_iterates_in_jdk_bucket_order() {
+        // Expected order from a reference JVM, including a resize at the
+        // thirteenth key
+        let mut vm = JvmCompatibleVm::new();
+        let map = vm.new_instance(HASH_MAP).unwrap();
+        let put = "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;";
+        for word in ["fumble", "hit", "critical", "miss", "graze"] {
+            let key = vm.new_string(word).unwrap();
+            vm.call_method(&map, "put", put, &[key, JvmValue::Reference(None)])
+                .unwrap();
+        }
+        assert_eq!(
+            render(&vm, &map),
+            "{graze=null, hit=null, critical=null, fumble=null, miss=null}"
+        );
+
+        let map = vm.new_instance(HASH_MAP).unwrap();
+        for key in [40, 3, 17, 100, 1, 64, 33, 5, 18, 2, 16, 50, 7] {
+            let key = boxed(&mut vm, key);
+            let value = boxed(&mut vm, 0);
+            vm.call_method(&map, "put", put, &[key, value]).unwrap();
+        }
+        assert_eq!(
+            render(&vm, &map),
+            "{64=0, 1=0, 33=0, 2=0, 3=0, 100=0, 5=0, 7=0, 40=0, 16=0, 17=0, 18=0, 50=0}"
+        );
+    }
+
+    #[test]
+    fn test_sorting_is_stable_and_rejects_incomparable_elements() {
+        let mut vm = JvmCompatibleVm::new();
+        let values: Vec<JvmValue> = [3, 1, 2, 1].map(|value| boxed(&mut vm, value)).to_vec();
+        let sorted = sort_values(&mut vm, values.clone(), &JvmValue::Reference(None)).unwrap();
+        assert_eq!(
+            sorted,
+            [&values[1], &values[3], &values[2], &values[0]].map(Clone::clone)
+        );
+
+        let reverse = vm.new_instance(REVERSE_COMPARATOR).unwrap();
+        let sorted = sort_values(&mut vm, values.clone(), &reverse).unwrap();
+        assert_eq!(
+            sorted,
+            [&values[0], &values[2], &values[1], &values[3]].map(Clone::clone)
+        );
+
+        let random = vm.new_instance("java/util/Random").unwrap();
+        let result = sort_values(
+            &mut vm,
+            vec![random.clone(), random],
+            &JvmValue::Reference(None),
+        );
+        assert!(matches!(result, Err(RuntimeError::Exception(_))));
+    }
+}
@@ -0,0 +1,1031 @@
+//! Kotlin standard library shims
+//!
+//! `kotlinc` output calls into `kotlin-stdlib` for null checks, ranges,
+//! `random()` and collection helpers. These natives cover the parts small
+//! programs use, so Kotlin class files run without the stdlib jar. Kotlin's
+//! `println` is inlined into `System.out.println` by the compiler; the
+//! `ConsoleKt` natives serve code compiled without inlining.
+
+use super::collections::{REVERSE_COMPARATOR, compare_natural, java_equals, new_list, sort_values};
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::error::RuntimeError;
+use crate::jvm::jvm_compatible_vm::{JvmCompatibleVm, JvmValue};
+use rand::Rng;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+const INTRINSICS: &str = "kotlin/jvm/internal/Intrinsics";
+const CONSOLE: &str = "kotlin/io/ConsoleKt";
+const INT_RANGE: &str = "kotlin/ranges/IntRange";
+const INT_PROGRESSION: &str = "kotlin/ranges/IntProgression";
+const PROGRESSION_ITERATOR: &str = "kotlin/ranges/IntProgressionIterator";
+const RANGES: &str = "kotlin/ranges/RangesKt";
+const DEFAULT_RANDOM: &str = "kotlin/random/Random$Default";
+const RANDOM_KT: &str = "kotlin/random/RandomKt";
+const COLLECTIONS: &str = "kotlin/collections/CollectionsKt";
+
+const NULL_POINTER: &str = "java/lang/NullPointerException";
+const ILLEGAL_ARGUMENT: &str = "java/lang/IllegalArgumentException";
+const NO_SUCH_ELEMENT: &str = "java/util/NoSuchElementException";
+
+/// Parameter types of the `ConsoleKt` overloads of `print` and `println`
+const CONSOLE_TYPES: &[&str] = &["I", "J", "F", "D", "Z", "C", "Ljava/lang/Object;"];
+
+pub struct KotlinNatives;
+
+impl NativeProvider for KotlinNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        register_intrinsics(registry);
+        register_console(registry);
+        register_ranges(registry);
+        register_random(registry);
+        register_collections(registry);
+    }
+}
+
+fn register_intrinsics(registry: &mut NativeRegistry) {
+    // `x!!`
+    registry.register(
+        INTRINSICS,
+        "checkNotNull",
+        "(Ljava/lang/Object;)V",
+        |vm, arguments| {
+            if argument(arguments, 0)?.is_null() {
+                return Err(vm.throw_exception(NULL_POINTER, None));
+            }
+            Ok(None)
+        },
+    );
+    registry.register(
+        INTRINSICS,
+        "checkNotNull",
+        "(Ljava/lang/Object;Ljava/lang/String;)V",
+        |vm, arguments| check_not_null(vm, arguments, NULL_POINTER, |_, message| message),
+    );
+    // Non-null parameters of public functions, checked on entry
+    for name in ["checkNotNullParameter", "checkParameterIsNotNull"] {
+        registry.register(
+            INTRINSICS,
+            name,
+            "(Ljava/lang/Object;Ljava/lang/String;)V",
+            |vm, arguments| {
+                check_not_null(vm, arguments, NULL_POINTER, |vm, parameter| {
+                    let method = match vm.calling_method() {
+                        Some((class_name, method_name)) => {
+                            format!("method {}.{method_name}, ", class_name.replace('/', "."))
+                        }
+                        None => String::new(),
+                    };
+                    format!(
+                        "Parameter specified as non-null is null: {method}parameter {parameter}"
+                    )
+                })
+            },
+        );
+    }
+    // Platform-typed values returned by Java methods
+    for (name, exception) in [
+        ("checkNotNullExpressionValue", NULL_POINTER),
+        (
+            "checkExpressionValueIsNotNull",
+            "java/lang/IllegalStateException",
+        ),
+    ] {
+        registry.register(
+            INTRINSICS,
+            name,
+            "(Ljava/lang/Object;Ljava/lang/String;)V",
+            move |vm, arguments| {
+                check_not_null(vm, arguments, exception, |_, expression| {
+                    format!("{expression} must not be null")
+                })
+            },
+        );
+    }
+    // `a == b` on references
+    registry.register(
+        INTRINSICS,
+        "areEqual",
+        "(Ljava/lang/Object;Ljava/lang/Object;)Z",
+        |vm, arguments| {
+            let equal = java_equals(vm, argument(arguments, 0)?, argument(arguments, 1)?)?;
+            Ok(Some(JvmValue::Boolean(equal)))
+        },
+    );
+    registry.register(INTRINSICS, "compare", "(II)I", |_, arguments| {
+        let a = argument(arguments, 0)?.as_int()?;
+        let b = argument(arguments, 1)?.as_int()?;
+        Ok(Some(JvmValue::Int(a.cmp(&b) as i32)))
+    });
+    registry.register(
+        INTRINSICS,
+        "stringPlus",
+        "(Ljava/lang/String;Ljava/lang/Object;)Ljava/lang/String;",
+        |vm, arguments| {
+            let text = format!(
+                "{}{}",
+                string_of(vm, argument(arguments, 0)?)?,
+                string_of(vm, argument(arguments, 1)?)?
+            );
+            Ok(Some(vm.new_string(text)?))
+        },
+    );
+}
+
+/// Raise `exception` if the first argument is null, with a message built
+/// from the name passed as the second
+fn check_not_null(
+    vm: &mut JvmCompatibleVm,
+    arguments: &[JvmValue],
+    exception: &str,
+    message: impl FnOnce(&JvmCompatibleVm, String) -> String,
+) -> Result<Option<JvmValue>, RuntimeError> {
+    if !argument(arguments, 0)?.is_null() {
+        return Ok(None);
+    }
+    let name = vm.string_value(argument(arguments, 1)?)?.to_string();
+    let message = message(vm, name);
+    Err(vm.throw_exception(exception, Some(message)))
+}
+
+fn register_console(registry: &mut NativeRegistry) {
+    for &parameter_type in CONSOLE_TYPES {
+        registry.register(
+            CONSOLE,
+            "print",
+            &format!("({parameter_type})V"),
+            move |vm, arguments| {
+                print!(
+                    "{}",
+                    vm.value_to_java_string(argument(arguments, 0)?, parameter_type)
+                );
+                Ok(None)
+            },
+        );
+        registry.register(
+            CONSOLE,
+            "println",
+            &format!("({parameter_type})V"),
+            move |vm, arguments| {
+                println!(
+                    "{}",
+                    vm.value_to_java_string(argument(arguments, 0)?, parameter_type)
+                );
+                Ok(None)
+            },
+        );
+    }
+    registry.register(CONSOLE, "println", "()V", |_, _| {
+        println!();
+        Ok(None)
+    });
+}
+
+/// `IntRange` is an `IntProgression` with step 1, so both classes share
+/// their natives. Progressions store the last element they actually reach.
+fn register_ranges(registry: &mut NativeRegistry) {
+    registry.register(INT_RANGE, "<init>", "(II)V", |vm, arguments| {
+        let first = argument(arguments, 1)?.as_int()?;
+        let last = argument(arguments, 2)?.as_int()?;
+        set_progression(vm, argument(arguments, 0)?, first, last, 1)?;
+        Ok(None)
+    });
+    for class_name in [INT_RANGE, INT_PROGRESSION] {
+        registry.register(class_name, "getFirst", "()I", |vm, arguments| {
+            let (first, _, _) = progression(vm, argument(arguments, 0)?)?;
+            Ok(Some(JvmValue::Int(first)))
+        });
+        registry.register(class_name, "getLast", "()I", |vm, arguments| {
+            let (_, last, _) = progression(vm, argument(arguments, 0)?)?;
+            Ok(Some(JvmValue::Int(last)))
+        });
+        registry.register(class_name, "getStep", "()I", |vm, arguments| {
+            let (_, _, step) = progression(vm, argument(arguments, 0)?)?;
+            Ok(Some(JvmValue::Int(step)))
+        });
+        registry.register(class_name, "isEmpty", "()Z", |vm, arguments| {
+            let empty = is_empty(progression(vm, argument(arguments, 0)?)?);
+            Ok(Some(JvmValue::Boolean(empty)))
+        });
+        registry.register(
+            class_name,
+            "toString",
+            "()Ljava/lang/String;",
+            |vm, arguments| {
+                let text = vm.value_to_java_string(argument(arguments, 0)?, "Ljava/lang/Object;");
+                Ok(Some(vm.new_string(text)?))
+            },
+        );
+        for descriptor in [
+            "()Ljava/util/Iterator;",
+            "()Lkotlin/collections/IntIterator;",
+        ] {
+            registry.register(class_name, "iterator", descriptor, |vm, arguments| {
+                let (first, last, step) = progression(vm, argument(arguments, 0)?)?;
+                let iterator = vm.new_instance(PROGRESSION_ITERATOR)?;
+                let has_next = !is_empty((first, last, step));
+                for (field, value) in [
+                    ("next", JvmValue::Int(if has_next { first } else { last })),
+                    ("finalElement", JvmValue::Int(last)),
+                    ("step", JvmValue::Int(step)),
+                    ("hasNext", JvmValue::Boolean(has_next)),
+                ] {
+                    vm.set_object_field(&iterator, field, value)?;
+                }
+                Ok(Some(iterator))
+            });
+        }
+    }
+    registry.register(INT_RANGE, "contains", "(I)Z", |vm, arguments| {
+        let (first, last, _) = progression(vm, argument(arguments, 0)?)?;
+        let value = argument(arguments, 1)?.as_int()?;
+        Ok(Some(JvmValue::Boolean(first <= value && value <= last)))
+    });
+    for (name, index) in [("getStart", 0), ("getEndInclusive", 1)] {
+        registry.register(
+            INT_RANGE,
+            name,
+            "()Ljava/lang/Integer;",
+            move |vm, arguments| {
+                let (first, last, _) = progression(vm, argument(arguments, 0)?)?;
+                let bound = [first, last][index];
+                Ok(Some(box_int(vm, bound)?))
+            },
+        );
+    }
+
+    registry.register(PROGRESSION_ITERATOR, "hasNext", "()Z", |vm, arguments| {
+        let iterator = argument(arguments, 0)?;
+        Ok(vm.object_field(iterator, "hasNext").cloned())
+    });
+    registry.register(PROGRESSION_ITERATOR, "nextInt", "()I", |vm, arguments| {
+        let value = next_int(vm, argument(arguments, 0)?)?;
+        Ok(Some(JvmValue::Int(value)))
+    });
+    registry.register(
+        PROGRESSION_ITERATOR,
+        "next",
+        "()Ljava/lang/Object;",
+        |vm, arguments| {
+            let value = next_int(vm, argument(arguments, 0)?)?;
+            Ok(Some(box_int(vm, value)?))
+        },
+    );
+
+    registry.register(
+        RANGES,
+        "downTo",
+        "(II)Lkotlin/ranges/IntProgression;",
+        |vm, arguments| {
+            let from = argument(arguments, 0)?.as_int()?;
+            let to = argument(arguments, 1)?.as_int()?;
+            Ok(Some(new_progression(vm, INT_PROGRESSION, from, to, -1)?))
+        },
+    );
+    registry.register(
+        RANGES,
+        "until",
+        "(II)Lkotlin/ranges/IntRange;",
+        |vm, arguments| {
+            let from = argument(arguments, 0)?.as_int()?;
+            let to = argument(arguments, 1)?.as_int()?;
+            // Nothing is below Int.MIN_VALUE, so that bound gives the empty range
+            let (first, last) = match to.checked_sub(1) {
+                Some(last) => (from, last),
+                None => (1, 0),
+            };
+            Ok(Some(new_progression(vm, INT_RANGE, first, last, 1)?))
+        },
+    );
+    registry.register(
+        RANGES,
+        "step",
+        "(Lkotlin/ranges/IntProgression;I)Lkotlin/ranges/IntProgression;",
+        |vm, arguments| {
+            let (first, last, step) = progression(vm, argument(arguments, 0)?)?;
+            let new_step = argument(arguments, 1)?.as_int()?;
+            if new_step <= 0 {
+                return Err(vm.throw_exception(
+                    ILLEGAL_ARGUMENT,
+                    Some(format!("Step must be positive, was: {new_step}.")),
+                ));
+            }
+            let new_step = if step > 0 { new_step } else { -new_step };
+            Ok(Some(new_progression(
+                vm,
+                INT_PROGRESSION,
+                first,
+                last,
+                new_step,
+            )?))
+        },
+    );
+    registry.register(RANGES, "coerceIn", "(III)I", |vm, arguments| {
+        let value = argument(arguments, 0)?.as_int()?;
+        let minimum = argument(arguments, 1)?.as_int()?;
+        let maximum = argument(arguments, 2)?.as_int()?;
+        if minimum > maximum {
+            return Err(vm.throw_exception(
+                ILLEGAL_ARGUMENT,
+                Some(format!(
+                    "Cannot coerce value to an empty range: maximum {maximum} is less than minimum {minimum}."
+                )),
+            ));
+        }
+        Ok(Some(JvmValue::Int(value.clamp(minimum, maximum))))
+    });
+    registry.register(RANGES, "coerceAtLeast", "(II)I", |_, arguments| {
+        let value = argument(arguments, 0)?.as_int()?;
+        let minimum = argument(arguments, 1)?.as_int()?;
+        Ok(Some(JvmValue::Int(value.max(minimum))))
+    });
+    registry.register(RANGES, "coerceAtMost", "(II)I", |_, arguments| {
+        let value = argument(arguments, 0)?.as_int()?;
+        let maximum = argument(arguments, 1)?.as_int()?;
+        Ok(Some(JvmValue::Int(value.min(maximum))))
+    });
+    // `range.random()`, which kotlinc inlines to a call with `Random.Default`
+    registry.register(
+        RANGES,
+        "random",
+        "(Lkotlin/ranges/IntRange;Lkotlin/random/Random;)I",
+        |vm, arguments| {
+            let range = argument(arguments, 0)?;
+            match random_in_range(vm, argument(arguments, 1)?, range) {
+                Err(RuntimeError::Exception(_)) if is_empty(progression(vm, range)?) => {
+                    let message = empty_range_message(vm, range);
+                    Err(vm.throw_exception(NO_SUCH_ELEMENT, Some(message)))
+                }
+                result => Ok(Some(JvmValue::Int(result?))),
+            }
+        },
+    );
+}
+
+/// `first`, `last` and `step` of a progression
+fn progression(vm: &JvmCompatibleVm, value: &JvmValue) -> Result<(i32, i32, i32), RuntimeError> {
+    if value.is_null() {
+        return Err(RuntimeError::NullPointer);
+    }
+    let field = |name| match vm.object_field(value, name) {
+        Some(field) => field.as_int(),
+        None => Err(RuntimeError::InvalidStackState),
+    };
+    Ok((field("first")?, field("last")?, field("step")?))
+}
+
+fn is_empty((first, last, step): (i32, i32, i32)) -> bool {
+    if step > 0 { first > last } else { first < last }
+}
+
+/// `IntProgression.fromClosedRange`: the last element is the bound rounded
+/// towards `first` to a whole number of steps
+fn set_progression(
+    vm: &mut JvmCompatibleVm,
+    progression: &JvmValue,
+    first: i32,
+    bound: i32,
+    step: i32,
+) -> Result<(), RuntimeError> {
+    let modulo = |a: i64, b: i64| a.rem_euclid(b);
+    let difference = |a: i32, b: i32, c: i32| {
+        modulo(
+            modulo(a as i64, c as i64) - modulo(b as i64, c as i64),
+            c as i64,
+        ) as i32
+    };
+    let last = if step > 0 {
+        if first >= bound {
+            bound
+        } else {
+            bound - difference(bound, first, step)
+        }
+    } else if first <= bound {
+        bound
+    } else {
+        bound + difference(first, bound, -step)
+    };
+    for (field, value) in [("first", first), ("last", last), ("step", step)] {
+        vm.set_object_field(progression, field, JvmValue::Int(value))?;
+    }
+    Ok(())
+}
+
+fn new_progression(
+    vm: &mut JvmCompatibleVm,
+    class_name: &str,
+    first: i32,
+    bound: i32,
+    step: i32,
+) -> Result<JvmValue, RuntimeError> {
+    let progression = vm.new_instance(class_name)?;
+    set_progression(vm, &progression, first, bound, step)?;
+    Ok(progression)
+}
+
+/// `IntProgressionIterator.nextInt`
+fn next_int(vm: &mut JvmCompatibleVm, iterator: &JvmValue) -> Result<i32, RuntimeError> {
+    let field = |name| match vm.object_field(iterator, name) {
+        Some(field) => field.as_int(),
+        None => Err(RuntimeError::InvalidStackState),
+    };
+    let (value, final_element, step) = (field("next")?, field("finalElement")?, field("step")?);
+    if value == final_element {
+        if vm.object_field(iterator, "hasNext") != Some(&JvmValue::Boolean(true)) {
+            return Err(vm.throw_exception(NO_SUCH_ELEMENT, None));
+        }
+        vm.set_object_field(iterator, "hasNext", JvmValue::Boolean(false))?;
+    } else {
+        vm.set_object_field(iterator, "next", JvmValue::Int(value.wrapping_add(step)))?;
+    }
+    Ok(value)
+}
+
+/// The text of `IntRange.toString` and `IntProgression.toString`, or `None`
+/// for other objects
+pub(crate) fn progression_to_string(
+    class_name: &str,
+    fields: &HashMap<String, JvmValue>,
+) -> Option<String> {
+    let field = |name| match fields.get(name) {
+        Some(JvmValue::Int(value)) => Some(*value),
+        _ => None,
+    };
+    let (first, last, step) = (field("first")?, field("last")?, field("step")?);
+    match class_name {
+        INT_RANGE => Some(format!("{first}..{last}")),
+        INT_PROGRESSION if step > 0 => Some(format!("{first}..{last} step {step}")),
+        INT_PROGRESSION => Some(format!("{first} downTo {last} step {}", -step)),
+        _ => None,
+    }
+}
+
+fn empty_range_message(vm: &JvmCompatibleVm, range: &JvmValue) -> String {
+    format!(
+        "Cannot get random in empty range: {}",
+        vm.value_to_java_string(range, "Ljava/lang/Object;")
+    )
+}
+
+/// `Random.Default` delegates to the platform generator, so it is backed by
+/// the host RNG like `ThreadLocalRandom`
+fn register_random(registry: &mut NativeRegistry) {
+    registry.register(DEFAULT_RANDOM, "nextInt", "()I", |_, _| {
+        Ok(Some(JvmValue::Int(rand::rng().random())))
+    });
+    registry.register(DEFAULT_RANDOM, "nextInt", "(I)I", |vm, arguments| {
+        let until = argument(arguments, 1)?.as_int()?;
+        Ok(Some(JvmValue::Int(next_int_between(vm, 0, until)?)))
+    });
+    registry.register(DEFAULT_RANDOM, "nextInt", "(II)I", |vm, arguments| {
+        let from = argument(arguments, 1)?.as_int()?;
+        let until = argument(arguments, 2)?.as_int()?;
+        Ok(Some(JvmValue::Int(next_int_between(vm, from, until)?)))
+    });
+    registry.register(DEFAULT_RANDOM, "nextLong", "()J", |_, _| {
+        Ok(Some(JvmValue::Long(rand::rng().random())))
+    });
+    registry.register(DEFAULT_RANDOM, "nextBoolean", "()Z", |_, _| {
+        Ok(Some(JvmValue::Boolean(rand::rng().random())))
+    });
+    registry.register(DEFAULT_RANDOM, "nextDouble", "()D", |_, _| {
+        Ok(Some(JvmValue::Double(rand::rng().random::<f64>())))
+    });
+    registry.register(
+        RANDOM_KT,
+        "nextInt",
+        "(Lkotlin/random/Random;Lkotlin/ranges/IntRange;)I",
+        |vm, arguments| {
+            let value = random_in_range(vm, argument(arguments, 0)?, argument(arguments, 1)?)?;
+            Ok(Some(JvmValue::Int(value)))
+        },
+    );
+}
+
+fn next_int_between(vm: &mut JvmCompatibleVm, from: i32, until: i32) -> Result<i32, RuntimeError> {
+    if from >= until {
+        return Err(vm.throw_exception(
+            ILLEGAL_ARGUMENT,
+            Some(format!("Random range is empty: [{from}, {until}).")),
+        ));
+    }
+    Ok(rand::rng().random_range(from..until))
+}
+
+/// `Random.nextInt(range)`, asking the generator for a half-open range
+fn random_in_range(
+    vm: &mut JvmCompatibleVm,
+    random: &JvmValue,
+    range: &JvmValue,
+) -> Result<i32, RuntimeError> {
+    let (first, last, step) = progression(vm, range)?;
+    if is_empty((first, last, step)) {
+        let message = empty_range_message(vm, range);
+        return Err(vm.throw_exception(ILLEGAL_ARGUMENT, Some(message)));
+    }
+    let next = |vm: &mut JvmCompatibleVm, descriptor, arguments: &[JvmValue]| match vm
+        .call_method(random, "nextInt", descriptor, arguments)?
+    {
+        Some(value) => value.as_int(),
+        None => Err(RuntimeError::InvalidStackState),
+    };
+    if last < i32::MAX {
+        next(
+            vm,
+            "(II)I",
+            &[JvmValue::Int(first), JvmValue::Int(last + 1)],
+        )
+    } else if first > i32::MIN {
+        Ok(next(
+            vm,
+            "(II)I",
+            &[JvmValue::Int(first - 1), JvmValue::Int(last)],
+        )? + 1)
+    } else {
+        next(vm, "()I", &[])
+    }
+}
+
+fn register_collections(registry: &mut NativeRegistry) {
+    registry.register(
+        COLLECTIONS,
+        "listOf",
+        "([Ljava/lang/Object;)Ljava/util/List;",
+        |vm, arguments| {
+            let elements = vm.array_mut(argument(arguments, 0)?)?.clone();
+            Ok(Some(new_list(vm, elements)?))
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "mutableListOf",
+        "([Ljava/lang/Object;)Ljava/util/List;",
+        |vm, arguments| {
+            let elements = vm.array_mut(argument(arguments, 0)?)?.clone();
+            Ok(Some(new_list(vm, elements)?))
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "listOf",
+        "(Ljava/lang/Object;)Ljava/util/List;",
+        |vm, arguments| Ok(Some(new_list(vm, vec![argument(arguments, 0)?.clone()])?)),
+    );
+    registry.register(COLLECTIONS, "emptyList", "()Ljava/util/List;", |vm, _| {
+        Ok(Some(new_list(vm, Vec::new())?))
+    });
+    // Used by the inlined `map` to presize its result
+    registry.register(
+        COLLECTIONS,
+        "collectionSizeOrDefault",
+        "(Ljava/lang/Iterable;I)I",
+        |vm, arguments| {
+            let iterable = argument(arguments, 0)?;
+            let size = match vm.call_method(iterable, "size", "()I", &[]) {
+                Err(RuntimeError::NoSuchMethod(_)) => argument(arguments, 1)?.clone(),
+                result => result?.ok_or(RuntimeError::InvalidStackState)?,
+            };
+            Ok(Some(size))
+        },
+    );
+
+    registry.register(
+        COLLECTIONS,
+        "sumOfInt",
+        "(Ljava/lang/Iterable;)I",
+        |vm, arguments| {
+            let mut sum = 0i32;
+            for element in elements(vm, argument(arguments, 0)?)? {
+                sum = sum.wrapping_add(call(vm, &element, "intValue", "()I", &[])?.as_int()?);
+            }
+            Ok(Some(JvmValue::Int(sum)))
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "sumOfLong",
+        "(Ljava/lang/Iterable;)J",
+        |vm, arguments| {
+            let mut sum = 0i64;
+            for element in elements(vm, argument(arguments, 0)?)? {
+                sum = sum.wrapping_add(call(vm, &element, "longValue", "()J", &[])?.as_long()?);
+            }
+            Ok(Some(JvmValue::Long(sum)))
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "sumOfDouble",
+        "(Ljava/lang/Iterable;)D",
+        |vm, arguments| {
+            let mut sum = 0.0;
+            for element in elements(vm, argument(arguments, 0)?)? {
+                sum += call(vm, &element, "doubleValue", "()D", &[])?.as_double()?;
+            }
+            Ok(Some(JvmValue::Double(sum)))
+        },
+    );
+
+    registry.register(
+        COLLECTIONS,
+        "sorted",
+        "(Ljava/lang/Iterable;)Ljava/util/List;",
+        |vm, arguments| {
+            let elements = elements(vm, argument(arguments, 0)?)?;
+            let sorted = sort_values(vm, elements, &JvmValue::Reference(None))?;
+            Ok(Some(new_list(vm, sorted)?))
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "sortedDescending",
+        "(Ljava/lang/Iterable;)Ljava/util/List;",
+        |vm, arguments| {
+            let elements = elements(vm, argument(arguments, 0)?)?;
+            let comparator = vm.new_instance(REVERSE_COMPARATOR)?;
+            let sorted = sort_values(vm, elements, &comparator)?;
+            Ok(Some(new_list(vm, sorted)?))
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "reversed",
+        "(Ljava/lang/Iterable;)Ljava/util/List;",
+        |vm, arguments| {
+            let mut elements = elements(vm, argument(arguments, 0)?)?;
+            elements.reverse();
+            Ok(Some(new_list(vm, elements)?))
+        },
+    );
+    registry.register(
+        COLLECTIONS,
+        "take",
+        "(Ljava/lang/Iterable;I)Ljava/util/List;",
+        |vm, arguments| {
+            let count = argument(arguments, 1)?.as_int()?;
+            if count < 0 {
+                return Err(vm.throw_exception(
+                    ILLEGAL_ARGUMENT,
+                    Some(format!(
+                        "Requested element count {count} is less than zero."
+                    )),
+                ));
+            }
+            let mut elements = elements(vm, argument(arguments, 0)?)?;
+            elements.truncate(count as usize);
+            Ok(Some(new_list(vm, elements)?))
+        },
+    );
+
+    // `max()` and `min()` became `maxOrThrow` and `minOrThrow` in Kotlin 1.7
+    for (name, replaced_when, or_null) in [
+        ("maxOrThrow", Ordering::Less, false),
+        ("minOrThrow", Ordering::Greater, false),
+        ("maxOrNull", Ordering::Less, true),
+        ("minOrNull", Ordering::Greater, true),
+    ] {
+        registry.register(
+            COLLECTIONS,
+            name,
+            "(Ljava/lang/Iterable;)Ljava/lang/Comparable;",
+            move |vm, arguments| {
+                let mut elements = elements(vm, argument(arguments, 0)?)?.into_iter();
+                let Some(mut best) = elements.next() else {
+                    if or_null {
+                        return Ok(Some(JvmValue::Reference(None)));
+                    }
+                    return Err(vm.throw_exception(NO_SUCH_ELEMENT, None));
+                };
+                for element in elements {
+                    if compare_natural(vm, &best, &element)? == replaced_when {
+                        best = element;
+                    }
+                }
+                Ok(Some(best))
+            },
+        );
+    }
+
+    registry.register(
+        COLLECTIONS,
+        "joinToString",
+        "(Ljava/lang/Iterable;Ljava/lang/CharSequence;Ljava/lang/CharSequence;Ljava/lang/CharSequence;ILjava/lang/CharSequence;Lkotlin/jvm/functions/Function1;)Ljava/lang/String;",
+        |vm, arguments| {
+            let options = JoinOptions {
+                separator: string_of(vm, argument(arguments, 1)?)?,
+                prefix: string_of(vm, argument(arguments, 2)?)?,
+                postfix: string_of(vm, argument(arguments, 3)?)?,
+                limit: argument(arguments, 4)?.as_int()?,
+                truncated: string_of(vm, argument(arguments, 5)?)?,
+                transform: argument(arguments, 6)?.clone(),
+            };
+            let text = join_to_string(vm, argument(arguments, 0)?, options)?;
+            Ok(Some(vm.new_string(text)?))
+        },
+    );
+    // Calls that leave out parameters go through the synthetic `$default`
+    // method, whose mask has a bit set for each parameter to default
+    registry.register(
+        COLLECTIONS,
+        "joinToString$default",
+        "(Ljava/lang/Iterable;Ljava/lang/CharSequence;Ljava/lang/CharSequence;Ljava/lang/CharSequence;ILjava/lang/CharSequence;Lkotlin/jvm/functions/Function1;ILjava/lang/Object;)Ljava/lang/String;",
+        |vm, arguments| {
+            let mask = argument(arguments, 7)?.as_int()?;
+            let text = |vm: &mut JvmCompatibleVm, index: usize, default: &str| {
+                if mask & (1 << (index - 1)) != 0 {
+                    Ok(default.to_string())
+                } else {
+                    string_of(vm, argument(arguments, index)?)
+                }
+            };
+            let options = JoinOptions {
+                separator: text(vm, 1, ", ")?,
+                prefix: text(vm, 2, "")?,
+                postfix: text(vm, 3, "")?,
+                limit: match mask & (1 << 3) {
+                    0 => argument(arguments, 4)?.as_int()?,
+                    _ => -1,
+                },
+                truncated: text(vm, 5, "...")?,
+                transform: match mask & (1 << 5) {
+                    0 => argument(arguments, 6)?.clone(),
+                    _ => JvmValue::Reference(None),
+                },
+            };
+            let text = join_to_string(vm, argument(arguments, 0)?, options)?;
+            Ok(Some(vm.new_string(text)?))
+        },
+    );
+}
+
+/// Parameters of `joinToString`
+struct JoinOptions {
+    separator: String,
+    prefix: String,
+    postfix: String,
+    limit: i32,
+    truncated: String,
+    /// A `Function1` applied to each element, or null
+    transform: JvmValue,
+}
+
+fn join_to_string(
+    vm: &mut JvmCompatibleVm,
+    iterable: &JvmValue,
+    options: JoinOptions,
+) -> Result<String, RuntimeError> {
+    let mut text = options.prefix;
+    let mut count = 0;
+    for element in elements(vm, iterable)? {
+        count += 1;
+        if count > 1 {
+            text.push_str(&options.separator);
+        }
+        if options.limit >= 0 && count > options.limit {
+            break;
+        }
+        let element = if options.transform.is_null() {
+            element
+        } else {
+            call(
+                vm,
+                &options.transform,
+                "invoke",
+                "(Ljava/lang/Object;)Ljava/lang/Object;",
+                &[element],
+            )?
+        };
+        text.push_str(&string_of(vm, &element)?);
+    }
+    if options.limit >= 0 && count > options.limit {
+        text.push_str(&options.truncated);
+    }
+    text.push_str(&options.postfix);
+    Ok(text)
+}
+
+/// The elements of any `Iterable`, collected through its iterator
+fn elements(vm: &mut JvmCompatibleVm, iterable: &JvmValue) -> Result<Vec<JvmValue>, RuntimeError> {
+    let iterator = call(vm, iterable, "iterator", "()Ljava/util/Iterator;", &[])?;
+    let mut elements = Vec::new();
+    while call(vm, &iterator, "hasNext", "()Z", &[])?.as_boolean()? {
+        elements.push(call(vm, &iterator, "next", "()Ljava/lang/Object;", &[])?);
+    }
+    Ok(elements)
+}
+
+/// Call a method that returns a value
+fn call(
+    vm: &mut JvmCompatibleVm,
+    receiver: &JvmValue,
+    name: &str,
+    descriptor: &str,
+    arguments: &[JvmValue],
+) -> Result<JvmValue, RuntimeError> {
+    vm.call_method(receiver, name, descriptor, arguments)?
+        .ok_or(RuntimeError::InvalidStackState)
+}
+
+/// `String.valueOf(Object)`, which renders objects by their `toString`
+fn string_of(vm: &mut JvmCompatibleVm, value: &JvmValue) -> Result<String, RuntimeError> {
+    let text = vm
+        .invoke_native(
+            "java/lang/String",
+            "valueOf",
+            "(Ljava/lang/Object;)Ljava/lang/String;",
+            std::slice::from_ref(value),
+        )?
+        .ok_or(RuntimeError::InvalidStackState)?;
+    Ok(vm.string_value(&text)?.to_string())
+}
+
+fn box_int(vm: &mut JvmCompatibleVm, value: i32) -> Result<JvmValue, RuntimeError> {
+    vm.invoke_native(
+        "java/lang/Integer",
+        "valueOf",
+        "(I)Ljava/lang/Integer;",
+        &[JvmValue::Int(value)],
+    )?
+    .ok_or(RuntimeError::InvalidStackState)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_static(
+        vm: &mut JvmCompatibleVm,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+        arguments: &[JvmValue],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        vm.invoke_native(class_name, name, descriptor, arguments)
+    }
+
+    fn render(vm: &JvmCompatibleVm, value: &JvmValue) -> String {
+        vm.value_to_java_string(value, "Ljava/lang/Object;")
+    }
+
+    fn exception_message(vm: &JvmCompatibleVm, error: RuntimeError) -> String {
+        let RuntimeError::Exception(id) = error else {
+            panic!("expected an exception, got {error:?}");
+        };
+        render(vm, &JvmValue::Reference(Some(id)))
+    }
+
+    #[test]
+    fn test_progressions_stop_at_the_last_reachable_element() {
+        let mut vm = JvmCompatibleVm::new();
+        let down = call_static(
+            &mut vm,
+            RANGES,
+            "downTo",
+            "(II)Lkotlin/ranges/IntProgression;",
+            &[JvmValue::Int(10), JvmValue::Int(1)],
+        )
+        .unwrap()
+        .unwrap();
+        let stepped = call_static(
+            &mut vm,
+            RANGES,
+            "step",
+            "(Lkotlin/ranges/IntProgression;I)Lkotlin/ranges/IntProgression;",
+            &[down, JvmValue::Int(3)],
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(render(&vm, &stepped), "10 downTo 1 step 3");
+        let values: Vec<String> = elements(&mut vm, &stepped)
+            .unwrap()
+            .iter()
+            .map(|value| render(&vm, value))
+            .collect();
+        assert_eq!(values, ["10", "7", "4", "1"]);
+
+        let range = new_progression(&mut vm, INT_RANGE, 1, 10, 1).unwrap();
+        let stepped = call_static(
+            &mut vm,
+            RANGES,
+            "step",
+            "(Lkotlin/ranges/IntProgression;I)Lkotlin/ranges/IntProgression;",
+            &[range, JvmValue::Int(4)],
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(render(&vm, &stepped), "1..9 step 4");
+
+        let empty = call_static(
+            &mut vm,
+            RANGES,
+            "until",
+            "(II)Lkotlin/ranges/IntRange;",
+            &[JvmValue::Int(1), JvmValue::Int(1)],
+        )
+        .unwrap()
+        .unwrap();
+        assert!(elements(&mut vm, &empty).unwrap().is_empty());
+        let random = vm.new_instance(DEFAULT_RANDOM).unwrap();
+        let error = call_static(
+            &mut vm,
+            RANGES,
+            "random",
+            "(Lkotlin/ranges/IntRange;Lkotlin/random/Random;)I",
+            &[empty, random],
+        )
+        .unwrap_err();
+        assert_eq!(
+            exception_message(&vm, error),
+            "java.util.NoSuchElementException: Cannot get random in empty range: 1..0"
+        );
+    }
+
+    #[test]
+    fn test_null_checks_raise_kotlin_messages() {
+        let mut vm = JvmCompatibleVm::new();
+        let null = JvmValue::Reference(None);
+        let name = vm.new_string("rolls").unwrap();
+        let descriptor = "(Ljava/lang/Object;Ljava/lang/String;)V";
+        assert_eq!(
+            call_static(
+                &mut vm,
+                INTRINSICS,
+                "checkNotNullParameter",
+                descriptor,
+                &[name.clone(), name.clone()]
+            )
+            .unwrap(),
+            None
+        );
+        for (method, expected) in [
+            (
+                "checkNotNullParameter",
+                "java.lang.NullPointerException: Parameter specified as non-null is null: parameter rolls",
+            ),
+            (
+                "checkNotNullExpressionValue",
+                "java.lang.NullPointerException: rolls must not be null",
+            ),
+            (
+                "checkExpressionValueIsNotNull",
+                "java.lang.IllegalStateException: rolls must not be null",
+            ),
+        ] {
+            let error = call_static(
+                &mut vm,
+                INTRINSICS,
+                method,
+                descriptor,
+                &[null.clone(), name.clone()],
+            )
+            .unwrap_err();
+            assert_eq!(exception_message(&vm, error), expected);
+        }
+        let error = call_static(
+            &mut vm,
+            INTRINSICS,
+            "checkNotNull",
+            "(Ljava/lang/Object;)V",
+            &[null],
+        )
+        .unwrap_err();
+        assert_eq!(
+            exception_message(&vm, error),
+            "java.lang.NullPointerException"
+        );
+    }
+
+    #[test]
+    fn test_join_to_string_fills_in_defaults_from_the_mask() {
+        let mut vm = JvmCompatibleVm::new();
+        let rolls: Vec<JvmValue> = [6, 2, 5]
+            .into_iter()
+            .map(|roll| box_int(&mut vm, roll).unwrap())
+            .collect();
+        let rolls = new_list(&mut vm, rolls).unwrap();
+        let null = JvmValue::Reference(None);
+        let prefix = vm.new_string("<").unwrap();
+        let descriptor = "(Ljava/lang/Iterable;Ljava/lang/CharSequence;Ljava/lang/CharSequence;Ljava/lang/CharSequence;ILjava/lang/CharSequence;Lkotlin/jvm/functions/Function1;ILjava/lang/Object;)Ljava/lang/String;";
+        // `joinToString(prefix = "<", limit = 2)`: everything else defaulted
+        let joined = call_static(
+            &mut vm,
+            COLLECTIONS,
+            "joinToString$default",
+            descriptor,
+            &[
+                rolls,
+                null.clone(),
+                prefix,
+                null.clone(),
+                JvmValue::Int(2),
+                null.clone(),
+                null.clone(),
+                JvmValue::Int(0b110101),
+                null,
+            ],
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(vm.string_value(&joined).unwrap(), "<6, 2, ...");
+    }
+}
@@ -0,0 +1,180 @@
+//! `java.lang.Math`
+
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::jvm::jvm_compatible_vm::JvmValue;
+use rand::Rng;
+
+const MATH: &str = "java/lang/Math";
+
+pub struct MathNatives;
+
+impl NativeProvider for MathNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        registry.register(MATH, "random", "()D", |_, _| {
+            Ok(Some(JvmValue::Double(rand::rng().random::<f64>())))
+        });
+        registry.register(MATH, "max", "(II)I", |_, arguments| {
+            let a = argument(arguments, 0)?.as_int()?;
+            let b = argument(arguments, 1)?.as_int()?;
+            Ok(Some(JvmValue::Int(a.max(b))))
+        });
+        registry.register(MATH, "min", "(II)I", |_, arguments| {
+            let a = argument(arguments, 0)?.as_int()?;
+            let b = argument(arguments, 1)?.as_int()?;
+            Ok(Some(JvmValue::Int(a.min(b))))
+        });
+        registry.register(MATH, "max", "(DD)D", |_, arguments| {
+            let a = argument(arguments, 0)?.as_double()?;
+            let b = argument(arguments, 1)?.as_double()?;
+            Ok(Some(JvmValue::Double(java_max(a, b))))
+        });
+        registry.register(MATH, "min", "(DD)D", |_, arguments| {
+            let a = argument(arguments, 0)?.as_double()?;
+            let b = argument(arguments, 1)?.as_double()?;
+            Ok(Some(JvmValue::Double(java_min(a, b))))
+        });
+        // Like Java, the absolute value of Integer.MIN_VALUE is itself
+        registry.register(MATH, "abs", "(I)I", |_, arguments| {
+            let value = argument(arguments, 0)?.as_int()?;
+            Ok(Some(JvmValue::Int(value.wrapping_abs())))
+        });
+        registry.register(MATH, "round", "(D)J", |_, arguments| {
+            let value = argument(arguments, 0)?.as_double()?;
+            Ok(Some(JvmValue::Long(java_round(value))))
+        });
+
+        let unary = [
+            ("abs", f64::abs as fn(f64) -> f64),
+            ("sqrt", f64::sqrt),
+            ("floor", f64::floor),
+            ("ceil", f64::ceil),
+            ("sin", f64::sin),
+            ("cos", f64::cos),
+            ("tan", f64::tan),
+            ("log", f64::ln),
+            ("exp", f64::exp),
+        ];
+        for (name, function) in unary {
+            registry.register(MATH, name, "(D)D", move |_, arguments| {
+                let value = argument(arguments, 0)?.as_double()?;
+                Ok(Some(JvmValue::Double(function(value))))
+            });
+        }
+        registry.register(MATH, "pow", "(DD)D", |_, arguments| {
+            let base = argument(arguments, 0)?.as_double()?;
+            let exponent = argument(arguments, 1)?.as_double()?;
+            Ok(Some(JvmValue::Double(base.powf(exponent))))
+        });
+    }
+}
+
+/// `Math.max`: NaN if either value is NaN, and 0.0 is above -0.0
+fn java_max(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == 0.0 && b == 0.0 {
+        if a.is_sign_negative() { b } else { a }
+    } else {
+        a.max(b)
+    }
+}
+
+/// `Math.min`: NaN if either value is NaN, and -0.0 is below 0.0
+fn java_min(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == 0.0 && b == 0.0 {
+        if a.is_sign_negative() { a } else { b }
+    } else {
+        a.min(b)
+    }
+}
+
+/// `Math.round`: rounds half up rather than away from zero, and saturates
+/// like a `d2l` conversion
+fn java_round(value: f64) -> i64 {
+    let floor = value.floor();
+    // value - floor is exact, unlike value + 0.5
+    let rounded = if value - floor >= 0.5 {
+        floor + 1.0
+    } else {
+        floor
+    };
+    rounded as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::jvm_compatible_vm::JvmCompatibleVm;
+
+    fn call(name: &str, descriptor: &str, arguments: &[JvmValue]) -> JvmValue {
+        let mut vm = JvmCompatibleVm::new();
+        vm.invoke_native(MATH, name, descriptor, arguments)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_math_matches_java_edge_cases() {
+        // Expected values from a reference JVM
+        assert_eq!(
+            call("abs", "(I)I", &[JvmValue::Int(i32::MIN)]),
+            JvmValue::Int(i32::MIN)
+        );
+        for (value, expected) in [
+            (-2.5, -2),
+            (2.5, 3),
+            (0.49999999999999994, 0),
+            (f64::NAN, 0),
+            (1e300, i64::MAX),
+        ] {
+            assert_eq!(
+                call("round", "(D)J", &[JvmValue::Double(value)]),
+                JvmValue::Long(expected),
+                "round({value})"
+            );
+        }
+
+        let max = call(
+            "max",
+            "(DD)D",
+            &[JvmValue::Double(-0.0), JvmValue::Double(0.0)],
+        );
+        assert_eq!(max.as_double().unwrap().to_bits(), 0.0f64.to_bits());
+        let min = call(
+            "min",
+            "(DD)D",
+            &[JvmValue::Double(0.0), JvmValue::Double(-0.0)],
+        );
+        assert_eq!(min.as_double().unwrap().to_bits(), (-0.0f64).to_bits());
+        let max = call(
+            "max",
+            "(DD)D",
+            &[JvmValue::Double(f64::NAN), JvmValue::Double(1.0)],
+        );
+        assert!(max.as_double().unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_math_functions() {
+        assert_eq!(
+            call("max", "(II)I", &[JvmValue::Int(3), JvmValue::Int(-4)]),
+            JvmValue::Int(3)
+        );
+        assert_eq!(
+            call(
+                "pow",
+                "(DD)D",
+                &[JvmValue::Double(2.0), JvmValue::Double(10.0)]
+            ),
+            JvmValue::Double(1024.0)
+        );
+        assert_eq!(
+            call("floor", "(D)D", &[JvmValue::Double(-1.5)]),
+            JvmValue::Double(-2.0)
+        );
+        let random = call("random", "()D", &[]).as_double().unwrap();
+        assert!((0.0..1.0).contains(&random));
+    }
+}
@@ -0,0 +1,165 @@
+//! Native method registry
+//!
+//! Library methods that are not loaded from class files are implemented in
+//! Rust and looked up by class, method name and descriptor. The JDK subset
+//! the VM understands is split into providers, and embedders can register
+//! their own natives, for example to call back into the host program.
+
+mod arrays;
+mod collections;
+mod kotlin;
+mod math;
+mod object;
+mod print_stream;
+mod random;
+mod string;
+mod wrappers;
+
+pub use arrays::ArraysNatives;
+pub use collections::CollectionNatives;
+pub use kotlin::KotlinNatives;
+pub use math::MathNatives;
+pub use object::ObjectNatives;
+pub use print_stream::PrintStreamNatives;
+pub use random::RandomNatives;
+pub use string::StringNatives;
+pub use wrappers::WrapperNatives;
+
+pub(crate) use collections::JavaHashMap;
+pub(crate) use kotlin::progression_to_string;
+pub(crate) use wrappers::boxed_descriptor;
+
+use super::jvm_compatible_vm::{JvmCompatibleVm, JvmValue};
+use crate::error::RuntimeError;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Implementation of a native method. It receives the receiver, if any,
+/// followed by the arguments, and returns `None` for `void` methods.
+pub type NativeMethod =
+    Rc<dyn Fn(&mut JvmCompatibleVm, &[JvmValue]) -> Result<Option<JvmValue>, RuntimeError>>;
+
+/// A group of related natives, such as the methods of `java.lang.Math`
+pub trait NativeProvider {
+    fn register(&self, registry: &mut NativeRegistry);
+}
+
+/// Native methods keyed by `(class, name, descriptor)`, with class names in
+/// internal form such as `java/lang/Math`
+#[derive(Clone, Default)]
+pub struct NativeRegistry {
+    methods: HashMap<(String, String, String), NativeMethod>,
+}
+
+impl NativeRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with the built-in JDK providers and the Kotlin stdlib shims
+    pub fn with_jdk() -> Self {
+        let mut registry = Self::new();
+        registry.add_provider(&ObjectNatives);
+        registry.add_provider(&PrintStreamNatives);
+        registry.add_provider(&MathNatives);
+        registry.add_provider(&StringNatives);
+        registry.add_provider(&WrapperNatives);
+        registry.add_provider(&RandomNatives);
+        registry.add_provider(&CollectionNatives);
+        registry.add_provider(&ArraysNatives);
+        registry.add_provider(&KotlinNatives);
+        registry
+    }
+
+    pub fn add_provider(&mut self, provider: &dyn NativeProvider) {
+        provider.register(self);
+    }
+
+    /// Register a native, replacing any previous one with the same key
+    pub fn register<F>(&mut self, class_name: &str, name: &str, descriptor: &str, method: F)
+    where
+        F: Fn(&mut JvmCompatibleVm, &[JvmValue]) -> Result<Option<JvmValue>, RuntimeError>
+            + 'static,
+    {
+        self.methods.insert(
+            (
+                class_name.to_string(),
+                name.to_string(),
+                descriptor.to_string(),
+            ),
+            Rc::new(method),
+        );
+    }
+
+    pub fn get(&self, class_name: &str, name: &str, descriptor: &str) -> Option<NativeMethod> {
+        self.methods
+            .get(&(
+                class_name.to_string(),
+                name.to_string(),
+                descriptor.to_string(),
+            ))
+            .cloned()
+    }
+
+    pub fn contains(&self, class_name: &str, name: &str, descriptor: &str) -> bool {
+        self.get(class_name, name, descriptor).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.methods.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+}
+
+/// The argument at `index`, for natives called with too few arguments
+fn argument(arguments: &[JvmValue], index: usize) -> Result<&JvmValue, RuntimeError> {
+    arguments.get(index).ok_or(RuntimeError::StackUnderflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_natives_are_called_with_arguments() {
+        let mut registry = NativeRegistry::new();
+        assert!(registry.is_empty());
+        registry.register("DiceHost", "roll", "(II)I", |_, arguments| {
+            let count = argument(arguments, 0)?.as_int()?;
+            let faces = argument(arguments, 1)?.as_int()?;
+            Ok(Some(JvmValue::Int(count * faces)))
+        });
+        assert!(registry.contains("DiceHost", "roll", "(II)I"));
+        assert!(!registry.contains("DiceHost", "roll", "(I)I"));
+
+        let roll = registry.get("DiceHost", "roll", "(II)I").unwrap();
+        let mut vm = JvmCompatibleVm::new();
+        let result = roll(&mut vm, &[JvmValue::Int(3), JvmValue::Int(6)]).unwrap();
+        assert_eq!(result, Some(JvmValue::Int(18)));
+        assert_eq!(
+            roll(&mut vm, &[JvmValue::Int(3)]).unwrap_err().to_string(),
+            "Stack underflow"
+        );
+    }
+
+    #[test]
+    fn test_jdk_registry_contains_every_provider() {
+        let registry = NativeRegistry::with_jdk();
+        assert!(registry.contains("java/io/PrintStream", "println", "(I)V"));
+        assert!(registry.contains("java/lang/Math", "random", "()D"));
+        assert!(registry.contains("java/lang/String", "hashCode", "()I"));
+        assert!(registry.contains("java/lang/Integer", "parseInt", "(Ljava/lang/String;)I"));
+        assert!(registry.contains("java/util/Random", "nextInt", "(I)I"));
+        assert!(registry.contains("java/util/ArrayList", "add", "(Ljava/lang/Object;)Z"));
+        assert!(registry.contains("java/util/Arrays", "sort", "([I)V"));
+        assert!(registry.contains(
+            "kotlin/jvm/internal/Intrinsics",
+            "checkNotNullParameter",
+            "(Ljava/lang/Object;Ljava/lang/String;)V"
+        ));
+    }
+}
@@ -0,0 +1,69 @@
+//! `java.lang.Object`, the fallback for virtual calls no other native or
+//! user method implements
+
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::jvm::jvm_compatible_vm::JvmValue;
+
+const OBJECT: &str = "java/lang/Object";
+
+pub struct ObjectNatives;
+
+impl NativeProvider for ObjectNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        // The identity hash code is the heap id, which `toString` also shows
+        registry.register(OBJECT, "hashCode", "()I", |_, arguments| {
+            let object_id = argument(arguments, 0)?.as_reference()?;
+            Ok(Some(JvmValue::Int(object_id as i32)))
+        });
+        registry.register(OBJECT, "equals", "(Ljava/lang/Object;)Z", |_, arguments| {
+            let same = argument(arguments, 0)? == argument(arguments, 1)?;
+            Ok(Some(JvmValue::Boolean(same)))
+        });
+        registry.register(
+            OBJECT,
+            "toString",
+            "()Ljava/lang/String;",
+            |vm, arguments| {
+                let text = vm.value_to_java_string(argument(arguments, 0)?, "Ljava/lang/Object;");
+                Ok(Some(vm.new_string(text)?))
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::jvm_compatible_vm::JvmCompatibleVm;
+
+    #[test]
+    fn test_object_methods_use_identity() {
+        let mut vm = JvmCompatibleVm::new();
+        let first = vm.new_instance("java/util/Random").unwrap();
+        let second = vm.new_instance("java/util/Random").unwrap();
+        let equals = |vm: &mut JvmCompatibleVm, other: &JvmValue| {
+            vm.call_method(
+                &first,
+                "equals",
+                "(Ljava/lang/Object;)Z",
+                std::slice::from_ref(other),
+            )
+            .unwrap()
+        };
+        assert_eq!(equals(&mut vm, &first), Some(JvmValue::Boolean(true)));
+        assert_eq!(equals(&mut vm, &second), Some(JvmValue::Boolean(false)));
+
+        let hash = vm
+            .call_method(&first, "hashCode", "()I", &[])
+            .unwrap()
+            .unwrap();
+        let text = vm
+            .call_method(&first, "toString", "()Ljava/lang/String;", &[])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vm.string_value(&text).unwrap(),
+            format!("java.util.Random@{:x}", hash.as_int().unwrap())
+        );
+    }
+}
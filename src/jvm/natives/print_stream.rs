@@ -0,0 +1,132 @@
+//! `java.io.PrintStream` for `System.out` and `System.err`
+
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::error::RuntimeError;
+use crate::jvm::jvm_compatible_vm::{JvmCompatibleVm, JvmValue};
+
+const PRINT_STREAM: &str = "java/io/PrintStream";
+
+/// Parameter types accepted by `print` and `println`
+const PRINTABLE_TYPES: &[&str] = &[
+    "I",
+    "J",
+    "F",
+    "D",
+    "Z",
+    "C",
+    "Ljava/lang/String;",
+    "Ljava/lang/Object;",
+];
+
+pub struct PrintStreamNatives;
+
+impl NativeProvider for PrintStreamNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        for &parameter_type in PRINTABLE_TYPES {
+            registry.register(
+                PRINT_STREAM,
+                "print",
+                &format!("({parameter_type})V"),
+                move |vm, arguments| {
+                    let (is_stderr, text) = render(vm, arguments, parameter_type)?;
+                    if is_stderr {
+                        eprint!("{text}");
+                    } else {
+                        print!("{text}");
+                    }
+                    Ok(None)
+                },
+            );
+            registry.register(
+                PRINT_STREAM,
+                "println",
+                &format!("({parameter_type})V"),
+                move |vm, arguments| {
+                    let (is_stderr, text) = render(vm, arguments, parameter_type)?;
+                    if is_stderr {
+                        eprintln!("{text}");
+                    } else {
+                        println!("{text}");
+                    }
+                    Ok(None)
+                },
+            );
+        }
+        registry.register(PRINT_STREAM, "println", "()V", |vm, arguments| {
+            if is_stderr(vm, argument(arguments, 0)?) {
+                eprintln!();
+            } else {
+                println!();
+            }
+            Ok(None)
+        });
+    }
+}
+
+fn is_stderr(vm: &JvmCompatibleVm, stream: &JvmValue) -> bool {
+    vm.object_field(stream, "is_stderr") == Some(&JvmValue::Int(1))
+}
+
+/// The target stream and the text `String.valueOf` gives for the argument
+fn render(
+    vm: &mut JvmCompatibleVm,
+    arguments: &[JvmValue],
+    parameter_type: &str,
+) -> Result<(bool, String), RuntimeError> {
+    let stream = argument(arguments, 0)?;
+    let value = argument(arguments, 1)?;
+    Ok((
+        is_stderr(vm, stream),
+        vm.string_value_of(value, parameter_type)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_render_like_string_value_of() {
+        let mut vm = JvmCompatibleVm::new();
+        let stream = JvmValue::Reference(None);
+        let greeting = vm.new_string("héllo").unwrap();
+        for (value, parameter_type, expected) in [
+            (JvmValue::Int(-7), "I", "-7"),
+            (JvmValue::Long(1 << 40), "J", "1099511627776"),
+            (JvmValue::Float(0.1), "F", "0.1"),
+            (JvmValue::Double(1e7), "D", "1.0E7"),
+            (JvmValue::Int(1), "Z", "true"),
+            (JvmValue::Int(0x3B1), "C", "α"),
+            (greeting, "Ljava/lang/String;", "héllo"),
+            (JvmValue::Reference(None), "Ljava/lang/Object;", "null"),
+        ] {
+            let (is_stderr, text) =
+                render(&mut vm, &[stream.clone(), value], parameter_type).unwrap();
+            assert!(!is_stderr);
+            assert_eq!(text, expected);
+        }
+    }
+
+    #[test]
+    fn test_objects_render_with_their_to_string() {
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/Printing.class");
+        let mut vm = JvmCompatibleVm::new();
+        vm.execute_class_file(fixture, &[]).unwrap();
+        // No constructor runs, so the inherited toString sees zero sides
+        let die = vm.new_instance("Printing$FudgeDie").unwrap();
+        let stream = JvmValue::Reference(None);
+        let (_, text) = render(&mut vm, &[stream, die], "Ljava/lang/Object;").unwrap();
+        assert_eq!(text, "d0");
+    }
+
+    #[test]
+    fn test_every_printable_type_is_registered() {
+        let registry = NativeRegistry::with_jdk();
+        for parameter_type in PRINTABLE_TYPES {
+            let descriptor = format!("({parameter_type})V");
+            assert!(registry.contains(PRINT_STREAM, "print", &descriptor));
+            assert!(registry.contains(PRINT_STREAM, "println", &descriptor));
+        }
+        assert!(registry.contains(PRINT_STREAM, "println", "()V"));
+    }
+}
@@ -0,0 +1,319 @@
+//! `java.util.Random` and `java.util.concurrent.ThreadLocalRandom`
+//!
+//! `Random` uses the same 48-bit linear congruential generator as the JDK,
+//! so seeded programs produce the same numbers as on a reference JVM. The
+//! generator state is kept in fields of the `Random` object.
+
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::error::RuntimeError;
+use crate::jvm::jvm_compatible_vm::{JvmCompatibleVm, JvmValue};
+use rand::Rng;
+
+const RANDOM: &str = "java/util/Random";
+const THREAD_LOCAL_RANDOM: &str = "java/util/concurrent/ThreadLocalRandom";
+
+const MULTIPLIER: i64 = 0x5DEECE66D;
+const ADDEND: i64 = 0xB;
+const MASK: i64 = (1 << 48) - 1;
+
+pub struct RandomNatives;
+
+impl NativeProvider for RandomNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        register_random(registry);
+        register_thread_local_random(registry);
+    }
+}
+
+fn register_random(registry: &mut NativeRegistry) {
+    // Unseeded generators draw their seed from the host
+    registry.register(RANDOM, "<init>", "()V", |vm, arguments| {
+        set_seed(vm, argument(arguments, 0)?, rand::rng().random())?;
+        Ok(None)
+    });
+    registry.register(RANDOM, "<init>", "(J)V", |vm, arguments| {
+        let seed = argument(arguments, 1)?.as_long()?;
+        set_seed(vm, argument(arguments, 0)?, seed)?;
+        Ok(None)
+    });
+    registry.register(RANDOM, "setSeed", "(J)V", |vm, arguments| {
+        let seed = argument(arguments, 1)?.as_long()?;
+        set_seed(vm, argument(arguments, 0)?, seed)?;
+        Ok(None)
+    });
+
+    registry.register(RANDOM, "nextInt", "()I", |vm, arguments| {
+        let value = next(vm, argument(arguments, 0)?, 32)?;
+        Ok(Some(JvmValue::Int(value)))
+    });
+    registry.register(RANDOM, "nextInt", "(I)I", |vm, arguments| {
+        let random = argument(arguments, 0)?;
+        let bound = argument(arguments, 1)?.as_int()?;
+        if bound <= 0 {
+            return Err(vm.throw_exception(
+                "java/lang/IllegalArgumentException",
+                Some("bound must be positive".to_string()),
+            ));
+        }
+        Ok(Some(JvmValue::Int(next_int_bounded(vm, random, bound)?)))
+    });
+    registry.register(RANDOM, "nextLong", "()J", |vm, arguments| {
+        let random = argument(arguments, 0)?;
+        let high = next(vm, random, 32)? as i64;
+        let low = next(vm, random, 32)? as i64;
+        Ok(Some(JvmValue::Long((high << 32).wrapping_add(low))))
+    });
+    registry.register(RANDOM, "nextBoolean", "()Z", |vm, arguments| {
+        let value = next(vm, argument(arguments, 0)?, 1)?;
+        Ok(Some(JvmValue::Boolean(value != 0)))
+    });
+    registry.register(RANDOM, "nextFloat", "()F", |vm, arguments| {
+        let value = next(vm, argument(arguments, 0)?, 24)?;
+        Ok(Some(JvmValue::Float(value as f32 / (1 << 24) as f32)))
+    });
+    registry.register(RANDOM, "nextDouble", "()D", |vm, arguments| {
+        let value = next_double(vm, argument(arguments, 0)?)?;
+        Ok(Some(JvmValue::Double(value)))
+    });
+    registry.register(RANDOM, "nextGaussian", "()D", |vm, arguments| {
+        let value = next_gaussian(vm, argument(arguments, 0)?)?;
+        Ok(Some(JvmValue::Double(value)))
+    });
+}
+
+/// `ThreadLocalRandom` cannot be seeded, so it is backed by the host RNG
+fn register_thread_local_random(registry: &mut NativeRegistry) {
+    registry.register(
+        THREAD_LOCAL_RANDOM,
+        "current",
+        "()Ljava/util/concurrent/ThreadLocalRandom;",
+        |vm, _| Ok(Some(vm.new_instance(THREAD_LOCAL_RANDOM)?)),
+    );
+    registry.register(THREAD_LOCAL_RANDOM, "nextInt", "(II)I", |vm, arguments| {
+        let origin = argument(arguments, 1)?.as_int()?;
+        let bound = argument(arguments, 2)?.as_int()?;
+        if origin >= bound {
+            return Err(vm.throw_exception(
+                "java/lang/IllegalArgumentException",
+                Some("bound must be greater than origin".to_string()),
+            ));
+        }
+        Ok(Some(JvmValue::Int(rand::rng().random_range(origin..bound))))
+    });
+    registry.register(THREAD_LOCAL_RANDOM, "nextInt", "(I)I", |vm, arguments| {
+        let bound = argument(arguments, 1)?.as_int()?;
+        if bound <= 0 {
+            return Err(vm.throw_exception(
+                "java/lang/IllegalArgumentException",
+                Some("bound must be positive".to_string()),
+            ));
+        }
+        Ok(Some(JvmValue::Int(rand::rng().random_range(0..bound))))
+    });
+    registry.register(THREAD_LOCAL_RANDOM, "nextDouble", "()D", |_, _| {
+        Ok(Some(JvmValue::Double(rand::rng().random::<f64>())))
+    });
+}
+
+/// `Random.setSeed`, which also discards a pending Gaussian
+fn set_seed(vm: &mut JvmCompatibleVm, random: &JvmValue, seed: i64) -> Result<(), RuntimeError> {
+    vm.set_object_field(random, "seed", JvmValue::Long((seed ^ MULTIPLIER) & MASK))?;
+    vm.set_object_field(random, "haveNextNextGaussian", JvmValue::Boolean(false))
+}
+
+/// `Random.next`: advance the generator and return its top `bits` bits
+fn next(vm: &mut JvmCompatibleVm, random: &JvmValue, bits: u32) -> Result<i32, RuntimeError> {
+    let seed = match vm.object_field(random, "seed") {
+        Some(seed) => seed.as_long()?,
+        None => return Err(RuntimeError::InvalidStackState),
+    };
+    let seed = seed.wrapping_mul(MULTIPLIER).wrapping_add(ADDEND) & MASK;
+    vm.set_object_field(random, "seed", JvmValue::Long(seed))?;
+    Ok((seed >> (48 - bits)) as i32)
+}
+
+/// `Random.nextInt(bound)`, rejecting values that would bias the result
+fn next_int_bounded(
+    vm: &mut JvmCompatibleVm,
+    random: &JvmValue,
+    bound: i32,
+) -> Result<i32, RuntimeError> {
+    let r = next(vm, random, 31)?;
+    let m = bound - 1;
+    if bound & m == 0 {
+        return Ok(((bound as i64 * r as i64) >> 31) as i32);
+    }
+    let mut u = r;
+    loop {
+        let r = u % bound;
+        if u.wrapping_sub(r).wrapping_add(m) >= 0 {
+            return Ok(r);
+        }
+        u = next(vm, random, 31)?;
+    }
+}
+
+fn next_double(vm: &mut JvmCompatibleVm, random: &JvmValue) -> Result<f64, RuntimeError> {
+    let high = next(vm, random, 26)? as i64;
+    let low = next(vm, random, 27)? as i64;
+    Ok(((high << 27) + low) as f64 * (1.0 / (1u64 << 53) as f64))
+}
+
+/// `Random.nextGaussian`: the polar method, which produces two values at a
+/// time and keeps the second for the next call
+fn next_gaussian(vm: &mut JvmCompatibleVm, random: &JvmValue) -> Result<f64, RuntimeError> {
+    if vm.object_field(random, "haveNextNextGaussian") == Some(&JvmValue::Boolean(true)) {
+        vm.set_object_field(random, "haveNextNextGaussian", JvmValue::Boolean(false))?;
+        return match vm.object_field(random, "nextNextGaussian") {
+            Some(value) => value.as_double(),
+            None => Err(RuntimeError::InvalidStackState),
+        };
+    }
+    let (v1, v2, s) = loop {
+        let v1 = 2.0 * next_double(vm, random)? - 1.0;
+        let v2 = 2.0 * next_double(vm, random)? - 1.0;
+        let s = v1 * v1 + v2 * v2;
+        if s < 1.0 && s != 0.0 {
+            break (v1, v2, s);
+        }
+    };
+    let multiplier = (-2.0 * s.ln() / s).sqrt();
+    vm.set_object_field(
+        random,
+        "nextNextGaussian",
+        JvmValue::Double(v2 * multiplier),
+    )?;
+    vm.set_object_field(random, "haveNextNextGaussian", JvmValue::Boolean(true))?;
+    Ok(v1 * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded(vm: &mut JvmCompatibleVm, seed: i64) -> JvmValue {
+        let random = vm.new_instance(RANDOM).unwrap();
+        vm.invoke_native(
+            RANDOM,
+            "<init>",
+            "(J)V",
+            &[random.clone(), JvmValue::Long(seed)],
+        )
+        .unwrap();
+        random
+    }
+
+    fn call(
+        vm: &mut JvmCompatibleVm,
+        name: &str,
+        descriptor: &str,
+        arguments: &[JvmValue],
+    ) -> JvmValue {
+        vm.invoke_native(RANDOM, name, descriptor, arguments)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_seeded_sequences_match_the_jdk() {
+        // Expected values from `new Random(0)` on a reference JVM
+        let mut vm = JvmCompatibleVm::new();
+        let random = seeded(&mut vm, 0);
+        let arguments = std::slice::from_ref(&random);
+        assert_eq!(
+            call(&mut vm, "nextInt", "()I", arguments),
+            JvmValue::Int(-1155484576)
+        );
+        assert_eq!(
+            call(&mut vm, "nextLong", "()J", arguments),
+            JvmValue::Long(-3109364765729502342)
+        );
+        assert_eq!(
+            call(&mut vm, "nextDouble", "()D", arguments),
+            JvmValue::Double(0.6063452159973596)
+        );
+
+        let random = seeded(&mut vm, 0);
+        let rolls: Vec<JvmValue> = (0..5)
+            .map(|_| {
+                call(
+                    &mut vm,
+                    "nextInt",
+                    "(I)I",
+                    &[random.clone(), JvmValue::Int(6)],
+                )
+            })
+            .collect();
+        let expected = [0, 4, 1, 5, 5].map(JvmValue::Int);
+        assert_eq!(rolls, expected);
+    }
+
+    #[test]
+    fn test_gaussians_come_in_pairs_until_reseeded() {
+        let mut vm = JvmCompatibleVm::new();
+        let random = seeded(&mut vm, 7);
+        let first = call(
+            &mut vm,
+            "nextGaussian",
+            "()D",
+            std::slice::from_ref(&random),
+        );
+
+        // Reseeding discards the pending second value
+        call(
+            &mut vm,
+            "nextGaussian",
+            "()D",
+            std::slice::from_ref(&random),
+        );
+        vm.invoke_native(
+            RANDOM,
+            "setSeed",
+            "(J)V",
+            &[random.clone(), JvmValue::Long(7)],
+        )
+        .unwrap();
+        let again = call(
+            &mut vm,
+            "nextGaussian",
+            "()D",
+            std::slice::from_ref(&random),
+        );
+        assert_eq!(first, again);
+    }
+
+    #[test]
+    fn test_thread_local_random_stays_in_range() {
+        let mut vm = JvmCompatibleVm::new();
+        let random = vm
+            .invoke_native(
+                THREAD_LOCAL_RANDOM,
+                "current",
+                "()Ljava/util/concurrent/ThreadLocalRandom;",
+                &[],
+            )
+            .unwrap()
+            .unwrap();
+        for _ in 0..100 {
+            let roll = vm
+                .invoke_native(
+                    THREAD_LOCAL_RANDOM,
+                    "nextInt",
+                    "(II)I",
+                    &[random.clone(), JvmValue::Int(1), JvmValue::Int(7)],
+                )
+                .unwrap()
+                .unwrap()
+                .as_int()
+                .unwrap();
+            assert!((1..7).contains(&roll));
+        }
+        let result = vm.invoke_native(
+            THREAD_LOCAL_RANDOM,
+            "nextInt",
+            "(II)I",
+            &[random, JvmValue::Int(7), JvmValue::Int(7)],
+        );
+        assert!(matches!(result, Err(RuntimeError::Exception(_))));
+    }
+}
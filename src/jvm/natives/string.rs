@@ -0,0 +1,443 @@
+//! `java.lang.String` and `java.lang.StringBuilder`
+//!
+//! Java strings are sequences of UTF-16 code units, so lengths and indices
+//! count code units rather than bytes or characters.
+
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::error::RuntimeError;
+use crate::jvm::jvm_compatible_vm::{JvmCompatibleVm, JvmValue};
+
+const STRING: &str = "java/lang/String";
+const STRING_BUILDER: &str = "java/lang/StringBuilder";
+
+/// Primitive types accepted by `String.valueOf`
+const PRIMITIVE_TYPES: &[&str] = &["I", "J", "F", "D", "Z", "C"];
+
+/// Parameter types accepted by `StringBuilder.append`
+const APPENDABLE_TYPES: &[&str] = &[
+    "I",
+    "J",
+    "F",
+    "D",
+    "Z",
+    "C",
+    "Ljava/lang/String;",
+    "Ljava/lang/Object;",
+];
+
+pub struct StringNatives;
+
+impl NativeProvider for StringNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        register_string(registry);
+        register_string_builder(registry);
+    }
+}
+
+fn register_string(registry: &mut NativeRegistry) {
+    registry.register(STRING, "length", "()I", |vm, arguments| {
+        let length = vm
+            .string_value(argument(arguments, 0)?)?
+            .encode_utf16()
+            .count();
+        Ok(Some(JvmValue::Int(length as i32)))
+    });
+    // s[0]*31^(n-1) + ... + s[n-1]
+    registry.register(STRING, "hashCode", "()I", |vm, arguments| {
+        let hash = vm
+            .string_value(argument(arguments, 0)?)?
+            .encode_utf16()
+            .fold(0i32, |hash, unit| {
+                hash.wrapping_mul(31).wrapping_add(unit as i32)
+            });
+        Ok(Some(JvmValue::Int(hash)))
+    });
+    registry.register(
+        STRING,
+        "equals",
+        "(Ljava/lang/Object;)Z",
+        |vm, arguments| {
+            let string = vm.string_value(argument(arguments, 0)?)?;
+            // Anything other than a string, including null, is unequal
+            let equal = vm
+                .string_value(argument(arguments, 1)?)
+                .is_ok_and(|other| other == string);
+            Ok(Some(JvmValue::Boolean(equal)))
+        },
+    );
+    registry.register(STRING, "charAt", "(I)C", |vm, arguments| {
+        let index = argument(arguments, 1)?.as_int()?;
+        let unit = usize::try_from(index).ok().and_then(|index| {
+            vm.string_value(&arguments[0])
+                .ok()?
+                .encode_utf16()
+                .nth(index)
+        });
+        match unit {
+            Some(unit) => Ok(Some(JvmValue::Char(unit))),
+            None => {
+                // Surface a null receiver before the index check
+                vm.string_value(&arguments[0])?;
+                Err(vm.throw_exception(
+                    "java/lang/StringIndexOutOfBoundsException",
+                    Some(format!("String index out of range: {index}")),
+                ))
+            }
+        }
+    });
+    registry.register(
+        STRING,
+        "substring",
+        "(II)Ljava/lang/String;",
+        |vm, arguments| {
+            let units: Vec<u16> = vm
+                .string_value(argument(arguments, 0)?)?
+                .encode_utf16()
+                .collect();
+            let begin = argument(arguments, 1)?.as_int()?;
+            let end = argument(arguments, 2)?.as_int()?;
+            substring(vm, &units, begin, end)
+        },
+    );
+    registry.register(
+        STRING,
+        "substring",
+        "(I)Ljava/lang/String;",
+        |vm, arguments| {
+            let units: Vec<u16> = vm
+                .string_value(argument(arguments, 0)?)?
+                .encode_utf16()
+                .collect();
+            let begin = argument(arguments, 1)?.as_int()?;
+            substring(vm, &units, begin, units.len() as i32)
+        },
+    );
+    registry.register(STRING, "indexOf", "(I)I", |vm, arguments| {
+        let target = argument(arguments, 1)?.as_int()?;
+        let string = vm.string_value(argument(arguments, 0)?)?;
+        // The argument is a code point, which may need a surrogate pair
+        let position = char::from_u32(target as u32).and_then(|target| {
+            let mut buffer = [0; 2];
+            let target: &[u16] = target.encode_utf16(&mut buffer);
+            let units: Vec<u16> = string.encode_utf16().collect();
+            units
+                .windows(target.len())
+                .position(|window| window == target)
+        });
+        Ok(Some(JvmValue::Int(
+            position.map_or(-1, |position| position as i32),
+        )))
+    });
+    registry.register(
+        STRING,
+        "toUpperCase",
+        "()Ljava/lang/String;",
+        |vm, arguments| {
+            let upper = vm.string_value(argument(arguments, 0)?)?.to_uppercase();
+            Ok(Some(vm.new_string(upper)?))
+        },
+    );
+    registry.register(
+        STRING,
+        "toLowerCase",
+        "()Ljava/lang/String;",
+        |vm, arguments| {
+            let lower = vm.string_value(argument(arguments, 0)?)?.to_lowercase();
+            Ok(Some(vm.new_string(lower)?))
+        },
+    );
+    registry.register(
+        STRING,
+        "toString",
+        "()Ljava/lang/String;",
+        |_, arguments| Ok(Some(argument(arguments, 0)?.clone())),
+    );
+    // Lexicographic order of UTF-16 code units, returning the difference of
+    // the first units that differ, or else of the lengths
+    for parameter_type in ["Ljava/lang/String;", "Ljava/lang/Object;"] {
+        registry.register(
+            STRING,
+            "compareTo",
+            &format!("({parameter_type})I"),
+            |vm, arguments| {
+                let string: Vec<u16> = vm
+                    .string_value(argument(arguments, 0)?)?
+                    .encode_utf16()
+                    .collect();
+                let other: Vec<u16> = vm
+                    .string_value(argument(arguments, 1)?)?
+                    .encode_utf16()
+                    .collect();
+                let difference = string
+                    .iter()
+                    .zip(&other)
+                    .find(|(a, b)| a != b)
+                    .map_or(string.len() as i32 - other.len() as i32, |(a, b)| {
+                        *a as i32 - *b as i32
+                    });
+                Ok(Some(JvmValue::Int(difference)))
+            },
+        );
+    }
+    for &parameter_type in PRIMITIVE_TYPES {
+        registry.register(
+            STRING,
+            "valueOf",
+            &format!("({parameter_type})Ljava/lang/String;"),
+            move |vm, arguments| {
+                let text = vm.value_to_java_string(argument(arguments, 0)?, parameter_type);
+                Ok(Some(vm.new_string(text)?))
+            },
+        );
+    }
+    // Objects are rendered by their own toString, which may be user code
+    registry.register(
+        STRING,
+        "valueOf",
+        "(Ljava/lang/Object;)Ljava/lang/String;",
+        |vm, arguments| {
+            let value = argument(arguments, 0)?;
+            if value.is_null() {
+                return Ok(Some(vm.new_string("null")?));
+            }
+            vm.call_method(value, "toString", "()Ljava/lang/String;", &[])
+        },
+    );
+    // String.trim removes every character up to and including U+0020
+    registry.register(STRING, "trim", "()Ljava/lang/String;", |vm, arguments| {
+        let trimmed = vm
+            .string_value(argument(arguments, 0)?)?
+            .trim_matches(|c: char| c <= ' ')
+            .to_string();
+        Ok(Some(vm.new_string(trimmed)?))
+    });
+    registry.register(
+        STRING,
+        "concat",
+        "(Ljava/lang/String;)Ljava/lang/String;",
+        |vm, arguments| {
+            let string = vm.string_value(argument(arguments, 0)?)?;
+            let other = vm.string_value(argument(arguments, 1)?)?;
+            vm.check_string_growth(string.len() + other.len())?;
+            let mut joined = String::new();
+            joined
+                .try_reserve_exact(string.len() + other.len())
+                .map_err(|_| RuntimeError::OutOfMemory)?;
+            joined.push_str(string);
+            joined.push_str(other);
+            Ok(Some(vm.new_string(joined)?))
+        },
+    );
+}
+
+/// `String.substring`, with Java's bounds checks on code unit indices
+fn substring(
+    vm: &mut JvmCompatibleVm,
+    units: &[u16],
+    begin: i32,
+    end: i32,
+) -> Result<Option<JvmValue>, RuntimeError> {
+    let length = units.len() as i32;
+    if begin < 0 || end > length || begin > end {
+        return Err(vm.throw_exception(
+            "java/lang/StringIndexOutOfBoundsException",
+            Some(format!("begin {begin}, end {end}, length {length}")),
+        ));
+    }
+    let text = String::from_utf16_lossy(&units[begin as usize..end as usize]);
+    Ok(Some(vm.new_string(text)?))
+}
+
+/// A builder keeps its contents in the same storage as strings, so it is
+/// rendered by its contents, as `StringBuilder.toString` would
+fn register_string_builder(registry: &mut NativeRegistry) {
+    registry.register(STRING_BUILDER, "<init>", "()V", |vm, arguments| {
+        vm.string_buffer_mut(argument(arguments, 0)?)?.clear();
+        Ok(None)
+    });
+    registry.register(
+        STRING_BUILDER,
+        "<init>",
+        "(Ljava/lang/String;)V",
+        |vm, arguments| {
+            let initial = vm.string_value(argument(arguments, 1)?)?.to_string();
+            vm.string_buffer_mut(&arguments[0])?.clear();
+            vm.append_to_buffer(&arguments[0], &initial)?;
+            Ok(None)
+        },
+    );
+    for &parameter_type in APPENDABLE_TYPES {
+        registry.register(
+            STRING_BUILDER,
+            "append",
+            &format!("({parameter_type})Ljava/lang/StringBuilder;"),
+            move |vm, arguments| {
+                let builder = argument(arguments, 0)?;
+                let text = vm.string_value_of(argument(arguments, 1)?, parameter_type)?;
+                vm.append_to_buffer(builder, &text)?;
+                Ok(Some(builder.clone()))
+            },
+        );
+    }
+    registry.register(STRING_BUILDER, "length", "()I", |vm, arguments| {
+        let length = vm
+            .string_buffer_mut(argument(arguments, 0)?)?
+            .encode_utf16()
+            .count();
+        Ok(Some(JvmValue::Int(length as i32)))
+    });
+    registry.register(
+        STRING_BUILDER,
+        "toString",
+        "()Ljava/lang/String;",
+        |vm, arguments| {
+            let contents = vm.string_buffer_mut(argument(arguments, 0)?)?.clone();
+            Ok(Some(vm.new_string(contents)?))
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(
+        vm: &mut JvmCompatibleVm,
+        name: &str,
+        descriptor: &str,
+        arguments: &[JvmValue],
+    ) -> Result<Option<JvmValue>, RuntimeError> {
+        vm.invoke_native(STRING, name, descriptor, arguments)
+    }
+
+    fn text(vm: &JvmCompatibleVm, value: Option<JvmValue>) -> String {
+        vm.string_value(&value.unwrap()).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_string_methods_count_utf16_code_units() {
+        let mut vm = JvmCompatibleVm::new();
+        let die = vm.new_string("🎲d20").unwrap();
+        assert_eq!(
+            call(&mut vm, "length", "()I", std::slice::from_ref(&die)).unwrap(),
+            Some(JvmValue::Int(5))
+        );
+        assert_eq!(
+            call(&mut vm, "charAt", "(I)C", &[die.clone(), JvmValue::Int(2)]).unwrap(),
+            Some(JvmValue::Char('d' as u16))
+        );
+        assert_eq!(
+            call(
+                &mut vm,
+                "indexOf",
+                "(I)I",
+                &[die.clone(), JvmValue::Int('2' as i32)]
+            )
+            .unwrap(),
+            Some(JvmValue::Int(3))
+        );
+        let result = call(
+            &mut vm,
+            "substring",
+            "(II)Ljava/lang/String;",
+            &[die.clone(), JvmValue::Int(2), JvmValue::Int(5)],
+        )
+        .unwrap();
+        assert_eq!(text(&vm, result), "d20");
+    }
+
+    #[test]
+    fn test_string_hash_code_and_equals() {
+        let mut vm = JvmCompatibleVm::new();
+        let first = vm.new_string("Aa").unwrap();
+        let second = vm.new_string("BB").unwrap();
+        let hash = |vm: &mut JvmCompatibleVm, value: &JvmValue| {
+            call(vm, "hashCode", "()I", std::slice::from_ref(value)).unwrap()
+        };
+        assert_eq!(hash(&mut vm, &first), Some(JvmValue::Int(2112)));
+        assert_eq!(hash(&mut vm, &first), hash(&mut vm, &second));
+
+        let equals = |vm: &mut JvmCompatibleVm, other: JvmValue| {
+            call(
+                vm,
+                "equals",
+                "(Ljava/lang/Object;)Z",
+                &[first.clone(), other],
+            )
+            .unwrap()
+        };
+        let copy = vm.new_string("Aa").unwrap();
+        assert_eq!(equals(&mut vm, copy), Some(JvmValue::Boolean(true)));
+        assert_eq!(
+            equals(&mut vm, second.clone()),
+            Some(JvmValue::Boolean(false))
+        );
+        assert_eq!(
+            equals(&mut vm, JvmValue::Reference(None)),
+            Some(JvmValue::Boolean(false))
+        );
+    }
+
+    #[test]
+    fn test_string_bounds_and_null_receiver_raise_exceptions() {
+        let mut vm = JvmCompatibleVm::new();
+        let die = vm.new_string("d6").unwrap();
+        assert!(matches!(
+            call(&mut vm, "charAt", "(I)C", &[die.clone(), JvmValue::Int(5)]),
+            Err(RuntimeError::Exception(_))
+        ));
+        assert!(matches!(
+            call(
+                &mut vm,
+                "substring",
+                "(II)Ljava/lang/String;",
+                &[die, JvmValue::Int(1), JvmValue::Int(0)]
+            ),
+            Err(RuntimeError::Exception(_))
+        ));
+        assert!(matches!(
+            call(&mut vm, "length", "()I", &[JvmValue::Reference(None)]),
+            Err(RuntimeError::NullPointer)
+        ));
+    }
+
+    #[test]
+    fn test_string_builder_appends_java_representations() {
+        let mut vm = JvmCompatibleVm::new();
+        let builder = vm.new_instance(STRING_BUILDER).unwrap();
+        vm.invoke_native(
+            STRING_BUILDER,
+            "<init>",
+            "()V",
+            std::slice::from_ref(&builder),
+        )
+        .unwrap();
+        let text_argument = vm.new_string("3d6=").unwrap();
+        for (value, parameter_type) in [
+            (text_argument, "Ljava/lang/String;"),
+            (JvmValue::Int(11), "I"),
+            (JvmValue::Int(1), "Z"),
+            (JvmValue::Double(0.5), "D"),
+        ] {
+            let descriptor = format!("({parameter_type})Ljava/lang/StringBuilder;");
+            let result = vm
+                .invoke_native(
+                    STRING_BUILDER,
+                    "append",
+                    &descriptor,
+                    &[builder.clone(), value],
+                )
+                .unwrap();
+            assert_eq!(result, Some(builder.clone()));
+        }
+        let result = vm
+            .invoke_native(
+                STRING_BUILDER,
+                "toString",
+                "()Ljava/lang/String;",
+                &[builder],
+            )
+            .unwrap();
+        assert_eq!(text(&vm, result), "3d6=11true0.5");
+    }
+}
@@ -0,0 +1,447 @@
+//! The primitive wrapper classes `Integer`, `Long`, `Float`, `Double`,
+//! `Boolean` and `Character`: parsing helpers and boxing
+//!
+//! A boxed value is an object of the wrapper class holding the primitive in
+//! its `value` field.
+
+use super::{NativeProvider, NativeRegistry, argument};
+use crate::error::RuntimeError;
+use crate::jvm::jvm_compatible_vm::{JvmCompatibleVm, JvmValue, format_java_double};
+use std::cmp::Ordering;
+
+/// Wrapper classes and the descriptor of the primitive they box
+const BOXED_TYPES: &[(&str, &str)] = &[
+    ("java/lang/Integer", "I"),
+    ("java/lang/Long", "J"),
+    ("java/lang/Float", "F"),
+    ("java/lang/Double", "D"),
+    ("java/lang/Boolean", "Z"),
+    ("java/lang/Character", "C"),
+];
+
+/// Numeric conversions every `Number` wrapper provides
+const NUMBER_VALUES: &[(&str, &str)] = &[
+    ("intValue", "I"),
+    ("longValue", "J"),
+    ("floatValue", "F"),
+    ("doubleValue", "D"),
+];
+
+/// The primitive descriptor boxed by a wrapper class
+pub(crate) fn boxed_descriptor(class_name: &str) -> Option<&'static str> {
+    BOXED_TYPES
+        .iter()
+        .find(|(name, _)| *name == class_name)
+        .map(|(_, descriptor)| *descriptor)
+}
+
+pub struct WrapperNatives;
+
+impl NativeProvider for WrapperNatives {
+    fn register(&self, registry: &mut NativeRegistry) {
+        registry.register(
+            "java/lang/Integer",
+            "parseInt",
+            "(Ljava/lang/String;)I",
+            |vm, arguments| {
+                let text = parse_argument(vm, argument(arguments, 0)?)?;
+                match text.parse::<i32>() {
+                    Ok(value) => Ok(Some(JvmValue::Int(value))),
+                    Err(_) => Err(number_format_exception(vm, &text)),
+                }
+            },
+        );
+        registry.register(
+            "java/lang/Integer",
+            "toString",
+            "(I)Ljava/lang/String;",
+            |vm, arguments| {
+                let value = argument(arguments, 0)?.as_int()?;
+                Ok(Some(vm.new_string(value.to_string())?))
+            },
+        );
+
+        // Double.parseDouble ignores surrounding whitespace, unlike parseInt
+        registry.register(
+            "java/lang/Double",
+            "parseDouble",
+            "(Ljava/lang/String;)D",
+            |vm, arguments| {
+                let text = vm.string_value(argument(arguments, 0)?)?.to_string();
+                match text.trim_matches(|c: char| c <= ' ').parse::<f64>() {
+                    Ok(value) => Ok(Some(JvmValue::Double(value))),
+                    Err(_) => Err(number_format_exception(vm, &text)),
+                }
+            },
+        );
+        registry.register(
+            "java/lang/Double",
+            "toString",
+            "(D)Ljava/lang/String;",
+            |vm, arguments| {
+                let value = argument(arguments, 0)?.as_double()?;
+                Ok(Some(vm.new_string(format_java_double(value))?))
+            },
+        );
+
+        // Anything but a case-insensitive "true", including null, is false
+        registry.register(
+            "java/lang/Boolean",
+            "parseBoolean",
+            "(Ljava/lang/String;)Z",
+            |vm, arguments| {
+                let value = vm
+                    .string_value(argument(arguments, 0)?)
+                    .is_ok_and(|text| text.eq_ignore_ascii_case("true"));
+                Ok(Some(JvmValue::Boolean(value)))
+            },
+        );
+        registry.register(
+            "java/lang/Boolean",
+            "toString",
+            "(Z)Ljava/lang/String;",
+            |vm, arguments| {
+                let value = argument(arguments, 0)?.as_boolean()?;
+                Ok(Some(vm.new_string(value.to_string())?))
+            },
+        );
+
+        // Only ASCII digits count as digits
+        let predicates = [
+            (
+                "isDigit",
+                (|c: char| c.is_ascii_digit()) as fn(char) -> bool,
+            ),
+            ("isLetter", char::is_alphabetic),
+        ];
+        for (name, predicate) in predicates {
+            registry.register("java/lang/Character", name, "(C)Z", move |_, arguments| {
+                let unit = argument(arguments, 0)?.as_char()?;
+                let result = char::from_u32(unit as u32).is_some_and(predicate);
+                Ok(Some(JvmValue::Boolean(result)))
+            });
+        }
+        let mappings = [
+            (
+                "toUpperCase",
+                (|c: char| c.to_uppercase().collect()) as fn(char) -> String,
+            ),
+            ("toLowerCase", |c| c.to_lowercase().collect()),
+        ];
+        for (name, mapping) in mappings {
+            registry.register("java/lang/Character", name, "(C)C", move |_, arguments| {
+                let unit = argument(arguments, 0)?.as_char()?;
+                Ok(Some(JvmValue::Char(map_case(unit, mapping))))
+            });
+        }
+
+        for &(class_name, descriptor) in BOXED_TYPES {
+            register_box(registry, class_name, descriptor);
+        }
+    }
+}
+
+/// Register `valueOf`, the unboxing methods and the `Object` and
+/// `Comparable` methods of a wrapper class
+fn register_box(registry: &mut NativeRegistry, class_name: &'static str, descriptor: &'static str) {
+    registry.register(
+        class_name,
+        "valueOf",
+        &format!("({descriptor})L{class_name};"),
+        move |vm, arguments| {
+            let value = primitive_value(argument(arguments, 0)?, descriptor)?;
+            let key = cache_key(&value);
+            if let Some(boxed) = key.and_then(|key| vm.box_cache_mut().get(&(class_name, key))) {
+                return Ok(Some(boxed.clone()));
+            }
+            let boxed = vm.new_instance(class_name)?;
+            vm.set_object_field(&boxed, "value", value)?;
+            if let Some(key) = key {
+                vm.box_cache_mut().insert((class_name, key), boxed.clone());
+            }
+            Ok(Some(boxed))
+        },
+    );
+
+    let unboxing: &[(&str, &str)] = match descriptor {
+        "Z" => &[("booleanValue", "Z")],
+        "C" => &[("charValue", "C")],
+        _ => NUMBER_VALUES,
+    };
+    for &(name, result_type) in unboxing {
+        registry.register(
+            class_name,
+            name,
+            &format!("(){result_type}"),
+            move |vm, arguments| {
+                let value = unbox(vm, argument(arguments, 0)?)?;
+                Ok(Some(convert_primitive(&value, result_type)?))
+            },
+        );
+    }
+
+    registry.register(class_name, "hashCode", "()I", |vm, arguments| {
+        let value = unbox(vm, argument(arguments, 0)?)?;
+        Ok(Some(JvmValue::Int(primitive_hash_code(&value)?)))
+    });
+    // Boxes are equal to boxes of the same class holding the same value;
+    // floating-point values compare by their bits, so NaN equals NaN
+    registry.register(
+        class_name,
+        "equals",
+        "(Ljava/lang/Object;)Z",
+        move |vm, arguments| {
+            let value = unbox(vm, argument(arguments, 0)?)?;
+            let other = argument(arguments, 1)?;
+            let equal = match vm.object_class(other) {
+                Some(other_class) if other_class == class_name => {
+                    let other = unbox(vm, other)?;
+                    compare_primitives(&value, &other, descriptor)? == Ordering::Equal
+                }
+                _ => false,
+            };
+            Ok(Some(JvmValue::Boolean(equal)))
+        },
+    );
+    registry.register(
+        class_name,
+        "toString",
+        "()Ljava/lang/String;",
+        move |vm, arguments| {
+            let value = unbox(vm, argument(arguments, 0)?)?;
+            let text = vm.value_to_java_string(&value, descriptor);
+            Ok(Some(vm.new_string(text)?))
+        },
+    );
+    for parameter_type in [format!("L{class_name};"), "Ljava/lang/Object;".to_string()] {
+        registry.register(
+            class_name,
+            "compareTo",
+            &format!("({parameter_type})I"),
+            move |vm, arguments| {
+                let value = unbox(vm, argument(arguments, 0)?)?;
+                let other = unbox(vm, argument(arguments, 1)?)?;
+                let ordering = compare_primitives(&value, &other, descriptor)?;
+                Ok(Some(JvmValue::Int(ordering as i32)))
+            },
+        );
+    }
+    registry.register(
+        class_name,
+        "compare",
+        &format!("({descriptor}{descriptor})I"),
+        move |_, arguments| {
+            let ordering =
+                compare_primitives(argument(arguments, 0)?, argument(arguments, 1)?, descriptor)?;
+            Ok(Some(JvmValue::Int(ordering as i32)))
+        },
+    );
+}
+
+/// The values whose boxes `valueOf` must reuse: -128 to 127 for integers,
+/// characters up to 127, and both booleans. Floating-point boxes are never
+/// shared.
+fn cache_key(value: &JvmValue) -> Option<i64> {
+    let (key, cached) = match *value {
+        JvmValue::Int(i) => (i64::from(i), -128..=127),
+        JvmValue::Long(l) => (l, -128..=127),
+        JvmValue::Char(c) => (i64::from(c), 0..=127),
+        JvmValue::Boolean(b) => (i64::from(b), 0..=1),
+        _ => return None,
+    };
+    cached.contains(&key).then_some(key)
+}
+
+/// The primitive held by a box; unboxing null is a NullPointerException
+fn unbox(vm: &JvmCompatibleVm, boxed: &JvmValue) -> Result<JvmValue, RuntimeError> {
+    boxed.as_reference()?;
+    vm.object_field(boxed, "value")
+        .cloned()
+        .ok_or(RuntimeError::InvalidStackState)
+}
+
+/// A stack value as the primitive type given by its descriptor
+fn primitive_value(value: &JvmValue, descriptor: &str) -> Result<JvmValue, RuntimeError> {
+    Ok(match descriptor {
+        "Z" => JvmValue::Boolean(value.as_boolean()?),
+        "C" => JvmValue::Char(value.as_char()?),
+        _ => convert_primitive(value, descriptor)?,
+    })
+}
+
+/// Widening or narrowing conversion between numeric primitives, as done by
+/// `Number.intValue` and friends
+fn convert_primitive(value: &JvmValue, descriptor: &str) -> Result<JvmValue, RuntimeError> {
+    Ok(match (descriptor, value) {
+        ("I", JvmValue::Long(l)) => JvmValue::Int(*l as i32),
+        ("I", JvmValue::Double(d)) => JvmValue::Int(*d as i32),
+        ("I", value) => JvmValue::Int(value.as_int()?),
+        ("J", JvmValue::Float(f)) => JvmValue::Long(*f as i64),
+        ("J", JvmValue::Double(d)) => JvmValue::Long(*d as i64),
+        ("J", value) => JvmValue::Long(value.as_long()?),
+        ("F", JvmValue::Long(l)) => JvmValue::Float(*l as f32),
+        ("F", JvmValue::Double(d)) => JvmValue::Float(*d as f32),
+        ("F", value) => JvmValue::Float(value.as_float()?),
+        ("D", value) => JvmValue::Double(value.as_double()?),
+        _ => value.clone(),
+    })
+}
+
+/// `hashCode` of a boxed primitive
+fn primitive_hash_code(value: &JvmValue) -> Result<i32, RuntimeError> {
+    Ok(match value {
+        JvmValue::Long(l) => (l ^ ((*l as u64) >> 32) as i64) as i32,
+        JvmValue::Double(d) => {
+            let bits = canonical_bits(*d);
+            (bits ^ (bits >> 32)) as i32
+        }
+        JvmValue::Float(f) => {
+            if f.is_nan() {
+                f32::NAN.to_bits() as i32
+            } else {
+                f.to_bits() as i32
+            }
+        }
+        JvmValue::Boolean(true) => 1231,
+        JvmValue::Boolean(false) => 1237,
+        value => value.as_int()?,
+    })
+}
+
+/// `Double.doubleToLongBits`, which maps every NaN to one bit pattern
+fn canonical_bits(value: f64) -> u64 {
+    if value.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        value.to_bits()
+    }
+}
+
+/// Natural ordering of two primitives of the given type. Floating-point
+/// values are ordered like `Double.compare`: -0.0 is below 0.0 and NaN is
+/// above everything.
+pub(super) fn compare_primitives(
+    a: &JvmValue,
+    b: &JvmValue,
+    descriptor: &str,
+) -> Result<Ordering, RuntimeError> {
+    Ok(match descriptor {
+        "F" | "D" => {
+            let (a, b) = (a.as_double()?, b.as_double()?);
+            match a.partial_cmp(&b) {
+                Some(Ordering::Equal) | None => {
+                    (canonical_bits(a) as i64).cmp(&(canonical_bits(b) as i64))
+                }
+                Some(ordering) => ordering,
+            }
+        }
+        "J" => a.as_long()?.cmp(&b.as_long()?),
+        _ => a.as_int()?.cmp(&b.as_int()?),
+    })
+}
+
+/// Apply a case mapping to a UTF-16 code unit. Like `Character.toUpperCase`,
+/// units whose mapping is not a single unit, such as 'ß' or surrogates, are
+/// returned unchanged.
+fn map_case(unit: u16, mapping: fn(char) -> String) -> u16 {
+    let Some(c) = char::from_u32(unit as u32) else {
+        return unit;
+    };
+    let mapped: Vec<u16> = mapping(c).encode_utf16().collect();
+    match mapped.as_slice() {
+        [mapped] => *mapped,
+        _ => unit,
+    }
+}
+
+/// The text of a string to parse; parsing null is a NumberFormatException
+fn parse_argument(vm: &mut JvmCompatibleVm, value: &JvmValue) -> Result<String, RuntimeError> {
+    if value.is_null() {
+        return Err(vm.throw_exception(
+            "java/lang/NumberFormatException",
+            Some("Cannot parse null string".to_string()),
+        ));
+    }
+    Ok(vm.string_value(value)?.to_string())
+}
+
+fn number_format_exception(vm: &mut JvmCompatibleVm, text: &str) -> RuntimeError {
+    vm.throw_exception(
+        "java/lang/NumberFormatException",
+        Some(format!("For input string: \"{text}\"")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing_numbers() {
+        let mut vm = JvmCompatibleVm::new();
+        let descriptor = "(Ljava/lang/String;)I";
+        for (text, expected) in [("42", 42), ("-7", -7), ("+3", 3)] {
+            let argument = vm.new_string(text).unwrap();
+            let result = vm.invoke_native("java/lang/Integer", "parseInt", descriptor, &[argument]);
+            assert_eq!(result.unwrap(), Some(JvmValue::Int(expected)));
+        }
+        for text in ["", " 1", "2147483648", "1d6"] {
+            let argument = vm.new_string(text).unwrap();
+            let result = vm.invoke_native("java/lang/Integer", "parseInt", descriptor, &[argument]);
+            assert!(
+                matches!(result, Err(RuntimeError::Exception(_))),
+                "{text:?}"
+            );
+        }
+        let null = JvmValue::Reference(None);
+        let result = vm.invoke_native("java/lang/Integer", "parseInt", descriptor, &[null]);
+        assert!(matches!(result, Err(RuntimeError::Exception(_))));
+
+        let argument = vm.new_string(" 1e3 ").unwrap();
+        let result = vm
+            .invoke_native(
+                "java/lang/Double",
+                "parseDouble",
+                "(Ljava/lang/String;)D",
+                &[argument],
+            )
+            .unwrap();
+        assert_eq!(result, Some(JvmValue::Double(1000.0)));
+    }
+
+    #[test]
+    fn test_to_string_uses_java_formatting() {
+        let mut vm = JvmCompatibleVm::new();
+        let result = vm
+            .invoke_native(
+                "java/lang/Double",
+                "toString",
+                "(D)Ljava/lang/String;",
+                &[JvmValue::Double(1e-4)],
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(vm.string_value(&result).unwrap(), "1.0E-4");
+    }
+
+    #[test]
+    fn test_character_helpers() {
+        let mut vm = JvmCompatibleVm::new();
+        let mut call = |name: &str, descriptor: &str, unit: char| {
+            vm.invoke_native(
+                "java/lang/Character",
+                name,
+                descriptor,
+                &[JvmValue::Char(unit as u16)],
+            )
+            .unwrap()
+            .unwrap()
+        };
+        assert_eq!(call("isDigit", "(C)Z", '7'), JvmValue::Boolean(true));
+        assert_eq!(call("isLetter", "(C)Z", '7'), JvmValue::Boolean(false));
+        assert_eq!(call("toUpperCase", "(C)C", 'd'), JvmValue::Char('D' as u16));
+        assert_eq!(call("isLetter", "(C)Z", 'Ť'), JvmValue::Boolean(true));
+        assert_eq!(call("toLowerCase", "(C)C", 'Ť'), JvmValue::Char('ť' as u16));
+        assert_eq!(call("toUpperCase", "(C)C", 'ß'), JvmValue::Char('ß' as u16));
+    }
+}
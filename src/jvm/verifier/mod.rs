@@ -0,0 +1,1701 @@
+//! Bytecode verifier
+//!
+//! Class files are checked before they run, in the manner of the JVM's
+//! type-checking verifier: each method is interpreted over types instead of
+//! values, and one that would overflow its operand stack, touch a local it
+//! does not have, use a value as the wrong type, load the wrong kind of
+//! constant or jump outside its code is rejected with a `VerifyError`.
+//! Where a method has a `StackMapTable`, the types at branch targets are
+//! taken from it; otherwise they are inferred by merging every path.
+//!
+//! References are checked as references without their class, since the
+//! class hierarchy is not known until classes are loaded.
+
+use super::class_file_parser::{ClassFile, CodeAttribute, MethodInfo};
+use super::class_file_writer::{instruction_offsets, instruction_size};
+use super::jvm_types::{ConstantPool, ConstantPoolEntry, JvmInstruction, method_parameter_types};
+use crate::error::RuntimeError;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+mod stack_map;
+
+pub use stack_map::add_stack_map_table;
+
+const ACC_STATIC: u16 = 0x0008;
+
+/// First class file version whose `invokestatic` and `invokespecial` may
+/// name interface methods
+const INTERFACE_STATIC_CALLS_VERSION: u16 = 52;
+
+/// Type of a local variable or operand stack entry. A long or double fills
+/// two local slots, the second holding `Top`, but is one stack entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    Reference,
+}
+
+impl Type {
+    /// Local variable slots, and `max_stack` units, the type takes
+    fn width(self) -> usize {
+        match self {
+            Type::Long | Type::Double => 2,
+            _ => 1,
+        }
+    }
+
+    /// Whether a value of this type can be used where `expected` is required
+    fn is_assignable_to(self, expected: Type) -> bool {
+        self == expected
+            || expected == Type::Top
+            || (self == Type::Null && expected == Type::Reference)
+    }
+
+    /// The type both values fit, if there is one
+    fn merge(self, other: Type) -> Option<Type> {
+        if self == other {
+            Some(self)
+        } else if self.is_assignable_to(Type::Reference) && other.is_assignable_to(Type::Reference)
+        {
+            Some(Type::Reference)
+        } else {
+            None
+        }
+    }
+}
+
+/// Type of a field descriptor, or `None` for the `V` of a void method
+fn descriptor_type(descriptor: &str) -> Result<Option<Type>, String> {
+    Ok(Some(match descriptor.as_bytes().first() {
+        Some(b'B' | b'C' | b'I' | b'S' | b'Z') => Type::Integer,
+        Some(b'F') => Type::Float,
+        Some(b'J') => Type::Long,
+        Some(b'D') => Type::Double,
+        Some(b'L' | b'[') => Type::Reference,
+        Some(b'V') => return Ok(None),
+        _ => return Err(format!("malformed descriptor {descriptor:?}")),
+    }))
+}
+
+/// Parameter types and return type of a method descriptor
+fn method_types(descriptor: &str) -> Result<(Vec<Type>, Option<Type>), String> {
+    let Some((_, return_descriptor)) = descriptor
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+    else {
+        return Err(format!("malformed method descriptor {descriptor:?}"));
+    };
+    let parameters = method_parameter_types(descriptor)
+        .into_iter()
+        .map(|parameter| {
+            descriptor_type(parameter)?
+                .ok_or_else(|| format!("malformed method descriptor {descriptor:?}"))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((parameters, descriptor_type(return_descriptor)?))
+}
+
+/// Types of the locals and operand stack before an instruction
+#[derive(Debug, Clone, PartialEq)]
+struct Frame {
+    /// One entry per slot, `max_locals` long
+    locals: Vec<Type>,
+    /// One entry per value, whatever its width
+    stack: Vec<Type>,
+}
+
+impl Frame {
+    /// Combine the frames of two paths into one, making locals of
+    /// different types unusable
+    fn merge(&self, other: &Frame) -> Result<Frame, String> {
+        if self.stack.len() != other.stack.len() {
+            return Err(format!(
+                "operand stack has {} entries on one path and {} on another",
+                self.stack.len(),
+                other.stack.len()
+            ));
+        }
+        let stack = self
+            .stack
+            .iter()
+            .zip(&other.stack)
+            .map(|(&a, &b)| {
+                a.merge(b).ok_or_else(|| {
+                    format!("operand stack holds {a:?} on one path and {b:?} on another")
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let locals = self
+            .locals
+            .iter()
+            .zip(&other.locals)
+            .map(|(&a, &b)| a.merge(b).unwrap_or(Type::Top))
+            .collect();
+        Ok(Frame { locals, stack })
+    }
+
+    /// Check that this frame can flow into a frame declared by the
+    /// `StackMapTable`
+    fn check_assignable_to(&self, declared: &Frame) -> Result<(), String> {
+        if self.stack.len() != declared.stack.len() {
+            return Err(format!(
+                "operand stack has {} entries, stack map frame expects {}",
+                self.stack.len(),
+                declared.stack.len()
+            ));
+        }
+        for (index, (&found, &expected)) in self.stack.iter().zip(&declared.stack).enumerate() {
+            if !found.is_assignable_to(expected) {
+                return Err(format!(
+                    "operand stack entry {index} is {found:?}, stack map frame expects {expected:?}"
+                ));
+            }
+        }
+        for (index, (&found, &expected)) in self.locals.iter().zip(&declared.locals).enumerate() {
+            if !found.is_assignable_to(expected) {
+                return Err(format!(
+                    "local {index} is {found:?}, stack map frame expects {expected:?}"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Exception table entry with instruction indices instead of byte offsets
+struct Handler {
+    start: usize,
+    end: usize,
+    handler: usize,
+}
+
+/// A failed check: the byte offset it applies to and what went wrong
+type Failure = (usize, String);
+
+/// Verify every method of a class, failing with the first `VerifyError`
+pub fn verify_class(class_file: &ClassFile) -> Result<(), RuntimeError> {
+    let class_name = class_file.class_name().unwrap_or_default();
+    let bootstrap_method_count = class_file.bootstrap_methods()?.len();
+
+    for method in &class_file.methods {
+        let Some(code) = &method.code else {
+            continue;
+        };
+        let verifier = MethodVerifier::new(class_file, method, code, bootstrap_method_count);
+        verifier
+            .verify()
+            .map_err(|failure| method_verify_error(class_name, method, failure))?;
+    }
+    Ok(())
+}
+
+/// Compute the `max_stack` and `max_locals` a method's code needs, ignoring
+/// the limits in its `Code` attribute. The code is verified on the way, so
+/// it fails where `verify_class` would for anything but those limits.
+pub fn code_limits(
+    class_file: &ClassFile,
+    method: &MethodInfo,
+) -> Result<(u16, u16), RuntimeError> {
+    let Some(code) = &method.code else {
+        return Ok((0, 0));
+    };
+    let class_name = class_file.class_name().unwrap_or_default();
+    let bootstrap_method_count = class_file.bootstrap_methods()?.len();
+    let mut verifier = MethodVerifier::new(class_file, method, code, bootstrap_method_count);
+    verifier.max_stack = u16::MAX as usize;
+
+    let (arguments, _) = verifier
+        .signature()
+        .map_err(|message| method_verify_error(class_name, method, (0, message)))?;
+    let mut max_locals: usize = arguments.iter().map(|value| value.width()).sum();
+    for instruction in &code.bytecode {
+        let used = match local_load(instruction).or_else(|| local_store(instruction)) {
+            Some((value, index)) => index + value.width(),
+            None => match instruction {
+                JvmInstruction::Iinc(index, _) => *index as usize + 1,
+                _ => 0,
+            },
+        };
+        max_locals = max_locals.max(used);
+    }
+    verifier.max_locals = max_locals;
+
+    verifier
+        .verify()
+        .map_err(|failure| method_verify_error(class_name, method, failure))?;
+    Ok((verifier.peak_stack.get() as u16, max_locals as u16))
+}
+
+fn method_verify_error(
+    class_name: &str,
+    method: &MethodInfo,
+    (offset, message): Failure,
+) -> RuntimeError {
+    RuntimeError::VerifyError {
+        method: format!("{class_name}.{}{}", method.name, method.descriptor),
+        offset,
+        message,
+    }
+}
+
+/// Checks one method's code
+struct MethodVerifier<'a> {
+    class_file: &'a ClassFile,
+    method: &'a MethodInfo,
+    code: &'a CodeAttribute,
+    bootstrap_method_count: usize,
+    /// Byte offset of each instruction
+    offsets: Vec<usize>,
+    /// Instruction index of each byte offset that starts an instruction
+    index_of: HashMap<usize, usize>,
+    max_stack: usize,
+    max_locals: usize,
+    /// Deepest the operand stack gets, in slots
+    peak_stack: Cell<usize>,
+}
+
+impl<'a> MethodVerifier<'a> {
+    fn new(
+        class_file: &'a ClassFile,
+        method: &'a MethodInfo,
+        code: &'a CodeAttribute,
+        bootstrap_method_count: usize,
+    ) -> Self {
+        let offsets = instruction_offsets(&code.bytecode);
+        let index_of = offsets
+            .iter()
+            .enumerate()
+            .map(|(index, &offset)| (offset, index))
+            .collect();
+        Self {
+            class_file,
+            method,
+            code,
+            bootstrap_method_count,
+            offsets,
+            index_of,
+            max_stack: code.max_stack as usize,
+            max_locals: code.max_locals as usize,
+            peak_stack: Cell::new(0),
+        }
+    }
+
+    fn constant_pool(&self) -> &'a ConstantPool {
+        &self.class_file.constant_pool
+    }
+
+    /// Length of the code in bytes
+    fn code_length(&self) -> usize {
+        match (self.offsets.last(), self.code.bytecode.last()) {
+            (Some(&offset), Some(instruction)) => offset + instruction_size(instruction, offset),
+            _ => 0,
+        }
+    }
+
+    /// Check the code, returning the types before each instruction, or
+    /// `None` where no path reaches it
+    fn verify(&self) -> Result<Vec<Option<Frame>>, Failure> {
+        let bytecode = &self.code.bytecode;
+        if bytecode.is_empty() {
+            return Err((0, "method has no code".to_string()));
+        }
+        for (index, instruction) in bytecode.iter().enumerate() {
+            if instruction
+                .branch_targets()
+                .iter()
+                .any(|&target| target as usize >= bytecode.len())
+            {
+                return Err((
+                    self.offsets[index],
+                    "branch target is outside the method".to_string(),
+                ));
+            }
+        }
+
+        let handlers = self.exception_handlers()?;
+        let (initial_locals, return_type) = self.signature().map_err(|message| (0, message))?;
+        let initial = self
+            .expand(&initial_locals, Vec::new())
+            .map_err(|message| (0, message))?;
+        let stack_map = self.stack_map_frames(&initial_locals)?;
+
+        // With a StackMapTable, code after an unconditional jump is only
+        // reachable through a declared frame
+        if let Some(frames) = &stack_map {
+            for index in 1..bytecode.len() {
+                if !falls_through(&bytecode[index - 1]) && !frames.contains_key(&index) {
+                    return Err((
+                        self.offsets[index],
+                        format!(
+                            "expected a stack map frame after {}",
+                            mnemonic(&bytecode[index - 1])
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let mut states: Vec<Option<Frame>> = vec![None; bytecode.len()];
+        let mut pending = Vec::new();
+        self.flow_into(0, &initial, false, &stack_map, &mut states, &mut pending)
+            .map_err(|message| (0, message))?;
+        // Declared frames are checked even where no path reaches them
+        for (&index, frame) in stack_map.iter().flatten() {
+            if states[index].is_none() {
+                states[index] = Some(frame.clone());
+                pending.push(index);
+            }
+        }
+
+        while let Some(index) = pending.pop() {
+            let offset = self.offsets[index];
+            let Some(before) = states[index].clone() else {
+                continue;
+            };
+            let instruction = &bytecode[index];
+            let mut after = before.clone();
+            self.execute(instruction, &mut after, return_type)
+                .map_err(|message| (offset, message))?;
+
+            // A handler can be entered with the locals from before or after
+            // any instruction it covers
+            for handler in handlers
+                .iter()
+                .filter(|handler| (handler.start..handler.end).contains(&index))
+            {
+                for locals in [&before.locals, &after.locals] {
+                    let entry = Frame {
+                        locals: locals.clone(),
+                        stack: vec![Type::Reference],
+                    };
+                    if self.max_stack == 0 {
+                        return Err((offset, self.stack_overflow()));
+                    }
+                    self.peak_stack.set(self.peak_stack.get().max(1));
+                    self.flow_into(
+                        handler.handler,
+                        &entry,
+                        true,
+                        &stack_map,
+                        &mut states,
+                        &mut pending,
+                    )
+                    .map_err(|message| (offset, message))?;
+                }
+            }
+
+            for target in instruction.branch_targets() {
+                self.flow_into(
+                    target as usize,
+                    &after,
+                    true,
+                    &stack_map,
+                    &mut states,
+                    &mut pending,
+                )
+                .map_err(|message| (offset, message))?;
+            }
+            if falls_through(instruction) {
+                if index + 1 == bytecode.len() {
+                    return Err((
+                        offset,
+                        "execution falls off the end of the code".to_string(),
+                    ));
+                }
+                self.flow_into(
+                    index + 1,
+                    &after,
+                    false,
+                    &stack_map,
+                    &mut states,
+                    &mut pending,
+                )
+                .map_err(|message| (offset, message))?;
+            }
+        }
+        Ok(states)
+    }
+
+    /// Record that control can reach `target` with `incoming`, queueing the
+    /// target whenever what is known about it changes
+    fn flow_into(
+        &self,
+        target: usize,
+        incoming: &Frame,
+        is_jump: bool,
+        stack_map: &Option<HashMap<usize, Frame>>,
+        states: &mut [Option<Frame>],
+        pending: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        let target_offset = self.offsets[target];
+        if let Some(frames) = stack_map {
+            if let Some(declared) = frames.get(&target) {
+                incoming
+                    .check_assignable_to(declared)
+                    .map_err(|message| format!("{message} at offset {target_offset}"))?;
+                if states[target].is_none() {
+                    states[target] = Some(declared.clone());
+                    pending.push(target);
+                }
+                return Ok(());
+            }
+            if is_jump {
+                return Err(format!(
+                    "expected a stack map frame at offset {target_offset}"
+                ));
+            }
+        }
+
+        let merged = match &states[target] {
+            Some(existing) => existing
+                .merge(incoming)
+                .map_err(|message| format!("{message} at offset {target_offset}"))?,
+            None => incoming.clone(),
+        };
+        if states[target].as_ref() != Some(&merged) {
+            states[target] = Some(merged);
+            pending.push(target);
+        }
+        Ok(())
+    }
+
+    /// Types of the locals on entry, one entry per value, and the return
+    /// type
+    fn signature(&self) -> Result<(Vec<Type>, Option<Type>), String> {
+        let (parameters, return_type) = method_types(&self.method.descriptor)?;
+        let mut locals = Vec::new();
+        if self.method.access_flags & ACC_STATIC == 0 {
+            locals.push(Type::Reference);
+        }
+        locals.extend(parameters);
+        Ok((locals, return_type))
+    }
+
+    /// Frame from the one-entry-per-value form used by method signatures
+    /// and the `StackMapTable`
+    fn expand(&self, values: &[Type], stack: Vec<Type>) -> Result<Frame, String> {
+        let mut locals = Vec::with_capacity(self.max_locals);
+        for &value in values {
+            locals.push(value);
+            if value.width() == 2 {
+                locals.push(Type::Top);
+            }
+        }
+        if locals.len() > self.max_locals {
+            return Err(format!(
+                "{} locals are needed but max_locals is {}",
+                locals.len(),
+                self.max_locals
+            ));
+        }
+        locals.resize(self.max_locals, Type::Top);
+        if stack.iter().map(|value| value.width()).sum::<usize>() > self.max_stack {
+            return Err(self.stack_overflow());
+        }
+        Ok(Frame { locals, stack })
+    }
+
+    fn exception_handlers(&self) -> Result<Vec<Handler>, Failure> {
+        let code_length = self.code_length();
+        let mut handlers = Vec::new();
+        for entry in &self.code.exception_table {
+            let start_pc = entry.start_pc as usize;
+            let index = |pc: u16| -> Result<usize, Failure> {
+                let pc = pc as usize;
+                if pc == code_length {
+                    return Ok(self.code.bytecode.len());
+                }
+                self.index_of.get(&pc).copied().ok_or_else(|| {
+                    (
+                        start_pc,
+                        format!("exception table offset {pc} is not an instruction boundary"),
+                    )
+                })
+            };
+            let handler = Handler {
+                start: index(entry.start_pc)?,
+                end: index(entry.end_pc)?,
+                handler: index(entry.handler_pc)?,
+            };
+            if handler.start >= handler.end || handler.handler >= self.code.bytecode.len() {
+                return Err((start_pc, "invalid exception table entry".to_string()));
+            }
+            if entry.catch_type != 0 {
+                self.class_name(entry.catch_type)
+                    .map_err(|message| (start_pc, message))?;
+            }
+            handlers.push(handler);
+        }
+        Ok(handlers)
+    }
+
+    /// Frames declared by the `StackMapTable`, by instruction index, or
+    /// `None` when the method has no table
+    fn stack_map_frames(
+        &self,
+        initial_locals: &[Type],
+    ) -> Result<Option<HashMap<usize, Frame>>, Failure> {
+        let constant_pool = self.constant_pool();
+        let Some(attribute) = self.code.attributes.iter().find(|attribute| {
+            constant_pool.get_utf8(attribute.name_index) == Some("StackMapTable")
+        }) else {
+            return Ok(None);
+        };
+
+        let mut reader = Reader {
+            bytes: &attribute.info,
+            position: 0,
+        };
+        let mut frames = HashMap::new();
+        let mut locals = initial_locals.to_vec();
+        let mut previous_offset: Option<usize> = None;
+        let count = reader.u16().map_err(|message| (0, message))?;
+
+        for _ in 0..count {
+            let at = previous_offset.unwrap_or(0);
+            let (delta, stack) = self
+                .stack_map_frame(&mut reader, &mut locals)
+                .map_err(|message| (at, message))?;
+            let offset = match previous_offset {
+                Some(previous) => previous + delta as usize + 1,
+                None => delta as usize,
+            };
+            previous_offset = Some(offset);
+
+            let Some(&index) = self.index_of.get(&offset) else {
+                return Err((
+                    offset,
+                    "stack map frame is not at an instruction boundary".to_string(),
+                ));
+            };
+            let frame = self
+                .expand(&locals, stack)
+                .map_err(|message| (offset, format!("stack map frame: {message}")))?;
+            frames.insert(index, frame);
+        }
+        Ok(Some(frames))
+    }
+
+    /// Decode one `stack_map_frame`, updating `locals` and returning its
+    /// offset delta and operand stack
+    fn stack_map_frame(
+        &self,
+        reader: &mut Reader,
+        locals: &mut Vec<Type>,
+    ) -> Result<(u16, Vec<Type>), String> {
+        let frame_type = reader.u8()?;
+        Ok(match frame_type {
+            // same_frame
+            0..=63 => (frame_type as u16, Vec::new()),
+            // same_locals_1_stack_item_frame
+            64..=127 => (
+                frame_type as u16 - 64,
+                vec![self.verification_type(reader)?],
+            ),
+            // same_locals_1_stack_item_frame_extended
+            247 => {
+                let delta = reader.u16()?;
+                (delta, vec![self.verification_type(reader)?])
+            }
+            // chop_frame
+            248..=250 => {
+                let delta = reader.u16()?;
+                let chopped = (251 - frame_type) as usize;
+                if chopped > locals.len() {
+                    return Err(format!(
+                        "stack map frame removes {chopped} of {} locals",
+                        locals.len()
+                    ));
+                }
+                locals.truncate(locals.len() - chopped);
+                (delta, Vec::new())
+            }
+            // same_frame_extended
+            251 => (reader.u16()?, Vec::new()),
+            // append_frame
+            252..=254 => {
+                let delta = reader.u16()?;
+                for _ in 0..frame_type - 251 {
+                    locals.push(self.verification_type(reader)?);
+                }
+                (delta, Vec::new())
+            }
+            // full_frame
+            255 => {
+                let delta = reader.u16()?;
+                let local_count = reader.u16()?;
+                *locals = (0..local_count)
+                    .map(|_| self.verification_type(reader))
+                    .collect::<Result<_, _>>()?;
+                let stack_count = reader.u16()?;
+                let stack = (0..stack_count)
+                    .map(|_| self.verification_type(reader))
+                    .collect::<Result<_, _>>()?;
+                (delta, stack)
+            }
+            _ => return Err(format!("reserved stack map frame type {frame_type}")),
+        })
+    }
+
+    /// Decode a `verification_type_info`. Uninitialized objects are checked
+    /// as references.
+    fn verification_type(&self, reader: &mut Reader) -> Result<Type, String> {
+        Ok(match reader.u8()? {
+            0 => Type::Top,
+            1 => Type::Integer,
+            2 => Type::Float,
+            3 => Type::Double,
+            4 => Type::Long,
+            5 => Type::Null,
+            6 => Type::Reference,
+            7 => {
+                self.class_name(reader.u16()?)?;
+                Type::Reference
+            }
+            8 => {
+                let offset = reader.u16()? as usize;
+                let creates_object = self.index_of.get(&offset).is_some_and(|&index| {
+                    matches!(self.code.bytecode[index], JvmInstruction::New(_))
+                });
+                if !creates_object {
+                    return Err(format!(
+                        "uninitialized type refers to offset {offset}, which is not a new instruction"
+                    ));
+                }
+                Type::Reference
+            }
+            tag => return Err(format!("unknown verification type {tag}")),
+        })
+    }
+
+    /// Apply an instruction's effect on the types in `frame`
+    fn execute(
+        &self,
+        instruction: &JvmInstruction,
+        frame: &mut Frame,
+        return_type: Option<Type>,
+    ) -> Result<(), String> {
+        if let Some((pops, pushes)) = stack_effect(instruction) {
+            for &expected in pops.iter().rev() {
+                self.pop(frame, expected)?;
+            }
+            for &value in pushes {
+                self.push(frame, value)?;
+            }
+            if let JvmInstruction::Newarray(atype) = instruction
+                && !(4..=11).contains(atype)
+            {
+                return Err(format!("invalid newarray type {atype}"));
+            }
+            return Ok(());
+        }
+        if let Some((expected, index)) = local_load(instruction) {
+            self.check_local(index, expected)?;
+            let found = frame.locals[index];
+            if !found.is_assignable_to(expected) {
+                return Err(format!(
+                    "local {index} holds {found:?}, expected {expected:?}"
+                ));
+            }
+            return self.push(frame, found);
+        }
+        if let Some((expected, index)) = local_store(instruction) {
+            let value = self.pop(frame, expected)?;
+            self.check_local(index, expected)?;
+            if index > 0 && frame.locals[index - 1].width() == 2 {
+                frame.locals[index - 1] = Type::Top;
+            }
+            frame.locals[index] = value;
+            if value.width() == 2 {
+                frame.locals[index + 1] = Type::Top;
+            }
+            return Ok(());
+        }
+
+        match instruction {
+            JvmInstruction::Ldc(index) | JvmInstruction::LdcW(index) => {
+                let value = self.constant_type(*index, false)?;
+                self.push(frame, value)
+            }
+            JvmInstruction::Ldc2W(index) => {
+                let value = self.constant_type(*index, true)?;
+                self.push(frame, value)
+            }
+            JvmInstruction::Iinc(index, _) => {
+                let index = *index as usize;
+                self.check_local(index, Type::Integer)?;
+                match frame.locals[index] {
+                    Type::Integer => Ok(()),
+                    found => Err(format!("local {index} holds {found:?}, expected Integer")),
+                }
+            }
+            JvmInstruction::Pop => self.pop_single(frame).map(drop),
+            JvmInstruction::Pop2 => self.pop_words(frame, 2).map(drop),
+            JvmInstruction::Dup => {
+                let value = self.pop_single(frame)?;
+                self.push(frame, value)?;
+                self.push(frame, value)
+            }
+            JvmInstruction::Dup2 => self.dup_words(frame, 2, 0),
+            JvmInstruction::DupX1 => self.dup_words(frame, 1, 1),
+            JvmInstruction::DupX2 => self.dup_words(frame, 1, 2),
+            JvmInstruction::Dup2X1 => self.dup_words(frame, 2, 1),
+            JvmInstruction::Dup2X2 => self.dup_words(frame, 2, 2),
+            JvmInstruction::Swap => {
+                let top = self.pop_single(frame)?;
+                let below = self.pop_single(frame)?;
+                self.push(frame, top)?;
+                self.push(frame, below)
+            }
+
+            JvmInstruction::Return => match return_type {
+                None => Ok(()),
+                Some(expected) => Err(format!("return in a method returning {expected:?}")),
+            },
+            JvmInstruction::Ireturn
+            | JvmInstruction::Lreturn
+            | JvmInstruction::Freturn
+            | JvmInstruction::Dreturn
+            | JvmInstruction::Areturn => {
+                let returned = match instruction {
+                    JvmInstruction::Ireturn => Type::Integer,
+                    JvmInstruction::Lreturn => Type::Long,
+                    JvmInstruction::Freturn => Type::Float,
+                    JvmInstruction::Dreturn => Type::Double,
+                    _ => Type::Reference,
+                };
+                if return_type != Some(returned) {
+                    return Err(format!(
+                        "{} in a method returning {}",
+                        mnemonic(instruction),
+                        return_type.map_or("void".to_string(), |value| format!("{value:?}"))
+                    ));
+                }
+                self.pop(frame, returned).map(drop)
+            }
+
+            JvmInstruction::Getstatic(index) => {
+                let value = self.field_type(*index)?;
+                self.push(frame, value)
+            }
+            JvmInstruction::Putstatic(index) => {
+                let value = self.field_type(*index)?;
+                self.pop(frame, value).map(drop)
+            }
+            JvmInstruction::Getfield(index) => {
+                let value = self.field_type(*index)?;
+                self.pop(frame, Type::Reference)?;
+                self.push(frame, value)
+            }
+            JvmInstruction::Putfield(index) => {
+                let value = self.field_type(*index)?;
+                self.pop(frame, value)?;
+                self.pop(frame, Type::Reference).map(drop)
+            }
+
+            JvmInstruction::Invokevirtual(_)
+            | JvmInstruction::Invokespecial(_)
+            | JvmInstruction::Invokestatic(_)
+            | JvmInstruction::Invokeinterface(_, _)
+            | JvmInstruction::Invokedynamic(_) => self.invoke(instruction, frame),
+
+            JvmInstruction::New(index) => {
+                if self.class_name(*index)?.starts_with('[') {
+                    return Err("new cannot create an array".to_string());
+                }
+                self.push(frame, Type::Reference)
+            }
+            JvmInstruction::Checkcast(index) => {
+                self.class_name(*index)?;
+                self.pop(frame, Type::Reference)?;
+                self.push(frame, Type::Reference)
+            }
+            JvmInstruction::Instanceof(index) => {
+                self.class_name(*index)?;
+                self.pop(frame, Type::Reference)?;
+                self.push(frame, Type::Integer)
+            }
+            JvmInstruction::Anewarray(index) => {
+                self.class_name(*index)?;
+                self.pop(frame, Type::Integer)?;
+                self.push(frame, Type::Reference)
+            }
+            JvmInstruction::Multianewarray(index, dimensions) => {
+                let class_name = self.class_name(*index)?;
+                let depth = class_name.bytes().take_while(|&byte| byte == b'[').count();
+                if *dimensions == 0 || depth < *dimensions as usize {
+                    return Err(format!(
+                        "multianewarray of {dimensions} dimensions for {class_name}"
+                    ));
+                }
+                for _ in 0..*dimensions {
+                    self.pop(frame, Type::Integer)?;
+                }
+                self.push(frame, Type::Reference)
+            }
+
+            _ => Err(format!("{} is not supported", mnemonic(instruction))),
+        }
+    }
+
+    /// Pop a method call's arguments and receiver and push its result
+    fn invoke(&self, instruction: &JvmInstruction, frame: &mut Frame) -> Result<(), String> {
+        let interface_calls = self.class_file.major_version >= INTERFACE_STATIC_CALLS_VERSION;
+        let (name, descriptor, has_receiver) = match instruction {
+            JvmInstruction::Invokevirtual(index) => {
+                let (name, descriptor) = self.member(*index, &["Methodref"])?;
+                (name, descriptor, true)
+            }
+            JvmInstruction::Invokespecial(index) | JvmInstruction::Invokestatic(index) => {
+                let kinds: &[&str] = if interface_calls {
+                    &["Methodref", "InterfaceMethodref"]
+                } else {
+                    &["Methodref"]
+                };
+                let (name, descriptor) = self.member(*index, kinds)?;
+                let has_receiver = matches!(instruction, JvmInstruction::Invokespecial(_));
+                (name, descriptor, has_receiver)
+            }
+            JvmInstruction::Invokeinterface(index, count) => {
+                let (name, descriptor) = self.member(*index, &["InterfaceMethodref"])?;
+                let (parameters, _) = method_types(descriptor)?;
+                let slots = 1 + parameters.iter().map(|value| value.width()).sum::<usize>();
+                if *count as usize != slots {
+                    return Err(format!(
+                        "invokeinterface count {count} does not match {descriptor}"
+                    ));
+                }
+                (name, descriptor, true)
+            }
+            JvmInstruction::Invokedynamic(index) => {
+                let entry = self.entry(*index)?;
+                let ConstantPoolEntry::InvokeDynamic(bootstrap_index, name_and_type) = entry else {
+                    return Err(self.wrong_kind(*index, entry, "an InvokeDynamic"));
+                };
+                if *bootstrap_index as usize >= self.bootstrap_method_count {
+                    return Err(format!("bootstrap method {bootstrap_index} does not exist"));
+                }
+                let (name, descriptor) = self.name_and_type(*name_and_type)?;
+                (name, descriptor, false)
+            }
+            _ => unreachable!("not an invoke instruction"),
+        };
+
+        let is_constructor =
+            name == "<init>" && matches!(instruction, JvmInstruction::Invokespecial(_));
+        if name.starts_with('<') && !is_constructor {
+            return Err(format!("{} cannot call {name}", mnemonic(instruction)));
+        }
+        let (parameters, return_type) = method_types(descriptor)?;
+        if is_constructor && return_type.is_some() {
+            return Err(format!("constructor {descriptor} must return void"));
+        }
+        for &parameter in parameters.iter().rev() {
+            self.pop(frame, parameter)?;
+        }
+        if has_receiver {
+            self.pop(frame, Type::Reference)?;
+        }
+        match return_type {
+            Some(value) => self.push(frame, value),
+            None => Ok(()),
+        }
+    }
+
+    fn pop(&self, frame: &mut Frame, expected: Type) -> Result<Type, String> {
+        let found = frame
+            .stack
+            .pop()
+            .ok_or_else(|| "operand stack underflow".to_string())?;
+        if !found.is_assignable_to(expected) {
+            return Err(format!(
+                "expected {expected:?} on the operand stack, found {found:?}"
+            ));
+        }
+        Ok(found)
+    }
+
+    /// Pop a value of any type that takes one slot
+    fn pop_single(&self, frame: &mut Frame) -> Result<Type, String> {
+        let found = self.pop(frame, Type::Top)?;
+        if found.width() == 2 {
+            return Err(format!(
+                "expected a one-slot value on the operand stack, found {found:?}"
+            ));
+        }
+        Ok(found)
+    }
+
+    /// Pop the types making up the top `words` stack words, bottom first,
+    /// failing if that would split a long or double
+    fn pop_words(&self, frame: &mut Frame, words: usize) -> Result<Vec<Type>, String> {
+        let mut values = Vec::new();
+        let mut taken = 0;
+        while taken < words {
+            let value = self.pop(frame, Type::Top)?;
+            taken += value.width();
+            values.push(value);
+        }
+        if taken != words {
+            return Err(format!(
+                "{words}-word stack operation would split a {:?}",
+                values.last().copied().unwrap_or(Type::Top)
+            ));
+        }
+        values.reverse();
+        Ok(values)
+    }
+
+    /// Copy the top `copied` words below the `skipped` words under them
+    fn dup_words(&self, frame: &mut Frame, copied: usize, skipped: usize) -> Result<(), String> {
+        let top = self.pop_words(frame, copied)?;
+        let below = self.pop_words(frame, skipped)?;
+        for value in top.iter().chain(&below).chain(&top) {
+            self.push(frame, *value)?;
+        }
+        Ok(())
+    }
+
+    fn push(&self, frame: &mut Frame, value: Type) -> Result<(), String> {
+        frame.stack.push(value);
+        let depth: usize = frame.stack.iter().map(|value| value.width()).sum();
+        self.peak_stack.set(self.peak_stack.get().max(depth));
+        if depth > self.max_stack {
+            return Err(self.stack_overflow());
+        }
+        Ok(())
+    }
+
+    fn stack_overflow(&self) -> String {
+        format!("operand stack exceeds max_stack {}", self.max_stack)
+    }
+
+    fn check_local(&self, index: usize, value: Type) -> Result<(), String> {
+        if index + value.width() > self.max_locals {
+            return Err(format!(
+                "local {index} is outside max_locals {}",
+                self.max_locals
+            ));
+        }
+        Ok(())
+    }
+
+    fn entry(&self, index: u16) -> Result<&'a ConstantPoolEntry, String> {
+        match self.constant_pool().get(index) {
+            Some(ConstantPoolEntry::Placeholder) | None => {
+                Err(format!("constant pool index {index} is not a valid entry"))
+            }
+            Some(entry) => Ok(entry),
+        }
+    }
+
+    fn wrong_kind(&self, index: u16, entry: &ConstantPoolEntry, expected: &str) -> String {
+        format!(
+            "constant pool index {index} is {}, expected {expected}",
+            entry_kind(entry)
+        )
+    }
+
+    fn utf8(&self, index: u16) -> Result<&'a str, String> {
+        self.constant_pool()
+            .get_utf8(index)
+            .ok_or_else(|| format!("constant pool index {index} is not a Utf8"))
+    }
+
+    /// Name of the class named by a `CONSTANT_Class` entry
+    fn class_name(&self, index: u16) -> Result<&'a str, String> {
+        match self.entry(index)? {
+            ConstantPoolEntry::Class(name_index) => self.utf8(*name_index),
+            entry => Err(self.wrong_kind(index, entry, "a Class")),
+        }
+    }
+
+    fn name_and_type(&self, index: u16) -> Result<(&'a str, &'a str), String> {
+        match self.entry(index)? {
+            ConstantPoolEntry::NameAndType(name_index, descriptor_index) => {
+                Ok((self.utf8(*name_index)?, self.utf8(*descriptor_index)?))
+            }
+            entry => Err(self.wrong_kind(index, entry, "a NameAndType")),
+        }
+    }
+
+    /// Name and descriptor of a field or method reference of one of the
+    /// given kinds
+    fn member(&self, index: u16, kinds: &[&str]) -> Result<(&'a str, &'a str), String> {
+        let entry = self.entry(index)?;
+        let (ConstantPoolEntry::Fieldref(class_index, name_and_type)
+        | ConstantPoolEntry::Methodref(class_index, name_and_type)
+        | ConstantPoolEntry::InterfaceMethodref(class_index, name_and_type)) = entry
+        else {
+            return Err(self.wrong_kind(index, entry, &format!("a {}", kinds.join(" or "))));
+        };
+        if !kinds.contains(&entry_kind(entry)) {
+            return Err(self.wrong_kind(index, entry, &format!("a {}", kinds.join(" or "))));
+        }
+        self.class_name(*class_index)?;
+        self.name_and_type(*name_and_type)
+    }
+
+    fn field_type(&self, index: u16) -> Result<Type, String> {
+        let (_, descriptor) = self.member(index, &["Fieldref"])?;
+        descriptor_type(descriptor)?
+            .ok_or_else(|| format!("malformed field descriptor {descriptor:?}"))
+    }
+
+    /// Type pushed by `ldc`, `ldc_w` or, when `wide`, `ldc2_w`
+    fn constant_type(&self, index: u16, wide: bool) -> Result<Type, String> {
+        let entry = self.entry(index)?;
+        let value = match entry {
+            ConstantPoolEntry::Integer(_) => Type::Integer,
+            ConstantPoolEntry::Float(_) => Type::Float,
+            ConstantPoolEntry::Long(_) => Type::Long,
+            ConstantPoolEntry::Double(_) => Type::Double,
+            ConstantPoolEntry::String(_)
+            | ConstantPoolEntry::Class(_)
+            | ConstantPoolEntry::MethodType(_)
+            | ConstantPoolEntry::MethodHandle(_, _) => Type::Reference,
+            ConstantPoolEntry::Dynamic(_, name_and_type) => {
+                let (_, descriptor) = self.name_and_type(*name_and_type)?;
+                descriptor_type(descriptor)?
+                    .ok_or_else(|| format!("malformed field descriptor {descriptor:?}"))?
+            }
+            _ => return Err(self.wrong_kind(index, entry, "a loadable constant")),
+        };
+        if (value.width() == 2) != wide {
+            let instruction = if wide { "ldc2_w" } else { "ldc" };
+            return Err(format!(
+                "{instruction} cannot load constant pool index {index} ({})",
+                entry_kind(entry)
+            ));
+        }
+        Ok(value)
+    }
+}
+
+/// Name of a constant pool entry's kind, as in the JVM specification
+fn entry_kind(entry: &ConstantPoolEntry) -> &'static str {
+    match entry {
+        ConstantPoolEntry::Utf8(_) => "Utf8",
+        ConstantPoolEntry::Class(_) => "Class",
+        ConstantPoolEntry::String(_) => "String",
+        ConstantPoolEntry::Fieldref(_, _) => "Fieldref",
+        ConstantPoolEntry::Methodref(_, _) => "Methodref",
+        ConstantPoolEntry::InterfaceMethodref(_, _) => "InterfaceMethodref",
+        ConstantPoolEntry::NameAndType(_, _) => "NameAndType",
+        ConstantPoolEntry::Integer(_) => "Integer",
+        ConstantPoolEntry::Float(_) => "Float",
+        ConstantPoolEntry::Long(_) => "Long",
+        ConstantPoolEntry::Double(_) => "Double",
+        ConstantPoolEntry::MethodHandle(_, _) => "MethodHandle",
+        ConstantPoolEntry::MethodType(_) => "MethodType",
+        ConstantPoolEntry::Dynamic(_, _) => "Dynamic",
+        ConstantPoolEntry::InvokeDynamic(_, _) => "InvokeDynamic",
+        ConstantPoolEntry::Module(_) => "Module",
+        ConstantPoolEntry::Package(_) => "Package",
+        ConstantPoolEntry::Placeholder => "Placeholder",
+    }
+}
+
+/// Instruction name for messages, e.g. `ireturn`
+fn mnemonic(instruction: &JvmInstruction) -> String {
+    let name = format!("{instruction:?}");
+    name.split(['(', ' '])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Whether execution can continue with the next instruction
+fn falls_through(instruction: &JvmInstruction) -> bool {
+    !matches!(
+        instruction,
+        JvmInstruction::Goto(_)
+            | JvmInstruction::Tableswitch { .. }
+            | JvmInstruction::Lookupswitch { .. }
+            | JvmInstruction::Athrow
+            | JvmInstruction::Return
+            | JvmInstruction::Ireturn
+            | JvmInstruction::Lreturn
+            | JvmInstruction::Freturn
+            | JvmInstruction::Dreturn
+            | JvmInstruction::Areturn
+    )
+}
+
+/// Operand stack types popped, bottom first, and pushed by instructions
+/// that need no constant pool or local variable
+fn stack_effect(instruction: &JvmInstruction) -> Option<(&'static [Type], &'static [Type])> {
+    use JvmInstruction::*;
+    use Type::{Double as D, Float as F, Integer as I, Long as J, Null, Reference as R};
+
+    Some(match instruction {
+        Nop | Goto(_) => (&[], &[]),
+        AconstNull => (&[], &[Null]),
+        IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 | Bipush(_)
+        | Sipush(_) => (&[], &[I]),
+        Lconst0 | Lconst1 => (&[], &[J]),
+        Fconst0 | Fconst1 | Fconst2 => (&[], &[F]),
+        Dconst0 | Dconst1 => (&[], &[D]),
+
+        Iadd | Isub | Imul | Idiv | Irem | Ishl | Ishr | Iushr | Iand | Ior | Ixor => {
+            (&[I, I], &[I])
+        }
+        Ladd | Lsub | Lmul | Ldiv | Lrem | Land | Lor | Lxor => (&[J, J], &[J]),
+        Lshl | Lshr | Lushr => (&[J, I], &[J]),
+        Fadd | Fsub | Fmul | Fdiv | Frem => (&[F, F], &[F]),
+        Dadd | Dsub | Dmul | Ddiv | Drem => (&[D, D], &[D]),
+        Ineg | I2b | I2c | I2s => (&[I], &[I]),
+        Lneg => (&[J], &[J]),
+        Fneg => (&[F], &[F]),
+        Dneg => (&[D], &[D]),
+
+        I2l => (&[I], &[J]),
+        I2f => (&[I], &[F]),
+        I2d => (&[I], &[D]),
+        L2i => (&[J], &[I]),
+        L2f => (&[J], &[F]),
+        L2d => (&[J], &[D]),
+        F2i => (&[F], &[I]),
+        F2l => (&[F], &[J]),
+        F2d => (&[F], &[D]),
+        D2i => (&[D], &[I]),
+        D2l => (&[D], &[J]),
+        D2f => (&[D], &[F]),
+
+        Lcmp => (&[J, J], &[I]),
+        Fcmpl | Fcmpg => (&[F, F], &[I]),
+        Dcmpl | Dcmpg => (&[D, D], &[I]),
+        Ifeq(_) | Ifne(_) | Iflt(_) | Ifge(_) | Ifgt(_) | Ifle(_) => (&[I], &[]),
+        IfIcmpeq(_) | IfIcmpne(_) | IfIcmplt(_) | IfIcmpge(_) | IfIcmpgt(_) | IfIcmple(_) => {
+            (&[I, I], &[])
+        }
+        IfAcmpeq(_) | IfAcmpne(_) => (&[R, R], &[]),
+        Ifnull(_) | Ifnonnull(_) | Athrow => (&[R], &[]),
+        Tableswitch { .. } | Lookupswitch { .. } => (&[I], &[]),
+
+        Newarray(_) => (&[I], &[R]),
+        Arraylength => (&[R], &[I]),
+        Iaload | Baload | Caload | Saload => (&[R, I], &[I]),
+        Laload => (&[R, I], &[J]),
+        Faload => (&[R, I], &[F]),
+        Daload => (&[R, I], &[D]),
+        Aaload => (&[R, I], &[R]),
+        Iastore | Bastore | Castore | Sastore => (&[R, I, I], &[]),
+        Lastore => (&[R, I, J], &[]),
+        Fastore => (&[R, I, F], &[]),
+        Dastore => (&[R, I, D], &[]),
+        Aastore => (&[R, I, R], &[]),
+        _ => return None,
+    })
+}
+
+/// Type and slot read by a local variable load
+fn local_load(instruction: &JvmInstruction) -> Option<(Type, usize)> {
+    use JvmInstruction::*;
+
+    Some(match *instruction {
+        Iload(index) => (Type::Integer, index as usize),
+        Iload0 => (Type::Integer, 0),
+        Iload1 => (Type::Integer, 1),
+        Iload2 => (Type::Integer, 2),
+        Iload3 => (Type::Integer, 3),
+        Lload(index) => (Type::Long, index as usize),
+        Lload0 => (Type::Long, 0),
+        Lload1 => (Type::Long, 1),
+        Lload2 => (Type::Long, 2),
+        Lload3 => (Type::Long, 3),
+        Fload(index) => (Type::Float, index as usize),
+        Fload0 => (Type::Float, 0),
+        Fload1 => (Type::Float, 1),
+        Fload2 => (Type::Float, 2),
+        Fload3 => (Type::Float, 3),
+        Dload(index) => (Type::Double, index as usize),
+        Dload0 => (Type::Double, 0),
+        Dload1 => (Type::Double, 1),
+        Dload2 => (Type::Double, 2),
+        Dload3 => (Type::Double, 3),
+        Aload(index) => (Type::Reference, index as usize),
+        Aload0 => (Type::Reference, 0),
+        Aload1 => (Type::Reference, 1),
+        Aload2 => (Type::Reference, 2),
+        Aload3 => (Type::Reference, 3),
+        _ => return None,
+    })
+}
+
+/// Type and slot written by a local variable store
+fn local_store(instruction: &JvmInstruction) -> Option<(Type, usize)> {
+    use JvmInstruction::*;
+
+    Some(match *instruction {
+        Istore(index) => (Type::Integer, index as usize),
+        Istore0 => (Type::Integer, 0),
+        Istore1 => (Type::Integer, 1),
+        Istore2 => (Type::Integer, 2),
+        Istore3 => (Type::Integer, 3),
+        Lstore(index) => (Type::Long, index as usize),
+        Lstore0 => (Type::Long, 0),
+        Lstore1 => (Type::Long, 1),
+        Lstore2 => (Type::Long, 2),
+        Lstore3 => (Type::Long, 3),
+        Fstore(index) => (Type::Float, index as usize),
+        Fstore0 => (Type::Float, 0),
+        Fstore1 => (Type::Float, 1),
+        Fstore2 => (Type::Float, 2),
+        Fstore3 => (Type::Float, 3),
+        Dstore(index) => (Type::Double, index as usize),
+        Dstore0 => (Type::Double, 0),
+        Dstore1 => (Type::Double, 1),
+        Dstore2 => (Type::Double, 2),
+        Dstore3 => (Type::Double, 3),
+        Astore(index) => (Type::Reference, index as usize),
+        Astore0 => (Type::Reference, 0),
+        Astore1 => (Type::Reference, 1),
+        Astore2 => (Type::Reference, 2),
+        Astore3 => (Type::Reference, 3),
+        _ => return None,
+    })
+}
+
+/// Big-endian reader over an attribute's contents
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.position)
+            .ok_or_else(|| "truncated StackMapTable".to_string())?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvm::class_file_parser::{AttributeInfo, ClassFileParser, parse_bytecode};
+    use crate::jvm::class_file_writer::ClassFileWriter;
+    use crate::jvm::class_loader::LoadedClass;
+    use crate::jvm::java_class_generator::{JavaClassGenerator, JavaVersion};
+    use crate::jvm::jvm_types::JvmInstruction::*;
+
+    /// Class `Check` with one static method `run`. Constant 5 is a Utf8 and
+    /// constant 9 an Integer.
+    fn class_with_method(
+        descriptor: &str,
+        max_stack: u16,
+        max_locals: u16,
+        bytecode: Vec<JvmInstruction>,
+        stack_map: Option<Vec<u8>>,
+    ) -> ClassFile {
+        let mut constant_pool = ConstantPool::new();
+        constant_pool.add_utf8("Check".to_string()).unwrap();
+        constant_pool.add_class(1).unwrap();
+        constant_pool
+            .add_utf8("java/lang/Object".to_string())
+            .unwrap();
+        constant_pool.add_class(3).unwrap();
+        constant_pool.add_utf8("run".to_string()).unwrap();
+        constant_pool.add_utf8(descriptor.to_string()).unwrap();
+        constant_pool.add_utf8("Code".to_string()).unwrap();
+        constant_pool.add_utf8("StackMapTable".to_string()).unwrap();
+        constant_pool.add_integer(6).unwrap();
+
+        let attributes = stack_map
+            .map(|info| AttributeInfo {
+                name_index: 8,
+                info,
+            })
+            .into_iter()
+            .collect();
+        ClassFile {
+            minor_version: 0,
+            major_version: 52,
+            constant_pool,
+            access_flags: 0x0021,
+            this_class: 2,
+            super_class: 4,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: vec![MethodInfo {
+                access_flags: 0x0009,
+                name_index: 5,
+                descriptor_index: 6,
+                name: "run".to_string(),
+                descriptor: descriptor.to_string(),
+                code: Some(CodeAttribute {
+                    name_index: 7,
+                    max_stack,
+                    max_locals,
+                    bytecode,
+                    exception_table: Vec::new(),
+                    attributes,
+                }),
+                attributes: Vec::new(),
+            }],
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Offset and message of the `VerifyError` for a class
+    fn verify_error(class_file: &ClassFile) -> (usize, String) {
+        match verify_class(class_file) {
+            Err(RuntimeError::VerifyError {
+                method,
+                offset,
+                message,
+            }) => {
+                assert!(method.starts_with("Check.run("), "{method}");
+                (offset, message)
+            }
+            other => panic!("expected a VerifyError, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_fixtures_and_generated_classes_verify() {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        for entry in std::fs::read_dir(fixtures).unwrap() {
+            let path = entry.unwrap().path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "class")
+            {
+                let class_file = ClassFileParser::parse(&std::fs::read(&path).unwrap()).unwrap();
+                if let Err(error) = verify_class(&class_file) {
+                    panic!("{}: {error}", path.display());
+                }
+            }
+        }
+
+        let class_data = JavaClassGenerator::new("DiceRoll".to_string())
+            .generate_dice_class("3d6")
+            .unwrap();
+        verify_class(&ClassFileParser::parse(&class_data).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_stack_and_local_limits() {
+        let class_file =
+            class_with_method("()V", 1, 0, vec![Iconst1, Iconst2, Iadd, Pop, Return], None);
+        assert_eq!(
+            verify_error(&class_file),
+            (1, "operand stack exceeds max_stack 1".to_string())
+        );
+        assert!(matches!(
+            LoadedClass::new(class_file),
+            Err(RuntimeError::VerifyError { offset: 1, .. })
+        ));
+
+        let class_file = class_with_method("()V", 1, 2, vec![Iload(3), Pop, Return], None);
+        assert_eq!(
+            verify_error(&class_file),
+            (0, "local 3 is outside max_locals 2".to_string())
+        );
+
+        // A long argument takes two slots
+        let class_file = class_with_method("(JI)V", 0, 2, vec![Return], None);
+        assert_eq!(
+            verify_error(&class_file),
+            (0, "3 locals are needed but max_locals is 2".to_string())
+        );
+        let class_file = class_with_method("(J)V", 2, 2, vec![Lload1, Pop, Return], None);
+        assert_eq!(
+            verify_error(&class_file),
+            (0, "local 1 is outside max_locals 2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_stack_words_keep_two_word_values_whole() {
+        // long, int -> long, int, long
+        let code = vec![Iconst1, Lload0, Dup2X1, Lstore0, Pop, Lstore0, Return];
+        verify_class(&class_with_method("(J)V", 5, 2, code, None)).unwrap();
+
+        let class_file =
+            class_with_method("(J)V", 5, 2, vec![Iconst1, Lload0, DupX1, Return], None);
+        assert_eq!(
+            verify_error(&class_file),
+            (2, "1-word stack operation would split a Long".to_string())
+        );
+
+        // Two ints pop together as two words, but an int and half a long do not
+        let code = vec![Iconst1, Iconst2, Dup2, Pop2, Pop2, Lload0, Pop2, Return];
+        verify_class(&class_with_method("(J)V", 4, 2, code, None)).unwrap();
+        let class_file = class_with_method("(J)V", 3, 2, vec![Lload0, Iconst1, Pop2, Return], None);
+        assert_eq!(
+            verify_error(&class_file),
+            (2, "2-word stack operation would split a Long".to_string())
+        );
+    }
+
+    #[test]
+    fn test_code_limits_are_computed_from_code() {
+        let class_file = class_with_method(
+            "(JI)V",
+            0,
+            0,
+            vec![Lload0, Lconst1, Ladd, Lstore(4), Iinc(2, 1), Return],
+            None,
+        );
+        let method = &class_file.methods[0];
+        assert_eq!(code_limits(&class_file, method).unwrap(), (4, 6));
+
+        // Generated dice loops need the same frame however many dice roll
+        for expression in ["3d6", "60000d6"] {
+            let class_data = JavaClassGenerator::new("DiceRoll".to_string())
+                .generate_dice_class(expression)
+                .unwrap();
+            let class_file = ClassFileParser::parse(&class_data).unwrap();
+            let code = class_file.main_method().unwrap().code.as_ref().unwrap();
+            assert_eq!((code.max_stack, code.max_locals), (4, 3));
+            assert!(code.bytecode.len() < 40);
+        }
+    }
+
+    #[test]
+    fn test_types_and_constant_kinds() {
+        let cases = [
+            (
+                vec![Fconst0, Istore0, Return],
+                1,
+                "expected Integer on the operand stack, found Float",
+            ),
+            (
+                vec![Lconst0, Pop, Return],
+                1,
+                "expected a one-slot value on the operand stack, found Long",
+            ),
+            (
+                vec![Iconst0, Ireturn],
+                1,
+                "ireturn in a method returning void",
+            ),
+            (
+                vec![Ldc(5), Pop, Return],
+                0,
+                "constant pool index 5 is Utf8, expected a loadable constant",
+            ),
+            (
+                vec![Ldc2W(9), Pop, Return],
+                0,
+                "ldc2_w cannot load constant pool index 9 (Integer)",
+            ),
+            (
+                vec![Getstatic(2), Pop, Return],
+                0,
+                "constant pool index 2 is Class, expected a Fieldref",
+            ),
+            (
+                vec![New(40), Pop, Return],
+                0,
+                "constant pool index 40 is not a valid entry",
+            ),
+            (vec![Iconst1], 0, "execution falls off the end of the code"),
+        ];
+        for (bytecode, offset, message) in cases {
+            let class_file = class_with_method("()V", 2, 1, bytecode, None);
+            assert_eq!(verify_error(&class_file), (offset, message.to_string()));
+        }
+    }
+
+    // Offsets: iload_0 0, ifeq 1, iconst_1 4, goto 5, <value> 8, pop 9,
+    // return 10
+    fn branching_method(value: JvmInstruction) -> Vec<JvmInstruction> {
+        vec![Iload0, Ifeq(4), Iconst1, Goto(5), value, Pop, Return]
+    }
+
+    #[test]
+    fn test_merge_points_without_stack_map() {
+        let class_file = class_with_method("(I)V", 1, 1, branching_method(Iconst0), None);
+        verify_class(&class_file).unwrap();
+
+        let class_file = class_with_method("(I)V", 1, 1, branching_method(Fconst0), None);
+        let (_, message) = verify_error(&class_file);
+        assert!(message.starts_with("operand stack holds"), "{message}");
+        assert!(message.ends_with("at offset 9"), "{message}");
+
+        // Local 1 is only set on one path, so it is unusable after the merge
+        let class_file = class_with_method(
+            "(I)V",
+            1,
+            2,
+            vec![Iload0, Ifeq(4), Iconst1, Istore1, Iload1, Pop, Return],
+            None,
+        );
+        assert_eq!(
+            verify_error(&class_file),
+            (6, "local 1 holds Top, expected Integer".to_string())
+        );
+    }
+
+    #[test]
+    fn test_merge_points_with_stack_map() {
+        // same_frame at 8, then same_locals_1_stack_item_frame at 9 with an
+        // int on the stack
+        let frames = vec![0, 2, 8, 64, 1];
+        let class_file = class_with_method(
+            "(I)V",
+            1,
+            1,
+            branching_method(Iconst0),
+            Some(frames.clone()),
+        );
+        verify_class(&class_file).unwrap();
+
+        let class_file = class_with_method("(I)V", 1, 1, branching_method(Fconst0), Some(frames));
+        assert_eq!(
+            verify_error(&class_file),
+            (
+                8,
+                "operand stack entry 0 is Float, stack map frame expects Integer at offset 9"
+                    .to_string()
+            )
+        );
+
+        // Without a frame at 8, the code after the goto is unreachable
+        let class_file = class_with_method(
+            "(I)V",
+            1,
+            1,
+            branching_method(Iconst0),
+            Some(vec![0, 1, 73, 1]),
+        );
+        assert_eq!(
+            verify_error(&class_file),
+            (8, "expected a stack map frame after goto".to_string())
+        );
+
+        // A frame in the middle of the ifeq
+        let class_file =
+            class_with_method("(I)V", 1, 1, branching_method(Iconst0), Some(vec![0, 1, 2]));
+        assert_eq!(
+            verify_error(&class_file),
+            (
+                2,
+                "stack map frame is not at an instruction boundary".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        let class_file = class_with_method("()V", 0, 0, vec![Goto(7), Return], None);
+        assert_eq!(
+            verify_error(&class_file),
+            (0, "branch target is outside the method".to_string())
+        );
+
+        assert!(matches!(
+            parse_bytecode(&[0xA7, 0x00, 0x02, 0xB1]),
+            Err(RuntimeError::VerifyError { offset: 0, .. })
+        ));
+
+        // A goto into the middle of itself, attributed to its method
+        let class_file = class_with_method("()V", 0, 0, vec![Goto(1), Return], None);
+        let mut class_data = ClassFileWriter::write(&class_file).unwrap();
+        let goto = class_data
+            .windows(3)
+            .position(|window| window == [0xA7, 0x00, 0x03])
+            .unwrap();
+        class_data[goto + 2] = 0x02;
+        match ClassFileParser::parse(&class_data) {
+            Err(RuntimeError::VerifyError {
+                method,
+                offset,
+                message,
+            }) => {
+                assert_eq!(method, "Check.run()V");
+                assert_eq!(offset, 0);
+                assert_eq!(message, "branch target 2 is not an instruction boundary");
+            }
+            other => panic!("expected a VerifyError, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_stack_map_tables_are_computed() {
+        // An int on the stack at the target, with the locals on entry
+        let mut class_file = class_with_method(
+            "(J[Ljava/lang/String;)I",
+            2,
+            4,
+            vec![
+                Iconst5,
+                Iconst0,
+                Ifeq(5),
+                Iconst1,
+                Iadd,
+                Aload2,
+                Arraylength,
+                Iadd,
+                Ireturn,
+            ],
+            None,
+        );
+        add_stack_map_table(&mut class_file, 0).unwrap();
+        let code = class_file.methods[0].code.as_ref().unwrap();
+        assert_eq!(code.attributes[0].name_index, 8);
+        assert_eq!(code.attributes[0].info, vec![0, 1, 64 + 7, 1]);
+        verify_class(&class_file).unwrap();
+
+        // A new local and a reference on the stack need a full frame
+        let mut class_file = class_with_method(
+            "([Ljava/lang/String;)V",
+            2,
+            2,
+            vec![Iconst0, Istore1, Aload0, Iload1, Ifeq(6), Nop, Pop, Return],
+            None,
+        );
+        add_stack_map_table(&mut class_file, 0).unwrap();
+        let code = class_file.methods[0].code.as_ref().unwrap();
+        assert_eq!(
+            code.attributes[0].info,
+            vec![0, 1, 255, 0, 8, 0, 2, 7, 0, 11, 1, 0, 1, 7, 0, 4]
+        );
+        assert_eq!(
+            class_file.constant_pool.get_class_name(11),
+            Some("[Ljava/lang/String;")
+        );
+        verify_class(&class_file).unwrap();
+
+        // Generated loops get a table for every Java release
+        for java_version in [
+            JavaVersion::Java8,
+            JavaVersion::Java11,
+            JavaVersion::Java17,
+            JavaVersion::Java21,
+        ] {
+            let mut generator = JavaClassGenerator::new("DiceRoll".to_string());
+            generator.set_java_version(java_version);
+            let class_data = generator.generate_dice_class("4d6").unwrap();
+            let class_file = ClassFileParser::parse(&class_data).unwrap();
+            assert_eq!(class_file.major_version, java_version.major_version());
+            let code = class_file.main_method().unwrap().code.as_ref().unwrap();
+            assert!(code.attributes.iter().any(|attribute| {
+                class_file.constant_pool.get_utf8(attribute.name_index) == Some("StackMapTable")
+            }));
+            verify_class(&class_file).unwrap();
+
+            let mut generator = JavaClassGenerator::new("dice/Roll".to_string());
+            generator.set_java_version(java_version);
+            let class_data = generator.generate_parametric_class().unwrap();
+            let class_file = ClassFileParser::parse(&class_data).unwrap();
+            verify_class(&class_file).unwrap();
+        }
+    }
+}
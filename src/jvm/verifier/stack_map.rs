@@ -0,0 +1,272 @@
+//! `StackMapTable` computation
+//!
+//! From version 50 on, class files declare the types at every branch
+//! target so the JVM can check a method in one pass. Generated code has no
+//! table, so one is built from the types the verifier infers, writing each
+//! frame in the most compact form that describes it.
+//!
+//! The verifier tracks references without their class. Arguments that no
+//! instruction overwrites keep the class from the method descriptor; any
+//! other reference is declared as `java/lang/Object`.
+
+use super::{
+    ACC_STATIC, Failure, MethodVerifier, Type, falls_through, local_store, method_verify_error,
+};
+use crate::error::RuntimeError;
+use crate::jvm::class_file_parser::{AttributeInfo, ClassFile};
+use crate::jvm::jvm_types::{ConstantPoolBuilder, method_parameter_types};
+use std::collections::{BTreeSet, HashMap};
+
+/// Type as a `StackMapTable` declares it
+#[derive(Debug, Clone, PartialEq)]
+enum DeclaredType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    Object(String),
+}
+
+/// Frame declared at the instruction starting at `offset`, with one entry
+/// per value
+#[derive(Debug)]
+struct DeclaredFrame {
+    offset: usize,
+    locals: Vec<DeclaredType>,
+    stack: Vec<DeclaredType>,
+}
+
+/// Give a method the `StackMapTable` its branches need, replacing any it
+/// already has. Methods that do not branch are left without one.
+pub fn add_stack_map_table(
+    class_file: &mut ClassFile,
+    method_index: usize,
+) -> Result<(), RuntimeError> {
+    let constant_pool = &class_file.constant_pool;
+    let Some(code) = &mut class_file.methods[method_index].code else {
+        return Ok(());
+    };
+    code.attributes
+        .retain(|attribute| constant_pool.get_utf8(attribute.name_index) != Some("StackMapTable"));
+
+    let class_name = class_file.class_name().unwrap_or_default().to_string();
+    let bootstrap_method_count = class_file.bootstrap_methods()?.len();
+    let method = &class_file.methods[method_index];
+    let Some(code) = &method.code else {
+        return Ok(());
+    };
+    let verifier = MethodVerifier::new(class_file, method, code, bootstrap_method_count);
+    let (initial, frames) = verifier
+        .declared_frames()
+        .map_err(|failure| method_verify_error(&class_name, method, failure))?;
+    if frames.is_empty() {
+        return Ok(());
+    }
+
+    let mut constant_pool =
+        ConstantPoolBuilder::from(std::mem::take(&mut class_file.constant_pool));
+    let table = encode_frames(&initial, &frames, &mut constant_pool)
+        .and_then(|info| Ok((constant_pool.utf8("StackMapTable")?, info)));
+    class_file.constant_pool = constant_pool.build();
+    let (name_index, info) = table.map_err(RuntimeError::InvalidClassFile)?;
+    if let Some(code) = &mut class_file.methods[method_index].code {
+        code.attributes.push(AttributeInfo { name_index, info });
+    }
+    Ok(())
+}
+
+impl MethodVerifier<'_> {
+    /// The locals on entry and every frame the table has to declare: at
+    /// branch targets, exception handlers and after unconditional jumps
+    fn declared_frames(&self) -> Result<(Vec<DeclaredType>, Vec<DeclaredFrame>), Failure> {
+        let states = self.verify()?;
+        let bytecode = &self.code.bytecode;
+        let mut needed = BTreeSet::new();
+        for (index, instruction) in bytecode.iter().enumerate() {
+            needed.extend(instruction.branch_targets().into_iter().map(usize::from));
+            if index > 0 && !falls_through(&bytecode[index - 1]) {
+                needed.insert(index);
+            }
+        }
+        needed.extend(
+            self.exception_handlers()?
+                .iter()
+                .map(|handler| handler.handler),
+        );
+
+        let classes = self.argument_classes();
+        let frames = needed
+            .into_iter()
+            .map(|index| {
+                let offset = self.offsets[index];
+                let frame = states[index].as_ref().ok_or_else(|| {
+                    (
+                        offset,
+                        "no stack map frame can be computed for unreachable code".to_string(),
+                    )
+                })?;
+                Ok(DeclaredFrame {
+                    offset,
+                    locals: declared_locals(&frame.locals, &classes),
+                    stack: frame
+                        .stack
+                        .iter()
+                        .map(|&value| declared_type(value, None))
+                        .collect(),
+                })
+            })
+            .collect::<Result<_, Failure>>()?;
+
+        let (arguments, _) = self.signature().map_err(|message| (0, message))?;
+        let initial = self
+            .expand(&arguments, Vec::new())
+            .map_err(|message| (0, message))?;
+        Ok((declared_locals(&initial.locals, &classes), frames))
+    }
+
+    /// Classes of the reference arguments no instruction overwrites, by
+    /// local slot
+    fn argument_classes(&self) -> HashMap<usize, String> {
+        let mut classes = HashMap::new();
+        let mut slot = 0;
+        if self.method.access_flags & ACC_STATIC == 0 {
+            if let Some(name) = self.class_file.class_name() {
+                classes.insert(slot, name.to_string());
+            }
+            slot += 1;
+        }
+        for parameter in method_parameter_types(&self.method.descriptor) {
+            if let Some(name) = parameter
+                .strip_prefix('L')
+                .and_then(|name| name.strip_suffix(';'))
+            {
+                classes.insert(slot, name.to_string());
+            } else if parameter.starts_with('[') {
+                classes.insert(slot, parameter.to_string());
+            }
+            slot += if matches!(parameter, "J" | "D") { 2 } else { 1 };
+        }
+        for instruction in &self.code.bytecode {
+            if let Some((_, index)) = local_store(instruction) {
+                classes.remove(&index);
+            }
+        }
+        classes
+    }
+}
+
+/// One entry per value for locals given one per slot, without the unusable
+/// locals at the end
+fn declared_locals(locals: &[Type], classes: &HashMap<usize, String>) -> Vec<DeclaredType> {
+    let mut declared = Vec::new();
+    let mut slot = 0;
+    while slot < locals.len() {
+        declared.push(declared_type(locals[slot], classes.get(&slot)));
+        slot += locals[slot].width();
+    }
+    while declared.last() == Some(&DeclaredType::Top) {
+        declared.pop();
+    }
+    declared
+}
+
+fn declared_type(value: Type, class: Option<&String>) -> DeclaredType {
+    match value {
+        Type::Top => DeclaredType::Top,
+        Type::Integer => DeclaredType::Integer,
+        Type::Float => DeclaredType::Float,
+        Type::Long => DeclaredType::Long,
+        Type::Double => DeclaredType::Double,
+        Type::Null => DeclaredType::Null,
+        Type::Reference => {
+            DeclaredType::Object(class.map_or("java/lang/Object", String::as_str).to_string())
+        }
+    }
+}
+
+/// Encode frames as the contents of a `StackMapTable` attribute
+fn encode_frames(
+    initial: &[DeclaredType],
+    frames: &[DeclaredFrame],
+    constant_pool: &mut ConstantPoolBuilder,
+) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(frames.len() as u16).to_be_bytes());
+    let mut locals = initial;
+    let mut previous_offset = None;
+
+    for frame in frames {
+        let delta = match previous_offset {
+            Some(previous) => frame.offset - previous - 1,
+            None => frame.offset,
+        } as u16;
+        previous_offset = Some(frame.offset);
+        let same_locals = frame.locals == locals;
+        let added = frame.locals.len().saturating_sub(locals.len());
+        let removed = locals.len().saturating_sub(frame.locals.len());
+
+        match frame.stack.as_slice() {
+            [] if same_locals && delta < 64 => bytes.push(delta as u8),
+            [] if same_locals => {
+                bytes.push(251);
+                bytes.extend_from_slice(&delta.to_be_bytes());
+            }
+            [value] if same_locals && delta < 64 => {
+                bytes.push(64 + delta as u8);
+                write_type(&mut bytes, value, constant_pool)?;
+            }
+            [value] if same_locals => {
+                bytes.push(247);
+                bytes.extend_from_slice(&delta.to_be_bytes());
+                write_type(&mut bytes, value, constant_pool)?;
+            }
+            [] if (1..=3).contains(&added) && frame.locals.starts_with(locals) => {
+                bytes.push(251 + added as u8);
+                bytes.extend_from_slice(&delta.to_be_bytes());
+                for value in &frame.locals[locals.len()..] {
+                    write_type(&mut bytes, value, constant_pool)?;
+                }
+            }
+            [] if (1..=3).contains(&removed) && locals.starts_with(&frame.locals) => {
+                bytes.push(251 - removed as u8);
+                bytes.extend_from_slice(&delta.to_be_bytes());
+            }
+            stack => {
+                bytes.push(255);
+                bytes.extend_from_slice(&delta.to_be_bytes());
+                for values in [frame.locals.as_slice(), stack] {
+                    bytes.extend_from_slice(&(values.len() as u16).to_be_bytes());
+                    for value in values {
+                        write_type(&mut bytes, value, constant_pool)?;
+                    }
+                }
+            }
+        }
+        locals = &frame.locals;
+    }
+    Ok(bytes)
+}
+
+/// Write a `verification_type_info`
+fn write_type(
+    bytes: &mut Vec<u8>,
+    value: &DeclaredType,
+    constant_pool: &mut ConstantPoolBuilder,
+) -> Result<(), String> {
+    match value {
+        DeclaredType::Top => bytes.push(0),
+        DeclaredType::Integer => bytes.push(1),
+        DeclaredType::Float => bytes.push(2),
+        DeclaredType::Double => bytes.push(3),
+        DeclaredType::Long => bytes.push(4),
+        DeclaredType::Null => bytes.push(5),
+        DeclaredType::Object(name) => {
+            bytes.push(7);
+            let index = constant_pool.class(name)?;
+            bytes.extend_from_slice(&index.to_be_bytes());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,829 @@
+use crate::error::RuntimeError;
+use std::collections::HashMap;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// General purpose flag marking entry names as UTF-8
+const UTF8_NAMES_FLAG: u16 = 0x0800;
+const DOS_DATE_1980_01_01: u16 = 0x0021;
+
+const MANIFEST_NAME: &str = "META-INF/MANIFEST.MF";
+
+/// Entry of a zip central directory
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    pub name: String,
+    pub method: u16,
+    pub crc32: u32,
+    pub compressed_size: usize,
+    pub uncompressed_size: usize,
+    local_header_offset: usize,
+}
+
+/// Read-only zip archive, such as a JAR file, held in memory. Entries may be
+/// stored or deflated; zip64 and encryption are not supported.
+#[derive(Debug, Clone)]
+pub struct ZipArchive {
+    data: Vec<u8>,
+    entries: Vec<ZipEntry>,
+    index: HashMap<String, usize>,
+}
+
+impl ZipArchive {
+    pub fn new(data: Vec<u8>) -> Result<Self, RuntimeError> {
+        let invalid = |message: &str| RuntimeError::InvalidArchive(message.to_string());
+
+        // The end of central directory record is followed only by an
+        // optional comment of up to 65535 bytes
+        let end = data
+            .len()
+            .checked_sub(END_OF_CENTRAL_DIRECTORY_SIZE)
+            .ok_or_else(|| invalid("file is too short"))?;
+        let lowest = end.saturating_sub(u16::MAX as usize);
+        let end_record = (lowest..=end)
+            .rev()
+            .find(|&offset| read_u32(&data, offset) == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+            .ok_or_else(|| invalid("end of central directory not found"))?;
+
+        let entry_count = read_u16(&data, end_record + 10).unwrap_or(0) as usize;
+        let directory_offset = read_u32(&data, end_record + 16).unwrap_or(0);
+        if directory_offset == u32::MAX {
+            return Err(invalid("zip64 archives are not supported"));
+        }
+
+        let mut entries = Vec::with_capacity(entry_count);
+        let mut offset = directory_offset as usize;
+        for _ in 0..entry_count {
+            if read_u32(&data, offset) != Some(CENTRAL_DIRECTORY_SIGNATURE) {
+                return Err(invalid("corrupt central directory"));
+            }
+            let field = |position: usize| {
+                read_u32(&data, offset + position).ok_or_else(|| invalid("truncated entry"))
+            };
+            let short_field = |position: usize| {
+                read_u16(&data, offset + position)
+                    .map(usize::from)
+                    .ok_or_else(|| invalid("truncated entry"))
+            };
+
+            let flags = short_field(8)?;
+            let method = short_field(10)? as u16;
+            let crc32 = field(16)?;
+            let compressed_size = field(20)?;
+            let uncompressed_size = field(24)?;
+            let name_length = short_field(28)?;
+            let extra_length = short_field(30)?;
+            let comment_length = short_field(32)?;
+            let local_header_offset = field(42)?;
+            if flags & 1 != 0 {
+                return Err(invalid("encrypted entries are not supported"));
+            }
+            if [compressed_size, uncompressed_size, local_header_offset].contains(&u32::MAX) {
+                return Err(invalid("zip64 entries are not supported"));
+            }
+
+            let name = data
+                .get(offset + 46..offset + 46 + name_length)
+                .ok_or_else(|| invalid("truncated entry name"))?;
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method,
+                crc32,
+                compressed_size: compressed_size as usize,
+                uncompressed_size: uncompressed_size as usize,
+                local_header_offset: local_header_offset as usize,
+            });
+            offset += 46 + name_length + extra_length + comment_length;
+        }
+
+        let index = entries
+            .iter()
+            .enumerate()
+            .map(|(position, entry)| (entry.name.clone(), position))
+            .collect();
+        Ok(Self {
+            data,
+            entries,
+            index,
+        })
+    }
+
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// Decompressed contents of the named entry, checked against its CRC
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>, RuntimeError> {
+        let Some(&position) = self.index.get(name) else {
+            return Ok(None);
+        };
+        let entry = &self.entries[position];
+        let invalid = |message: String| RuntimeError::InvalidArchive(format!("{name}: {message}"));
+
+        let header = entry.local_header_offset;
+        if read_u32(&self.data, header) != Some(LOCAL_FILE_HEADER_SIGNATURE) {
+            return Err(invalid("corrupt local file header".to_string()));
+        }
+        let name_length = read_u16(&self.data, header + 26).unwrap_or(0) as usize;
+        let extra_length = read_u16(&self.data, header + 28).unwrap_or(0) as usize;
+        let start = header + 30 + name_length + extra_length;
+        let compressed = self
+            .data
+            .get(start..start + entry.compressed_size)
+            .ok_or_else(|| invalid("truncated entry data".to_string()))?;
+
+        let contents = match entry.method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => inflate(compressed).map_err(invalid)?,
+            method => return Err(invalid(format!("unsupported compression method {method}"))),
+        };
+        if contents.len() != entry.uncompressed_size || crc32(&contents) != entry.crc32 {
+            return Err(invalid("checksum mismatch".to_string()));
+        }
+        Ok(Some(contents))
+    }
+
+    /// `Main-Class` named by the JAR manifest, if there is one
+    pub fn main_class(&self) -> Result<Option<String>, RuntimeError> {
+        let Some(manifest) = self.read(MANIFEST_NAME)? else {
+            return Ok(None);
+        };
+        Ok(manifest_attribute(
+            &String::from_utf8_lossy(&manifest),
+            "Main-Class",
+        ))
+    }
+}
+
+/// Zip archive built in memory. Entries are deflated when that makes them
+/// smaller and stored otherwise.
+#[derive(Debug, Default)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    entry_count: usize,
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &str, contents: &[u8]) -> Result<(), RuntimeError> {
+        let too_large = |what: &str| {
+            RuntimeError::InvalidArchive(format!("{name}: {what} is too large for a zip archive"))
+        };
+        let deflated = deflate(contents);
+        let (method, stored) = if deflated.len() < contents.len() {
+            (METHOD_DEFLATED, deflated.as_slice())
+        } else {
+            (METHOD_STORED, contents)
+        };
+        let name_length = u16::try_from(name.len()).map_err(|_| too_large("name"))?;
+        let compressed_size = zip_u32(stored.len()).ok_or_else(|| too_large("entry"))?;
+        let uncompressed_size = zip_u32(contents.len()).ok_or_else(|| too_large("entry"))?;
+        let offset = zip_u32(self.data.len()).ok_or_else(|| too_large("archive"))?;
+        if self.entry_count == u16::MAX as usize {
+            return Err(too_large("entry count"));
+        }
+
+        // Fields shared by the local header and the central directory:
+        // version needed, UTF-8 names flag, method, DOS time and date of
+        // 1980-01-01 00:00, CRC and sizes
+        let mut fields = Vec::with_capacity(26);
+        fields.extend_from_slice(&20u16.to_le_bytes());
+        fields.extend_from_slice(&UTF8_NAMES_FLAG.to_le_bytes());
+        fields.extend_from_slice(&method.to_le_bytes());
+        fields.extend_from_slice(&0u16.to_le_bytes());
+        fields.extend_from_slice(&DOS_DATE_1980_01_01.to_le_bytes());
+        fields.extend_from_slice(&crc32(contents).to_le_bytes());
+        fields.extend_from_slice(&compressed_size.to_le_bytes());
+        fields.extend_from_slice(&uncompressed_size.to_le_bytes());
+        fields.extend_from_slice(&name_length.to_le_bytes());
+        // No extra field
+        fields.extend_from_slice(&0u16.to_le_bytes());
+
+        self.data
+            .extend_from_slice(&LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        self.data.extend_from_slice(&fields);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(stored);
+
+        let directory = &mut self.central_directory;
+        directory.extend_from_slice(&CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        // Made by version 2.0
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&fields);
+        // Comment length, disk number, internal and external attributes
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+        self.entry_count += 1;
+        Ok(())
+    }
+
+    /// The archive with its central directory
+    pub fn finish(self) -> Result<Vec<u8>, RuntimeError> {
+        let too_large = || RuntimeError::InvalidArchive("archive is too large".to_string());
+        let mut data = self.data;
+        let directory_offset = zip_u32(data.len()).ok_or_else(too_large)?;
+        let directory_size = zip_u32(self.central_directory.len()).ok_or_else(too_large)?;
+        data.extend_from_slice(&self.central_directory);
+
+        data.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        // This disk and the disk with the central directory
+        data.extend_from_slice(&[0; 4]);
+        for _ in 0..2 {
+            data.extend_from_slice(&(self.entry_count as u16).to_le_bytes());
+        }
+        data.extend_from_slice(&directory_size.to_le_bytes());
+        data.extend_from_slice(&directory_offset.to_le_bytes());
+        // No comment
+        data.extend_from_slice(&0u16.to_le_bytes());
+        Ok(data)
+    }
+}
+
+/// A size or offset that fits a zip without zip64, whose marker is
+/// `u32::MAX`
+fn zip_u32(value: usize) -> Option<u32> {
+    u32::try_from(value).ok().filter(|&value| value != u32::MAX)
+}
+
+/// Package classes, given by binary name such as `dice/Main`, as a JAR
+/// whose manifest makes `main_class` the class `java -jar` runs
+pub fn write_jar(main_class: &str, classes: &[(String, Vec<u8>)]) -> Result<Vec<u8>, RuntimeError> {
+    let manifest = format!(
+        "Manifest-Version: 1.0\r\n{}Created-By: dice-rust\r\n\r\n",
+        manifest_line("Main-Class", &main_class.replace('/', "."))
+    );
+    let mut zip = ZipWriter::new();
+    zip.add(MANIFEST_NAME, manifest.as_bytes())?;
+    for (name, class_data) in classes {
+        zip.add(&format!("{name}.class"), class_data)?;
+    }
+    zip.finish()
+}
+
+/// A manifest header, continued on lines starting with a space so that
+/// none is longer than 72 bytes
+fn manifest_line(name: &str, value: &str) -> String {
+    let mut line = String::new();
+    let mut width = 0;
+    for character in format!("{name}: {value}").chars() {
+        if width + character.len_utf8() > 72 {
+            line.push_str("\r\n ");
+            width = 1;
+        }
+        line.push(character);
+        width += character.len_utf8();
+    }
+    line.push_str("\r\n");
+    line
+}
+
+/// Value of a header in the main section of a manifest
+fn manifest_attribute(manifest: &str, name: &str) -> Option<String> {
+    let mut headers: Vec<String> = Vec::new();
+    for line in manifest.lines() {
+        if line.is_empty() {
+            break;
+        }
+        match (line.strip_prefix(' '), headers.last_mut()) {
+            (Some(continuation), Some(header)) => header.push_str(continuation),
+            _ => headers.push(line.to_string()),
+        }
+    }
+    headers.iter().find_map(|header| {
+        let (key, value) = header.split_once(':')?;
+        key.eq_ignore_ascii_case(name)
+            .then(|| value.trim().to_string())
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+};
+
+/// CRC-32 checksum as used by zip and gzip
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Base lengths and extra bits for length codes 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances and extra bits for distance codes 0..=29
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code length code lengths are stored in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u8) -> Result<u32, String> {
+        let mut value = 0;
+        for shift in 0..count {
+            let byte = self
+                .data
+                .get(self.position / 8)
+                .ok_or("unexpected end of deflate stream")?;
+            value |= u32::from((byte >> (self.position % 8)) & 1) << shift;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+}
+
+/// Canonical Huffman code decoded one bit at a time
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols: Vec<(u8, u16)> = lengths
+            .iter()
+            .enumerate()
+            .filter(|(_, length)| **length != 0)
+            .map(|(symbol, &length)| (length, symbol as u16))
+            .collect();
+        symbols.sort_unstable();
+        Self {
+            counts,
+            symbols: symbols.into_iter().map(|(_, symbol)| symbol).collect(),
+        }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+/// Decompress a raw DEFLATE stream (RFC 1951)
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut output = Vec::new();
+
+    loop {
+        let is_final = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let length = reader.bits(16)?;
+                let complement = reader.bits(16)?;
+                if length != !complement & 0xFFFF {
+                    return Err("corrupt stored block length".to_string());
+                }
+                let start = reader.position / 8;
+                let block = data
+                    .get(start..start + length as usize)
+                    .ok_or("unexpected end of deflate stream")?;
+                output.extend_from_slice(block);
+                reader.position += length as usize * 8;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+
+        if is_final {
+            return Ok(output);
+        }
+    }
+}
+
+fn read_dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_length_lengths = [0u8; 19];
+    for &position in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_length_lengths[position] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_lengths.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("repeat without a previous length")?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() != literal_count + distance_count {
+        return Err("code lengths overflow the tables".to_string());
+    }
+
+    let (literal_lengths, distance_lengths) = lengths.split_at(literal_count);
+    Ok((
+        Huffman::new(literal_lengths),
+        Huffman::new(distance_lengths),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let code = symbol - 257;
+                let length = LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code])? as usize;
+                let code = distances.decode(reader)? as usize;
+                if code >= DISTANCE_BASE.len() {
+                    return Err("invalid distance code".to_string());
+                }
+                let distance =
+                    DISTANCE_BASE[code] as usize + reader.bits(DISTANCE_EXTRA[code])? as usize;
+                let start = output
+                    .len()
+                    .checked_sub(distance)
+                    .ok_or("distance reaches before the start of the output")?;
+                // The copy may overlap the bytes it produces
+                for offset in 0..length {
+                    output.push(output[start + offset]);
+                }
+            }
+            _ => return Err("invalid literal/length code".to_string()),
+        }
+    }
+}
+
+/// Largest distance a deflate match can reach back
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Earlier positions with the same hash tried for each match
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    /// Write the low `count` bits of `value`, least significant first
+    fn bits(&mut self, value: u32, count: u8) {
+        for shift in 0..count {
+            self.buffer |= ((value >> shift) & 1) << self.count;
+            self.count += 1;
+            if self.count == 8 {
+                self.bytes.push(self.buffer as u8);
+                self.buffer = 0;
+                self.count = 0;
+            }
+        }
+    }
+
+    /// Write a Huffman code, which is stored most significant bit first
+    fn code(&mut self, code: u32, length: u8) {
+        for shift in (0..length).rev() {
+            self.bits(code >> shift, 1);
+        }
+    }
+
+    /// Write a literal/length symbol with the fixed Huffman code
+    fn literal_length(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Earlier positions of each three-byte sequence, newest first
+struct MatchFinder<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl<'a> MatchFinder<'a> {
+    const NONE: usize = usize::MAX;
+
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            head: vec![Self::NONE; 1 << HASH_BITS],
+            previous: vec![Self::NONE; data.len()],
+        }
+    }
+
+    fn hash(&self, position: usize) -> usize {
+        let bytes = &self.data[position..position + MIN_MATCH];
+        let key = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        (key.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, position: usize) {
+        if position + MIN_MATCH <= self.data.len() {
+            let key = self.hash(position);
+            self.previous[position] = self.head[key];
+            self.head[key] = position;
+        }
+    }
+
+    /// Length and distance of the longest earlier match for the bytes at
+    /// `position`, if one is long enough to encode
+    fn longest_match(&self, position: usize) -> Option<(usize, usize)> {
+        let longest = MAX_MATCH.min(self.data.len() - position);
+        if longest < MIN_MATCH {
+            return None;
+        }
+        let (mut length, mut distance) = (0, 0);
+        let mut candidate = self.head[self.hash(position)];
+        let mut chain = 0;
+        while candidate != Self::NONE && position - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+            let matched = self.data[candidate..]
+                .iter()
+                .zip(&self.data[position..position + longest])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if matched > length {
+                (length, distance) = (matched, position - candidate);
+                if matched == longest {
+                    break;
+                }
+            }
+            candidate = self.previous[candidate];
+            chain += 1;
+        }
+        (length >= MIN_MATCH).then_some((length, distance))
+    }
+}
+
+/// Compress data as a single deflate block with the fixed Huffman codes
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut matches = MatchFinder::new(data);
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        buffer: 0,
+        count: 0,
+    };
+    // Final block, fixed Huffman codes
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let mut position = 0;
+    while position < data.len() {
+        match matches.longest_match(position) {
+            Some((length, distance)) => {
+                let code = LENGTH_BASE
+                    .iter()
+                    .rposition(|&base| base as usize <= length)
+                    .unwrap_or(0);
+                writer.literal_length(257 + code as u16);
+                writer.bits(
+                    (length - LENGTH_BASE[code] as usize) as u32,
+                    LENGTH_EXTRA[code],
+                );
+                let code = DISTANCE_BASE
+                    .iter()
+                    .rposition(|&base| base as usize <= distance)
+                    .unwrap_or(0);
+                writer.code(code as u32, 5);
+                writer.bits(
+                    (distance - DISTANCE_BASE[code] as usize) as u32,
+                    DISTANCE_EXTRA[code],
+                );
+                for offset in 0..length {
+                    matches.insert(position + offset);
+                }
+                position += length;
+            }
+            None => {
+                writer.literal_length(u16::from(data[position]));
+                matches.insert(position);
+                position += 1;
+            }
+        }
+    }
+    writer.literal_length(256);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_inflate_block_types() {
+        // Stored block
+        assert_eq!(
+            inflate(&[0x01, 0x03, 0x00, 0xFC, 0xFF, b'd', b'2', b'0']).unwrap(),
+            b"d20"
+        );
+        // Fixed Huffman block with a back-reference, from zlib at level 9
+        let fixed = [0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x27, 0x01];
+        assert_eq!(inflate(&fixed).unwrap(), b"hello hello hello hello");
+
+        assert!(inflate(&[0x07]).is_err());
+    }
+
+    #[test]
+    fn test_read_jar_entries() {
+        let data = std::fs::read("tests/fixtures/dice-lib.jar").unwrap();
+        let archive = ZipArchive::new(data).unwrap();
+
+        assert!(archive.contains("dice/Dice.class"));
+        assert!(
+            archive
+                .entries()
+                .iter()
+                .any(|entry| entry.method == METHOD_DEFLATED)
+        );
+        let class_data = archive.read("dice/Dice.class").unwrap().unwrap();
+        assert_eq!(&class_data[..4], &[0xCA, 0xFE, 0xBA, 0xBE]);
+        let manifest = archive.read("META-INF/MANIFEST.MF").unwrap().unwrap();
+        assert!(
+            String::from_utf8(manifest)
+                .unwrap()
+                .starts_with("Manifest-Version")
+        );
+        assert!(archive.read("dice/Missing.class").unwrap().is_none());
+
+        assert!(matches!(
+            ZipArchive::new(vec![0; 8]),
+            Err(RuntimeError::InvalidArchive(_))
+        ));
+    }
+
+    #[test]
+    fn test_deflate_round_trip() {
+        let mut state = 0x2545_F491_u32;
+        let noise: Vec<u8> = (0..70_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let repeated = b"3d6 2d20 1d100 ".repeat(5000);
+
+        for data in [
+            &b""[..],
+            b"d",
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            &noise,
+            &repeated,
+        ] {
+            let compressed = deflate(data);
+            assert_eq!(inflate(&compressed).unwrap(), data);
+        }
+        assert!(deflate(&repeated).len() < repeated.len() / 50);
+    }
+
+    #[test]
+    fn test_write_zip_and_jar() {
+        let mut zip = ZipWriter::new();
+        zip.add("short.txt", b"d6").unwrap();
+        zip.add("long.txt", &b"d6".repeat(100)).unwrap();
+        let archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+        let methods: Vec<u16> = archive.entries().iter().map(|entry| entry.method).collect();
+        assert_eq!(methods, vec![METHOD_STORED, METHOD_DEFLATED]);
+        assert_eq!(archive.read("short.txt").unwrap(), Some(b"d6".to_vec()));
+        assert_eq!(archive.read("long.txt").unwrap(), Some(b"d6".repeat(100)));
+
+        let class_data = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52];
+        let data = write_jar(
+            "dice/Main",
+            &[("dice/Main".to_string(), class_data.clone())],
+        )
+        .unwrap();
+        let archive = ZipArchive::new(data).unwrap();
+        assert_eq!(archive.entries()[0].name, MANIFEST_NAME);
+        assert_eq!(archive.main_class().unwrap(), Some("dice.Main".to_string()));
+        assert_eq!(archive.read("dice/Main.class").unwrap(), Some(class_data));
+
+        // Long names are continued on further lines
+        let name = format!("dice/{}/Main", "nested".repeat(20));
+        let line = manifest_line("Main-Class", &name);
+        assert!(line.split("\r\n").all(|line| line.len() <= 72));
+        assert_eq!(manifest_attribute(&line, "main-class"), Some(name));
+
+        let library =
+            ZipArchive::new(std::fs::read("tests/fixtures/dice-lib.jar").unwrap()).unwrap();
+        assert_eq!(library.main_class().unwrap(), None);
+    }
+}
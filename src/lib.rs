@@ -1,7 +1,10 @@
 pub mod analyzer;
 pub mod ast;
+pub mod c_generator;
 pub mod error;
+pub mod il_generator;
 pub mod jvm;
 pub mod lexer;
 pub mod parser;
 pub mod stack_vm;
+pub mod wasm_generator;
@@ -1,5 +1,6 @@
-use clap::{Parser, Subcommand};
-use dice_rust::{jvm, stack_vm::StackVm};
+use clap::{Parser, Subcommand, ValueEnum};
+use dice_rust::{c_generator, il_generator, jvm, stack_vm::StackVm, wasm_generator};
+use std::time::Duration;
 
 fn generate_and_execute_jvm_bytecode(
     expression: &str,
@@ -15,6 +16,89 @@ fn generate_and_execute_jvm_bytecode(
     Ok(())
 }
 
+fn execute_program(
+    class: &str,
+    classpath: Option<&str>,
+    verbose: bool,
+    limits: jvm::VmLimits,
+    args: &[String],
+) -> Result<(), dice_rust::error::RuntimeError> {
+    let mut vm = jvm::JvmCompatibleVm::new();
+    vm.set_verbose(verbose);
+    vm.set_limits(limits);
+
+    if class.ends_with(".jar") {
+        if let Some(classpath) = classpath {
+            vm.set_classpath(classpath)?;
+        }
+        vm.execute_jar(class, args)?;
+        return Ok(());
+    }
+
+    // With a classpath, a name that is not a class file is a main class
+    // to look up on it, as in `java -cp lib.jar dice.Main`
+    if let Some(classpath) = classpath {
+        vm.set_classpath(classpath)?;
+        let class_file = if class.ends_with(".class") {
+            class.to_string()
+        } else {
+            format!("{class}.class")
+        };
+        if !std::path::Path::new(&class_file).is_file() {
+            vm.execute_main_class(class, args)?;
+            return Ok(());
+        }
+    }
+    vm.execute_class_file(class, args)?;
+    Ok(())
+}
+
+fn compile_program(
+    expression: Option<&str>,
+    output: &str,
+    target: Target,
+    java_version: jvm::JavaVersion,
+    jar: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = match expression {
+        Some(expression) => jvm::DiceSource::Expression(expression),
+        None => jvm::DiceSource::Arguments,
+    };
+    match (target, source, jar) {
+        (Target::Class, source, Some(jar)) => {
+            jvm::generate_java_jar(source, output, java_version, jar)
+        }
+        (Target::Class, source, None) => jvm::generate_java_class(source, output, java_version),
+        (_, jvm::DiceSource::Arguments, _) => {
+            Err("--parametric is only supported with --target class".into())
+        }
+        (_, _, Some(_)) => Err("--jar is only supported with --target class".into()),
+        (Target::JavaSrc, jvm::DiceSource::Expression(expression), None) => {
+            jvm::generate_java_source(expression, output)
+        }
+        (Target::Il, jvm::DiceSource::Expression(expression), None) => {
+            il_generator::generate_il_source(expression, output)
+        }
+        (Target::Wasm, jvm::DiceSource::Expression(expression), None) => {
+            wasm_generator::generate_wasm_module(expression, output, false)
+        }
+        (Target::Wat, jvm::DiceSource::Expression(expression), None) => {
+            wasm_generator::generate_wasm_module(expression, output, true)
+        }
+        (Target::C, jvm::DiceSource::Expression(expression), None) => {
+            c_generator::generate_c_source(expression, output)
+        }
+    }
+}
+
+/// A timeout in seconds, which may be fractional
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid number: {value}"))?;
+    Duration::try_from_secs_f64(seconds).map_err(|error| error.to_string())
+}
+
 #[derive(Parser)]
 #[command(name = "dice-rust")]
 #[command(about = "A dice rolling language interpreter with multi-VM support")]
@@ -24,6 +108,23 @@ struct Cli {
     command: Commands,
 }
 
+/// What `compile` generates
+#[derive(Clone, Copy, ValueEnum)]
+enum Target {
+    /// A Java class file
+    Class,
+    /// Java source code
+    JavaSrc,
+    /// .NET ILAsm source code
+    Il,
+    /// A WebAssembly module
+    Wasm,
+    /// A WebAssembly module in the text format
+    Wat,
+    /// C99 source code
+    C,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     #[command(about = "Run dice expressions using stack VM or JVM-compatible VM")]
@@ -35,24 +136,108 @@ enum Commands {
             help = "Use JVM-compatible virtual machine instead of the default stack VM"
         )]
         jvm: bool,
+        #[arg(
+            long,
+            value_name = "N",
+            conflicts_with = "jvm",
+            help = "Seed the stack VM's xorshift generator so that rolls repeat"
+        )]
+        seed: Option<u64>,
         #[arg(short, long, help = "Enable verbose output for debugging")]
         verbose: bool,
     },
-    #[command(about = "Compile dice expressions to Java class files")]
+    #[command(
+        about = "Compile dice expressions to Java class files, Java source, .NET IL, WebAssembly or C"
+    )]
     Compile {
-        #[arg(value_name = "EXPRESSION")]
-        expression: String,
+        #[arg(value_name = "EXPRESSION", required_unless_present = "parametric")]
+        expression: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "expression",
+            help = "Generate a reusable class that takes the dice as arguments"
+        )]
+        parametric: bool,
         #[arg(short, long, default_value = "DiceRoll")]
         output: String,
+        #[arg(long, value_enum, default_value = "class", help = "What to generate")]
+        target: Target,
+        #[arg(
+            long,
+            value_name = "VERSION",
+            default_value = "8",
+            help = "Java release the class file targets: 8, 11, 17 or 21"
+        )]
+        java_version: jvm::JavaVersion,
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Package the class into a runnable JAR instead of writing a class file"
+        )]
+        jar: Option<String>,
         #[arg(short, long, help = "Enable verbose output for debugging")]
         verbose: bool,
     },
     #[command(about = "Execute compiled Java class files")]
     Execute {
-        #[arg(value_name = "CLASS_FILE")]
+        #[arg(
+            value_name = "CLASS_FILE",
+            help = "Class file or JAR to run, or the name of a main class on the classpath"
+        )]
         class_file: String,
         #[arg(short, long, help = "Enable verbose output for debugging")]
         verbose: bool,
+        #[arg(
+            long,
+            visible_alias = "class-path",
+            value_name = "PATH",
+            help = "Directories and JAR files to load classes from, separated like PATH"
+        )]
+        classpath: Option<String>,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Stop the program after it executes this many instructions"
+        )]
+        max_steps: Option<u64>,
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            value_parser = parse_timeout,
+            help = "Stop the program after this much wall-clock time"
+        )]
+        timeout: Option<Duration>,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Stop the program when its call stack is deeper than this [default: 10000]"
+        )]
+        max_call_depth: Option<usize>,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Stop the program after it allocates this many objects"
+        )]
+        max_heap_objects: Option<usize>,
+        #[arg(
+            long,
+            value_name = "BYTES",
+            help = "Stop the program after it allocates this many bytes of strings [default: 256 MiB]"
+        )]
+        max_string_bytes: Option<usize>,
+        #[arg(
+            long,
+            value_name = "BYTES",
+            help = "Stop the program after it allocates this many bytes of arrays [default: 1 GiB]"
+        )]
+        max_array_bytes: Option<usize>,
+        #[arg(
+            value_name = "ARGS",
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "Arguments passed to the program's main method"
+        )]
+        args: Vec<String>,
     },
 }
 
@@ -63,6 +248,7 @@ fn main() {
         Commands::Run {
             expression,
             jvm,
+            seed,
             verbose,
         } => {
             if jvm {
@@ -73,6 +259,9 @@ fn main() {
                 }
             } else {
                 let mut stack_vm = StackVm::new();
+                if let Some(seed) = seed {
+                    stack_vm.set_seed(seed);
+                }
                 match stack_vm.execute(&expression) {
                     Ok(()) => (),
                     Err(e) => eprintln!("Error occurred: {e}"),
@@ -81,22 +270,50 @@ fn main() {
         }
         Commands::Compile {
             expression,
+            parametric: _,
             output,
+            target,
+            java_version,
+            jar,
             verbose: _,
         } => {
-            if let Err(e) = jvm::generate_java_class(&expression, &output) {
-                eprintln!("Java class generation error: {e}");
+            let result = compile_program(
+                expression.as_deref(),
+                &output,
+                target,
+                java_version,
+                jar.as_deref(),
+            );
+            if let Err(e) = result {
+                eprintln!("Code generation error: {e}");
             }
         }
         Commands::Execute {
             class_file,
             verbose,
+            classpath,
+            max_steps,
+            timeout,
+            max_call_depth,
+            max_heap_objects,
+            max_string_bytes,
+            max_array_bytes,
+            args,
         } => {
-            let mut vm = jvm::JvmCompatibleVm::new();
-            vm.set_verbose(verbose);
-            match vm.execute_class_file(&class_file) {
+            let defaults = jvm::VmLimits::default();
+            let limits = jvm::VmLimits {
+                max_instructions: max_steps,
+                max_call_depth: max_call_depth.or(defaults.max_call_depth),
+                max_heap_objects,
+                max_string_bytes: max_string_bytes.or(defaults.max_string_bytes),
+                max_array_bytes: max_array_bytes.or(defaults.max_array_bytes),
+                timeout,
+            };
+            match execute_program(&class_file, classpath.as_deref(), verbose, limits, &args) {
                 Ok(_) => (),
-                Err(e) => eprintln!("JVM execution error: {e:?}"),
+                // The VM has already printed the Java stack trace
+                Err(dice_rust::error::RuntimeError::UncaughtException(_)) => std::process::exit(1),
+                Err(e) => eprintln!("JVM execution error: {e}"),
             }
         }
     }
@@ -1,5 +1,6 @@
 use crate::{analyzer::SemanticAnalyzer, error::RuntimeError};
 use rand::prelude::*;
+use std::io::{self, Write};
 
 /// Represents the control flow result of executing an instruction
 #[derive(Debug, PartialEq)]
@@ -14,7 +15,7 @@ enum ExecutionControl {
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
-enum Instruction {
+pub(crate) enum Instruction {
     // Constants
     LdcI4(u32), // Load 32-bit integer constant
 
@@ -62,9 +63,9 @@ enum Instruction {
     CallRandom, // Generate random number
 }
 
-type Bytecode = Vec<Instruction>;
+pub(crate) type Bytecode = Vec<Instruction>;
 
-struct Compiler;
+pub(crate) struct Compiler;
 impl Compiler {
     pub fn compile(source: &str) -> Result<Bytecode, Box<dyn std::error::Error>> {
         let mut bytecode = Vec::<Instruction>::new();
@@ -143,10 +144,46 @@ impl Compiler {
     }
 }
 
+/// xorshift64 with shifts 13, 7 and 17, the generator behind seeded
+/// runs. The C backend embeds the same generator, so a seed gives the same
+/// rolls in both.
+#[derive(Debug, Clone)]
+pub struct Xorshift64 {
+    state: u64,
+}
+
+impl Xorshift64 {
+    /// State used in place of zero, from which xorshift never moves
+    pub const ZERO_SEED_STATE: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = if self.state == 0 {
+            Self::ZERO_SEED_STATE
+        } else {
+            self.state
+        };
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// A roll between 1 and `faces`, which must not be zero
+    pub fn roll(&mut self, faces: u32) -> u32 {
+        (self.next_u64() % u64::from(faces)) as u32 + 1
+    }
+}
+
 pub struct StackVm {
     stack: Vec<u32>,
     locals: [u32; 3], // Local variables 0, 1, 2
     rng: ThreadRng,
+    seeded_rng: Option<Xorshift64>,
 }
 
 impl Default for StackVm {
@@ -161,16 +198,34 @@ impl StackVm {
             stack: Vec::new(),
             locals: [0; 3],
             rng: ThreadRng::default(),
+            seeded_rng: None,
         }
     }
 
+    /// Roll with a [`Xorshift64`] seeded with `seed` instead of the thread
+    /// RNG, so that runs repeat
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seeded_rng = Some(Xorshift64::new(seed));
+    }
+
     pub fn execute(&mut self, source: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.execute_with_output(source, &mut io::stdout(), &mut io::stderr())
+    }
+
+    /// Execute, writing what the program prints to `out` and `err` instead
+    /// of stdout and stderr
+    pub fn execute_with_output(
+        &mut self,
+        source: &str,
+        out: &mut dyn Write,
+        err: &mut dyn Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let bytecode = Compiler::compile(source)?;
         let mut pc = 0;
 
         while pc < bytecode.len() {
             let instruction = &bytecode[pc];
-            let control = self.execute_instruction(instruction)?;
+            let control = self.execute_instruction(instruction, out, err)?;
 
             match control {
                 ExecutionControl::Terminate => {
@@ -202,6 +257,8 @@ impl StackVm {
     fn execute_instruction(
         &mut self,
         instruction: &Instruction,
+        out: &mut dyn Write,
+        err: &mut dyn Write,
     ) -> Result<ExecutionControl, Box<dyn std::error::Error>> {
         match instruction {
             // Constants
@@ -315,21 +372,21 @@ impl StackVm {
             // I/O operations
             Instruction::CallWriteLine => {
                 let value = self.stack.pop().ok_or(RuntimeError::InvalidStackState)?;
-                println!("{value}");
+                writeln!(out, "{value}")?;
             }
             Instruction::CallWrite => {
                 let value = self.stack.pop().ok_or(RuntimeError::InvalidStackState)?;
-                print!("{value}");
+                write!(out, "{value}")?;
             }
             Instruction::CallWriteStr(s) => {
-                print!("{s}");
+                write!(out, "{s}")?;
             }
             Instruction::CallWriteLineErr => {
                 let value = self.stack.pop().ok_or(RuntimeError::InvalidStackState)?;
-                eprintln!("{value}");
+                writeln!(err, "{value}")?;
             }
             Instruction::CallWriteStrErr(s) => {
-                eprint!("{s}");
+                write!(err, "{s}")?;
             }
 
             // Random number generation
@@ -339,7 +396,10 @@ impl StackVm {
                     self.stack.push(0);
                 } else {
                     // Generate random number between 1 and max (inclusive)
-                    let random_value = self.rng.random_range(1..=max);
+                    let random_value = match &mut self.seeded_rng {
+                        Some(rng) => rng.roll(max),
+                        None => self.rng.random_range(1..=max),
+                    };
                     self.stack.push(random_value);
                 }
             }
@@ -0,0 +1,574 @@
+use crate::analyzer::SemanticAnalyzer;
+use crate::ast::{ExpressionKind, StatementKind};
+use std::fmt::Write;
+use std::fs;
+
+const I32: u8 = 0x7f;
+
+/// Locals of `roll`
+const TOTAL: u32 = 0;
+const INDEX: u32 = 1;
+const DIE: u32 = 2;
+
+/// Imported host functions, which come before `roll` in the function index
+/// space
+const RANDOM: u32 = 0;
+const EMIT: u32 = 1;
+const ROLL_FUNCTION: u32 = 2;
+
+/// Instructions used by generated modules
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WasmInstruction {
+    Block,
+    Loop,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Call(u32),
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    I32Const(i32),
+    I32GeU,
+    I32Add,
+}
+
+impl WasmInstruction {
+    fn encode(self, bytes: &mut Vec<u8>) {
+        match self {
+            // Blocks have no result
+            WasmInstruction::Block => bytes.extend_from_slice(&[0x02, 0x40]),
+            WasmInstruction::Loop => bytes.extend_from_slice(&[0x03, 0x40]),
+            WasmInstruction::End => bytes.push(0x0b),
+            WasmInstruction::Br(depth) => encode_index(bytes, 0x0c, depth),
+            WasmInstruction::BrIf(depth) => encode_index(bytes, 0x0d, depth),
+            WasmInstruction::Call(function) => encode_index(bytes, 0x10, function),
+            WasmInstruction::LocalGet(local) => encode_index(bytes, 0x20, local),
+            WasmInstruction::LocalSet(local) => encode_index(bytes, 0x21, local),
+            WasmInstruction::LocalTee(local) => encode_index(bytes, 0x22, local),
+            WasmInstruction::I32Const(value) => {
+                bytes.push(0x41);
+                write_signed(bytes, value.into());
+            }
+            WasmInstruction::I32GeU => bytes.push(0x4f),
+            WasmInstruction::I32Add => bytes.push(0x6a),
+        }
+    }
+
+    /// The instruction in the text format
+    fn text(self) -> String {
+        match self {
+            WasmInstruction::Block => "block".to_string(),
+            WasmInstruction::Loop => "loop".to_string(),
+            WasmInstruction::End => "end".to_string(),
+            WasmInstruction::Br(depth) => format!("br {depth}"),
+            WasmInstruction::BrIf(depth) => format!("br_if {depth}"),
+            WasmInstruction::Call(function) => format!("call {}", function_name(function)),
+            WasmInstruction::LocalGet(local) => format!("local.get {}", local_name(local)),
+            WasmInstruction::LocalSet(local) => format!("local.set {}", local_name(local)),
+            WasmInstruction::LocalTee(local) => format!("local.tee {}", local_name(local)),
+            WasmInstruction::I32Const(value) => format!("i32.const {value}"),
+            WasmInstruction::I32GeU => "i32.ge_u".to_string(),
+            WasmInstruction::I32Add => "i32.add".to_string(),
+        }
+    }
+}
+
+fn encode_index(bytes: &mut Vec<u8>, opcode: u8, index: u32) {
+    bytes.push(opcode);
+    write_unsigned(bytes, index);
+}
+
+fn function_name(function: u32) -> &'static str {
+    match function {
+        RANDOM => "$random",
+        EMIT => "$emit",
+        _ => "$roll",
+    }
+}
+
+fn local_name(local: u32) -> &'static str {
+    match local {
+        TOTAL => "$total",
+        INDEX => "$i",
+        _ => "$die",
+    }
+}
+
+/// Generates a WebAssembly module for a dice expression.
+///
+/// The module imports `env.random(faces) -> i32`, which returns a roll of
+/// one die, and `env.emit(i32)`, which receives each roll. It exports
+/// `roll() -> i32`, which rolls the dice and returns their total.
+pub struct WasmGenerator {
+    count: u32,
+    faces: u32,
+}
+
+impl WasmGenerator {
+    pub fn new(expression: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut analyzer = SemanticAnalyzer::new(expression)?;
+        let ast = analyzer.analyze()?;
+
+        let Some(stmt) = ast.statement else {
+            return Err("Invalid expression".into());
+        };
+        let StatementKind::Expression { expr } = stmt.kind;
+        let ExpressionKind::Dice { count, faces } = expr.kind;
+        Ok(Self { count, faces })
+    }
+
+    /// Body of `roll`, a loop rolling one die per iteration. Counts and
+    /// faces are unsigned, as in the stack VM.
+    fn roll_body(&self) -> Vec<WasmInstruction> {
+        use WasmInstruction::*;
+        vec![
+            Block,
+            Loop,
+            // while (i < count)
+            LocalGet(INDEX),
+            I32Const(self.count as i32),
+            I32GeU,
+            BrIf(1),
+            // emit(die = random(faces)); total += die; i++
+            I32Const(self.faces as i32),
+            Call(RANDOM),
+            LocalTee(DIE),
+            Call(EMIT),
+            LocalGet(TOTAL),
+            LocalGet(DIE),
+            I32Add,
+            LocalSet(TOTAL),
+            LocalGet(INDEX),
+            I32Const(1),
+            I32Add,
+            LocalSet(INDEX),
+            Br(0),
+            End,
+            End,
+            LocalGet(TOTAL),
+        ]
+    }
+
+    /// The module in the binary format
+    pub fn generate_wasm(&self) -> Vec<u8> {
+        let mut module = b"\0asm".to_vec();
+        module.extend_from_slice(&1u32.to_le_bytes());
+
+        // Types: random, emit and roll
+        write_section(
+            &mut module,
+            1,
+            &[
+                &[0x60, 1, I32, 1, I32][..],
+                &[0x60, 1, I32, 0],
+                &[0x60, 0, 1, I32],
+            ],
+        );
+
+        let mut imports = Vec::new();
+        for (name, type_index) in [("random", RANDOM), ("emit", EMIT)] {
+            let mut import = Vec::new();
+            write_name(&mut import, "env");
+            write_name(&mut import, name);
+            import.push(0x00); // function
+            write_unsigned(&mut import, type_index);
+            imports.push(import);
+        }
+        write_section(&mut module, 2, &imports);
+
+        // roll has the third type
+        write_section(&mut module, 3, &[&[2][..]]);
+
+        let mut export = Vec::new();
+        write_name(&mut export, "roll");
+        export.push(0x00); // function
+        write_unsigned(&mut export, ROLL_FUNCTION);
+        write_section(&mut module, 7, &[export]);
+
+        // Three i32 locals, then the body
+        let mut body = vec![1, 3, I32];
+        for instruction in self.roll_body() {
+            instruction.encode(&mut body);
+        }
+        WasmInstruction::End.encode(&mut body);
+        let mut code = Vec::new();
+        write_unsigned(&mut code, body.len() as u32);
+        code.extend(body);
+        write_section(&mut module, 10, &[code]);
+        module
+    }
+
+    /// The module in the text format
+    pub fn generate_wat(&self) -> Result<String, std::fmt::Error> {
+        let mut wat = String::new();
+        writeln!(wat, "(module")?;
+        writeln!(
+            wat,
+            "  (import \"env\" \"random\" (func $random (param i32) (result i32)))"
+        )?;
+        writeln!(wat, "  (import \"env\" \"emit\" (func $emit (param i32)))")?;
+        writeln!(wat, "  (func $roll (export \"roll\") (result i32)")?;
+        writeln!(
+            wat,
+            "    (local $total i32) (local $i i32) (local $die i32)"
+        )?;
+        let mut depth = 2;
+        for instruction in self.roll_body() {
+            if instruction == WasmInstruction::End {
+                depth -= 1;
+            }
+            writeln!(wat, "{}{}", "  ".repeat(depth), instruction.text())?;
+            if matches!(instruction, WasmInstruction::Block | WasmInstruction::Loop) {
+                depth += 1;
+            }
+        }
+        writeln!(wat, "  )")?;
+        writeln!(wat, ")")?;
+        Ok(wat)
+    }
+}
+
+/// Write a section holding a vector of entries
+fn write_section<T: AsRef<[u8]>>(module: &mut Vec<u8>, id: u8, entries: &[T]) {
+    let mut contents = Vec::new();
+    write_unsigned(&mut contents, entries.len() as u32);
+    for entry in entries {
+        contents.extend_from_slice(entry.as_ref());
+    }
+    module.push(id);
+    write_unsigned(module, contents.len() as u32);
+    module.extend(contents);
+}
+
+fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    write_unsigned(bytes, name.len() as u32);
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+/// Unsigned LEB128
+fn write_unsigned(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Signed LEB128
+fn write_signed(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Generate a WebAssembly module for a dice expression, as a binary `.wasm`
+/// file or, with `text`, a `.wat` file
+pub fn generate_wasm_module(
+    expression: &str,
+    output: &str,
+    text: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let generator = WasmGenerator::new(expression)?;
+    let filename = if text {
+        let filename = format!("{output}.wat");
+        fs::write(&filename, generator.generate_wat()?)?;
+        filename
+    } else {
+        let filename = format!("{output}.wasm");
+        fs::write(&filename, generator.generate_wasm())?;
+        filename
+    };
+
+    println!("Generated: {filename}");
+    println!("Imports: env.random(faces) -> i32, env.emit(roll)");
+    println!("Exports: roll() -> i32");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a module back, checking its structure as a validator would
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        position: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn byte(&mut self) -> u8 {
+            let byte = self.bytes[self.position];
+            self.position += 1;
+            byte
+        }
+
+        fn take(&mut self, length: usize) -> &'a [u8] {
+            let bytes = &self.bytes[self.position..self.position + length];
+            self.position += length;
+            bytes
+        }
+
+        fn unsigned(&mut self) -> u32 {
+            let (mut value, mut shift) = (0u32, 0);
+            loop {
+                let byte = self.byte();
+                value |= u32::from(byte & 0x7f) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    return value;
+                }
+            }
+        }
+
+        fn signed(&mut self) -> i32 {
+            let (mut value, mut shift) = (0i64, 0);
+            loop {
+                let byte = self.byte();
+                value |= i64::from(byte & 0x7f) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    if byte & 0x40 != 0 {
+                        value |= -1 << shift;
+                    }
+                    return i32::try_from(value).expect("i32.const out of range");
+                }
+            }
+        }
+
+        fn name(&mut self) -> String {
+            let length = self.unsigned() as usize;
+            String::from_utf8(self.take(length).to_vec()).unwrap()
+        }
+
+        fn value_types(&mut self) -> Vec<u8> {
+            let count = self.unsigned() as usize;
+            self.take(count).to_vec()
+        }
+
+        fn instruction(&mut self) -> WasmInstruction {
+            let opcode = self.byte();
+            match opcode {
+                0x02 | 0x03 => {
+                    assert_eq!(self.byte(), 0x40, "block type");
+                    if opcode == 0x02 {
+                        WasmInstruction::Block
+                    } else {
+                        WasmInstruction::Loop
+                    }
+                }
+                0x0b => WasmInstruction::End,
+                0x0c => WasmInstruction::Br(self.unsigned()),
+                0x0d => WasmInstruction::BrIf(self.unsigned()),
+                0x10 => WasmInstruction::Call(self.unsigned()),
+                0x20 => WasmInstruction::LocalGet(self.unsigned()),
+                0x21 => WasmInstruction::LocalSet(self.unsigned()),
+                0x22 => WasmInstruction::LocalTee(self.unsigned()),
+                0x41 => WasmInstruction::I32Const(self.signed()),
+                0x4f => WasmInstruction::I32GeU,
+                0x6a => WasmInstruction::I32Add,
+                _ => panic!("unexpected opcode {opcode:#04x}"),
+            }
+        }
+    }
+
+    /// `roll`'s locals and body, after checking the module imports the
+    /// host functions and exports `roll` with their expected types
+    fn validate(module: &[u8]) -> (u32, Vec<WasmInstruction>) {
+        let mut reader = Reader {
+            bytes: module,
+            position: 0,
+        };
+        assert_eq!(reader.take(4), b"\0asm");
+        assert_eq!(reader.take(4), [1, 0, 0, 0]);
+
+        let (mut types, mut imports, mut functions, mut exports) = (vec![], vec![], vec![], vec![]);
+        let mut body = None;
+        let mut last_id = 0;
+        while reader.position < module.len() {
+            let id = reader.byte();
+            assert!(id > last_id, "section {id} out of order");
+            last_id = id;
+            let size = reader.unsigned() as usize;
+            let end = reader.position + size;
+            let count = reader.unsigned();
+            for _ in 0..count {
+                match id {
+                    1 => {
+                        assert_eq!(reader.byte(), 0x60);
+                        types.push((reader.value_types(), reader.value_types()));
+                    }
+                    2 => {
+                        let name = (reader.name(), reader.name());
+                        assert_eq!(reader.byte(), 0x00, "function import");
+                        imports.push((name, reader.unsigned()));
+                    }
+                    3 => functions.push(reader.unsigned()),
+                    7 => {
+                        let name = reader.name();
+                        assert_eq!(reader.byte(), 0x00, "function export");
+                        exports.push((name, reader.unsigned()));
+                    }
+                    10 => {
+                        let body_end = reader.unsigned() as usize + reader.position;
+                        let mut locals = 0;
+                        for _ in 0..reader.unsigned() {
+                            locals += reader.unsigned();
+                            assert_eq!(reader.byte(), I32);
+                        }
+                        let mut instructions = Vec::new();
+                        while reader.position < body_end {
+                            instructions.push(reader.instruction());
+                        }
+                        assert_eq!(reader.position, body_end);
+                        body = Some((locals, instructions));
+                    }
+                    _ => panic!("unexpected section {id}"),
+                }
+            }
+            assert_eq!(reader.position, end, "size of section {id}");
+        }
+
+        let signature = |index: u32| {
+            let (params, results) = &types[index as usize];
+            (params.as_slice(), results.as_slice())
+        };
+        let [
+            ((module_a, random), random_type),
+            ((module_b, emit), emit_type),
+        ] = imports.as_slice()
+        else {
+            panic!("expected two imports, got {imports:?}");
+        };
+        assert_eq!((module_a.as_str(), random.as_str()), ("env", "random"));
+        assert_eq!(signature(*random_type), (&[I32][..], &[I32][..]));
+        assert_eq!((module_b.as_str(), emit.as_str()), ("env", "emit"));
+        assert_eq!(signature(*emit_type), (&[I32][..], &[][..]));
+        assert_eq!(exports, vec![("roll".to_string(), ROLL_FUNCTION)]);
+        assert_eq!(functions.len(), 1);
+        assert_eq!(signature(functions[0]), (&[][..], &[I32][..]));
+        body.expect("code section")
+    }
+
+    /// Run `roll`, returning the rolls it emits and its result
+    fn interpret(
+        locals: u32,
+        body: &[WasmInstruction],
+        mut random: impl FnMut(i32) -> i32,
+    ) -> (Vec<i32>, i32) {
+        // Where each block and loop ends
+        let mut ends = vec![0; body.len()];
+        let mut open = Vec::new();
+        for (pc, instruction) in body.iter().enumerate() {
+            match instruction {
+                WasmInstruction::Block | WasmInstruction::Loop => open.push(pc),
+                WasmInstruction::End => {
+                    if let Some(start) = open.pop() {
+                        ends[start] = pc;
+                    }
+                }
+                _ => {}
+            }
+        }
+        assert!(open.is_empty(), "unbalanced blocks");
+
+        let mut locals = vec![0i32; locals as usize];
+        let (mut stack, mut labels, mut emitted) = (Vec::new(), Vec::new(), Vec::new());
+        let mut pc = 0;
+        loop {
+            let instruction = body[pc];
+            pc += 1;
+            let mut branch = None;
+            match instruction {
+                WasmInstruction::Block | WasmInstruction::Loop => labels.push(pc - 1),
+                WasmInstruction::End => {
+                    if labels.pop().is_none() {
+                        assert_eq!(stack.len(), 1, "roll returns one value");
+                        return (emitted, stack[0]);
+                    }
+                }
+                WasmInstruction::Br(depth) => branch = Some(depth),
+                WasmInstruction::BrIf(depth) => {
+                    if stack.pop().unwrap() != 0 {
+                        branch = Some(depth);
+                    }
+                }
+                WasmInstruction::Call(RANDOM) => {
+                    let faces = stack.pop().unwrap();
+                    stack.push(random(faces));
+                }
+                WasmInstruction::Call(EMIT) => emitted.push(stack.pop().unwrap()),
+                WasmInstruction::Call(function) => panic!("call to {function}"),
+                WasmInstruction::LocalGet(local) => stack.push(locals[local as usize]),
+                WasmInstruction::LocalSet(local) => locals[local as usize] = stack.pop().unwrap(),
+                WasmInstruction::LocalTee(local) => {
+                    locals[local as usize] = *stack.last().unwrap();
+                }
+                WasmInstruction::I32Const(value) => stack.push(value),
+                WasmInstruction::I32GeU => {
+                    let (b, a) = (stack.pop().unwrap(), stack.pop().unwrap());
+                    stack.push(i32::from(a as u32 >= b as u32));
+                }
+                WasmInstruction::I32Add => {
+                    let (b, a) = (stack.pop().unwrap(), stack.pop().unwrap());
+                    stack.push(a.wrapping_add(b));
+                }
+            }
+            if let Some(depth) = branch {
+                let start = labels[labels.len() - 1 - depth as usize];
+                labels.truncate(labels.len() - 1 - depth as usize);
+                if body[start] == WasmInstruction::Loop {
+                    pc = start;
+                } else {
+                    pc = ends[start] + 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_modules_validate_and_roll() {
+        for (expression, count, faces) in [("1d6", 1, 6), ("3d6", 3, 6), ("10D100", 10, 100)] {
+            let module = WasmGenerator::new(expression).unwrap().generate_wasm();
+            let (locals, body) = validate(&module);
+            assert_eq!(locals, 3);
+
+            let mut rolls = 0;
+            let (emitted, total) = interpret(locals, &body, |requested| {
+                assert_eq!(requested, faces);
+                rolls += 1;
+                rolls
+            });
+            assert_eq!(emitted, (1..=count).collect::<Vec<_>>());
+            assert_eq!(total, count * (count + 1) / 2);
+        }
+
+        // Counts past i32::MAX are compared unsigned
+        let module = WasmGenerator::new("3000000000d4000000000")
+            .unwrap()
+            .generate_wasm();
+        let (_, body) = validate(&module);
+        assert!(body.contains(&WasmInstruction::I32Const(3000000000u32 as i32)));
+        assert!(body.contains(&WasmInstruction::I32Const(4000000000u32 as i32)));
+
+        assert!(WasmGenerator::new("0d6").is_err());
+    }
+
+    #[test]
+    fn test_generated_text_matches_golden_file() {
+        let wat = WasmGenerator::new("3d6").unwrap().generate_wat().unwrap();
+        assert_eq!(wat, include_str!("../tests/golden/wasm/DiceRoll_3d6.wat"));
+    }
+}
@@ -0,0 +1,51 @@
+// Conformance table for pop2, dup2 and wide: javac emits them for compound
+// assignments to long and double array elements and fields, for discarded
+// long results and for int locals stepped by more than a byte
+public class Accumulators {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    long total;
+    double weight;
+    static long rolls = 0;
+
+    static long roll() {
+        rolls++;
+        return rolls * 6;
+    }
+
+    public static void main(String[] args) {
+        long[] longs = new long[3];
+        longs[1]++;
+        longs[1] += 5000000000L;
+        longs[2]--;
+        record("longs " + longs[0] + " " + longs[1] + " " + longs[2]);
+
+        double[] doubles = {0.5, 1.5};
+        doubles[0] *= 3;
+        double old = doubles[1]++;
+        record("doubles " + doubles[0] + " " + doubles[1] + " " + old);
+
+        Accumulators accumulators = new Accumulators();
+        accumulators.total += 7;
+        long seen = accumulators.total += 1000000000000L;
+        accumulators.weight += 0.25;
+        record("fields " + accumulators.total + " " + seen + " " + accumulators.weight);
+
+        roll();
+        roll();
+        record("discarded " + rolls + " " + roll());
+
+        int steps = 3;
+        steps += 1000;
+        record("wide " + steps);
+        steps -= 30000;
+        record("wide " + steps);
+    }
+}
@@ -0,0 +1,75 @@
+// Conformance table for typed arithmetic. The operands are non-final
+// statics so javac cannot fold the expressions into constants.
+public class Arithmetic {
+    static int intMax = Integer.MAX_VALUE;
+    static int intMin = Integer.MIN_VALUE;
+    static int minusOne = -1;
+    static int seven = 7;
+    static int shift = 33;
+    static long longMax = Long.MAX_VALUE;
+    static long longMin = Long.MIN_VALUE;
+    static long bigLong = 123456789012345L;
+    static float floatZero = 0.0f;
+    static float third = 1.0f / 3;
+    static float bigFloat = 3.0e10f;
+    static double doubleZero = 0.0;
+    static double nan = Double.NaN;
+    static double bigDouble = -1.0e300;
+
+    static String[] results = new String[40];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    public static void main(String[] args) {
+        record("iadd " + (intMax + 1));
+        record("isub " + (intMin - 1));
+        record("imul " + (intMax * 2));
+        record("idiv " + (intMin / minusOne));
+        record("irem " + (-seven % 3));
+        record("ineg " + (-intMin));
+        record("ishl " + (seven << shift));
+        record("ishr " + (intMin >> shift));
+        record("iushr " + (intMin >>> shift));
+        record("ibits " + ((seven & 5) | (seven ^ 12)));
+        int counter = intMax;
+        counter += 10;
+        record("iinc " + counter);
+
+        record("ladd " + (longMax + 1));
+        record("lmul " + (bigLong * bigLong));
+        record("ldiv " + (longMin / minusOne));
+        record("lrem " + (bigLong % -seven));
+        record("lneg " + (-longMin));
+        record("lshl " + (bigLong << 65));
+        record("lshr " + (longMin >> shift));
+        record("lushr " + (longMin >>> shift));
+        record("lbits " + ((bigLong & 0xFFFF) ^ (bigLong | 1L)));
+
+        record("fdiv " + (1.0f / floatZero) + " " + (floatZero / floatZero));
+        record("fmul " + (third * 3) + " " + (bigFloat * bigFloat));
+        record("frem " + (-7.5f % 2.0f) + " " + (-floatZero));
+        record("ddiv " + (-1.0 / doubleZero) + " " + (doubleZero / doubleZero));
+        record("drem " + (10.5 % -3.0) + " " + (nan % 1.0));
+        record("dneg " + (-doubleZero) + " " + (bigDouble * bigDouble));
+
+        record("d2i " + (int) nan + " " + (int) bigDouble + " " + (int) -bigDouble);
+        record("d2l " + (long) bigDouble + " " + (long) 1.0e19 + " " + (long) -2.9);
+        record("f2i " + (int) bigFloat + " " + (int) (floatZero / floatZero));
+        record("f2l " + (long) bigFloat + " " + (long) -3.7f);
+        record("l2i " + (int) bigLong + " " + (int) longMax);
+        record("narrow " + (byte) (intMax - 127) + " " + (short) 70000 * seven + " " + (int) (char) -seven);
+        record("i2f " + (float) (16777217 * seven / seven) + " " + (double) intMin);
+        record("l2f " + (float) longMax + " " + (double) bigLong);
+        record("d2f " + (float) bigDouble + " " + (float) (1.0 / 3));
+
+        record("lcmp " + (bigLong > longMin) + " " + (longMax == bigLong));
+        record("fcmp " + (third < floatZero / floatZero) + " " + (third > floatZero / floatZero));
+        record("dcmp " + (nan == nan) + " " + (nan != nan) + " " + (doubleZero == -doubleZero));
+        record("icmp " + (intMin < intMax) + " " + (seven >= 7) + " " + (args != null));
+    }
+}
@@ -0,0 +1,45 @@
+// Conformance table for aastore: arrays keep their component type at run
+// time, so a store through an Object[] view can still fail
+public class ArrayStores {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    public static void main(String[] args) {
+        Object[] strings = new String[2];
+        try {
+            strings[0] = Integer.valueOf(1);
+            record("stored");
+        } catch (ArrayStoreException e) {
+            record("ase");
+        }
+        strings[0] = "d6";
+        strings[1] = null;
+        record("strings " + strings[0] + " " + strings[1]);
+
+        Object[] numbers = new Number[2];
+        numbers[0] = Integer.valueOf(3);
+        numbers[1] = Long.valueOf(4L);
+        try {
+            numbers[1] = "4";
+        } catch (ArrayStoreException e) {
+            record("numbers " + numbers[0] + " " + numbers[1] + " " + e.getMessage());
+        }
+
+        Object[] rows = new int[2][];
+        rows[0] = new int[1];
+        try {
+            rows[1] = new long[1];
+        } catch (ArrayStoreException e) {
+            record("rows " + e.getMessage());
+        }
+        Object[] objects = new Object[1];
+        objects[0] = rows;
+        record("objects " + (objects[0] == rows));
+    }
+}
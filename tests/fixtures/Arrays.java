@@ -0,0 +1,34 @@
+public class Arrays {
+    public static void main(String[] args) {
+        int[] rolls = new int[6];
+        rolls[0] = 3;
+        rolls[5] = rolls[0] * 2;
+        System.out.println(rolls.length);
+        System.out.println(rolls[5]);
+
+        long[] totals = { 10000000000L, 2L };
+        double[] means = new double[2];
+        means[1] = 3.5;
+        char[] faces = { 'd', 'D' };
+        byte[] small = { (byte) 200 };
+        short[] medium = new short[1];
+        medium[0] = (short) 70000;
+        boolean[] flags = new boolean[2];
+        flags[1] = true;
+        System.out.println(totals[0] + " " + totals[1]);
+        System.out.println(means[1]);
+        System.out.println(faces[1]);
+        System.out.println(small[0] + " " + medium[0] + " " + flags[0] + " " + flags[1]);
+
+        int[][] grid = new int[2][3];
+        grid[1][2] = 7;
+        String[][] names = new String[2][];
+        System.out.println(grid[1][2] + grid[1].length + " " + names.length);
+
+        System.out.println(args.length);
+        if (args.length > 0) {
+            System.out.println("first: " + args[0]);
+        }
+        System.out.println(rolls[args.length + 6]);
+    }
+}
@@ -0,0 +1,33 @@
+// Conformance table for boxing: valueOf shares boxes for small values, so
+// == compares them equal, while larger values get a box each
+public class Boxing {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    public static void main(String[] args) {
+        Integer a = 127, b = 127;
+        Integer c = 128, d = 128;
+        Integer low = -128, lower = Integer.valueOf(-128);
+        record("Integer " + (a == b) + " " + (c == d) + " " + (low == lower) + " " + c.equals(d));
+
+        Long e = 100L, f = 100L;
+        Long g = 1000L, h = 1000L;
+        record("Long " + (e == f) + " " + (g == h));
+
+        Character i = 'd', j = 'd';
+        Character k = '\u00e9', l = '\u00e9';
+        record("Character " + (i == j) + " " + (k == l));
+
+        Boolean m = true, n = Boolean.valueOf(true);
+        record("Boolean " + (m == n));
+
+        Double o = 6.0, p = 6.0;
+        record("Double " + (o == p) + " " + o.equals(p));
+    }
+}
@@ -0,0 +1,88 @@
+// Conformance table for method calls: every return kind, long and double
+// arguments taking two local slots, and recursion deeper than the host stack
+// would allow if each call nested
+public class Calls {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    static int fibonacci(int n) {
+        return n < 2 ? n : fibonacci(n - 1) + fibonacci(n - 2);
+    }
+
+    static int sumTo(int n) {
+        return n == 0 ? 0 : n + sumTo(n - 1);
+    }
+
+    static long total(int dice, long sides, double scale, int bonus) {
+        return (long) (dice * sides * scale) + bonus;
+    }
+
+    static double average(long total, int rolls) {
+        return (double) total / rolls;
+    }
+
+    static float half(float value) {
+        return value / 2;
+    }
+
+    static String describe(int roll) {
+        return roll == 20 ? "critical" : "roll " + roll;
+    }
+
+    static int[] faces(int sides) {
+        int[] faces = new int[sides];
+        for (int i = 0; i < sides; i++) {
+            faces[i] = i + 1;
+        }
+        return faces;
+    }
+
+    static boolean isEven(int value) {
+        return value % 2 == 0;
+    }
+
+    static char initial(String name) {
+        return name.charAt(0);
+    }
+
+    static int fail(int depth) {
+        if (depth == 0) {
+            throw new IllegalStateException("bottom");
+        }
+        return fail(depth - 1) + 1;
+    }
+
+    final long seed;
+
+    Calls(long seed) {
+        this.seed = seed;
+    }
+
+    Calls next(long step, int times) {
+        return times == 0 ? this : new Calls(seed + step).next(step, times - 1);
+    }
+
+    public static void main(String[] args) {
+        record("fibonacci " + fibonacci(25));
+        record("sumTo " + sumTo(5000));
+        record("total " + total(6, 1_000_000_000_000L, 0.5, 3));
+        record("average " + average(7L, 2));
+        record("half " + half(5f));
+        record("describe " + describe(20) + " " + describe(7));
+        int[] d6 = faces(6);
+        record("faces " + d6.length + " " + d6[5]);
+        record("even " + isEven(4) + " " + initial("dice"));
+        record("next " + new Calls(1L).next(10_000_000_000L, 3).seed);
+        try {
+            fail(100);
+        } catch (IllegalStateException e) {
+            record("caught " + e.getMessage());
+        }
+    }
+}
@@ -0,0 +1,16 @@
+public class Concat {
+    public static void main(String[] args) {
+        int roll = 4;
+        long total = 10000000000L;
+        double mean = 3.5;
+        char face = 'd';
+        boolean critical = true;
+        String label = null;
+        System.out.println("roll = " + roll);
+        System.out.println(roll + "" + face + 6 + ": " + critical);
+        System.out.println("total=" + total + ", mean=" + mean);
+        System.out.println("label: " + label + " \u0001 " + 2.0 + " " + 1e10);
+        String greeting = "Hello, " + "dice" + "!";
+        System.out.println(greeting.length());
+    }
+}
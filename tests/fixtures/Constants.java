@@ -0,0 +1,11 @@
+public class Constants {
+    static final long SEED = 123456789012L;
+    static final double HALF = 0.5;
+
+    public static void main(String[] args) {
+        double scale = 2.5;
+        System.out.println(scale);
+        System.out.println(SEED);
+        System.out.println(HALF);
+    }
+}
@@ -0,0 +1,9 @@
+public class Countdown {
+    public static void main(String[] args) {
+        int n = 3;
+        while (n > 0) {
+            System.out.println(n);
+            n = n - 1;
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// Conformance table for the dup_x forms javac emits when a post-increment
+// or an assignment is used as a value
+public class Counters {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    int ticks;
+    long total;
+
+    // dup_x1
+    int tick() {
+        return this.ticks++;
+    }
+
+    // dup2_x1
+    long add(long amount) {
+        return this.total += amount;
+    }
+
+    // dup_x2
+    static int store(int[] values, int index, int value) {
+        return values[index] = value;
+    }
+
+    // dup2_x2
+    static long store(long[] values, int index, long value) {
+        return values[index] = value;
+    }
+
+    static double store(double[] values, int index, double value) {
+        return values[index] = value;
+    }
+
+    public static void main(String[] args) {
+        Counters counters = new Counters();
+        int first = counters.tick();
+        int second = counters.tick();
+        record("ticks " + first + " " + second + " " + counters.ticks);
+        long big = counters.add(5000000000L);
+        record("total " + big + " " + counters.add(-1) + " " + counters.total);
+
+        int[] ints = new int[2];
+        record("ints " + store(ints, 1, 7) + " " + ints[0] + " " + ints[1]);
+        long[] longs = new long[2];
+        record("longs " + store(longs, 0, -3L) + " " + longs[0] + " " + longs[1]);
+        double[] doubles = new double[2];
+        record("doubles " + store(doubles, 1, 2.5) + " " + doubles[1]);
+    }
+}
@@ -0,0 +1,61 @@
+// Conformance table for interface default methods: inherited, overridden
+// by a more specific interface, called through Interface.super, and
+// overridden again by a class
+public class Defaults {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    interface Roller {
+        int faces();
+
+        default int roll(int seed) {
+            return seed % faces() + 1;
+        }
+
+        default String name() {
+            return "d" + faces();
+        }
+    }
+
+    interface Exploding extends Roller {
+        @Override
+        default String name() {
+            return "exploding " + Roller.super.name();
+        }
+    }
+
+    static class D6 implements Roller {
+        public int faces() {
+            return 6;
+        }
+    }
+
+    static class ExplodingD6 extends D6 implements Exploding {
+    }
+
+    static class Coin implements Exploding {
+        public int faces() {
+            return 2;
+        }
+
+        @Override
+        public String name() {
+            return "coin";
+        }
+    }
+
+    public static void main(String[] args) {
+        Roller d6 = new D6();
+        record("d6 " + d6.roll(10) + " " + d6.name());
+        Roller exploding = new ExplodingD6();
+        record("exploding " + exploding.roll(11) + " " + exploding.name());
+        Coin coin = new Coin();
+        record("coin " + coin.roll(3) + " " + coin.name());
+    }
+}
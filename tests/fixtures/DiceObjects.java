@@ -0,0 +1,70 @@
+abstract class Die {
+    static int created;
+    static String prefix = "d";
+
+    protected final int sides;
+    private int lastRoll;
+
+    Die(int sides) {
+        this.sides = sides;
+        created = created + 1;
+    }
+
+    int roll(int seed) {
+        lastRoll = seed % sides + 1;
+        return lastRoll;
+    }
+
+    int lastRoll() {
+        return lastRoll;
+    }
+
+    int bonus() {
+        return 0;
+    }
+
+    int total(int seed) {
+        return roll(seed) + bonus();
+    }
+}
+
+class FairDie extends Die {
+    FairDie(int sides) {
+        super(sides);
+    }
+}
+
+class LoadedDie extends Die {
+    private final int weight;
+
+    LoadedDie(int sides, int weight) {
+        super(sides);
+        this.weight = weight;
+    }
+
+    @Override
+    int roll(int seed) {
+        return super.roll(seed + weight);
+    }
+
+    @Override
+    int bonus() {
+        return weight;
+    }
+}
+
+public class DiceObjects {
+    static int rolls = 10;
+
+    public static void main(String[] args) {
+        Die plain = new FairDie(6);
+        Die loaded = new LoadedDie(6, 2);
+        System.out.println(plain.total(3));
+        System.out.println(loaded.total(3));
+        System.out.println(loaded.lastRoll());
+        System.out.println(Die.prefix + loaded.sides);
+        System.out.println(Die.created);
+        rolls = rolls + Die.created;
+        System.out.println(rolls);
+    }
+}
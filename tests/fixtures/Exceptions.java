@@ -0,0 +1,65 @@
+public class Exceptions {
+    static class RollException extends RuntimeException {
+        RollException(String message) {
+            super(message);
+        }
+    }
+
+    static class TooManyDiceException extends RollException {
+        TooManyDiceException(int count) {
+            super("too many dice: " + count);
+        }
+    }
+
+    static int divide(int total, int count) {
+        return total / count;
+    }
+
+    static int checkedCount(int count) {
+        if (count / 101 != 0) {
+            throw new TooManyDiceException(count);
+        }
+        return count;
+    }
+
+    static int roll(int count) {
+        try {
+            return checkedCount(count) * 3;
+        } finally {
+            System.out.println("rolled " + count);
+        }
+    }
+
+    public static void main(String[] args) {
+        try {
+            System.out.println(divide(12, 0));
+        } catch (ArithmeticException e) {
+            System.out.println("caught " + e.getMessage());
+        }
+
+        try {
+            System.out.println(roll(2));
+            System.out.println(roll(500));
+        } catch (RollException e) {
+            System.out.println(e);
+        }
+
+        int[] dice = new int[2];
+        try {
+            dice[3] = 6;
+        } catch (IndexOutOfBoundsException e) {
+            System.out.println(e.getMessage());
+        }
+
+        String missing = null;
+        try {
+            System.out.println(missing.length());
+        } catch (NullPointerException e) {
+            System.out.println("null string");
+        } catch (RuntimeException e) {
+            System.out.println("unreachable");
+        }
+
+        throw new RollException("no dice left");
+    }
+}
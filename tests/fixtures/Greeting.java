@@ -0,0 +1,6 @@
+public class Greeting {
+    public static void main(String[] args) {
+        int sides = 6;
+        System.out.println("Rolling d" + sides);
+    }
+}
@@ -0,0 +1,46 @@
+// Conformance table for instance fields: a subclass field that hides a
+// superclass field of the same name is a separate slot, reached through
+// super or the static type of the reference
+public class Hiding {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    static class Die {
+        int sides = 1;
+        long rolls = 10;
+
+        int dieSides() {
+            return sides;
+        }
+    }
+
+    static class LoadedDie extends Die {
+        int sides = 2;
+        // Same name, different type
+        int rolls = 20;
+
+        void load() {
+            super.sides = super.sides + 2;
+            sides = sides + 1;
+        }
+
+        String describe() {
+            return super.sides + " " + sides + " " + dieSides() + " " + super.rolls + " " + rolls;
+        }
+    }
+
+    public static void main(String[] args) {
+        LoadedDie die = new LoadedDie();
+        record("fields " + die.describe());
+        die.load();
+        record("loaded " + die.describe());
+        Die base = die;
+        record("static type " + base.sides + " " + ((LoadedDie) base).sides);
+    }
+}
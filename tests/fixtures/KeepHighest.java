@@ -0,0 +1,89 @@
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.Collections;
+import java.util.Comparator;
+import java.util.HashMap;
+import java.util.List;
+import java.util.Map;
+import java.util.Random;
+
+// Conformance table for the collection classes a dice program uses: keeping
+// the highest rolls, counting faces and sorting arrays
+public class KeepHighest {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    // Sorts highest first through a user-defined comparator
+    static class Descending implements Comparator<Integer> {
+        public int compare(Integer a, Integer b) {
+            return b - a;
+        }
+    }
+
+    static int keepHighest(List<Integer> rolls, int keep) {
+        List<Integer> sorted = new ArrayList<>(rolls);
+        Collections.sort(sorted, Collections.reverseOrder());
+        int total = 0;
+        for (int roll : sorted.subList(0, keep)) {
+            total += roll;
+        }
+        return total;
+    }
+
+    public static void main(String[] args) {
+        Random random = new Random(7);
+        List<Integer> rolls = new ArrayList<>();
+        for (int i = 0; i < 6; i++) {
+            rolls.add(random.nextInt(6) + 1);
+        }
+        record("rolls " + rolls + " size " + rolls.size());
+        record("4d6kh3 " + keepHighest(rolls, 3));
+
+        List<Integer> sorted = new ArrayList<>(rolls);
+        Collections.sort(sorted);
+        record("sorted " + sorted + " max " + Collections.max(rolls));
+        sorted.sort(new Descending());
+        record("descending " + sorted);
+
+        int first = rolls.get(0);
+        rolls.remove(Integer.valueOf(first));
+        rolls.remove(0);
+        record("removed " + rolls + " contains " + rolls.contains(first) + " " + rolls.indexOf(6));
+
+        Map<Integer, Integer> counts = new HashMap<>();
+        for (int i = 0; i < 60; i++) {
+            int face = random.nextInt(6) + 1;
+            counts.put(face, counts.getOrDefault(face, 0) + 1);
+        }
+        record("counts " + counts);
+
+        Map<String, Integer> names = new HashMap<>();
+        String[] words = {"fumble", "hit", "critical", "miss", "graze", "hit"};
+        for (String word : words) {
+            Integer seen = names.get(word);
+            names.put(word, seen == null ? 1 : seen + 1);
+        }
+        record("names " + names + " " + names.containsKey("miss") + " " + names.get("dodge"));
+
+        int[] pool = {4, 1, 6, 3, 6, 2};
+        Arrays.sort(pool);
+        double[] weights = {0.5, -0.0, 0.0, -1.5};
+        Arrays.sort(weights);
+        record("arrays " + Arrays.toString(pool) + " " + Arrays.toString(weights));
+
+        Object boxed = Integer.valueOf(42);
+        record("boxing " + boxed.equals(42) + " " + boxed.hashCode() + " " + (boxed instanceof Integer));
+        try {
+            String text = (String) boxed;
+            record("cast " + text);
+        } catch (ClassCastException e) {
+            record("cast failed");
+        }
+    }
+}
@@ -0,0 +1,37 @@
+import kotlin.random.Random
+
+// Conformance table for the Kotlin stdlib shims: rolling with ranges and
+// random(), summarising with listOf, sorted and sum, and null checks.
+// The test compiles it with kotlinc, when one is installed.
+private val results = arrayOfNulls<String>(10)
+private var count = 0
+
+fun record(result: String) {
+    results[count] = result
+    count++
+    println(result)
+}
+
+fun main(args: Array<String>) {
+    val rolls = listOf(4, 1, 6, 3, 6)
+    record("rolls " + rolls.sorted() + " sum " + rolls.sum())
+    record("descending " + rolls.sortedDescending().joinToString(" "))
+    record("max " + rolls.max() + " min " + rolls.min())
+
+    val d6 = 1..6
+    record("range " + d6 + " " + d6.first + " " + d6.last + " sum " + d6.sum())
+    record("countdown " + (10 downTo 1 step 3).joinToString())
+
+    val pool = (1..100).map { (1..6).random() }
+    record("pool " + pool.size + " " + (pool.min() >= 1 && pool.max() <= 6))
+    val d20 = Random.nextInt(1, 21)
+    record("d20 " + (d20 in 1..20))
+
+    val name: String? = if (args.isEmpty()) null else args[0]
+    record("equal " + (name == null) + " " + (name == "dice"))
+    try {
+        record("name " + name!!.length)
+    } catch (e: NullPointerException) {
+        record("npe " + e.message)
+    }
+}
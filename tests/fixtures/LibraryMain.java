@@ -0,0 +1,9 @@
+import dice.Dice;
+
+public class LibraryMain {
+    public static void main(String[] args) {
+        Dice dice = new Dice(3, 6);
+        dice.printNotation();
+        System.out.println(dice.maximum());
+    }
+}
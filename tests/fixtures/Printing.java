@@ -0,0 +1,49 @@
+// Conformance table for turning objects into text: string concatenation,
+// StringBuilder.append and String.valueOf all call a user-defined toString
+public class Printing {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    static class Die {
+        final int sides;
+
+        Die(int sides) {
+            this.sides = sides;
+        }
+
+        @Override
+        public String toString() {
+            return "d" + sides;
+        }
+    }
+
+    // Inherits Die.toString
+    static class FudgeDie extends Die {
+        FudgeDie() {
+            super(3);
+        }
+    }
+
+    public static void main(String[] args) {
+        Die die = new Die(6);
+        Object fudge = new FudgeDie();
+        Die missing = null;
+        System.out.println(die);
+        System.out.print(fudge);
+        System.out.println();
+
+        record("concat " + die + " " + fudge + " " + missing);
+        StringBuilder builder = new StringBuilder();
+        builder.append(die).append('+').append(fudge).append(' ').append((Object) missing);
+        record("builder " + builder);
+        record("valueOf " + String.valueOf(die) + " " + String.valueOf((Object) missing));
+        Object[] pool = {new Die(20), new Die(4)};
+        record("pool " + pool[0] + pool[1]);
+    }
+}
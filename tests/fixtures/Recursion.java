@@ -0,0 +1,10 @@
+// Unbounded recursion, which a JVM stops with a StackOverflowError
+public class Recursion {
+    static int depth(int n) {
+        return depth(n + 1);
+    }
+
+    public static void main(String[] args) {
+        System.out.println(depth(0));
+    }
+}
@@ -0,0 +1,51 @@
+import java.util.Random;
+import java.util.concurrent.ThreadLocalRandom;
+
+// Conformance table for java.util.Random. Seeded generators must produce
+// the same sequence as a reference JVM.
+public class SeededRandom {
+    static String[] results = new String[16];
+    static int count = 0;
+
+    static void record(String result) {
+        results[count] = result;
+        count++;
+        System.out.println(result);
+    }
+
+    public static void main(String[] args) {
+        Random random = new Random(42);
+        String rolls = "";
+        for (int i = 0; i < 10; i++) {
+            rolls = rolls + (random.nextInt(6) + 1);
+        }
+        record("3d6 " + rolls);
+        record("nextInt " + random.nextInt());
+        record("power of two " + random.nextInt(16) + " " + random.nextInt(1 << 30));
+        record("large bound " + random.nextInt(2000000000) + " " + random.nextInt(1500000000));
+        record("nextLong " + random.nextLong());
+        record("nextDouble " + random.nextDouble());
+        record("nextFloat " + random.nextFloat());
+        record("nextBoolean " + random.nextBoolean() + " " + random.nextBoolean());
+        record("nextGaussian " + random.nextGaussian() + " " + random.nextGaussian());
+
+        random.setSeed(-1L);
+        record("setSeed " + random.nextInt(100) + " " + random.nextGaussian());
+
+        try {
+            random.nextInt(0);
+        } catch (IllegalArgumentException e) {
+            record("bound " + e.getMessage());
+        }
+
+        // Only the range of an unseeded generator can be checked
+        boolean inRange = true;
+        for (int i = 0; i < 100; i++) {
+            int roll = ThreadLocalRandom.current().nextInt(1, 7);
+            if (roll < 1 || roll > 6) {
+                inRange = false;
+            }
+        }
+        record("threadLocal " + inRange);
+    }
+}
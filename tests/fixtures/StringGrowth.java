@@ -0,0 +1,16 @@
+// Doubles a string until the VM's string limit stops it, with concatenation
+// or, given the argument "builder", with a StringBuilder
+public class StringGrowth {
+    public static void main(String[] args) {
+        if (args.length > 0 && args[0].equals("builder")) {
+            StringBuilder builder = new StringBuilder("d6");
+            while (true) {
+                builder.append(builder.toString());
+            }
+        }
+        String s = "d6";
+        while (true) {
+            s = s + s;
+        }
+    }
+}
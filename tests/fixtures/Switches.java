@@ -0,0 +1,64 @@
+public class Switches {
+    // Dense keys compile to tableswitch
+    static void critical(int roll) {
+        String result;
+        switch (roll) {
+            case 1:
+                result = "fumble";
+                break;
+            case 2:
+            case 3:
+                result = "miss";
+                break;
+            case 4:
+                result = "hit";
+                break;
+            case 5:
+                result = "strong hit";
+                break;
+            case 6:
+                result = "critical";
+                break;
+            default:
+                result = "invalid";
+        }
+        System.out.println(roll + ": " + result);
+    }
+
+    // Sparse keys compile to lookupswitch
+    static int bonus(int roll) {
+        switch (roll) {
+            case -100:
+                return -5;
+            case 1:
+                return -1;
+            case 20:
+                return 10;
+            case 1000:
+                return 50;
+            default:
+                return 0;
+        }
+    }
+
+    // Java 14+ switch expression on strings: lookupswitch on hashCode,
+    // equals to rule out collisions, then tableswitch on the case index
+    static int sides(String die) {
+        return switch (die) {
+            case "d4" -> 4;
+            case "d6" -> 6;
+            case "d20" -> 20;
+            case "Aa", "BB" -> 2; // "Aa" and "BB" share a hash code
+            default -> 0;
+        };
+    }
+
+    public static void main(String[] args) {
+        for (int roll = 0; roll <= 7; roll++) {
+            critical(roll);
+        }
+        System.out.println(bonus(-100) + bonus(1) + bonus(20) + bonus(1000) + bonus(7));
+        System.out.println(sides("d4") + " " + sides("d6") + " " + sides("d20"));
+        System.out.println(sides("Aa") + " " + sides("BB") + " " + sides("d100"));
+    }
+}
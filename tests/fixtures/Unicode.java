@@ -0,0 +1,6 @@
+public class Unicode {
+    public static void main(String[] args) {
+        System.out.println("🎲 Würfel");
+        System.out.println("nul:\0:end");
+    }
+}
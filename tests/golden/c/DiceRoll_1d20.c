@@ -0,0 +1,57 @@
+/* 1d20, generated by dice-rust */
+#include <stddef.h>
+#include <stdint.h>
+
+#define DICE_COUNT UINT32_C(1)
+#define DICE_FACES UINT32_C(20)
+
+/* xorshift64 (13, 7, 17). A zero state, which it would never leave, is
+   replaced by a fixed one. */
+static uint64_t dice_next(uint64_t *rng_state)
+{
+    uint64_t x = *rng_state ? *rng_state : UINT64_C(0x9E3779B97F4A7C15);
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    *rng_state = x;
+    return x;
+}
+
+/* Roll 1d20, storing the first cap rolls in out_rolls, and return
+   their total. */
+uint32_t dice_roll(uint64_t *rng_state, int32_t *out_rolls, size_t cap)
+{
+    uint32_t total = 0;
+    for (uint32_t i = 0; i < DICE_COUNT; i++) {
+        uint32_t roll = (uint32_t)(dice_next(rng_state) % DICE_FACES) + 1;
+        if (i < cap) {
+            out_rolls[i] = (int32_t)roll;
+        }
+        total += roll;
+    }
+    return total;
+}
+
+#ifndef DICE_NO_MAIN
+#include <inttypes.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <time.h>
+
+/* Usage: program [seed] */
+int main(int argc, char **argv)
+{
+    uint64_t rng_state = argc > 1 ? strtoull(argv[1], NULL, 10) : (uint64_t)time(NULL);
+    int32_t *rolls = (int32_t *)malloc(sizeof *rolls * DICE_COUNT);
+    if (rolls == NULL) {
+        fputs("out of memory\n", stderr);
+        return 1;
+    }
+    dice_roll(&rng_state, rolls, DICE_COUNT);
+    for (uint32_t i = 0; i < DICE_COUNT; i++) {
+        printf("%" PRIu32 "\n", (uint32_t)rolls[i]);
+    }
+    free(rolls);
+    return 0;
+}
+#endif
@@ -0,0 +1,6 @@
+public class DiceRoll {
+    public static void main(String[] args) {
+        int roll = (int) (Math.random() * 6 + 1);
+        System.out.println(roll);
+    }
+}
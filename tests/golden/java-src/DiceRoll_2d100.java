@@ -0,0 +1,11 @@
+public class DiceRoll {
+    public static void main(String[] args) {
+        int total = 0;
+        for (int i = 0; i < 2; i++) {
+            int roll = (int) (Math.random() * 100 + 1);
+            System.out.println(roll);
+            total += roll;
+        }
+        System.err.println("Total: " + total);
+    }
+}
@@ -0,0 +1,13 @@
+package games.dice;
+
+public class Roll {
+    public static void main(String[] args) {
+        int total = 0;
+        for (int i = 0; i < 60000; i++) {
+            int roll = (int) (Math.random() * 20 + 1);
+            System.out.println(roll);
+            total += roll;
+        }
+        System.err.println("Total: " + total);
+    }
+}
@@ -0,0 +1,29 @@
+(module
+  (import "env" "random" (func $random (param i32) (result i32)))
+  (import "env" "emit" (func $emit (param i32)))
+  (func $roll (export "roll") (result i32)
+    (local $total i32) (local $i i32) (local $die i32)
+    block
+      loop
+        local.get $i
+        i32.const 3
+        i32.ge_u
+        br_if 1
+        i32.const 6
+        call $random
+        local.tee $die
+        call $emit
+        local.get $total
+        local.get $die
+        i32.add
+        local.set $total
+        local.get $i
+        i32.const 1
+        i32.add
+        local.set $i
+        br 0
+      end
+    end
+    local.get $total
+  )
+)